@@ -0,0 +1,150 @@
+//! Measures how much the evaluator itself costs on trees that are deep, wide or loop a lot. The
+//! leaves do next to nothing, so the numbers are the overhead of running the nodes.
+
+mod harness;
+
+use async_behavior_tree::behavior_tree::runner::Runner;
+use async_behavior_tree::behavior_tree::scheduler::Scheduler;
+use async_behavior_tree::behavior_tree::Behavior::*;
+use async_behavior_tree::behavior_tree::{Actionable, Behavior, Response, TreeInstance};
+use harness::{Evaluator, Harness, Recursive};
+
+/// A leaf with a fixed outcome, like the scripted actions of `bt-test`.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum Leaf {
+    Succeed,
+    Fail,
+    // succeeds while the state is above 0, counting it down, and fails at 0
+    CountDown,
+}
+
+impl Actionable for Leaf {
+    type ActionError = &'static str;
+    type ActionArgs = ();
+    type ActionState = u32;
+
+    async fn run(&self, _: &(), count: &mut u32) -> Result<Response, &'static str> {
+        match self {
+            Leaf::Succeed => Ok(Response::Success),
+            Leaf::Fail => Ok(Response::Failure),
+            Leaf::CountDown if *count == 0 => Ok(Response::Failure),
+            Leaf::CountDown => {
+                *count -= 1;
+                Ok(Response::Success)
+            }
+        }
+    }
+
+    fn is_read_only(&self) -> bool {
+        !matches!(self, Leaf::CountDown)
+    }
+}
+
+fn deep_invert_chain(depth: usize) -> Behavior<Leaf> {
+    (0..depth).fold(Action(Leaf::Succeed), |child, _| Invert(Box::new(child)))
+}
+
+fn wide_failing_select(width: usize) -> Behavior<Leaf> {
+    Select(vec![Action(Leaf::Fail); width])
+}
+
+fn counting_loop() -> Behavior<Leaf> {
+    While {
+        condition: Box::new(Action(Leaf::CountDown)),
+        action: Box::new(Action(Leaf::Succeed)),
+    }
+}
+
+// what an agent of a fleet might tick: a few conditions guarding a few steps
+fn agent_tree() -> Behavior<Leaf> {
+    Select(vec![
+        Sequence(vec![Action(Leaf::Fail), Action(Leaf::Succeed)]),
+        Sequence(vec![
+            Action(Leaf::Succeed),
+            Invert(Box::new(Action(Leaf::Fail))),
+            Action(Leaf::Succeed),
+        ]),
+    ])
+}
+
+// about 10k nodes, shallow enough to stay below the recursion limit of the JSON parser
+#[cfg(feature = "serde")]
+fn large_tree() -> Behavior<Leaf> {
+    Sequence(
+        (0..100)
+            .map(|_| Select((0..100).map(|_| Action(Leaf::Fail)).collect()))
+            .collect(),
+    )
+}
+
+// a tree that doesn't run to the end measures less than its name says
+fn expect<E: Evaluator>(
+    harness: &Harness,
+    tree: &mut E::Tree<Leaf>,
+    mut count: u32,
+    expected: Response,
+) {
+    let result = harness.block_on(E::tick(tree, &(), &mut count));
+    assert_eq!(result.ok(), Some(expected));
+}
+
+fn evaluator_benches<E: Evaluator>(harness: &Harness) {
+    let mut deep = E::load(deep_invert_chain(10_000));
+    expect::<E>(harness, &mut deep, 0, Response::Success);
+    harness.bench(&format!("{}/deep_invert_chain_10k", E::NAME), |rt| {
+        rt.block_on(E::tick(&mut deep, &(), &mut 0)).is_ok()
+    });
+
+    let mut wide = E::load(wide_failing_select(1_000));
+    expect::<E>(harness, &mut wide, 0, Response::Failure);
+    harness.bench(&format!("{}/wide_failing_select_1k", E::NAME), |rt| {
+        rt.block_on(E::tick(&mut wide, &(), &mut 0)).is_ok()
+    });
+
+    let mut looping = E::load(counting_loop());
+    expect::<E>(harness, &mut looping, 100_000, Response::Success);
+    harness.bench(&format!("{}/while_loop_100k", E::NAME), |rt| {
+        rt.block_on(E::tick(&mut looping, &(), &mut 100_000))
+            .is_ok()
+    });
+}
+
+// the scheduler ticks `TreeInstance`s, so this one only runs the recursive evaluator
+fn scheduler_benches(harness: &Harness) {
+    let mut agent = Recursive::load(agent_tree());
+    expect::<Recursive>(harness, &mut agent, 0, Response::Success);
+    let mut scheduler = Scheduler::new();
+    for _ in 0..100 {
+        scheduler.add(Runner::new(TreeInstance::new(agent_tree())), (), 0);
+    }
+    harness.bench("scheduler/tick_100_agents", |rt| {
+        rt.block_on(scheduler.tick_all()).len()
+    });
+}
+
+#[cfg(feature = "serde")]
+fn serde_benches(harness: &Harness) {
+    let tree = large_tree();
+    harness.bench("serde/round_trip_10k_nodes", |_| {
+        let json = serde_json::to_string(&tree).unwrap();
+        serde_json::from_str::<Behavior<Leaf>>(&json).unwrap()
+    });
+}
+
+#[cfg(not(feature = "serde"))]
+fn serde_benches(_: &Harness) {}
+
+fn main() {
+    // running and dropping the deep chain recurses once per node
+    let benches = std::thread::Builder::new()
+        .stack_size(1 << 30)
+        .spawn(|| {
+            let harness = Harness::from_args();
+            evaluator_benches::<Recursive>(&harness);
+            scheduler_benches(&harness);
+            serde_benches(&harness);
+        })
+        .expect("a thread for the benches");
+    benches.join().expect("the benches to finish");
+}
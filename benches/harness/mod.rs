@@ -0,0 +1,146 @@
+use async_behavior_tree::behavior_tree::{
+    Actionable, Behavior, BehaviorError, Response, TreeInstance,
+};
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+
+/// A way of running trees the benches measure. `Recursive` runs them as `TreeInstance`s do
+/// today; an iterative or compiled evaluator gets an impl of its own and runs the same benches.
+pub trait Evaluator {
+    const NAME: &'static str;
+
+    type Tree<A: Actionable>;
+
+    fn load<A: Actionable>(behavior: Behavior<A>) -> Self::Tree<A>;
+
+    fn tick<'a, A: Actionable>(
+        tree: &'a mut Self::Tree<A>,
+        args: &'a A::ActionArgs,
+        state: &'a mut A::ActionState,
+    ) -> impl Future<Output = Result<Response, BehaviorError<A::ActionError>>> + 'a;
+}
+
+pub struct Recursive;
+
+impl Evaluator for Recursive {
+    const NAME: &'static str = "recursive";
+
+    type Tree<A: Actionable> = TreeInstance<A>;
+
+    fn load<A: Actionable>(behavior: Behavior<A>) -> TreeInstance<A> {
+        TreeInstance::new(behavior)
+    }
+
+    fn tick<'a, A: Actionable>(
+        tree: &'a mut TreeInstance<A>,
+        args: &'a A::ActionArgs,
+        state: &'a mut A::ActionState,
+    ) -> impl Future<Output = Result<Response, BehaviorError<A::ActionError>>> + 'a {
+        tree.run(args, state)
+    }
+}
+
+const WARM_UP: usize = 3;
+const SAMPLES: usize = 20;
+
+/// Times benches and compares them with the baseline of an earlier run.
+///
+/// `cargo bench -- <filter>` runs the benches whose name contains the filter, and
+/// `cargo bench -- --save-baseline` stores the results as the baseline to compare later runs
+/// with, in `target/bench-baselines`.
+pub struct Harness {
+    filter: Option<String>,
+    save_baseline: bool,
+    runtime: Runtime,
+}
+
+impl Harness {
+    pub fn from_args() -> Self {
+        let mut filter = None;
+        let mut save_baseline = false;
+        for arg in std::env::args().skip(1) {
+            match arg.as_str() {
+                "--save-baseline" => save_baseline = true,
+                // passed by `cargo bench`
+                "--bench" => {}
+                other => filter = Some(other.to_string()),
+            }
+        }
+        Self {
+            filter,
+            save_baseline,
+            runtime: tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("a tokio runtime"),
+        }
+    }
+
+    /// Runs `future` on the runtime of the benches, to check a tree before timing it.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Times `routine`, which gets the runtime to block on.
+    pub fn bench<T>(&self, name: &str, mut routine: impl FnMut(&Runtime) -> T) {
+        if self
+            .filter
+            .as_ref()
+            .is_some_and(|filter| !name.contains(filter))
+        {
+            return;
+        }
+        for _ in 0..WARM_UP {
+            std::hint::black_box(routine(&self.runtime));
+        }
+        let mut samples: Vec<Duration> = (0..SAMPLES)
+            .map(|_| {
+                let start = Instant::now();
+                std::hint::black_box(routine(&self.runtime));
+                start.elapsed()
+            })
+            .collect();
+        samples.sort();
+        let median = samples[SAMPLES / 2];
+
+        let comparison = match self.baseline(name) {
+            Some(baseline) => {
+                let change = median.as_secs_f64() / baseline.as_secs_f64() - 1.0;
+                format!(" ({:+.1}% against {:?})", change * 100.0, baseline)
+            }
+            None => String::new(),
+        };
+        println!(
+            "{:<40} median {:>12?}  min {:>12?}{}",
+            name, median, samples[0], comparison
+        );
+        if self.save_baseline {
+            self.save(name, median);
+        }
+    }
+
+    fn baseline_path(name: &str) -> PathBuf {
+        let target = std::env::var_os("CARGO_TARGET_DIR").unwrap_or_else(|| "target".into());
+        PathBuf::from(target)
+            .join("bench-baselines")
+            .join(name.replace('/', "__"))
+    }
+
+    fn baseline(&self, name: &str) -> Option<Duration> {
+        let nanos = std::fs::read_to_string(Self::baseline_path(name)).ok()?;
+        Some(Duration::from_nanos(nanos.trim().parse().ok()?))
+    }
+
+    fn save(&self, name: &str, median: Duration) {
+        let path = Self::baseline_path(name);
+        let saved = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&path, median.as_nanos().to_string()));
+        if let Err(err) = saved {
+            eprintln!("cannot save the baseline of {}: {}", name, err);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use async_behavior_tree::behavior_tree::Behavior::{Action, Select, Sequence};
+use async_behavior_tree::behavior_tree::Response::Success;
+use async_behavior_tree::behavior_tree::{Actionable, Behavior, Response};
+
+#[derive(Clone, Debug)]
+enum MyAction {
+    Fail,
+    Greet,
+    Wave,
+    Bow,
+}
+
+impl Actionable for MyAction {
+    type ActionError = anyhow::Error;
+    type ActionArgs = ();
+    type ActionState = State;
+
+    async fn run(
+        &self,
+        _args: &Self::ActionArgs,
+        state: &mut Self::ActionState,
+    ) -> Result<Response, Self::ActionError> {
+        match self {
+            MyAction::Greet => {
+                state.num_greets += 1;
+                Ok(Success)
+            }
+            MyAction::Wave => {
+                state.num_waves += 1;
+                Ok(Success)
+            }
+            MyAction::Bow => {
+                state.num_bows += 1;
+                Ok(Success)
+            }
+            MyAction::Fail => {
+                anyhow::bail!("Broken")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+struct State {
+    num_greets: u32,
+    num_waves: u32,
+    num_bows: u32,
+}
+
+#[tokio::main]
+async fn main() {
+    let bt: Behavior<MyAction> = Select(vec![
+        Action(MyAction::Fail),
+        Action(MyAction::Wave),
+        Action(MyAction::Greet),
+        Action(MyAction::Bow),
+    ]);
+
+    let mut my_state = State {
+        num_greets: 0,
+        num_waves: 0,
+        num_bows: 0,
+    };
+
+    bt.run(&(), &mut my_state).await.unwrap();
+    println!("{:?}", my_state);
+    assert_eq!(
+        my_state,
+        State {
+            num_greets: 0,
+            num_waves: 1,
+            num_bows: 0,
+        }
+    );
+
+    let bt: Behavior<MyAction> = Sequence(vec![
+        Action(MyAction::Wave),
+        Action(MyAction::Greet),
+        Action(MyAction::Fail),
+        Action(MyAction::Bow),
+    ]);
+
+    let mut my_state = State {
+        num_greets: 0,
+        num_waves: 0,
+        num_bows: 0,
+    };
+
+    let result = bt.run(&(), &mut my_state).await;
+    println!("{:?}", my_state);
+    assert!(result.is_err());
+    assert_eq!(
+        my_state,
+        State {
+            num_greets: 1,
+            num_waves: 1,
+            num_bows: 0,
+        }
+    );
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use crate::{MyAction, State};
+    use async_behavior_tree::behavior_tree::audit::{AuditOptions, StateChange};
+    use async_behavior_tree::behavior_tree::observer::{BehaviorObserver, TreeEvent};
+    use async_behavior_tree::behavior_tree::Behavior::{Action, Sequence};
+    use async_behavior_tree::behavior_tree::{NodePath, TreeInstance};
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<(NodePath, TreeEvent)>>>);
+
+    impl<A> BehaviorObserver<A> for Recorder {
+        fn on_event(&mut self, path: &[usize], event: &TreeEvent) {
+            self.0.lock().unwrap().push((path.to_vec(), event.clone()));
+        }
+    }
+
+    fn changed(pointer: &str, old: u32, new: u32) -> TreeEvent {
+        TreeEvent::StateChanged {
+            changes: vec![StateChange {
+                pointer: pointer.to_string(),
+                old: Some(json!(old)),
+                new: Some(json!(new)),
+            }],
+            truncated: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_audit_sequence_example() {
+        let bt = Sequence(vec![
+            Action(MyAction::Wave),
+            Action(MyAction::Greet),
+            Action(MyAction::Fail),
+            Action(MyAction::Bow),
+        ]);
+        let recorder = Recorder::default();
+        let mut instance = TreeInstance::new(bt)
+            .with_audit(AuditOptions::default())
+            .with_observer(recorder.clone());
+        let mut state = State {
+            num_greets: 0,
+            num_waves: 0,
+            num_bows: 0,
+        };
+
+        assert!(instance.run(&(), &mut state).await.is_err());
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![
+                (vec![0], changed("/num_waves", 0, 1)),
+                (vec![1], changed("/num_greets", 0, 1)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,27 @@
+#![no_main]
+
+use async_behavior_tree::behavior_tree::loader::{LoadOptions, UntypedTree};
+use libfuzzer_sys::fuzz_target;
+
+// tree files load or fail with a `LoadError`, and whatever loads saves and loads again into the
+// same document
+fuzz_target!(|data: &[u8]| {
+    let Ok(json) = std::str::from_utf8(data) else {
+        return;
+    };
+    for lenient in [false, true] {
+        let load = |json: &str| {
+            if lenient {
+                UntypedTree::from_json_with(json, LoadOptions::lenient())
+            } else {
+                UntypedTree::from_json(json)
+            }
+        };
+        let Ok(loaded) = load(json) else {
+            continue;
+        };
+        let saved = loaded.to_json().expect("a loaded tree saves");
+        let reloaded = load(&saved).expect("a saved tree loads");
+        assert_eq!(reloaded.to_json().expect("a loaded tree saves"), saved);
+    }
+});
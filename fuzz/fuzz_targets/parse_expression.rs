@@ -0,0 +1,16 @@
+#![no_main]
+
+use async_behavior_tree::behavior_tree::expr::Expression;
+use async_behavior_tree::behavior_tree::Blackboard;
+use libfuzzer_sys::fuzz_target;
+
+// expressions parse or fail with an `ExprError`, evaluating them never panics, and their source
+// parses again into the same expression
+fuzz_target!(|source: &str| {
+    let Ok(expression) = Expression::parse(source) else {
+        return;
+    };
+    let _ = expression.eval(&Blackboard::new(), &(), None);
+    let reparsed = Expression::parse(expression.source()).expect("the source parses again");
+    assert_eq!(reparsed, expression);
+});
@@ -1,48 +1,1179 @@
-use anyhow::anyhow;
-use async_trait::async_trait;
+use crate::behavior_tree::blackboard::{template_keys, BlackboardValue};
+use crate::behavior_tree::cancel::{CancelReason, CancellationToken};
+#[cfg(feature = "serde")]
+use crate::behavior_tree::clock::duration_millis;
+use crate::behavior_tree::clock::parse_timestamp;
+use crate::behavior_tree::compare::{CompareOp, ValueRef};
+#[cfg(feature = "serde")]
+use crate::behavior_tree::composite::serde_composite;
+use crate::behavior_tree::composite::{Children, CompositeNode};
+use crate::behavior_tree::concurrent::Batch;
+use crate::behavior_tree::dataflow::PipelineStage;
+#[cfg(feature = "serde")]
+use crate::behavior_tree::decorator::serde_decorator;
+use crate::behavior_tree::decorator::{Decorator, Executor, Unresolved};
+use crate::behavior_tree::experiment::Variant;
+use crate::behavior_tree::expr::Expression;
+use crate::behavior_tree::instance::{ArmStats, RunContext};
+use crate::behavior_tree::lazy::{ChildGenerator, Unmapped};
+use crate::behavior_tree::observer::{BehaviorObserver, LogLevel, TreeEvent};
+use crate::behavior_tree::parallel::ParallelPolicy;
+use crate::behavior_tree::runner::AssertMode;
+use crate::behavior_tree::schedule::TimeWindow;
+use crate::behavior_tree::toggle::WhenDisabled;
+use futures_util::future::{select, Either};
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::{pin, Pin};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub mod arbiter;
+#[cfg(feature = "serde")]
+pub mod audit;
+pub mod blackboard;
+pub mod boxed;
+mod bt_macro;
+pub mod cancel;
+#[cfg(feature = "serde")]
+pub mod catalog;
+pub mod clock;
+pub mod compare;
+pub mod compose;
+pub mod composite;
+pub mod concurrent;
+pub mod dataflow;
+pub mod debugger;
+pub mod decorator;
+pub mod editor;
+pub mod experiment;
+#[cfg(feature = "serde")]
+pub mod export;
+pub mod expr;
+pub mod heartbeat;
+pub mod history;
+pub mod instance;
+#[cfg(feature = "serde")]
+pub mod io;
+#[cfg(feature = "serde")]
+pub mod json_state;
+pub mod lazy;
+#[cfg(feature = "serde")]
+pub mod loader;
+pub mod observer;
+pub mod parallel;
+pub mod pipeline;
+mod random;
+#[cfg(feature = "serde")]
+pub mod registry;
+#[cfg(feature = "serde")]
+pub mod replay;
+#[cfg(feature = "serde")]
+pub mod report;
+pub mod rng;
+pub mod runner;
+#[cfg(feature = "serde")]
+pub mod scenario;
+pub mod schedule;
+pub mod scheduler;
+#[cfg(feature = "tokio")]
+pub mod spawn;
+pub mod subtree;
+mod sync;
+pub mod toggle;
+pub mod typed;
+
+pub use crate::embedded::Response;
+pub use blackboard::Blackboard;
+pub use instance::{ActionContext, InstanceSnapshot, NodeMemory, NodePath, TreeInstance};
+pub use rng::TreeRng;
 
 // inspired by @chamlis design from spacetraders discord
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Behavior<A> {
     Action(A),
     Invert(Box<Behavior<A>>),
     Select(Vec<Behavior<A>>),
     Sequence(Vec<Behavior<A>>),
+    // Like Sequence and Select, but every run starts over at the first child, so the conditions
+    // in front of a running child are checked again each tick. A running child is stopped,
+    // forgetting what it kept in the instance, when a child before it now decides the node: one
+    // failing in a ReactiveSequence, or one succeeding or running in a ReactiveSelect.
+    ReactiveSequence(Vec<Behavior<A>>),
+    ReactiveSelect(Vec<Behavior<A>>),
     // Success,
     // Run the action while the condition is successful or until the action returns a failure.
+    // An action that was running resumes without the condition being checked first.
     While {
         condition: Box<Behavior<A>>,
         action: Box<Behavior<A>>,
     },
+    // Like Select, but learns from past runs which child to try first.
+    // Per-child counts live in the TreeInstance the tree runs in.
+    AdaptiveSelect {
+        children: Vec<Behavior<A>>,
+        strategy: SelectStrategy,
+    },
+    // Like Select, but tries its children in an order shuffled anew on every run, drawn from
+    // the rng of the instance. A running child is resumed first.
+    RandomSelect(Vec<Behavior<A>>),
+    // Like RandomSelect, but draws each next child to try in proportion to its weight among the
+    // children left. Children of weight 0 are only tried after all the others.
+    WeightedSelect(Vec<(f32, Behavior<A>)>),
+    // Runs its children concurrently, see `TreeInstance::with_parallel`. `RequireAll` succeeds
+    // once every child succeeded and fails as soon as one fails, `RequireAny` succeeds as soon as
+    // one child succeeds and fails once all failed, and `Threshold` succeeds or fails once the
+    // given numbers of children did. Children still running when the node is decided are
+    // stopped; children that finished while others are `Running` don't run again until it is
+    // decided.
+    Parallel {
+        children: Vec<Behavior<A>>,
+        policy: ParallelPolicy,
+    },
+    // Like Sequence and Select, with children the generator makes when the node gets to them.
+    // Generated children run at the path of their index but aren't part of the tree otherwise,
+    // and they can't be saved.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    LazySequence(Arc<dyn ChildGenerator<A>>),
+    #[cfg_attr(feature = "serde", serde(skip))]
+    LazySelect(Arc<dyn ChildGenerator<A>>),
+    // Runs its stages in order like a Sequence. A stage may read only the keys earlier stages
+    // output and keys the blackboard holds before the pipeline runs, and only the keys it
+    // outputs survive it: its other writes are undone and reported as `UndeclaredWrite` events.
+    Pipeline(Vec<PipelineStage<A>>),
+    // Runs the branch of one variant, picked by hashing the value of `key` (e.g. the ship
+    // symbol) with the salt, so each unit always runs the same variant. Per-variant counts live
+    // in the TreeInstance the tree runs in.
+    Experiment {
+        key: ValueRef,
+        variants: Vec<Variant<A>>,
+        #[cfg_attr(feature = "serde", serde(default))]
+        salt: String,
+    },
+    // Succeeds if the blackboard holds `value` under `key`.
+    CheckKey {
+        key: String,
+        value: BlackboardValue,
+    },
+    // Stores what `value` resolves to on the blackboard under `key` and succeeds.
+    SetKey {
+        key: String,
+        value: ValueRef,
+    },
+    // Succeeds if the value under `key` differs from the one seen the last time this node
+    // succeeded, fails otherwise. On the very first run it succeeds only if `fire_on_first` is set.
+    OnChanged {
+        key: String,
+        #[cfg_attr(feature = "serde", serde(default))]
+        fire_on_first: bool,
+    },
+    // Succeeds if the expression evaluates to true, see `Expression` for the syntax.
+    Expr {
+        source: Expression,
+    },
+    // Succeeds if `left op right` holds. Accessors are looked up in the instance's registry.
+    Compare {
+        left: ValueRef,
+        op: CompareOp,
+        right: ValueRef,
+    },
+    // Emits `message` with `${key}` placeholders filled from the blackboard as a `LogEmitted`
+    // event, which a `TracingObserver` passes on to `tracing`, and succeeds.
+    Log {
+        level: LogLevel,
+        message: String,
+    },
+    // Fails with a `Thrown` error carrying `code`, which a `TryCatch` can catch by code.
+    // `${key}` placeholders in the message are filled from the blackboard.
+    Throw {
+        code: String,
+        #[cfg_attr(feature = "serde", serde(default))]
+        message: Option<String>,
+    },
+    // Runs `body`; if it fails, runs the branch of the first clause matching the failure.
+    TryCatch {
+        #[cfg_attr(feature = "serde", serde(rename = "try"))]
+        body: Box<Behavior<A>>,
+        catch: Vec<CatchClause<A>>,
+    },
+    // Waits until the timestamp `until` resolves to, an RFC3339 string or epoch millis, and
+    // succeeds. Succeeds right away if that time has passed.
+    SleepUntil {
+        until: ValueRef,
+    },
+    // Succeeds if the clock's time falls inside one of the windows, fails otherwise. See
+    // `TimeWindow` for how windows are written.
+    Schedule {
+        windows: Vec<TimeWindow>,
+    },
+    // Runs `child`. At the `soft` deadline it cancels the token of the child's run with
+    // `CancelReason::TimedOut` so the child can wrap up, and results in whatever the child then
+    // results in. If the child is still running at the `hard` deadline it is stopped and the
+    // node fails. Both deadlines are given in milliseconds; `soft` has to come before `hard`.
+    GracefulTimeout {
+        child: Box<Behavior<A>>,
+        #[cfg_attr(feature = "serde", serde(with = "duration_millis"))]
+        soft: Duration,
+        #[cfg_attr(feature = "serde", serde(with = "duration_millis"))]
+        hard: Duration,
+    },
+    // Runs its child and watches the heartbeats of the actions under it. An action that didn't
+    // beat for `stall_after` milliseconds is reported with a `Stalled` event once the child stops
+    // running; with `cancel` the child is stopped right away and the node fails.
+    StallGuard {
+        child: Box<Behavior<A>>,
+        #[cfg_attr(feature = "serde", serde(with = "duration_millis"))]
+        stall_after: Duration,
+        #[cfg_attr(feature = "serde", serde(default))]
+        cancel: bool,
+    },
+    // Runs `child` again while it fails, at most `max_attempts` times in all, sleeping `backoff`
+    // milliseconds between attempts, and results in what the last attempt resulted in. Errors
+    // that abort the run, like action errors, aren't retried; actions return
+    // `Response::Failure` to be retried. A child returning `Running` doesn't use up an attempt.
+    // `max_attempts` has to be at least 1.
+    Retry {
+        child: Box<Behavior<A>>,
+        max_attempts: usize,
+        #[cfg_attr(feature = "serde", serde(default, with = "duration_millis::option"))]
+        backoff: Option<Duration>,
+    },
+    // Runs `child` until it succeeded `times` times, stopping at the first run that doesn't
+    // succeed and resulting in what that run resulted in. Succeeds right away for `times` 0.
+    Repeat {
+        child: Box<Behavior<A>>,
+        times: usize,
+    },
+    // Runs `child` and fails if it doesn't finish within `duration` milliseconds. The child is
+    // dropped at the deadline, keeping whatever it changed in the state until then; use
+    // `GracefulTimeout` to give it a chance to wrap up.
+    Timeout {
+        child: Box<Behavior<A>>,
+        #[cfg_attr(feature = "serde", serde(with = "duration_millis"))]
+        duration: Duration,
+    },
+    // Runs `child`, then fails without running it until `duration` milliseconds passed since it
+    // last succeeded or failed, measured on the instance's sleeper. Only a `TreeInstance`
+    // remembers when that was; a plain `run` always runs the child.
+    Cooldown {
+        child: Box<Behavior<A>>,
+        #[cfg_attr(feature = "serde", serde(with = "duration_millis"))]
+        duration: Duration,
+    },
+    // Sleeps a random duration in `[min, max]` drawn from the instance's rng and succeeds.
+    // Both bounds are given in milliseconds.
+    Jitter {
+        #[cfg_attr(feature = "serde", serde(with = "duration_millis"))]
+        min: Duration,
+        #[cfg_attr(feature = "serde", serde(with = "duration_millis"))]
+        max: Duration,
+    },
+    // Pauses the run like a path breakpoint when the runner has a debugger attached,
+    // succeeds right away otherwise.
+    Breakpoint {
+        label: String,
+    },
+    // Runs `child` unchanged; the name labels the subtree for people reading the tree. When the
+    // node is disabled at runtime it results in `when_disabled` without running `child`.
+    Named {
+        name: String,
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "WhenDisabled::is_default")
+        )]
+        when_disabled: WhenDisabled,
+        child: Box<Behavior<A>>,
+    },
+    // Runs `child` with the keys of the typed blackboard under `namespace`, inside the
+    // namespaces of the nodes above. The blackboard of the instance isn't namespaced.
+    Namespaced {
+        namespace: String,
+        child: Box<Behavior<A>>,
+    },
+    // Stands in for the tree registered under `name` in a `SubtreeRegistry`, with the blackboard
+    // keys of that tree renamed as `remapping` says. Fails if it runs before the registry
+    // resolved it.
+    Subtree {
+        name: String,
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "BTreeMap::is_empty")
+        )]
+        remapping: BTreeMap<String, String>,
+    },
+    // Always fails.
+    AlwaysFail,
+    // Starts `child` on a task of its own and succeeds right away, storing the id of the task
+    // on the blackboard under `handle_key`. The child runs on clones of the args and state, see
+    // `TreeInstance::with_spawning`. Spawning runs the child on a tokio task, so without the
+    // `tokio` feature `Spawn` and `Join` nodes fail.
+    Spawn {
+        child: Box<Behavior<A>>,
+        handle_key: String,
+    },
+    // Waits for the task of the `Spawn` whose id is under `handle_key` and results in what its
+    // child resulted in. Cancels the task and fails if it doesn't finish within `timeout`
+    // milliseconds. A task can be joined once.
+    Join {
+        handle_key: String,
+        #[cfg_attr(feature = "serde", serde(default, with = "duration_millis::option"))]
+        timeout: Option<Duration>,
+    },
+    // Runs `child` through a user-defined decorator, which decides when and how often it runs.
+    // Saved as the decorator's name and params; a `DecoratorRegistry` builds the decorators of
+    // loaded trees.
+    Decorated {
+        #[cfg_attr(feature = "serde", serde(with = "serde_decorator"))]
+        decorator: Arc<dyn Decorator<A>>,
+        child: Box<Behavior<A>>,
+    },
+    // Runs its children through a user-defined composite, which decides which of them run, in
+    // which order, and what the node results in. Saved like `Decorated`; a `CompositeRegistry`
+    // builds the composites of loaded trees.
+    Composite {
+        #[cfg_attr(feature = "serde", serde(with = "serde_composite"))]
+        node: Arc<dyn CompositeNode<A>>,
+        children: Vec<Behavior<A>>,
+    },
+    // A node of a kind this version doesn't know, kept by lenient loading. `raw` is the node as
+    // it was in the file and is what gets saved again, `children` are the nodes of known kinds
+    // found inside it, for inspection only. Fails when run.
+    #[cfg(feature = "serde")]
+    Opaque {
+        kind: String,
+        raw: serde_json::Value,
+        children: Vec<Behavior<A>>,
+    },
+    // Succeeds if the condition succeeds. Otherwise the whole tree run fails with an
+    // `AssertionFailed` error that no enclosing node can swallow.
+    Assert {
+        condition: Box<Behavior<A>>,
+        message: String,
+    },
+}
+
+/// A `TryCatch` clause. It handles failures thrown with one of `codes`; a clause without codes
+/// handles every failure, thrown or not.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CatchClause<A> {
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub codes: Vec<String>,
+    pub branch: Behavior<A>,
+}
+
+impl<A> CatchClause<A> {
+    fn matches(&self, code: Option<&str>) -> bool {
+        self.codes.is_empty() || code.is_some_and(|code| self.codes.iter().any(|c| c == code))
+    }
+}
+
+/// The error a `Throw` node fails with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thrown {
+    pub code: String,
+    pub message: Option<String>,
+    pub path: NodePath,
+}
+
+impl fmt::Display for Thrown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` thrown at {:?}", self.code, self.path)?;
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Thrown {}
+
+/// The error a failing `Assert` aborts the tree with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssertionFailed {
+    pub message: String,
+    pub path: NodePath,
+    // the `Debug` output of the state, if the instance was set up to capture it
+    pub state: Option<String>,
+}
+
+impl fmt::Display for AssertionFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "assertion failed at {:?}: {}", self.path, self.message)?;
+        if let Some(state) = &self.state {
+            write!(f, " (state: {})", state)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AssertionFailed {}
+
+/// Why running a tree failed: one of its actions failed, or a node did.
+#[derive(Debug)]
+pub enum BehaviorError<E> {
+    /// The error of a failing action.
+    Action(E),
+    /// A node failed on its own, like a misconfigured node or an expression that can't be
+    /// evaluated. Conditions that don't hold return `Ok(Response::Failure)` instead.
+    Failed(String),
+    /// A `Sequence` or `Select` failed with the error of one of its children, or a `Parallel`
+    /// with the errors of the children that failed. Children returning `Failure` have none.
+    ChildrenFailed {
+        message: String,
+        children: Vec<ChildError<E>>,
+    },
+    /// A `Throw` node failed with a code no enclosing `TryCatch` caught.
+    Thrown(Thrown),
+    /// An `Assert` failed while asserts are fatal.
+    AssertionFailed(AssertionFailed),
+    /// The run was stopped through its `CancellationToken`.
+    Cancelled(String),
+    /// An action error a replayed trace recorded, which has only the error message.
+    Replayed(String),
+}
+
+/// The error a child of a failing node failed with.
+#[derive(Debug)]
+pub struct ChildError<E> {
+    /// The path of the child from the root, see [`Behavior::node_at`] for its node.
+    pub path: NodePath,
+    pub error: BehaviorError<E>,
+}
+
+impl<E> BehaviorError<E> {
+    pub fn failed(message: impl Into<String>) -> Self {
+        Self::Failed(message.into())
+    }
+
+    /// The errors of the children the error comes from, empty if it isn't a `ChildrenFailed`.
+    pub fn child_errors(&self) -> &[ChildError<E>] {
+        match self {
+            Self::ChildrenFailed { children, .. } => children,
+            _ => &[],
+        }
+    }
+
+    /// The path of the node the error started at, following the first child error down, if
+    /// the error knows it.
+    pub fn origin(&self) -> Option<&[usize]> {
+        match self {
+            Self::ChildrenFailed { children, .. } => {
+                let first = children.first()?;
+                first.error.origin().or(Some(&first.path))
+            }
+            Self::AssertionFailed(failed) => Some(&failed.path),
+            _ => None,
+        }
+    }
+
+    /// Whether the error aborts the whole run instead of being handled by the enclosing nodes.
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, Self::AssertionFailed(_) | Self::Cancelled(_))
+    }
+
+    /// Whether nodes that try again, like `Retry` and `Assert`, pass the error on instead of
+    /// taking it as a failure: action errors and fatal errors. Actions return
+    /// `Ok(Response::Failure)` to fail.
+    pub fn is_hard(&self) -> bool {
+        self.is_fatal() || matches!(self, Self::Action(_) | Self::Replayed(_))
+    }
+
+    // hard errors and thrown codes reach the caller as-is
+    fn propagates(&self) -> bool {
+        self.is_hard() || matches!(self, Self::Thrown(_))
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for BehaviorError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Action(err) => err.fmt(f),
+            Self::Failed(message) | Self::Cancelled(message) | Self::Replayed(message) => {
+                f.write_str(message)
+            }
+            Self::Thrown(thrown) => thrown.fmt(f),
+            Self::AssertionFailed(failed) => failed.fmt(f),
+            Self::ChildrenFailed { message, children } => {
+                f.write_str(&describe_children(message, children, &|err| {
+                    err.to_string()
+                }))
+            }
+        }
+    }
+}
+
+/// `message` followed by the paths and errors of the children, as in
+/// `one behavior failed ([2]: one behavior failed ([2, 0]: disabled))`.
+pub(crate) fn describe_children<E>(
+    message: &str,
+    children: &[ChildError<E>],
+    describe: &dyn Fn(&BehaviorError<E>) -> String,
+) -> String {
+    if children.is_empty() {
+        return message.to_string();
+    }
+    let children: Vec<_> = children
+        .iter()
+        .map(|child| format!("{:?}: {}", child.path, describe(&child.error)))
+        .collect();
+    format!("{} ({})", message, children.join("; "))
+}
+
+// the error of a sequence-like node whose child `index` failed with `error`
+fn one_child_failed<E>(path: &[usize], index: usize, error: BehaviorError<E>) -> BehaviorError<E> {
+    let mut path = path.to_vec();
+    path.push(index);
+    BehaviorError::ChildrenFailed {
+        message: "one behavior failed".to_string(),
+        children: vec![ChildError { path, error }],
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for BehaviorError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Action(err) => Some(err),
+            Self::Thrown(thrown) => Some(thrown),
+            Self::AssertionFailed(failed) => Some(failed),
+            Self::ChildrenFailed { children, .. } => children
+                .first()
+                .map(|child| &child.error as &(dyn std::error::Error + 'static)),
+            Self::Failed(_) | Self::Cancelled(_) | Self::Replayed(_) => None,
+        }
+    }
+}
+
+impl<A> Behavior<A> {
+    /// The direct children of this node, indexed the same way as the node paths.
+    pub fn children(&self) -> Vec<&Behavior<A>> {
+        match self {
+            Behavior::Action(_)
+            | Behavior::CheckKey { .. }
+            | Behavior::SetKey { .. }
+            | Behavior::OnChanged { .. }
+            | Behavior::Expr { .. }
+            | Behavior::Compare { .. }
+            | Behavior::Log { .. }
+            | Behavior::Breakpoint { .. }
+            | Behavior::SleepUntil { .. }
+            | Behavior::Schedule { .. }
+            | Behavior::Jitter { .. }
+            | Behavior::AlwaysFail
+            | Behavior::Join { .. }
+            | Behavior::Subtree { .. }
+            | Behavior::LazySequence(_)
+            | Behavior::LazySelect(_)
+            | Behavior::Throw { .. } => vec![],
+            Behavior::TryCatch { body, catch } => std::iter::once(body.as_ref())
+                .chain(catch.iter().map(|clause| &clause.branch))
+                .collect(),
+            Behavior::Invert(b)
+            | Behavior::Named { child: b, .. }
+            | Behavior::Namespaced { child: b, .. }
+            | Behavior::Decorated { child: b, .. }
+            | Behavior::Spawn { child: b, .. }
+            | Behavior::GracefulTimeout { child: b, .. }
+            | Behavior::StallGuard { child: b, .. }
+            | Behavior::Retry { child: b, .. }
+            | Behavior::Repeat { child: b, .. }
+            | Behavior::Timeout { child: b, .. }
+            | Behavior::Cooldown { child: b, .. }
+            | Behavior::Assert { condition: b, .. } => vec![b.as_ref()],
+            Behavior::Select(behaviors)
+            | Behavior::Sequence(behaviors)
+            | Behavior::ReactiveSequence(behaviors)
+            | Behavior::ReactiveSelect(behaviors)
+            | Behavior::RandomSelect(behaviors) => behaviors.iter().collect(),
+            Behavior::WeightedSelect(children) => children.iter().map(|(_, b)| b).collect(),
+            Behavior::While { condition, action } => vec![condition.as_ref(), action.as_ref()],
+            Behavior::AdaptiveSelect { children, .. }
+            | Behavior::Parallel { children, .. }
+            | Behavior::Composite { children, .. } => children.iter().collect(),
+            Behavior::Experiment { variants, .. } => {
+                variants.iter().map(|variant| &variant.branch).collect()
+            }
+            Behavior::Pipeline(stages) => stages.iter().map(|stage| &stage.branch).collect(),
+            #[cfg(feature = "serde")]
+            Behavior::Opaque { children, .. } => children.iter().collect(),
+        }
+    }
+
+    /// Like [`children`](Self::children), for changing the children in place.
+    pub fn children_mut(&mut self) -> Vec<&mut Behavior<A>> {
+        match self {
+            Behavior::Action(_)
+            | Behavior::CheckKey { .. }
+            | Behavior::SetKey { .. }
+            | Behavior::OnChanged { .. }
+            | Behavior::Expr { .. }
+            | Behavior::Compare { .. }
+            | Behavior::Log { .. }
+            | Behavior::Breakpoint { .. }
+            | Behavior::SleepUntil { .. }
+            | Behavior::Schedule { .. }
+            | Behavior::Jitter { .. }
+            | Behavior::AlwaysFail
+            | Behavior::Join { .. }
+            | Behavior::Subtree { .. }
+            | Behavior::LazySequence(_)
+            | Behavior::LazySelect(_)
+            | Behavior::Throw { .. } => vec![],
+            Behavior::TryCatch { body, catch } => std::iter::once(body.as_mut())
+                .chain(catch.iter_mut().map(|clause| &mut clause.branch))
+                .collect(),
+            Behavior::Invert(b)
+            | Behavior::Named { child: b, .. }
+            | Behavior::Namespaced { child: b, .. }
+            | Behavior::Decorated { child: b, .. }
+            | Behavior::Spawn { child: b, .. }
+            | Behavior::GracefulTimeout { child: b, .. }
+            | Behavior::StallGuard { child: b, .. }
+            | Behavior::Retry { child: b, .. }
+            | Behavior::Repeat { child: b, .. }
+            | Behavior::Timeout { child: b, .. }
+            | Behavior::Cooldown { child: b, .. }
+            | Behavior::Assert { condition: b, .. } => vec![b.as_mut()],
+            Behavior::Select(behaviors)
+            | Behavior::Sequence(behaviors)
+            | Behavior::ReactiveSequence(behaviors)
+            | Behavior::ReactiveSelect(behaviors)
+            | Behavior::RandomSelect(behaviors) => behaviors.iter_mut().collect(),
+            Behavior::WeightedSelect(children) => children.iter_mut().map(|(_, b)| b).collect(),
+            Behavior::While { condition, action } => vec![condition.as_mut(), action.as_mut()],
+            Behavior::AdaptiveSelect { children, .. }
+            | Behavior::Parallel { children, .. }
+            | Behavior::Composite { children, .. } => children.iter_mut().collect(),
+            Behavior::Experiment { variants, .. } => variants
+                .iter_mut()
+                .map(|variant| &mut variant.branch)
+                .collect(),
+            Behavior::Pipeline(stages) => {
+                stages.iter_mut().map(|stage| &mut stage.branch).collect()
+            }
+            #[cfg(feature = "serde")]
+            Behavior::Opaque { children, .. } => children.iter_mut().collect(),
+        }
+    }
+
+    /// The descendant at `path`, this node for `[]`.
+    pub fn node_at(&self, path: &[usize]) -> Option<&Behavior<A>> {
+        match path.split_first() {
+            Some((i, rest)) => self.children().into_iter().nth(*i)?.node_at(rest),
+            None => Some(self),
+        }
+    }
+
+    pub fn node_at_mut(&mut self, path: &[usize]) -> Option<&mut Behavior<A>> {
+        match path.split_first() {
+            Some((i, rest)) => self.children_mut().into_iter().nth(*i)?.node_at_mut(rest),
+            None => Some(self),
+        }
+    }
+
+    /// The children of nodes taking any number of them, which can have children added and
+    /// removed.
+    pub(crate) fn child_list_mut(&mut self) -> Option<&mut Vec<Behavior<A>>> {
+        match self {
+            Behavior::Select(children)
+            | Behavior::Sequence(children)
+            | Behavior::ReactiveSequence(children)
+            | Behavior::ReactiveSelect(children)
+            | Behavior::RandomSelect(children)
+            | Behavior::AdaptiveSelect { children, .. }
+            | Behavior::Parallel { children, .. }
+            | Behavior::Composite { children, .. } => Some(children),
+            _ => None,
+        }
+    }
+
+    /// Calls `f` with the path and node of this node and all of its descendants, parents first.
+    pub fn walk<'a>(&'a self, f: &mut impl FnMut(&[usize], &'a Behavior<A>)) {
+        fn go<'a, A>(
+            node: &'a Behavior<A>,
+            path: &mut NodePath,
+            f: &mut impl FnMut(&[usize], &'a Behavior<A>),
+        ) {
+            f(path, node);
+            for (i, child) in node.children().into_iter().enumerate() {
+                path.push(i);
+                go(child, path, f);
+                path.pop();
+            }
+        }
+        go(self, &mut vec![], f)
+    }
+
+    /// Converts every action with `f`, which gets the path of the action and may replace it with
+    /// a whole subtree. Decorators and composites can't be converted along with the actions; they
+    /// are kept by name and params and have to be built again with their registry.
+    pub fn map_actions<B>(self, f: &mut impl FnMut(&[usize], A) -> Behavior<B>) -> Behavior<B> {
+        self.map_actions_at(&mut vec![], f)
+    }
+
+    /// Converts every action with `f`, keeping the shape of the tree.
+    pub fn map_action<B>(self, mut f: impl FnMut(A) -> B) -> Behavior<B> {
+        self.map_actions(&mut |_, action| Behavior::Action(f(action)))
+    }
+
+    fn map_actions_at<B>(
+        self,
+        path: &mut NodePath,
+        f: &mut impl FnMut(&[usize], A) -> Behavior<B>,
+    ) -> Behavior<B> {
+        let mut child = |index: usize, node: Behavior<A>, f: &mut _| {
+            path.push(index);
+            let mapped = node.map_actions_at(path, f);
+            path.pop();
+            mapped
+        };
+        match self {
+            Behavior::Action(a) => f(path, a),
+            Behavior::Invert(b) => Behavior::Invert(Box::new(child(0, *b, f))),
+            Behavior::Select(behaviors) => Behavior::Select(
+                behaviors
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, b)| child(i, b, f))
+                    .collect(),
+            ),
+            Behavior::Sequence(behaviors) => Behavior::Sequence(
+                behaviors
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, b)| child(i, b, f))
+                    .collect(),
+            ),
+            Behavior::ReactiveSequence(behaviors) => Behavior::ReactiveSequence(
+                behaviors
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, b)| child(i, b, f))
+                    .collect(),
+            ),
+            Behavior::ReactiveSelect(behaviors) => Behavior::ReactiveSelect(
+                behaviors
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, b)| child(i, b, f))
+                    .collect(),
+            ),
+            Behavior::While { condition, action } => Behavior::While {
+                condition: Box::new(child(0, *condition, f)),
+                action: Box::new(child(1, *action, f)),
+            },
+            Behavior::AdaptiveSelect { children, strategy } => Behavior::AdaptiveSelect {
+                children: children
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, b)| child(i, b, f))
+                    .collect(),
+                strategy,
+            },
+            Behavior::RandomSelect(behaviors) => Behavior::RandomSelect(
+                behaviors
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, b)| child(i, b, f))
+                    .collect(),
+            ),
+            Behavior::WeightedSelect(children) => Behavior::WeightedSelect(
+                children
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, (weight, b))| (weight, child(i, b, f)))
+                    .collect(),
+            ),
+            Behavior::Parallel { children, policy } => Behavior::Parallel {
+                children: children
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, b)| child(i, b, f))
+                    .collect(),
+                policy,
+            },
+            Behavior::Experiment {
+                key,
+                variants,
+                salt,
+            } => Behavior::Experiment {
+                key,
+                variants: variants
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, variant)| Variant {
+                        name: variant.name,
+                        weight: variant.weight,
+                        branch: child(i, variant.branch, f),
+                    })
+                    .collect(),
+                salt,
+            },
+            Behavior::LazySequence(_) => Behavior::LazySequence(Arc::new(Unmapped)),
+            Behavior::LazySelect(_) => Behavior::LazySelect(Arc::new(Unmapped)),
+            Behavior::Pipeline(stages) => Behavior::Pipeline(
+                stages
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, stage)| PipelineStage {
+                        name: stage.name,
+                        inputs: stage.inputs,
+                        outputs: stage.outputs,
+                        branch: child(i, stage.branch, f),
+                    })
+                    .collect(),
+            ),
+            Behavior::CheckKey { key, value } => Behavior::CheckKey { key, value },
+            Behavior::SetKey { key, value } => Behavior::SetKey { key, value },
+            Behavior::OnChanged { key, fire_on_first } => {
+                Behavior::OnChanged { key, fire_on_first }
+            }
+            Behavior::Expr { source } => Behavior::Expr { source },
+            Behavior::Compare { left, op, right } => Behavior::Compare { left, op, right },
+            Behavior::Log { level, message } => Behavior::Log { level, message },
+            Behavior::Throw { code, message } => Behavior::Throw { code, message },
+            Behavior::TryCatch { body, catch } => Behavior::TryCatch {
+                body: Box::new(child(0, *body, f)),
+                catch: catch
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, clause)| CatchClause {
+                        codes: clause.codes,
+                        branch: child(i + 1, clause.branch, f),
+                    })
+                    .collect(),
+            },
+            Behavior::SleepUntil { until } => Behavior::SleepUntil { until },
+            Behavior::Schedule { windows } => Behavior::Schedule { windows },
+            Behavior::GracefulTimeout {
+                child: b,
+                soft,
+                hard,
+            } => Behavior::GracefulTimeout {
+                child: Box::new(child(0, *b, f)),
+                soft,
+                hard,
+            },
+            Behavior::StallGuard {
+                child: b,
+                stall_after,
+                cancel,
+            } => Behavior::StallGuard {
+                child: Box::new(child(0, *b, f)),
+                stall_after,
+                cancel,
+            },
+            Behavior::Retry {
+                child: b,
+                max_attempts,
+                backoff,
+            } => Behavior::Retry {
+                child: Box::new(child(0, *b, f)),
+                max_attempts,
+                backoff,
+            },
+            Behavior::Repeat { child: b, times } => Behavior::Repeat {
+                child: Box::new(child(0, *b, f)),
+                times,
+            },
+            Behavior::Timeout { child: b, duration } => Behavior::Timeout {
+                child: Box::new(child(0, *b, f)),
+                duration,
+            },
+            Behavior::Cooldown { child: b, duration } => Behavior::Cooldown {
+                child: Box::new(child(0, *b, f)),
+                duration,
+            },
+            Behavior::Jitter { min, max } => Behavior::Jitter { min, max },
+            Behavior::Breakpoint { label } => Behavior::Breakpoint { label },
+            Behavior::Named {
+                name,
+                when_disabled,
+                child: b,
+            } => Behavior::Named {
+                name,
+                when_disabled,
+                child: Box::new(child(0, *b, f)),
+            },
+            Behavior::Namespaced {
+                namespace,
+                child: b,
+            } => Behavior::Namespaced {
+                namespace,
+                child: Box::new(child(0, *b, f)),
+            },
+            Behavior::AlwaysFail => Behavior::AlwaysFail,
+            Behavior::Spawn {
+                child: b,
+                handle_key,
+            } => Behavior::Spawn {
+                child: Box::new(child(0, *b, f)),
+                handle_key,
+            },
+            Behavior::Join {
+                handle_key,
+                timeout,
+            } => Behavior::Join {
+                handle_key,
+                timeout,
+            },
+            Behavior::Subtree { name, remapping } => Behavior::Subtree { name, remapping },
+            Behavior::Decorated {
+                decorator,
+                child: b,
+            } => Behavior::Decorated {
+                decorator: Arc::new(Unresolved::of(decorator.as_ref())),
+                child: Box::new(child(0, *b, f)),
+            },
+            Behavior::Composite { node, children } => Behavior::Composite {
+                node: Arc::new(Unresolved::of_composite(node.as_ref())),
+                children: children
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, b)| child(i, b, f))
+                    .collect(),
+            },
+            #[cfg(feature = "serde")]
+            Behavior::Opaque {
+                kind,
+                raw,
+                children,
+            } => Behavior::Opaque {
+                kind,
+                raw,
+                children: children
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, b)| child(i, b, f))
+                    .collect(),
+            },
+            Behavior::Assert { condition, message } => Behavior::Assert {
+                condition: Box::new(child(0, *condition, f)),
+                message,
+            },
+        }
+    }
+
+    /// Why this node can't run as configured, not looking at its children.
+    pub fn config_error(&self) -> Option<String> {
+        match self {
+            Behavior::Jitter { min, max } if min > max => Some(format!(
+                "jitter min {}ms is greater than max {}ms",
+                min.as_millis(),
+                max.as_millis()
+            )),
+            Behavior::Experiment { variants, .. } => experiment::config_error(variants),
+            Behavior::GracefulTimeout { soft, hard, .. } if soft >= hard => Some(format!(
+                "graceful timeout soft {}ms is not before hard {}ms",
+                soft.as_millis(),
+                hard.as_millis()
+            )),
+            Behavior::StallGuard { stall_after, .. } if stall_after.is_zero() => {
+                Some("stall guard has a zero stall_after".to_string())
+            }
+            Behavior::Retry {
+                max_attempts: 0, ..
+            } => Some("retry has zero max_attempts".to_string()),
+            Behavior::LazySequence(generator) | Behavior::LazySelect(generator)
+                if generator.is_placeholder() =>
+            {
+                Some("the child generator was lost converting the actions".to_string())
+            }
+            Behavior::Schedule { windows } if windows.is_empty() => {
+                Some("schedule has no windows".to_string())
+            }
+            // would always fail; an empty Sequence is fine and always succeeds
+            Behavior::Select(children)
+            | Behavior::ReactiveSelect(children)
+            | Behavior::AdaptiveSelect { children, .. }
+            | Behavior::RandomSelect(children)
+                if children.is_empty() =>
+            {
+                Some("select has no children".to_string())
+            }
+            Behavior::WeightedSelect(children) => random::config_error(children),
+            Behavior::Parallel {
+                children,
+                policy: ParallelPolicy::RequireAny,
+            } if children.is_empty() => Some("parallel node has no children".to_string()),
+            Behavior::Parallel {
+                policy:
+                    ParallelPolicy::Threshold {
+                        successes,
+                        failures,
+                    },
+                ..
+            } if *successes == 0 || *failures == 0 => {
+                Some("parallel thresholds must be at least 1".to_string())
+            }
+            Behavior::Parallel {
+                children,
+                policy: ParallelPolicy::Threshold { successes, .. },
+            } if *successes > children.len() => Some(format!(
+                "parallel success threshold of {} is above its {} children",
+                successes,
+                children.len()
+            )),
+            _ => None,
+        }
+    }
+
+    /// The blackboard keys this node writes itself, not including its children.
+    pub fn written_keys(&self) -> Vec<&str> {
+        match self {
+            Behavior::SetKey { key, .. }
+            | Behavior::Spawn {
+                handle_key: key, ..
+            } => {
+                vec![key.as_str()]
+            }
+            Behavior::Pipeline(stages) => stages
+                .iter()
+                .flat_map(|stage| stage.outputs.iter().map(String::as_str))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The blackboard keys this node reads itself, not including its children.
+    pub fn referenced_keys(&self) -> Vec<&str> {
+        match self {
+            Behavior::CheckKey { key, .. } | Behavior::OnChanged { key, .. } => {
+                vec![key.as_str()]
+            }
+            Behavior::Expr { source } => source.blackboard_keys(),
+            Behavior::Compare { left, right, .. } => [left, right]
+                .into_iter()
+                .filter_map(|side| match side {
+                    ValueRef::Key(key) => Some(key.as_str()),
+                    _ => None,
+                })
+                .collect(),
+            Behavior::Join { handle_key, .. } => vec![handle_key.as_str()],
+            Behavior::SetKey {
+                value: ValueRef::Key(key),
+                ..
+            } => vec![key.as_str()],
+            Behavior::Pipeline(stages) => dataflow::external_inputs(stages),
+            Behavior::SleepUntil {
+                until: ValueRef::Key(key),
+            }
+            | Behavior::Experiment {
+                key: ValueRef::Key(key),
+                ..
+            } => vec![key.as_str()],
+            Behavior::Log { message, .. } => template_keys(message),
+            Behavior::Throw {
+                message: Some(message),
+                ..
+            } => template_keys(message),
+            _ => vec![],
+        }
+    }
+}
+
+/// How an `AdaptiveSelect` picks the child it tries first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SelectStrategy {
+    // Tries a random child with probability `epsilon`, otherwise the one with the best success rate.
+    EpsilonGreedy { epsilon: f64 },
+    // Tries the child with the highest upper confidence bound; untried children first.
+    Ucb1,
+}
+
+impl SelectStrategy {
+    fn choose(&self, stats: &[ArmStats], rng: &mut TreeRng) -> usize {
+        match self {
+            SelectStrategy::EpsilonGreedy { epsilon } => {
+                if rng.gen_f64() < *epsilon {
+                    rng.gen_index(stats.len())
+                } else if let Some(untried) = stats.iter().position(|s| s.attempts() == 0) {
+                    untried
+                } else {
+                    best_index(stats.iter().map(ArmStats::success_rate))
+                }
+            }
+            SelectStrategy::Ucb1 => {
+                if let Some(untried) = stats.iter().position(|s| s.attempts() == 0) {
+                    return untried;
+                }
+                let total: u64 = stats.iter().map(ArmStats::attempts).sum();
+                let ln_total = (total as f64).ln();
+                best_index(
+                    stats
+                        .iter()
+                        .map(|s| s.success_rate() + (2.0 * ln_total / s.attempts() as f64).sqrt()),
+                )
+            }
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
-pub enum Response {
-    Success,
-    Running,
+// index of the largest value, the first one on ties
+fn best_index(values: impl Iterator<Item = f64>) -> usize {
+    let mut best = (0, f64::NEG_INFINITY);
+    for (i, v) in values.enumerate() {
+        if v > best.1 {
+            best = (i, v);
+        }
+    }
+    best.0
 }
 
-#[async_trait]
-pub trait Actionable: Serialize + Clone + Send + Sync {
-    type ActionError: From<anyhow::Error> + Send + Sync;
-    type ActionArgs: Clone + Send + Sync;
+/// Something a tree can run as a leaf.
+///
+/// Implementations write `run` as a plain `async fn`; the future it returns has to be `Send`.
+/// Impls written for the old `#[async_trait]` signature only need the attribute removed.
+pub trait Actionable: Clone + Send + Sync {
+    type ActionError: Send + Sync;
+    type ActionArgs: Send + Sync;
     type ActionState: Send + Sync;
 
-    async fn run(
+    fn run(
+        &self,
+        args: &Self::ActionArgs,
+        state: &mut Self::ActionState,
+    ) -> impl Future<Output = Result<Response, Self::ActionError>> + Send;
+
+    /// Like [`run`](Self::run), with `cx` giving the action the cancellation token, typed
+    /// blackboard and heartbeats of its run. The evaluator calls this one; by default it ignores
+    /// `cx` and calls `run`. Actions that override it can implement `run` as a call to it with
+    /// `ActionContext::default()`.
+    fn run_with(
         &self,
+        cx: &ActionContext,
         args: &Self::ActionArgs,
         state: &mut Self::ActionState,
-    ) -> Result<Response, Self::ActionError>;
+    ) -> impl Future<Output = Result<Response, Self::ActionError>> + Send {
+        let _ = cx;
+        self.run(args, state)
+    }
+
+    /// Name of the action for logs and tooling. Defaults to the name of the action type.
+    fn name(&self) -> String {
+        let type_name = std::any::type_name::<Self>();
+        let base = type_name.split('<').next().unwrap_or(type_name);
+        base.rsplit("::").next().unwrap_or(base).to_string()
+    }
+
+    /// Whether the action only reads the state, like a condition. Instances built
+    /// `with_purity_check` fail the run if a read-only action changes the state.
+    fn is_read_only(&self) -> bool {
+        false
+    }
 }
 
-#[async_trait]
 impl<A> Actionable for Behavior<A>
 where
-    A: Actionable + Serialize,
+    A: Actionable,
 {
-    type ActionError = <A as Actionable>::ActionError;
+    type ActionError = BehaviorError<A::ActionError>;
     type ActionArgs = <A as Actionable>::ActionArgs;
     type ActionState = <A as Actionable>::ActionState;
 
@@ -51,86 +1182,833 @@ where
         args: &Self::ActionArgs,
         state: &mut Self::ActionState,
     ) -> Result<Response, Self::ActionError> {
-        match self {
-            Behavior::Action(a) => {
-                let result = a.run(args, state).await;
-                result
+        self.run_in(&mut RunContext::<A>::default(), args, state)
+            .await
+    }
+
+    // a tree run as an action runs with the token and typed blackboard of the outer run
+    async fn run_with(
+        &self,
+        cx: &ActionContext,
+        args: &Self::ActionArgs,
+        state: &mut Self::ActionState,
+    ) -> Result<Response, Self::ActionError> {
+        let mut ctx = RunContext::<A> {
+            cancellation: cx.cancellation().clone(),
+            typed: cx.typed_blackboard().clone(),
+            ..RunContext::default()
+        };
+        self.run_in(&mut ctx, args, state).await
+    }
+}
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+impl<A> Behavior<A>
+where
+    A: Actionable,
+{
+    /// Like [`run`](Actionable::run), stopping once `token` is cancelled: actions already
+    /// running finish, but no node starts after that, and the run fails with a
+    /// [`BehaviorError::Cancelled`] error.
+    pub async fn run_cancellable(
+        &self,
+        args: &A::ActionArgs,
+        state: &mut A::ActionState,
+        token: &CancellationToken,
+    ) -> Result<Response, BehaviorError<A::ActionError>> {
+        let mut ctx = RunContext::<A> {
+            cancellation: token.clone(),
+            ..RunContext::default()
+        };
+        self.run_in(&mut ctx, args, state).await
+    }
+
+    /// Like [`run_cancellable`](Self::run_cancellable), also dropping the actions running when
+    /// `token` is cancelled, keeping whatever they changed in the state until then. The error
+    /// names the node that was running.
+    pub async fn run_abortable(
+        &self,
+        args: &A::ActionArgs,
+        state: &mut A::ActionState,
+        token: &CancellationToken,
+    ) -> Result<Response, BehaviorError<A::ActionError>> {
+        let mut ctx = RunContext::<A> {
+            cancellation: token.clone(),
+            ..RunContext::default()
+        };
+        let result = {
+            let run = self.run_in(&mut ctx, args, state);
+            match select(run, pin!(token.cancelled())).await {
+                Either::Left((result, _)) => Some(result),
+                Either::Right(_) => None,
+            }
+        };
+        result.unwrap_or_else(|| {
+            Err(BehaviorError::Cancelled(format!(
+                "cancelled while running the node at {:?}",
+                ctx.path
+            )))
+        })
+    }
+
+    /// Like [`run`](Actionable::run), telling `observer` about the nodes it runs and the events
+    /// they emit.
+    pub async fn run_with_observer(
+        &self,
+        args: &A::ActionArgs,
+        state: &mut A::ActionState,
+        observer: impl BehaviorObserver<A> + 'static,
+    ) -> Result<Response, BehaviorError<A::ActionError>> {
+        let mut ctx = RunContext::<A> {
+            observers: vec![Box::new(observer)],
+            ..RunContext::default()
+        };
+        self.run_in(&mut ctx, args, state).await
+    }
+
+    pub(crate) fn run_in<'a>(
+        &'a self,
+        ctx: &'a mut RunContext<A>,
+        args: &'a A::ActionArgs,
+        state: &'a mut A::ActionState,
+    ) -> BoxFuture<'a, Result<Response, BehaviorError<A::ActionError>>> {
+        Box::pin(async move {
+            // a soft timeout only asks the children to wrap up, they may still start nodes
+            if ctx.cancellation.reason() == Some(CancelReason::Requested) {
+                return Err(BehaviorError::Cancelled(format!(
+                    "cancelled before the node at {:?}",
+                    ctx.path
+                )));
+            }
+            for observer in &mut ctx.observers {
+                observer.on_node_enter(&ctx.path, self);
+            }
+            ctx.error_reported = false;
+            let result = self.evaluate(ctx, args, state).await;
+            for observer in &mut ctx.observers {
+                if let Err(err) = &result {
+                    if !ctx.error_reported {
+                        observer.on_error(&ctx.path, self, err);
+                    }
+                }
+                observer.on_node_exit(&ctx.path, self, &result);
+            }
+            ctx.error_reported = result.is_err();
+            result
+        })
+    }
+
+    async fn evaluate(
+        &self,
+        ctx: &mut RunContext<A>,
+        args: &A::ActionArgs,
+        state: &mut A::ActionState,
+    ) -> Result<Response, BehaviorError<A::ActionError>> {
+        if let Some(visited) = &mut ctx.visited {
+            visited.push(ctx.path.clone());
+        }
+        if let Some(outcome) = ctx.toggles.check(&ctx.path, self) {
+            ctx.emit(TreeEvent::SkippedDisabled { outcome });
+            return match outcome {
+                WhenDisabled::Succeed => Ok(Response::Success),
+                WhenDisabled::Fail => Ok(Response::Failure),
+            };
+        }
+        if let Some(debugger) = &ctx.debugger {
+            let is_node = matches!(self, Behavior::Breakpoint { .. });
+            if !is_node && debugger.has_breakpoint(&ctx.path) {
+                ctx.hit_breakpoint(None).await;
             }
+        }
+        match self {
+            Behavior::Action(a) => ctx.run_action(a, args, state).await,
             Behavior::Invert(b) => {
-                let result = b.run(args, state).await;
+                let result = b.run_child(0, ctx, args, state).await;
                 match result {
-                    Ok(r) => {
-                        let inverted = match r {
-                            Response::Success => {
-                                Err(Self::ActionError::from(anyhow!("Inverted Ok")))
-                            }
-                            Response::Running => Ok(Response::Running),
-                        };
-                        inverted
-                    }
-                    Err(_) => Ok(Response::Success),
+                    Ok(r) => match r {
+                        Response::Success => Ok(Response::Failure),
+                        Response::Running => Ok(Response::Running),
+                        Response::Failure => Ok(Response::Success),
+                    },
+                    Err(e) => Err(e),
                 }
             }
             Behavior::Select(behaviors) => {
-                for b in behaviors {
-                    let result = b.run(args, state).await;
+                let mut batch = Batch::new();
+                for i in ctx.take_cursor(behaviors.len())..behaviors.len() {
+                    let result = batch.run(behaviors, i, ctx, args, state).await;
                     match result {
+                        Ok(Response::Failure) => continue,
+                        Ok(Response::Running) => {
+                            ctx.set_cursor(i);
+                            return Ok(Response::Running);
+                        }
                         Ok(r) => return Ok(r),
-                        Err(_) => continue,
+                        Err(e) if e.propagates() => return Err(e),
+                        Err(e) => return Err(one_child_failed(&ctx.path, i, e)),
                     }
                 }
-                Err(Self::ActionError::from(anyhow!("No behavior successful")))
-            } // Behavior::Sequence(_) => {}
-            // Behavior::Success => {}
-            // Behavior::While { .. } => {}
+                Ok(Response::Failure)
+            }
             Behavior::Sequence(behaviors) => {
-                for b in behaviors {
-                    let result = b.run(args, state).await;
+                let mut batch = Batch::new();
+                for i in ctx.take_cursor(behaviors.len())..behaviors.len() {
+                    let result = batch.run(behaviors, i, ctx, args, state).await;
                     match result {
-                        Ok(r) => continue,
-                        Err(_) => {
-                            return Err(Self::ActionError::from(anyhow!("one behavior failed")))
+                        Ok(Response::Failure) => return Ok(Response::Failure),
+                        Ok(Response::Running) => {
+                            ctx.set_cursor(i);
+                            return Ok(Response::Running);
                         }
+                        Ok(_) => continue,
+                        Err(e) if e.propagates() => return Err(e),
+                        Err(e) => return Err(one_child_failed(&ctx.path, i, e)),
                     }
                 }
                 Ok(Response::Success)
             }
-            Behavior::While { condition, action } => loop {
-                let condition_result = condition.run(args, state).await;
-
-                match condition_result {
-                    Err(_) => return Ok(Response::Success),
-                    Ok(_) => {
-                        let action_result = action.run(args, state).await;
-                        match action_result {
-                            Ok(_) => continue,
-                            Err(_) => {
-                                return Err(Self::ActionError::from(anyhow!("action failed")))
-                            }
+            Behavior::ReactiveSequence(behaviors) => {
+                let running = ctx.take_cursor(behaviors.len());
+                for (i, child) in behaviors.iter().enumerate() {
+                    let decided = match child.run_child(i, ctx, args, state).await {
+                        Ok(Response::Success) => continue,
+                        Ok(Response::Running) => {
+                            ctx.set_cursor(i);
+                            Ok(Response::Running)
+                        }
+                        Ok(Response::Failure) => Ok(Response::Failure),
+                        Err(e) if e.propagates() => Err(e),
+                        Err(e) => Err(one_child_failed(&ctx.path, i, e)),
+                    };
+                    ctx.preempt(running, i);
+                    return decided;
+                }
+                Ok(Response::Success)
+            }
+            Behavior::ReactiveSelect(behaviors) => {
+                let running = ctx.take_cursor(behaviors.len());
+                for (i, child) in behaviors.iter().enumerate() {
+                    let decided = match child.run_child(i, ctx, args, state).await {
+                        Ok(Response::Failure) => continue,
+                        Ok(Response::Running) => {
+                            ctx.set_cursor(i);
+                            Ok(Response::Running)
+                        }
+                        Ok(r) => Ok(r),
+                        Err(e) if e.propagates() => Err(e),
+                        Err(e) => Err(one_child_failed(&ctx.path, i, e)),
+                    };
+                    ctx.preempt(running, i);
+                    return decided;
+                }
+                Ok(Response::Failure)
+            }
+            Behavior::LazySequence(generator) | Behavior::LazySelect(generator)
+                if generator.is_placeholder() =>
+            {
+                Err(BehaviorError::failed(
+                    self.config_error().unwrap_or_default(),
+                ))
+            }
+            Behavior::LazySequence(generator) => {
+                let mut i = ctx.take_cursor(usize::MAX);
+                while let Some(child) = generator.child(i, state) {
+                    match child.run_child(i, ctx, args, state).await {
+                        Ok(Response::Failure) => return Ok(Response::Failure),
+                        Ok(Response::Running) => {
+                            ctx.set_cursor(i);
+                            return Ok(Response::Running);
                         }
+                        Ok(_) => i += 1,
+                        Err(e) if e.propagates() => return Err(e),
+                        Err(e) => return Err(one_child_failed(&ctx.path, i, e)),
                     }
                 }
-            },
-        }
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use crate::behavior_tree::Behavior::*;
-    use crate::behavior_tree::{Actionable, Behavior, Response};
-    use anyhow::anyhow;
-    use async_trait::async_trait;
-    use serde::Serialize;
-
-    #[derive(Clone, Debug, Serialize)]
-    enum MyAction {
-        Increase,
+                Ok(Response::Success)
+            }
+            Behavior::LazySelect(generator) => {
+                let mut i = ctx.take_cursor(usize::MAX);
+                while let Some(child) = generator.child(i, state) {
+                    match child.run_child(i, ctx, args, state).await {
+                        Ok(Response::Failure) => {}
+                        Ok(Response::Running) => {
+                            ctx.set_cursor(i);
+                            return Ok(Response::Running);
+                        }
+                        Ok(r) => return Ok(r),
+                        Err(e) if e.propagates() => return Err(e),
+                        Err(e) => return Err(one_child_failed(&ctx.path, i, e)),
+                    }
+                    i += 1;
+                }
+                Ok(Response::Failure)
+            }
+            Behavior::While { condition, action } => {
+                // a body that was running resumes without checking the condition first
+                let mut in_action = ctx.take_cursor(2) == 1;
+                loop {
+                    if !in_action {
+                        let condition_result = condition.run_child(0, ctx, args, state).await;
+                        match condition_result {
+                            Err(e) if e.propagates() => return Err(e),
+                            Err(e) => return Err(one_child_failed(&ctx.path, 0, e)),
+                            Ok(Response::Failure) => return Ok(Response::Success),
+                            Ok(Response::Running) => return Ok(Response::Running),
+                            Ok(Response::Success) => {}
+                        }
+                    }
+                    in_action = false;
+                    let action_result = action.run_child(1, ctx, args, state).await;
+                    match action_result {
+                        Ok(Response::Failure) => return Ok(Response::Failure),
+                        Ok(Response::Running) => {
+                            ctx.set_cursor(1);
+                            return Ok(Response::Running);
+                        }
+                        Ok(Response::Success) => continue,
+                        Err(e) if e.propagates() => return Err(e),
+                        Err(e) => return Err(one_child_failed(&ctx.path, 1, e)),
+                    }
+                }
+            }
+            Behavior::AdaptiveSelect { children, strategy } => {
+                if children.is_empty() {
+                    return Ok(Response::Failure);
+                }
+                let stats = ctx.adaptive_stats(children.len());
+                let first = match stats.iter().position(|arm| arm.running) {
+                    Some(running) => {
+                        stats[running].running = false;
+                        running
+                    }
+                    None => {
+                        let stats = stats.clone();
+                        strategy.choose(&stats, &mut ctx.rng)
+                    }
+                };
+                let order =
+                    std::iter::once(first).chain((0..children.len()).filter(|i| *i != first));
+
+                for i in order {
+                    let result = children[i].run_child(i, ctx, args, state).await;
+                    match result {
+                        Ok(Response::Failure) => {
+                            ctx.adaptive_stats(children.len())[i].failures += 1;
+                        }
+                        Ok(Response::Running) => {
+                            ctx.adaptive_stats(children.len())[i].running = true;
+                            return Ok(Response::Running);
+                        }
+                        Ok(r) => {
+                            ctx.adaptive_stats(children.len())[i].successes += 1;
+                            return Ok(r);
+                        }
+                        Err(e) => {
+                            if !e.is_fatal() {
+                                ctx.adaptive_stats(children.len())[i].failures += 1;
+                            }
+                            if e.propagates() {
+                                return Err(e);
+                            }
+                            return Err(one_child_failed(&ctx.path, i, e));
+                        }
+                    }
+                }
+                Ok(Response::Failure)
+            }
+            Behavior::RandomSelect(children) => {
+                let mut order: Vec<usize> = (0..children.len()).collect();
+                ctx.rng.shuffle(&mut order);
+                let children: Vec<_> = children.iter().collect();
+                random::select(&children, order, ctx, args, state).await
+            }
+            Behavior::WeightedSelect(children) => {
+                if let Some(err) = self.config_error() {
+                    return Err(BehaviorError::failed(err));
+                }
+                let weights: Vec<_> = children.iter().map(|(weight, _)| *weight).collect();
+                let order = random::weighted_order(&weights, &mut ctx.rng);
+                let children: Vec<_> = children.iter().map(|(_, child)| child).collect();
+                random::select(&children, order, ctx, args, state).await
+            }
+            Behavior::Parallel { children, policy } => {
+                parallel::run(children, *policy, ctx, args, state).await
+            }
+            Behavior::Pipeline(stages) => {
+                let start = ctx.take_cursor(stages.len());
+                for (i, stage) in stages.iter().enumerate().skip(start) {
+                    let missing = stage
+                        .inputs
+                        .iter()
+                        .find(|input| !ctx.blackboard.contains_key(input));
+                    if let Some(input) = missing {
+                        return Err(BehaviorError::failed(format!(
+                            "pipeline stage `{}` reads `{}`, which is not set",
+                            stage.name, input
+                        )));
+                    }
+                    let before = ctx.blackboard.clone();
+                    let result = stage.branch.run_child(i, ctx, args, state).await;
+                    let undeclared =
+                        dataflow::revert_undeclared(&before, &mut ctx.blackboard, &stage.outputs);
+                    for key in undeclared {
+                        ctx.emit(TreeEvent::UndeclaredWrite {
+                            stage: stage.name.clone(),
+                            key,
+                        });
+                    }
+                    match result {
+                        Ok(Response::Failure) => return Ok(Response::Failure),
+                        Ok(Response::Running) => {
+                            ctx.set_cursor(i);
+                            return Ok(Response::Running);
+                        }
+                        Ok(_) => continue,
+                        Err(e) if e.propagates() => return Err(e),
+                        Err(error) => {
+                            let mut path = ctx.path.clone();
+                            path.push(i);
+                            return Err(BehaviorError::ChildrenFailed {
+                                message: format!("pipeline stage `{}` failed", stage.name),
+                                children: vec![ChildError { path, error }],
+                            });
+                        }
+                    }
+                }
+                Ok(Response::Success)
+            }
+            Behavior::Experiment {
+                key,
+                variants,
+                salt,
+            } => {
+                if let Some(err) = self.config_error() {
+                    return Err(BehaviorError::failed(err));
+                }
+                let unit = key
+                    .resolve(&ctx.blackboard, &ctx.accessors, state)
+                    .map_err(|err| {
+                        BehaviorError::failed(format!("experiment unit {}: {}", key, err))
+                    })?;
+                let i = experiment::assign(variants, salt, &unit);
+                ctx.emit(TreeEvent::VariantAssigned {
+                    unit: experiment::unit_name(&unit),
+                    variant: variants[i].name.clone(),
+                });
+                let result = variants[i].branch.run_child(i, ctx, args, state).await;
+                match &result {
+                    Ok(Response::Success) => ctx.variant_stats(variants.len())[i].successes += 1,
+                    Ok(Response::Failure) => ctx.variant_stats(variants.len())[i].failures += 1,
+                    Err(e) if !e.is_fatal() => ctx.variant_stats(variants.len())[i].failures += 1,
+                    _ => {}
+                }
+                result
+            }
+            Behavior::CheckKey { key, value } => match ctx.blackboard.get(key) {
+                Some(actual) if actual == value => Ok(Response::Success),
+                _ => Ok(Response::Failure),
+            },
+            Behavior::SetKey { key, value } => {
+                let value = value
+                    .resolve(&ctx.blackboard, &ctx.accessors, state)
+                    .map_err(|err| {
+                        BehaviorError::failed(format!("value for `{}`: {}", key, err))
+                    })?;
+                ctx.blackboard.set(key.clone(), value);
+                Ok(Response::Success)
+            }
+            Behavior::OnChanged { key, fire_on_first } => {
+                let current = ctx.blackboard.get(key).cloned();
+                let changed = match ctx.last_seen() {
+                    Some(last) => *last != current,
+                    None => {
+                        if !fire_on_first {
+                            ctx.set_last_seen(current.clone());
+                        }
+                        *fire_on_first
+                    }
+                };
+                if changed {
+                    ctx.set_last_seen(current);
+                    Ok(Response::Success)
+                } else {
+                    Ok(Response::Failure)
+                }
+            }
+            Behavior::Expr { source } => {
+                match source.eval(&ctx.blackboard, &*state, ctx.field_access) {
+                    Ok(BlackboardValue::Bool(true)) => Ok(Response::Success),
+                    Ok(BlackboardValue::Bool(false)) => Ok(Response::Failure),
+                    Ok(other) => Err(BehaviorError::failed(format!(
+                        "expression `{}` evaluated to {}, expected a bool",
+                        source, other
+                    ))),
+                    Err(err) => Err(BehaviorError::failed(err.to_string())),
+                }
+            }
+            Behavior::Compare { left, op, right } => {
+                match compare::compare(left, *op, right, &ctx.blackboard, &ctx.accessors, state) {
+                    Ok(true) => Ok(Response::Success),
+                    Ok(false) => Ok(Response::Failure),
+                    Err(err) => Err(BehaviorError::failed(err.to_string())),
+                }
+            }
+            Behavior::Log { level, message } => {
+                let message = ctx.blackboard.interpolate(message);
+                ctx.emit(TreeEvent::LogEmitted {
+                    level: *level,
+                    message,
+                });
+                Ok(Response::Success)
+            }
+            Behavior::Throw { code, message } => {
+                let thrown = Thrown {
+                    code: code.clone(),
+                    message: message.as_ref().map(|m| ctx.blackboard.interpolate(m)),
+                    path: ctx.path.clone(),
+                };
+                ctx.emit(TreeEvent::Thrown {
+                    code: thrown.code.clone(),
+                    message: thrown.message.clone(),
+                });
+                Err(BehaviorError::Thrown(thrown))
+            }
+            Behavior::TryCatch { body, catch } => {
+                let result = body.run_child(0, ctx, args, state).await;
+                let code = match &result {
+                    Err(e) if e.is_fatal() => return result,
+                    Err(BehaviorError::Thrown(thrown)) => Some(thrown.code.as_str()),
+                    Ok(Response::Failure) | Err(_) => None,
+                    Ok(_) => return result,
+                };
+                match catch.iter().position(|clause| clause.matches(code)) {
+                    Some(i) => catch[i].branch.run_child(i + 1, ctx, args, state).await,
+                    None => result,
+                }
+            }
+            Behavior::SleepUntil { until } => {
+                let deadline = until
+                    .resolve(&ctx.blackboard, &ctx.accessors, state)
+                    .and_then(|value| parse_timestamp(&value))
+                    .map_err(|err| {
+                        BehaviorError::failed(format!("invalid timestamp in {}: {}", until, err))
+                    })?;
+                let Ok(remaining) = deadline.duration_since(ctx.clock.now()) else {
+                    return Ok(Response::Success);
+                };
+                if ctx.sleep(remaining).await {
+                    Ok(Response::Success)
+                } else {
+                    Err(BehaviorError::Cancelled(format!(
+                        "cancelled while sleeping until {}",
+                        until
+                    )))
+                }
+            }
+            Behavior::Schedule { windows } => {
+                if let Some(err) = self.config_error() {
+                    return Err(BehaviorError::failed(err));
+                }
+                let now = ctx.clock.now();
+                if windows.iter().any(|window| window.contains(now)) {
+                    Ok(Response::Success)
+                } else {
+                    Ok(Response::Failure)
+                }
+            }
+            Behavior::GracefulTimeout { child, soft, hard } => {
+                if let Some(err) = self.config_error() {
+                    return Err(BehaviorError::failed(err));
+                }
+                let outer = ctx.cancellation.clone();
+                let signal = CancellationToken::new();
+                ctx.cancellation = signal.clone();
+                let depth = ctx.path.len();
+                let sleeper = ctx.sleeper.clone();
+                let hard_deadline = sleeper.sleep(*hard);
+                let result = {
+                    let mut run = child.run_child(0, ctx, args, state);
+                    let soft_deadline = async {
+                        match select(sleeper.sleep(*soft), pin!(outer.cancelled())).await {
+                            Either::Left(_) => CancelReason::TimedOut,
+                            Either::Right(_) => outer.reason().unwrap_or(CancelReason::Requested),
+                        }
+                    };
+                    match select(&mut run, pin!(soft_deadline)).await {
+                        Either::Left((result, _)) => Some(result),
+                        Either::Right((reason, _)) => {
+                            signal.cancel_with(reason);
+                            match select(run, hard_deadline).await {
+                                Either::Left((result, _)) => Some(result),
+                                Either::Right(_) => None,
+                            }
+                        }
+                    }
+                };
+                // a child stopped midway leaves its part of the path behind
+                ctx.path.truncate(depth);
+                ctx.cancellation = outer;
+                // a child stopped at the hard deadline fails like any other child
+                result.unwrap_or(Ok(Response::Failure))
+            }
+            Behavior::StallGuard {
+                child,
+                stall_after,
+                cancel,
+            } => {
+                if let Some(err) = self.config_error() {
+                    return Err(BehaviorError::failed(err));
+                }
+                let heartbeats = ctx.heartbeats.clone();
+                let guarded = ctx.path.clone();
+                let mut stalls = vec![];
+                let result = {
+                    let run = child.run_child(0, ctx, args, state);
+                    let watch = async {
+                        loop {
+                            let stall =
+                                heartbeat::stalled(&heartbeats, &guarded, *stall_after).await;
+                            stalls.push(stall);
+                            if *cancel {
+                                break;
+                            }
+                        }
+                    };
+                    match select(run, pin!(watch)).await {
+                        Either::Left((result, _)) => Some(result),
+                        Either::Right(_) => None,
+                    }
+                };
+                // a child stopped midway leaves its part of the path behind
+                ctx.path.truncate(guarded.len());
+                for (path, age) in &stalls {
+                    ctx.emit(TreeEvent::Stalled {
+                        path: path.clone(),
+                        last_heartbeat_age: *age,
+                    });
+                }
+                // the `Stalled` events tell why a stopped child failed
+                result.unwrap_or(Ok(Response::Failure))
+            }
+            Behavior::Retry {
+                child,
+                max_attempts,
+                backoff,
+            } => {
+                if let Some(err) = self.config_error() {
+                    return Err(BehaviorError::failed(err));
+                }
+                let mut failed = ctx.take_count();
+                loop {
+                    let result = child.run_child(0, ctx, args, state).await;
+                    match &result {
+                        Ok(Response::Success) => return result,
+                        Ok(Response::Running) => {
+                            ctx.set_count(failed);
+                            return result;
+                        }
+                        Err(e) if e.is_hard() => return result,
+                        Ok(Response::Failure) | Err(_) => failed += 1,
+                    }
+                    if failed >= *max_attempts {
+                        return result;
+                    }
+                    if let Some(backoff) = backoff {
+                        if !ctx.sleep(*backoff).await {
+                            return Err(BehaviorError::Cancelled(
+                                "cancelled during retry backoff".to_string(),
+                            ));
+                        }
+                    }
+                }
+            }
+            Behavior::Repeat { child, times } => {
+                let mut succeeded = ctx.take_count();
+                while succeeded < *times {
+                    match child.run_child(0, ctx, args, state).await {
+                        Ok(Response::Success) => succeeded += 1,
+                        Ok(Response::Running) => {
+                            ctx.set_count(succeeded);
+                            return Ok(Response::Running);
+                        }
+                        other => return other,
+                    }
+                }
+                Ok(Response::Success)
+            }
+            Behavior::Timeout { child, duration } => {
+                let depth = ctx.path.len();
+                let deadline = ctx.sleeper.sleep(*duration);
+                let run = child.run_child(0, ctx, args, state);
+                let result = match select(run, deadline).await {
+                    Either::Left((result, _)) => Some(result),
+                    Either::Right(_) => None,
+                };
+                // a child stopped midway leaves its part of the path behind
+                ctx.path.truncate(depth);
+                result.unwrap_or(Ok(Response::Failure))
+            }
+            Behavior::Cooldown { child, duration } => {
+                let now = ctx.sleeper.now();
+                if let Some(NodeMemory::FinishedAt(finished)) = ctx.memory.get(&ctx.path) {
+                    if now < *finished + *duration {
+                        return Ok(Response::Failure);
+                    }
+                }
+                let result = child.run_child(0, ctx, args, state).await;
+                if matches!(result, Ok(Response::Success | Response::Failure)) {
+                    let finished = NodeMemory::FinishedAt(ctx.sleeper.now());
+                    ctx.memory.insert(ctx.path.clone(), finished);
+                }
+                result
+            }
+            Behavior::Jitter { min, max } => {
+                if let Some(err) = self.config_error() {
+                    return Err(BehaviorError::failed(err));
+                }
+                let millis = ctx
+                    .rng
+                    .gen_inclusive(min.as_millis() as u64, max.as_millis() as u64);
+                let delay = Duration::from_millis(millis);
+                ctx.emit(TreeEvent::JitterChosen { delay });
+                if ctx.sleep(delay).await {
+                    Ok(Response::Success)
+                } else {
+                    Err(BehaviorError::Cancelled(
+                        "cancelled during jitter".to_string(),
+                    ))
+                }
+            }
+            Behavior::Breakpoint { label } => {
+                if ctx.debugger.is_some() {
+                    ctx.hit_breakpoint(Some(label.clone())).await;
+                }
+                Ok(Response::Success)
+            }
+            Behavior::Named { child, .. } => child.run_child(0, ctx, args, state).await,
+            Behavior::Namespaced { namespace, child } => {
+                ctx.namespaces.insert(ctx.path.clone(), namespace.clone());
+                let result = child.run_child(0, ctx, args, state).await;
+                ctx.namespaces.remove(&ctx.path);
+                result
+            }
+            Behavior::AlwaysFail => Ok(Response::Failure),
+            #[cfg(feature = "tokio")]
+            Behavior::Spawn { child, handle_key } => {
+                let typed = ctx.typed_blackboard();
+                let Some(spawner) = &mut ctx.spawner else {
+                    return Err(BehaviorError::failed(
+                        "spawning needs an instance built with_spawning",
+                    ));
+                };
+                let sleeper = ctx.sleeper.clone();
+                let id = spawner.spawn(child, &ctx.path, typed, sleeper, args, state);
+                ctx.blackboard.set(handle_key.clone(), id);
+                Ok(Response::Success)
+            }
+            #[cfg(feature = "tokio")]
+            Behavior::Join {
+                handle_key,
+                timeout,
+            } => spawn::join(ctx, handle_key, *timeout).await,
+            #[cfg(not(feature = "tokio"))]
+            Behavior::Spawn { .. } | Behavior::Join { .. } => {
+                Err(BehaviorError::failed("spawning needs the `tokio` feature"))
+            }
+            Behavior::Subtree { name, .. } => Err(BehaviorError::failed(format!(
+                "subtree `{}` was never resolved by a SubtreeRegistry",
+                name
+            ))),
+            Behavior::Decorated { decorator, child } => {
+                decorator
+                    .decorate(Executor::new(child, ctx), args, state)
+                    .await
+            }
+            Behavior::Composite { node, children } => {
+                node.run(Children::new(children, ctx), args, state).await
+            }
+            #[cfg(feature = "serde")]
+            Behavior::Opaque { kind, .. } => Err(BehaviorError::failed(format!(
+                "node kind `{}` is unknown to this version and can't run",
+                kind
+            ))),
+            Behavior::Assert { condition, message } => {
+                match condition.run_child(0, ctx, args, state).await {
+                    Err(e) if e.is_hard() => Err(e),
+                    Ok(Response::Failure) | Err(_) => {
+                        let failed = AssertionFailed {
+                            message: message.clone(),
+                            path: ctx.path.clone(),
+                            state: ctx.state_debug.map(|debug| debug(state)),
+                        };
+                        match ctx.assert_mode {
+                            AssertMode::Fatal => Err(BehaviorError::AssertionFailed(failed)),
+                            AssertMode::Warn => {
+                                ctx.emit(TreeEvent::LogEmitted {
+                                    level: LogLevel::Warn,
+                                    message: failed.to_string(),
+                                });
+                                Ok(Response::Success)
+                            }
+                        }
+                    }
+                    ok => ok,
+                }
+            }
+        }
+    }
+
+    fn run_child<'a>(
+        &'a self,
+        index: usize,
+        ctx: &'a mut RunContext<A>,
+        args: &'a A::ActionArgs,
+        state: &'a mut A::ActionState,
+    ) -> BoxFuture<'a, Result<Response, BehaviorError<A::ActionError>>> {
+        Box::pin(async move {
+            ctx.path.push(index);
+            let result = self.run_in(ctx, args, state).await;
+            ctx.path.pop();
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tokio")]
+    use crate::behavior_tree::cancel::{CancelReason, CancellationToken};
+    #[cfg(feature = "tokio")]
+    use crate::behavior_tree::clock::{SystemClock, TokioClock};
+    use crate::behavior_tree::compare::{AccessorRegistry, CompareOp, ValueRef};
+    #[cfg(feature = "tokio")]
+    use crate::behavior_tree::debugger::{DebugController, PausedAt};
+    use crate::behavior_tree::expr::Expression;
+    use crate::behavior_tree::observer::{BehaviorObserver, LogLevel, TreeEvent};
+    use crate::behavior_tree::runner::{AssertMode, Runner};
+    #[cfg(feature = "tokio")]
+    use crate::behavior_tree::ActionContext;
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{
+        Actionable, AssertionFailed, Behavior, BehaviorError, CatchClause, NodeMemory, NodePath,
+        Response, SelectStrategy, Thrown, TreeInstance, TreeRng,
+    };
+    use std::sync::{Arc, Mutex};
+    #[cfg(any(feature = "serde", feature = "tokio"))]
+    use std::time::Duration;
+    #[cfg(feature = "tokio")]
+    use std::time::UNIX_EPOCH;
+    #[cfg(feature = "tokio")]
+    use tokio::time::{timeout, Instant};
+
+    #[derive(Clone, Debug)]
+    enum MyAction {
+        Increase,
         Decrease,
         IsLowerThan5,
     }
 
-    #[async_trait]
     impl Actionable for MyAction {
         type ActionError = anyhow::Error;
         type ActionArgs = ();
@@ -138,7 +2016,7 @@ mod tests {
 
         async fn run(
             &self,
-            args: &Self::ActionArgs,
+            _args: &Self::ActionArgs,
             state: &mut Self::ActionState,
         ) -> Result<Response, Self::ActionError> {
             match self {
@@ -154,20 +2032,20 @@ mod tests {
                     if state.0 < 5 {
                         Ok(Response::Success)
                     } else {
-                        Err(anyhow!(">= 5"))
+                        Ok(Response::Failure)
                     }
                 }
             }
         }
     }
 
-    #[derive(Debug, Eq, PartialEq)]
+    #[derive(Debug, Clone, Eq, PartialEq)]
     struct MyState(i32);
 
     #[tokio::test]
     async fn test_select() {
         let bt: Behavior<MyAction> =
-            Select(vec![Action(MyAction::Increase), Action(MyAction::Decrease)]).into();
+            Select(vec![Action(MyAction::Increase), Action(MyAction::Decrease)]);
 
         let mut my_state = MyState(0);
 
@@ -179,7 +2057,7 @@ mod tests {
     #[tokio::test]
     async fn test_sequence() {
         let bt: Behavior<MyAction> =
-            Sequence(vec![Action(MyAction::Increase), Action(MyAction::Decrease)]).into();
+            Sequence(vec![Action(MyAction::Increase), Action(MyAction::Decrease)]);
 
         let mut my_state = MyState(0);
 
@@ -202,6 +2080,191 @@ mod tests {
         assert_eq!(my_state, MyState(5));
     }
 
+    #[test]
+    fn test_action_name_defaults_to_type_name() {
+        assert_eq!(MyAction::Increase.name(), "MyAction");
+    }
+
+    // an error type without any conversion from `anyhow::Error`
+    #[derive(Debug, PartialEq, thiserror::Error)]
+    enum DockError {
+        #[error("bay {0} doesn't exist")]
+        NoSuchBay(u32),
+    }
+
+    #[derive(Clone, Debug)]
+    struct Dock(u32);
+
+    impl Actionable for Dock {
+        type ActionError = DockError;
+        type ActionArgs = ();
+        type ActionState = Vec<u32>;
+
+        async fn run(&self, _: &(), docked: &mut Vec<u32>) -> Result<Response, DockError> {
+            if self.0 > 9 {
+                return Err(DockError::NoSuchBay(self.0));
+            }
+            if docked.contains(&self.0) {
+                return Ok(Response::Failure);
+            }
+            docked.push(self.0);
+            Ok(Response::Success)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_action_error_without_anyhow_conversion() {
+        let bt = Sequence(vec![
+            Select(vec![Action(Dock(1)), Action(Dock(2))]),
+            Invert(Box::new(Action(Dock(2)))),
+            While {
+                condition: Box::new(Invert(Box::new(Action(Dock(3))))),
+                action: Box::new(Action(Dock(4))),
+            },
+        ]);
+        let mut runner = Runner::new(TreeInstance::new(bt));
+        let mut docked = vec![1];
+
+        runner.tick(&(), &mut docked).await.unwrap();
+        assert_eq!(docked, vec![1, 2, 3]);
+
+        let err = Action(Dock(12)).run(&(), &mut docked).await.unwrap_err();
+        assert!(matches!(
+            err,
+            BehaviorError::Action(DockError::NoSuchBay(12))
+        ));
+        assert_eq!(err.to_string(), "bay 12 doesn't exist");
+
+        // both bays are taken now
+        let result = runner.tick(&(), &mut docked).await.unwrap();
+        assert_eq!(result, Response::Failure);
+    }
+
+    #[tokio::test]
+    async fn test_failing_children_keep_their_errors() {
+        // fails with an error while `key` isn't set
+        let check = |key: &str| Expr {
+            source: Expression::parse(&format!("bb.{} == \"full\"", key)).unwrap(),
+        };
+        let bt = Sequence(vec![
+            Action(MyAction::Increase),
+            Select(vec![
+                Sequence(vec![Action(MyAction::Increase), check("fuel")]),
+                Action(MyAction::IsLowerThan5),
+                check("cargo"),
+                Action(MyAction::IsLowerThan5),
+            ]),
+        ]);
+        let mut state = MyState(4);
+        let err = bt.run(&(), &mut state).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "one behavior failed ([1]: one behavior failed ([1, 0]: one behavior failed \
+             ([1, 0, 1]: expression `bb.fuel == \"full\"`: blackboard key `fuel` is not set)))"
+        );
+        assert_eq!(err.origin(), Some(&[1, 0, 1][..]));
+
+        let [select] = err.child_errors() else {
+            panic!("{:?}", err);
+        };
+        assert_eq!(select.path, [1]);
+        let [sequence] = select.error.child_errors() else {
+            panic!("{:?}", select.error);
+        };
+        assert_eq!(sequence.path, [1, 0]);
+        assert!(matches!(
+            &sequence.error.child_errors()[0].error,
+            BehaviorError::Failed(message) if message.ends_with("key `fuel` is not set")
+        ));
+
+        // children returning `Failure` have no errors to keep, the select just fails
+        let result = Select(vec![Action(MyAction::IsLowerThan5), AlwaysFail])
+            .run(&(), &mut state)
+            .await;
+        assert_eq!(result.unwrap(), Response::Failure);
+
+        // nor do the bodies of loops lose theirs
+        let bt = While {
+            condition: Box::new(Action(MyAction::IsLowerThan5)),
+            action: Box::new(check("fuel")),
+        };
+        let err = bt.run(&(), &mut MyState(0)).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "one behavior failed ([1]: expression `bb.fuel == \"full\"`: blackboard key `fuel` \
+             is not set)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_action_errors_abort_the_run() {
+        // the select doesn't try its second child after the first one errs
+        let mut docked = vec![];
+        let bt = Select(vec![Action(Dock(12)), Action(Dock(5))]);
+        let err = bt.run(&(), &mut docked).await.unwrap_err();
+        assert!(matches!(
+            err,
+            BehaviorError::Action(DockError::NoSuchBay(12))
+        ));
+        assert!(docked.is_empty());
+
+        // nor do inverts and loop conditions take the error for a failure
+        let bt = Sequence(vec![
+            Action(Dock(1)),
+            While {
+                condition: Box::new(Invert(Box::new(Action(Dock(12))))),
+                action: Box::new(Action(Dock(2))),
+            },
+        ]);
+        let err = bt.run(&(), &mut docked).await.unwrap_err();
+        assert_eq!(err.to_string(), "bay 12 doesn't exist");
+        assert_eq!(docked, [1]);
+
+        // failures only make the nodes try something else
+        let bt = Sequence(vec![Action(Dock(1)), Action(Dock(3))]);
+        assert_eq!(bt.run(&(), &mut docked).await.unwrap(), Response::Failure);
+        let bt = Select(vec![Action(Dock(1)), Invert(Box::new(Action(Dock(1))))]);
+        assert_eq!(bt.run(&(), &mut docked).await.unwrap(), Response::Success);
+        assert_eq!(docked, [1]);
+    }
+
+    #[tokio::test]
+    async fn test_node_errors_stop_the_selects() {
+        let unset = || Expr {
+            source: Expression::parse("bb.fuel == \"full\"").unwrap(),
+        };
+        let throw = || Throw {
+            code: "E42".to_string(),
+            message: None,
+        };
+        let selects = |first: Behavior<MyAction>| {
+            let children = vec![first, Action(MyAction::Increase)];
+            [
+                Select(children.clone()),
+                ReactiveSelect(children.clone()),
+                AdaptiveSelect {
+                    children,
+                    strategy: SelectStrategy::Ucb1,
+                },
+            ]
+        };
+        let mut state = MyState(0);
+        for bt in selects(unset()) {
+            let err = bt.run(&(), &mut state).await.unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "one behavior failed ([0]: expression `bb.fuel == \"full\"`: blackboard key \
+                 `fuel` is not set)"
+            );
+        }
+        // thrown codes pass on as they are, for a `TryCatch` further up
+        for bt in selects(throw()) {
+            let err = bt.run(&(), &mut state).await.unwrap_err();
+            assert!(matches!(err, BehaviorError::Thrown(_)), "{:?}", err);
+        }
+        assert_eq!(state, MyState(0));
+    }
+
     #[tokio::test]
     async fn test_while_failing_immediately() {
         let bt: Behavior<MyAction> = While {
@@ -215,4 +2278,1185 @@ mod tests {
         println!("{:?}", my_state);
         assert_eq!(my_state, MyState(42));
     }
+
+    // succeeds with the given probability in percent, drawn from the rng in `Galaxy`
+    #[derive(Clone, Debug)]
+    struct Trade {
+        success_percent: u32,
+    }
+
+    struct Galaxy {
+        rng: TreeRng,
+    }
+
+    impl Actionable for Trade {
+        type ActionError = anyhow::Error;
+        type ActionArgs = ();
+        type ActionState = Galaxy;
+
+        async fn run(
+            &self,
+            _args: &Self::ActionArgs,
+            state: &mut Self::ActionState,
+        ) -> Result<Response, Self::ActionError> {
+            if state.rng.gen_f64() * 100.0 < self.success_percent as f64 {
+                Ok(Response::Success)
+            } else {
+                Ok(Response::Failure)
+            }
+        }
+    }
+
+    fn trading_strategies(strategy: SelectStrategy) -> Behavior<Trade> {
+        AdaptiveSelect {
+            children: vec![
+                Action(Trade {
+                    success_percent: 20,
+                }),
+                Action(Trade {
+                    success_percent: 80,
+                }),
+                Action(Trade {
+                    success_percent: 40,
+                }),
+            ],
+            strategy,
+        }
+    }
+
+    async fn learned_attempts(strategy: SelectStrategy) -> Vec<u64> {
+        let mut instance = TreeInstance::with_seed(trading_strategies(strategy), 1);
+        let mut galaxy = Galaxy {
+            rng: TreeRng::seeded(2),
+        };
+
+        for _ in 0..300 {
+            let _ = instance.run(&(), &mut galaxy).await;
+        }
+
+        let Some(NodeMemory::Adaptive(stats)) = instance.memory(&[]) else {
+            panic!("no stats recorded");
+        };
+        stats.iter().map(|s| s.attempts()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_adaptive_select_epsilon_greedy_converges() {
+        let attempts = learned_attempts(SelectStrategy::EpsilonGreedy { epsilon: 0.1 }).await;
+        println!("{:?}", attempts);
+        assert!(attempts[1] > attempts[0] + attempts[2]);
+    }
+
+    #[tokio::test]
+    async fn test_adaptive_select_ucb1_converges() {
+        let attempts = learned_attempts(SelectStrategy::Ucb1).await;
+        println!("{:?}", attempts);
+        assert!(attempts[1] > attempts[0]);
+        assert!(attempts[1] > attempts[2]);
+    }
+
+    #[tokio::test]
+    async fn test_adaptive_select_falls_back_like_select() {
+        let bt: Behavior<MyAction> = AdaptiveSelect {
+            children: vec![Action(MyAction::IsLowerThan5), Action(MyAction::Decrease)],
+            strategy: SelectStrategy::Ucb1,
+        };
+
+        let mut my_state = MyState(42);
+
+        bt.run(&(), &mut my_state).await.unwrap();
+        assert_eq!(my_state, MyState(41));
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn test_adaptive_select_stats_survive_snapshot() {
+        let bt = trading_strategies(SelectStrategy::EpsilonGreedy { epsilon: 0.2 });
+        let mut instance = TreeInstance::with_seed(bt.clone(), 3);
+        let mut galaxy = Galaxy {
+            rng: TreeRng::seeded(4),
+        };
+        for _ in 0..20 {
+            let _ = instance.run(&(), &mut galaxy).await;
+        }
+
+        let json = serde_json::to_string(&instance.snapshot()).unwrap();
+        let mut restored = TreeInstance::new(bt);
+        restored.restore(serde_json::from_str(&json).unwrap());
+
+        assert_eq!(restored.snapshot(), instance.snapshot());
+    }
+
+    #[tokio::test]
+    async fn test_on_changed_fires_once_per_change() {
+        let bt: Behavior<MyAction> = Sequence(vec![
+            OnChanged {
+                key: "waypoint".to_string(),
+                fire_on_first: false,
+            },
+            Action(MyAction::Increase),
+        ]);
+        let mut instance = TreeInstance::new(bt);
+        instance.blackboard_mut().set("waypoint", "X1-A");
+        let mut my_state = MyState(0);
+
+        assert_eq!(
+            instance.run(&(), &mut my_state).await.unwrap(),
+            Response::Failure
+        );
+        assert_eq!(
+            instance.run(&(), &mut my_state).await.unwrap(),
+            Response::Failure
+        );
+        assert_eq!(my_state, MyState(0));
+
+        instance.blackboard_mut().set("waypoint", "X1-B");
+        instance.run(&(), &mut my_state).await.unwrap();
+        assert_eq!(
+            instance.run(&(), &mut my_state).await.unwrap(),
+            Response::Failure
+        );
+        assert_eq!(my_state, MyState(1));
+
+        instance.blackboard_mut().set("waypoint", "X1-A");
+        instance.run(&(), &mut my_state).await.unwrap();
+        assert_eq!(my_state, MyState(2));
+    }
+
+    #[tokio::test]
+    async fn test_on_changed_fire_on_first_and_reset() {
+        let bt: Behavior<MyAction> = OnChanged {
+            key: "waypoint".to_string(),
+            fire_on_first: true,
+        };
+        let mut instance = TreeInstance::new(bt);
+        instance.blackboard_mut().set("waypoint", "X1-A");
+        let mut my_state = MyState(0);
+
+        instance.run(&(), &mut my_state).await.unwrap();
+        assert_eq!(
+            instance.run(&(), &mut my_state).await.unwrap(),
+            Response::Failure
+        );
+
+        instance.reset();
+        instance.run(&(), &mut my_state).await.unwrap();
+        assert_eq!(
+            instance.run(&(), &mut my_state).await.unwrap(),
+            Response::Failure
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expr_node() {
+        let bt: Behavior<MyAction> = While {
+            condition: Box::new(Expr {
+                source: Expression::parse("bb.limit > 3").unwrap(),
+            }),
+            action: Box::new(Sequence(vec![
+                Action(MyAction::Increase),
+                Action(MyAction::IsLowerThan5),
+            ])),
+        };
+        let mut instance = TreeInstance::new(bt);
+        instance.blackboard_mut().set("limit", 4);
+        let mut my_state = MyState(0);
+
+        let response = instance.run(&(), &mut my_state).await.unwrap();
+        assert_eq!(
+            (response, my_state.clone()),
+            (Response::Failure, MyState(5))
+        );
+
+        instance.blackboard_mut().set("limit", 3);
+        instance.run(&(), &mut my_state).await.unwrap();
+        assert_eq!(my_state, MyState(5));
+    }
+
+    #[tokio::test]
+    async fn test_compare_node() {
+        let bt: Behavior<MyAction> = While {
+            condition: Box::new(Compare {
+                left: ValueRef::Accessor("count".to_string()),
+                op: CompareOp::Lt,
+                right: ValueRef::Key("limit".to_string()),
+            }),
+            action: Box::new(Action(MyAction::Increase)),
+        };
+        let accessors = AccessorRegistry::new().register("count", |s: &MyState| s.0.into());
+        let mut instance = TreeInstance::new(bt).with_accessors(accessors);
+        instance.blackboard_mut().set("limit", 3);
+        let mut my_state = MyState(0);
+
+        instance.run(&(), &mut my_state).await.unwrap();
+        assert_eq!(my_state, MyState(3));
+    }
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<(NodePath, TreeEvent)>>>);
+
+    impl<A> BehaviorObserver<A> for Recorder {
+        fn on_event(&mut self, path: &[usize], event: &TreeEvent) {
+            self.0.lock().unwrap().push((path.to_vec(), event.clone()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_log_node_interpolates_and_emits() {
+        let bt: Behavior<MyAction> = Sequence(vec![
+            Action(MyAction::Increase),
+            Log {
+                level: LogLevel::Info,
+                message: "docking ${ship} at ${waypoint}".to_string(),
+            },
+        ]);
+        let recorder = Recorder::default();
+        let mut instance = TreeInstance::new(bt).with_observer(recorder.clone());
+        instance.blackboard_mut().set("ship", "BOT-1");
+        let mut my_state = MyState(0);
+
+        instance.run(&(), &mut my_state).await.unwrap();
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![(
+                vec![1],
+                TreeEvent::LogEmitted {
+                    level: LogLevel::Info,
+                    message: "docking BOT-1 at <unset:waypoint>".to_string(),
+                }
+            )]
+        );
+    }
+
+    fn guarded_increase() -> Behavior<MyAction> {
+        Sequence(vec![
+            Assert {
+                condition: Box::new(Action(MyAction::IsLowerThan5)),
+                message: "count stays below 5".to_string(),
+            },
+            Action(MyAction::Increase),
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_passing_assert_is_transparent() {
+        let mut my_state = MyState(0);
+
+        guarded_increase().run(&(), &mut my_state).await.unwrap();
+        assert_eq!(my_state, MyState(1));
+    }
+
+    #[tokio::test]
+    async fn test_failing_assert_aborts_the_tree() {
+        let bt: Behavior<MyAction> = Select(vec![
+            Invert(Box::new(guarded_increase())),
+            Action(MyAction::Increase),
+        ]);
+        let mut instance = TreeInstance::new(bt).with_state_debug();
+        let mut my_state = MyState(42);
+
+        let err = instance.run(&(), &mut my_state).await.unwrap_err();
+        let BehaviorError::AssertionFailed(failed) = err else {
+            panic!("expected a failed assertion, got {:?}", err);
+        };
+        assert_eq!(
+            failed,
+            AssertionFailed {
+                message: "count stays below 5".to_string(),
+                path: vec![0, 0, 0],
+                state: Some("MyState(42)".to_string()),
+            }
+        );
+        assert_eq!(my_state, MyState(42));
+
+        // a fresh run starts without the fatal error
+        let mut my_state = MyState(0);
+        instance.run(&(), &mut my_state).await.unwrap();
+        assert_eq!(my_state, MyState(2));
+    }
+
+    // conditions on `MyState`; `CountedIsPositive` counts its runs in the state by mistake
+    #[derive(Clone, Debug)]
+    enum Check {
+        IsPositive,
+        CountedIsPositive,
+    }
+
+    impl Actionable for Check {
+        type ActionError = anyhow::Error;
+        type ActionArgs = ();
+        type ActionState = MyState;
+
+        async fn run(&self, _: &(), state: &mut MyState) -> Result<Response, anyhow::Error> {
+            if let Check::CountedIsPositive = self {
+                state.0 += 1;
+            }
+            match state.0 > 0 {
+                true => Ok(Response::Success),
+                false => Ok(Response::Failure),
+            }
+        }
+
+        fn is_read_only(&self) -> bool {
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn test_purity_check_catches_mutating_condition() {
+        let bt = Sequence(vec![
+            Action(Check::IsPositive),
+            Action(Check::CountedIsPositive),
+        ]);
+        bt.run(&(), &mut MyState(1)).await.unwrap();
+
+        let mut instance = TreeInstance::new(bt).with_purity_check();
+        let err = instance.run(&(), &mut MyState(1)).await.unwrap_err();
+        let BehaviorError::AssertionFailed(failed) = err else {
+            panic!("expected a failed assertion, got {:?}", err);
+        };
+        assert_eq!(
+            failed,
+            AssertionFailed {
+                message: "read-only action `Check` changed the state".to_string(),
+                path: vec![1],
+                state: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_assert_downgraded_to_warning() {
+        let bt: Behavior<MyAction> = Select(vec![guarded_increase(), Action(MyAction::Decrease)]);
+        let recorder = Recorder::default();
+        let mut runner = Runner::new(TreeInstance::new(bt).with_observer(recorder.clone()))
+            .with_assert_mode(AssertMode::Warn);
+        let mut my_state = MyState(42);
+
+        runner.tick(&(), &mut my_state).await.unwrap();
+        assert_eq!(my_state, MyState(43));
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![(
+                vec![0, 0],
+                TreeEvent::LogEmitted {
+                    level: LogLevel::Warn,
+                    message: "assertion failed at [0, 0]: count stays below 5".to_string(),
+                }
+            )]
+        );
+    }
+
+    fn with_breakpoint() -> Behavior<MyAction> {
+        Sequence(vec![
+            Action(MyAction::Increase),
+            Breakpoint {
+                label: "after first increase".to_string(),
+            },
+            Action(MyAction::Increase),
+        ])
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn test_breakpoint_pauses_until_resumed() {
+        let controller = DebugController::new();
+        controller.add_breakpoint(vec![2]);
+        let recorder = Recorder::default();
+        let mut runner =
+            Runner::new(TreeInstance::new(with_breakpoint()).with_observer(recorder.clone()))
+                .with_debugger(controller.clone());
+        let mut my_state = MyState(0);
+
+        {
+            let tick = runner.tick(&(), &mut my_state);
+            tokio::pin!(tick);
+            assert!(timeout(Duration::from_secs(1), &mut tick).await.is_err());
+            assert_eq!(
+                controller.paused_at(),
+                Some(PausedAt {
+                    path: vec![1],
+                    label: Some("after first increase".to_string()),
+                })
+            );
+
+            controller.resume();
+            assert!(timeout(Duration::from_secs(1), &mut tick).await.is_err());
+            assert_eq!(controller.wait_until_paused().await.path, vec![2]);
+
+            controller.resume();
+            tick.await.unwrap();
+        }
+        assert_eq!(my_state, MyState(2));
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![
+                (
+                    vec![1],
+                    TreeEvent::BreakpointHit {
+                        label: Some("after first increase".to_string()),
+                        path: vec![1],
+                    }
+                ),
+                (
+                    vec![2],
+                    TreeEvent::BreakpointHit {
+                        label: None,
+                        path: vec![2],
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_breakpoint_without_debugger_is_transparent() {
+        let mut runner = Runner::new(TreeInstance::new(with_breakpoint()));
+        let mut my_state = MyState(0);
+
+        runner.tick(&(), &mut my_state).await.unwrap();
+        assert_eq!(my_state, MyState(2));
+    }
+
+    fn refuel_or_throw(catch: Vec<CatchClause<MyAction>>) -> Behavior<MyAction> {
+        TryCatch {
+            body: Box::new(Sequence(vec![
+                Action(MyAction::Increase),
+                Select(vec![
+                    Action(MyAction::IsLowerThan5),
+                    Throw {
+                        code: "no_fuel".to_string(),
+                        message: Some("${ship} ran dry".to_string()),
+                    },
+                ]),
+            ])),
+            catch,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_thrown_code_is_caught_by_matching_clause() {
+        let bt = refuel_or_throw(vec![
+            CatchClause {
+                codes: vec!["no_cargo".to_string()],
+                branch: Action(MyAction::Increase),
+            },
+            CatchClause {
+                codes: vec!["no_fuel".to_string()],
+                branch: Action(MyAction::Decrease),
+            },
+        ]);
+        let recorder = Recorder::default();
+        let mut instance = TreeInstance::new(bt).with_observer(recorder.clone());
+        instance.blackboard_mut().set("ship", "BOT-1");
+        let mut my_state = MyState(10);
+
+        instance.run(&(), &mut my_state).await.unwrap();
+        assert_eq!(my_state, MyState(10));
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![(
+                vec![0, 1, 1],
+                TreeEvent::Thrown {
+                    code: "no_fuel".to_string(),
+                    message: Some("BOT-1 ran dry".to_string()),
+                }
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unmatched_code_propagates() {
+        let bt = refuel_or_throw(vec![CatchClause {
+            codes: vec!["no_cargo".to_string()],
+            branch: Action(MyAction::Increase),
+        }]);
+        let mut my_state = MyState(10);
+
+        let err = bt.run(&(), &mut my_state).await.unwrap_err();
+        let BehaviorError::Thrown(thrown) = err else {
+            panic!("expected a thrown code, got {:?}", err);
+        };
+        assert_eq!(
+            thrown,
+            Thrown {
+                code: "no_fuel".to_string(),
+                message: Some("<unset:ship> ran dry".to_string()),
+                path: vec![0, 1, 1],
+            }
+        );
+        assert_eq!(my_state, MyState(11));
+    }
+
+    #[tokio::test]
+    async fn test_catch_all_handles_plain_failures() {
+        let bt: Behavior<MyAction> = TryCatch {
+            body: Box::new(Action(MyAction::IsLowerThan5)),
+            catch: vec![CatchClause {
+                codes: vec![],
+                branch: Action(MyAction::Decrease),
+            }],
+        };
+        let mut my_state = MyState(10);
+
+        bt.run(&(), &mut my_state).await.unwrap();
+        assert_eq!(my_state, MyState(9));
+    }
+
+    #[cfg(feature = "tokio")]
+    fn market_run() -> TreeInstance<MyAction> {
+        let bt: Behavior<MyAction> = Sequence(vec![
+            SleepUntil {
+                until: ValueRef::Key("market_opens".to_string()),
+            },
+            Action(MyAction::Increase),
+        ]);
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        TreeInstance::new(bt).with_clock(TokioClock::starting_at(start))
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn test_sleep_until_future_and_past() {
+        let mut instance = market_run();
+        let mut my_state = MyState(0);
+        let started = Instant::now();
+
+        // two hours after the clock's start
+        instance
+            .blackboard_mut()
+            .set("market_opens", "2023-11-15T00:13:20Z");
+        instance.run(&(), &mut my_state).await.unwrap();
+        assert_eq!(started.elapsed(), Duration::from_secs(2 * 3600));
+        assert_eq!(my_state, MyState(1));
+
+        instance
+            .blackboard_mut()
+            .set("market_opens", 1_700_000_000_000i64);
+        instance.run(&(), &mut my_state).await.unwrap();
+        assert_eq!(started.elapsed(), Duration::from_secs(2 * 3600));
+        assert_eq!(my_state, MyState(2));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn test_sleep_until_cancelled() {
+        let mut instance = market_run();
+        instance
+            .blackboard_mut()
+            .set("market_opens", "2024-11-14T00:00:00Z");
+        let token = CancellationToken::new();
+        let mut runner = Runner::new(instance).with_cancellation(token.clone());
+        let mut my_state = MyState(0);
+
+        let (result, _) = tokio::join!(runner.tick(&(), &mut my_state), async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            token.cancel();
+        });
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "cancelled while sleeping until bb.market_opens"
+        );
+        assert_eq!(my_state, MyState(0));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn test_cancelled_runs_start_no_more_nodes() {
+        // increases the state after 100ms
+        #[derive(Clone, Debug)]
+        struct SlowIncrease;
+
+        impl Actionable for SlowIncrease {
+            type ActionError = String;
+            type ActionArgs = ();
+            type ActionState = MyState;
+
+            async fn run(&self, _: &(), state: &mut MyState) -> Result<Response, String> {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                state.0 += 1;
+                Ok(Response::Success)
+            }
+        }
+
+        let bt = While {
+            condition: Box::new(Invert(Box::new(AlwaysFail))),
+            action: Box::new(Action(SlowIncrease)),
+        };
+        let token = CancellationToken::new();
+        let mut my_state = MyState(0);
+        let started = Instant::now();
+        let (result, _) = tokio::join!(bt.run_cancellable(&(), &mut my_state, &token), async {
+            tokio::time::sleep(Duration::from_millis(350)).await;
+            token.cancel();
+        });
+        // the increase running at the cancellation finishes, the condition after it never starts
+        assert!(matches!(result, Err(BehaviorError::Cancelled(_))));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "cancelled before the node at [0]"
+        );
+        assert_eq!(my_state, MyState(4));
+        assert_eq!(started.elapsed(), Duration::from_millis(400));
+
+        // an aborted run drops the increase right away
+        let token = CancellationToken::new();
+        let mut my_state = MyState(0);
+        let started = Instant::now();
+        let (result, _) = tokio::join!(bt.run_abortable(&(), &mut my_state, &token), async {
+            tokio::time::sleep(Duration::from_millis(350)).await;
+            token.cancel();
+        });
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "cancelled while running the node at [1]"
+        );
+        assert_eq!(my_state, MyState(3));
+        assert_eq!(started.elapsed(), Duration::from_millis(350));
+    }
+
+    #[tokio::test]
+    async fn test_sleep_until_invalid_timestamp_names_the_key() {
+        let bt: Behavior<MyAction> = SleepUntil {
+            until: ValueRef::Key("market_opens".to_string()),
+        };
+        let mut instance = TreeInstance::new(bt);
+        instance.blackboard_mut().set("market_opens", "soon");
+
+        let err = instance.run(&(), &mut MyState(0)).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid timestamp in bb.market_opens: \"soon\" is not an RFC3339 timestamp"
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn test_jitter_sleeps_within_bounds() {
+        let bt: Behavior<MyAction> = Sequence(vec![
+            Jitter {
+                min: Duration::from_millis(100),
+                max: Duration::from_millis(500),
+            },
+            Action(MyAction::Increase),
+        ]);
+        let recorder = Recorder::default();
+        let mut instance = TreeInstance::with_seed(bt.clone(), 7).with_observer(recorder.clone());
+        let mut my_state = MyState(0);
+
+        let started = Instant::now();
+        {
+            let tick = instance.run(&(), &mut my_state);
+            tokio::pin!(tick);
+            assert!(timeout(Duration::from_millis(99), &mut tick).await.is_err());
+            tokio::time::advance(Duration::from_millis(402)).await;
+            tick.await.unwrap();
+        }
+
+        let events = recorder.0.lock().unwrap().clone();
+        let [(path, TreeEvent::JitterChosen { delay })] = events.as_slice() else {
+            panic!("unexpected events: {:?}", events);
+        };
+        assert_eq!(path, &vec![0]);
+        assert!((100..=500).contains(&delay.as_millis()));
+        assert!(started.elapsed() >= *delay);
+        assert_eq!(my_state, MyState(1));
+
+        // the same seed draws the same delay
+        let replay = Recorder::default();
+        let mut instance = TreeInstance::with_seed(bt, 7).with_observer(replay.clone());
+        instance.run(&(), &mut MyState(0)).await.unwrap();
+        assert_eq!(*replay.0.lock().unwrap(), events);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_jitter_serializes_millis() {
+        let bt: Behavior<()> = Jitter {
+            min: Duration::from_millis(250),
+            max: Duration::from_secs(2),
+        };
+        assert_eq!(
+            serde_json::to_string(&bt).unwrap(),
+            r#"{"Jitter":{"min":250,"max":2000}}"#
+        );
+    }
+
+    // works for `work` ms; asked to wrap up, it cleans up for `cleanup` ms instead
+    #[cfg(feature = "tokio")]
+    #[derive(Clone, Debug)]
+    struct Survey {
+        work: u64,
+        cleanup: u64,
+    }
+
+    #[cfg(feature = "tokio")]
+    impl Actionable for Survey {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = Vec<&'static str>;
+
+        async fn run(&self, args: &(), log: &mut Vec<&'static str>) -> Result<Response, String> {
+            self.run_with(&ActionContext::default(), args, log).await
+        }
+
+        async fn run_with(
+            &self,
+            cx: &ActionContext,
+            _: &(),
+            log: &mut Vec<&'static str>,
+        ) -> Result<Response, String> {
+            let token = cx.cancellation();
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_millis(self.work)) => {
+                    log.push("surveyed");
+                    return Ok(Response::Success);
+                }
+                _ = token.cancelled() => {}
+            }
+            assert_eq!(token.reason(), Some(CancelReason::TimedOut));
+            tokio::time::sleep(Duration::from_millis(self.cleanup)).await;
+            log.push("cleaned up");
+            Ok(Response::Success)
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    fn graceful(work: u64, cleanup: u64) -> Behavior<Survey> {
+        GracefulTimeout {
+            child: Box::new(Action(Survey { work, cleanup })),
+            soft: Duration::from_millis(1000),
+            hard: Duration::from_millis(3000),
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn test_graceful_timeout_lets_the_child_wrap_up() {
+        let started = Instant::now();
+        let mut log = vec![];
+        graceful(500, 0).run(&(), &mut log).await.unwrap();
+        assert_eq!(started.elapsed(), Duration::from_millis(500));
+        assert_eq!(log, ["surveyed"]);
+
+        let started = Instant::now();
+        let mut log = vec![];
+        graceful(10_000, 1500).run(&(), &mut log).await.unwrap();
+        assert_eq!(started.elapsed(), Duration::from_millis(2500));
+        assert_eq!(log, ["cleaned up"]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn test_graceful_timeout_stops_the_child_at_the_hard_deadline() {
+        let started = Instant::now();
+        let mut log = vec![];
+        let result = graceful(10_000, 5000).run(&(), &mut log).await.unwrap();
+        assert_eq!(started.elapsed(), Duration::from_millis(3000));
+        assert_eq!(result, Response::Failure);
+        assert!(log.is_empty());
+
+        // the tree goes on after the stopped child
+        let bt = Select(vec![
+            graceful(10_000, 5000),
+            Action(Survey {
+                work: 0,
+                cleanup: 0,
+            }),
+        ]);
+        let mut instance = TreeInstance::new(bt).with_history(1);
+        instance.run(&(), &mut log).await.unwrap();
+        assert_eq!(log, ["surveyed"]);
+        assert_eq!(
+            instance.history()[0].last_run.visited,
+            [vec![], vec![0], vec![0, 0], vec![1]]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_graceful_timeout_durations() {
+        let bt: Behavior<()> = GracefulTimeout {
+            child: Box::new(AlwaysFail),
+            soft: Duration::from_millis(3000),
+            hard: Duration::from_millis(3000),
+        };
+        assert_eq!(
+            bt.config_error().as_deref(),
+            Some("graceful timeout soft 3000ms is not before hard 3000ms")
+        );
+        let json = serde_json::to_string(&bt).unwrap();
+        assert_eq!(
+            json,
+            r#"{"GracefulTimeout":{"child":"AlwaysFail","soft":3000,"hard":3000}}"#
+        );
+        let back: Behavior<()> = serde_json::from_str(&json).unwrap();
+        assert_eq!(format!("{:?}", back), format!("{:?}", bt));
+    }
+
+    // a flaky request failing its first `failures` calls; with `pending` the very first call
+    // responds `Running` instead
+    #[cfg(feature = "tokio")]
+    #[derive(Clone, Debug)]
+    struct Request {
+        failures: u32,
+        pending: bool,
+    }
+
+    #[cfg(feature = "tokio")]
+    #[derive(Default)]
+    struct Calls {
+        calls: u32,
+        failed: u32,
+    }
+
+    #[cfg(feature = "tokio")]
+    impl Actionable for Request {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = Calls;
+
+        async fn run(&self, _: &(), calls: &mut Calls) -> Result<Response, String> {
+            calls.calls += 1;
+            if self.pending && calls.calls == 1 {
+                Ok(Response::Running)
+            } else if calls.failed < self.failures {
+                calls.failed += 1;
+                Ok(Response::Failure)
+            } else {
+                Ok(Response::Success)
+            }
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    fn retry(request: Request, max_attempts: usize) -> Behavior<Request> {
+        Retry {
+            child: Box::new(Action(request)),
+            max_attempts,
+            backoff: Some(Duration::from_millis(100)),
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_reruns_failing_children() {
+        let request = Request {
+            failures: 2,
+            pending: false,
+        };
+        let started = Instant::now();
+        let mut calls = Calls::default();
+        let result = retry(request.clone(), 3).run(&(), &mut calls).await;
+        assert_eq!(result.unwrap(), Response::Success);
+        assert_eq!(calls.calls, 3);
+        assert_eq!(started.elapsed(), Duration::from_millis(200));
+
+        // the last attempt fails without a backoff after it
+        let started = Instant::now();
+        let mut calls = Calls::default();
+        let result = retry(request, 2).run(&(), &mut calls).await;
+        assert_eq!(result.unwrap(), Response::Failure);
+        assert_eq!(calls.calls, 2);
+        assert_eq!(started.elapsed(), Duration::from_millis(100));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn test_running_children_dont_use_up_attempts() {
+        let request = Request {
+            failures: 1,
+            pending: true,
+        };
+        let mut instance = TreeInstance::new(retry(request, 2));
+        let mut calls = Calls::default();
+        let running = instance.run(&(), &mut calls).await.unwrap();
+        assert_eq!(running, Response::Running);
+        let result = instance.run(&(), &mut calls).await.unwrap();
+        assert_eq!(result, Response::Success);
+        assert_eq!((calls.calls, calls.failed), (3, 1));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_retry_attempts_and_backoff() {
+        let bt: Behavior<()> = Retry {
+            child: Box::new(AlwaysFail),
+            max_attempts: 0,
+            backoff: Some(Duration::from_millis(250)),
+        };
+        assert_eq!(
+            bt.config_error().as_deref(),
+            Some("retry has zero max_attempts")
+        );
+        assert_eq!(
+            serde_json::to_string(&bt).unwrap(),
+            r#"{"Retry":{"child":"AlwaysFail","max_attempts":0,"backoff":250}}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_repeat_stops_at_the_first_failure() {
+        let mut my_state = MyState(0);
+        let bt = Repeat {
+            child: Box::new(Action(MyAction::Increase)),
+            times: 3,
+        };
+        bt.run(&(), &mut my_state).await.unwrap();
+        assert_eq!(my_state, MyState(3));
+
+        let bt = Repeat {
+            child: Box::new(Sequence(vec![
+                Action(MyAction::IsLowerThan5),
+                Action(MyAction::Increase),
+            ])),
+            times: 10,
+        };
+        let result = bt.run(&(), &mut my_state).await.unwrap();
+        assert_eq!(result, Response::Failure);
+        assert_eq!(my_state, MyState(5));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn test_timeout_drops_the_child_at_the_deadline() {
+        let survey = |work| Timeout {
+            child: Box::new(Action(Survey { work, cleanup: 0 })),
+            duration: Duration::from_millis(1000),
+        };
+        let mut log = vec![];
+        let bt = survey(10_000);
+        {
+            let tick = bt.run(&(), &mut log);
+            tokio::pin!(tick);
+            assert!(timeout(Duration::from_millis(999), &mut tick)
+                .await
+                .is_err());
+            tokio::time::advance(Duration::from_millis(1)).await;
+            assert_eq!(tick.await.unwrap(), Response::Failure);
+        }
+        assert!(log.is_empty());
+
+        let started = Instant::now();
+        survey(500).run(&(), &mut log).await.unwrap();
+        assert_eq!(started.elapsed(), Duration::from_millis(500));
+        assert_eq!(log, ["surveyed"]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn test_cooldown_fails_until_the_duration_passed() {
+        let bt = Cooldown {
+            child: Box::new(Action(Request {
+                failures: 1,
+                pending: false,
+            })),
+            duration: Duration::from_millis(1000),
+        };
+        // the wall clock barely moves during the test, the sleeper's time moves with tokio's
+        let mut instance = TreeInstance::new(bt).with_clock(SystemClock);
+        let mut calls = Calls::default();
+        assert_eq!(
+            instance.run(&(), &mut calls).await.unwrap(),
+            Response::Failure
+        );
+        tokio::time::advance(Duration::from_millis(999)).await;
+        assert_eq!(
+            instance.run(&(), &mut calls).await.unwrap(),
+            Response::Failure
+        );
+        assert_eq!(calls.calls, 1);
+
+        tokio::time::advance(Duration::from_millis(1)).await;
+        assert_eq!(
+            instance.run(&(), &mut calls).await.unwrap(),
+            Response::Success
+        );
+        assert_eq!(calls.calls, 2);
+    }
+
+    // `Do` responds `Running` as often as it is told to before each success
+    #[derive(Clone, Debug)]
+    enum Chore {
+        Do(&'static str, u32),
+        // succeeds while fewer than this many chores are done
+        Fewer(u32),
+    }
+
+    #[derive(Default)]
+    struct Chores {
+        log: Vec<&'static str>,
+        calls: std::collections::HashMap<&'static str, u32>,
+        done: u32,
+    }
+
+    impl Actionable for Chore {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = Chores;
+
+        async fn run(&self, _: &(), chores: &mut Chores) -> Result<Response, String> {
+            match self {
+                Chore::Do(name, running) => {
+                    chores.log.push(name);
+                    let calls = chores.calls.entry(name).or_default();
+                    *calls += 1;
+                    if !calls.is_multiple_of(running + 1) {
+                        return Ok(Response::Running);
+                    }
+                    chores.done += 1;
+                    Ok(Response::Success)
+                }
+                Chore::Fewer(limit) => {
+                    chores.log.push("check");
+                    match chores.done < *limit {
+                        true => Ok(Response::Success),
+                        false => Ok(Response::Failure),
+                    }
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_running_sequence_resumes_at_the_running_child() {
+        let bt = Sequence(vec![
+            Action(Chore::Do("dock", 0)),
+            Action(Chore::Do("refuel", 2)),
+            Action(Chore::Do("undock", 0)),
+        ]);
+        let mut instance = TreeInstance::new(bt);
+        let mut chores = Chores::default();
+        let mut responses = vec![];
+        for _ in 0..3 {
+            responses.push(instance.run(&(), &mut chores).await.unwrap());
+        }
+        assert_eq!(
+            responses,
+            [Response::Running, Response::Running, Response::Success]
+        );
+        assert_eq!(chores.log, ["dock", "refuel", "refuel", "refuel", "undock"]);
+
+        // a finished sequence starts over, and so does a reset one
+        instance.run(&(), &mut chores).await.unwrap();
+        instance.reset();
+        instance.run(&(), &mut chores).await.unwrap();
+        assert_eq!(chores.log[5..], ["dock", "refuel", "dock", "refuel"]);
+    }
+
+    #[tokio::test]
+    async fn test_while_resumes_its_running_action() {
+        let bt = While {
+            condition: Box::new(Action(Chore::Fewer(2))),
+            action: Box::new(Action(Chore::Do("mine", 1))),
+        };
+        let mut instance = TreeInstance::new(bt);
+        let mut chores = Chores::default();
+        let mut responses = vec![];
+        for _ in 0..3 {
+            responses.push(instance.run(&(), &mut chores).await.unwrap());
+        }
+        assert_eq!(
+            responses,
+            [Response::Running, Response::Running, Response::Success]
+        );
+        // the condition isn't checked again before a running action resumes
+        assert_eq!(
+            chores.log,
+            ["check", "mine", "mine", "check", "mine", "mine", "check"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reactive_sequence_checks_its_conditions_every_tick() {
+        let bt = ReactiveSequence(vec![
+            Action(Chore::Fewer(1)),
+            Sequence(vec![
+                Action(Chore::Do("undock", 0)),
+                Action(Chore::Do("mine", 3)),
+            ]),
+        ]);
+        let mut instance = TreeInstance::new(bt);
+        let mut chores = Chores::default();
+        let result = instance.run(&(), &mut chores).await.unwrap();
+        assert_eq!(result, Response::Running);
+        // undocking counts as a chore done, so the check now fails and mining is stopped
+        let result = instance.run(&(), &mut chores).await.unwrap();
+        assert_eq!(result, Response::Failure);
+        assert_eq!(chores.log, ["check", "undock", "mine", "check"]);
+
+        // the stopped sequence starts over instead of resuming at `mine`
+        chores.done = 0;
+        let result = instance.run(&(), &mut chores).await.unwrap();
+        assert_eq!(result, Response::Running);
+        assert_eq!(chores.log[4..], ["check", "undock", "mine"]);
+    }
+
+    #[tokio::test]
+    async fn test_reactive_select_preempts_lower_priority_branches() {
+        // flee once a chore is done, mine otherwise
+        let bt = ReactiveSelect(vec![
+            Sequence(vec![
+                Invert(Box::new(Action(Chore::Fewer(1)))),
+                Action(Chore::Do("flee", 0)),
+            ]),
+            Sequence(vec![
+                Action(Chore::Do("undock", 0)),
+                Action(Chore::Do("mine", 3)),
+            ]),
+        ]);
+        let recorder = Recorder::default();
+        let mut instance = TreeInstance::new(bt).with_observer(recorder.clone());
+        let mut chores = Chores::default();
+        let result = instance.run(&(), &mut chores).await.unwrap();
+        assert_eq!(result, Response::Running);
+        assert!(recorder.0.lock().unwrap().is_empty());
+
+        let result = instance.run(&(), &mut chores).await.unwrap();
+        assert_eq!(result, Response::Success);
+        assert_eq!(chores.log, ["check", "undock", "mine", "check", "flee"]);
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [(vec![], TreeEvent::Preempted { child: 1 })]
+        );
+
+        chores.done = 0;
+        instance.run(&(), &mut chores).await.unwrap();
+        assert_eq!(chores.log[5..], ["check", "undock", "mine"]);
+    }
+
+    #[tokio::test]
+    async fn test_adaptive_select_resumes_its_running_child() {
+        // explores at random on every choice, so only resuming keeps it on one child
+        let bt = AdaptiveSelect {
+            children: vec![Action(Chore::Do("mine", 5)), Action(Chore::Do("trade", 5))],
+            strategy: SelectStrategy::EpsilonGreedy { epsilon: 1.0 },
+        };
+        let mut instance = TreeInstance::with_seed(bt, 7);
+        let mut chores = Chores::default();
+        for _ in 0..5 {
+            let result = instance.run(&(), &mut chores).await.unwrap();
+            assert_eq!(result, Response::Running);
+        }
+        let result = instance.run(&(), &mut chores).await.unwrap();
+        assert_eq!(result, Response::Success);
+        assert!(chores.log.iter().all(|chore| *chore == chores.log[0]));
+        assert_eq!(chores.log.len(), 6);
+
+        let Some(NodeMemory::Adaptive(stats)) = instance.memory(&[]) else {
+            panic!("no stats recorded");
+        };
+        assert_eq!(stats.iter().map(|arm| arm.successes).sum::<u64>(), 1);
+        assert!(stats.iter().all(|arm| !arm.running));
+
+        // resetting stops the running child but keeps what was learned
+        instance.run(&(), &mut chores).await.unwrap();
+        instance.reset();
+        let Some(NodeMemory::Adaptive(reset)) = instance.memory(&[]) else {
+            panic!("reset dropped the stats");
+        };
+        assert_eq!(reset.iter().map(|arm| arm.attempts()).sum::<u64>(), 1);
+        assert!(reset.iter().all(|arm| !arm.running));
+    }
 }
@@ -0,0 +1,327 @@
+use crate::behavior_tree::clock::Sleeper;
+#[cfg(feature = "serde")]
+use crate::behavior_tree::decorator::DecoratorRegistry;
+use crate::behavior_tree::decorator::{Decorator, Executor};
+use crate::behavior_tree::observer::TreeEvent;
+use crate::behavior_tree::sync::{Permit, Semaphore};
+use crate::behavior_tree::{Actionable, BehaviorError, BoxFuture, Response};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "serde")]
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How often a resource of a [`ResourceArbiter`] was asked for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceStats {
+    pub capacity: usize,
+    pub in_use: usize,
+    pub acquired: u64,
+    // acquisitions that had to wait for the resource
+    pub contended: u64,
+    // requests given up on because the resource was busy
+    pub refused: u64,
+    pub waited: Duration,
+}
+
+struct Resource {
+    permits: Arc<Semaphore>,
+    stats: Mutex<ResourceStats>,
+}
+
+/// Named resources with a capacity each, shared by all trees that need them, e.g. an API only
+/// one agent of the fleet may use at a time. Trees take resources with [`Requires`] nodes.
+#[derive(Default)]
+pub struct ResourceArbiter {
+    resources: BTreeMap<String, Resource>,
+}
+
+impl ResourceArbiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a resource `capacity` trees may hold at the same time.
+    pub fn with_resource(mut self, name: impl Into<String>, capacity: usize) -> Self {
+        self.resources.insert(
+            name.into(),
+            Resource {
+                permits: Arc::new(Semaphore::new(capacity)),
+                stats: Mutex::new(ResourceStats {
+                    capacity,
+                    ..ResourceStats::default()
+                }),
+            },
+        );
+        self
+    }
+
+    pub fn stats(&self) -> BTreeMap<String, ResourceStats> {
+        self.resources
+            .iter()
+            .map(|(name, resource)| {
+                let mut stats = *resource.stats.lock().unwrap();
+                stats.in_use = stats.capacity - resource.permits.available_permits();
+                (name.clone(), stats)
+            })
+            .collect()
+    }
+
+    /// Lets `registry` build saved `requires` decorators taking resources of this arbiter.
+    #[cfg(feature = "serde")]
+    pub fn register<A: Actionable>(self: &Arc<Self>, registry: &mut DecoratorRegistry<A>) {
+        let arbiter = self.clone();
+        registry.register("requires", move |params| {
+            let params: RequiresParams =
+                serde_json::from_value(params.clone()).map_err(|err| err.to_string())?;
+            Ok(Arc::new(Requires {
+                resources: params.resources,
+                when_busy: params.when_busy,
+                arbiter: arbiter.clone(),
+            }) as Arc<dyn Decorator<A>>)
+        });
+    }
+
+    // takes every resource in `names`, in alphabetical order so that trees requiring the same
+    // resources can't deadlock each other
+    async fn acquire(
+        &self,
+        names: &BTreeSet<String>,
+        when_busy: WhenBusy,
+        sleeper: &dyn Sleeper,
+        acquired: &mut Vec<(String, Duration)>,
+    ) -> Result<Vec<Permit>, String> {
+        let mut permits = Vec::with_capacity(names.len());
+        for name in names {
+            let resource = self
+                .resources
+                .get(name)
+                .ok_or_else(|| format!("unknown resource `{}`", name))?;
+            let start = sleeper.now();
+            let permit = match resource.permits.try_acquire() {
+                Some(permit) => permit,
+                None if when_busy == WhenBusy::Fail => {
+                    resource.stats.lock().unwrap().refused += 1;
+                    return Err(format!("resource `{}` is busy", name));
+                }
+                None => {
+                    let permit = resource.permits.acquire().await;
+                    resource.stats.lock().unwrap().contended += 1;
+                    permit
+                }
+            };
+            let waited = sleeper.now() - start;
+            let mut stats = resource.stats.lock().unwrap();
+            stats.acquired += 1;
+            stats.waited += waited;
+            acquired.push((name.clone(), waited));
+            permits.push(permit);
+        }
+        Ok(permits)
+    }
+}
+
+/// What a [`Requires`] node does when a resource it needs is held by other trees.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum WhenBusy {
+    #[default]
+    Wait,
+    Fail,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct RequiresParams {
+    resources: Vec<String>,
+    #[serde(default)]
+    when_busy: WhenBusy,
+}
+
+/// Decorator holding resources of a [`ResourceArbiter`] while its child runs. Saved as
+/// `{"name": "requires", "params": {"resources": ["faction_api"], "when_busy": "Wait"}}`.
+pub struct Requires {
+    pub resources: Vec<String>,
+    pub when_busy: WhenBusy,
+    arbiter: Arc<ResourceArbiter>,
+}
+
+impl Requires {
+    pub fn new(arbiter: Arc<ResourceArbiter>, resources: &[&str], when_busy: WhenBusy) -> Self {
+        Self {
+            resources: resources.iter().map(|name| name.to_string()).collect(),
+            when_busy,
+            arbiter,
+        }
+    }
+}
+
+impl<A> Decorator<A> for Requires {
+    fn name(&self) -> String {
+        "requires".to_string()
+    }
+
+    #[cfg(feature = "serde")]
+    fn params(&self) -> Value {
+        serde_json::to_value(RequiresParams {
+            resources: self.resources.clone(),
+            when_busy: self.when_busy,
+        })
+        .unwrap_or(Value::Null)
+    }
+
+    fn decorate<'a>(
+        &'a self,
+        mut child: Executor<'a, A>,
+        args: &'a A::ActionArgs,
+        state: &'a mut A::ActionState,
+    ) -> BoxFuture<'a, Result<Response, BehaviorError<A::ActionError>>>
+    where
+        A: Actionable,
+    {
+        Box::pin(async move {
+            let names = self.resources.iter().cloned().collect();
+            let mut acquired = vec![];
+            let sleeper = child.sleeper();
+            let permits = self
+                .arbiter
+                .acquire(&names, self.when_busy, &*sleeper, &mut acquired)
+                .await;
+            for (resource, waited) in acquired {
+                child.emit(TreeEvent::ResourceAcquired { resource, waited });
+            }
+            let _permits = permits.map_err(BehaviorError::failed)?;
+            child.run(args, state).await
+        })
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use crate::behavior_tree::arbiter::{Requires, ResourceArbiter, WhenBusy};
+    use crate::behavior_tree::observer::{BehaviorObserver, TreeEvent};
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{Actionable, Behavior, Response, TreeInstance};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    // how many trees negotiate right now, and the most there ever were
+    #[derive(Default)]
+    struct Negotiations {
+        active: u32,
+        most: u32,
+    }
+
+    #[derive(Clone, Debug)]
+    struct Negotiate;
+
+    impl Actionable for Negotiate {
+        type ActionError = String;
+        type ActionArgs = Arc<Mutex<Negotiations>>;
+        type ActionState = ();
+
+        async fn run(
+            &self,
+            negotiations: &Self::ActionArgs,
+            _: &mut (),
+        ) -> Result<Response, String> {
+            {
+                let mut negotiations = negotiations.lock().unwrap();
+                negotiations.active += 1;
+                negotiations.most = negotiations.most.max(negotiations.active);
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            negotiations.lock().unwrap().active -= 1;
+            Ok(Response::Success)
+        }
+    }
+
+    fn requires(
+        arbiter: &Arc<ResourceArbiter>,
+        resources: &[&str],
+        when_busy: WhenBusy,
+    ) -> Behavior<Negotiate> {
+        Decorated {
+            decorator: Arc::new(Requires::new(arbiter.clone(), resources, when_busy)),
+            child: Box::new(Action(Negotiate)),
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<TreeEvent>>>);
+
+    impl<A> BehaviorObserver<A> for Recorder {
+        fn on_event(&mut self, _: &[usize], event: &TreeEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_trees_take_turns_on_an_exclusive_resource() {
+        let arbiter = Arc::new(ResourceArbiter::new().with_resource("faction_api", 1));
+        let negotiations = Arc::new(Mutex::new(Negotiations::default()));
+        let bt = requires(&arbiter, &["faction_api"], WhenBusy::Wait);
+        let recorder = Recorder::default();
+        let mut alpha = TreeInstance::new(bt.clone());
+        let mut beta = TreeInstance::new(bt).with_observer(recorder.clone());
+
+        let (mut alpha_state, mut beta_state) = ((), ());
+        let (a, b) = tokio::join!(
+            alpha.run(&negotiations, &mut alpha_state),
+            beta.run(&negotiations, &mut beta_state)
+        );
+        a.unwrap();
+        b.unwrap();
+        assert_eq!(negotiations.lock().unwrap().most, 1);
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![TreeEvent::ResourceAcquired {
+                resource: "faction_api".to_string(),
+                waited: Duration::from_millis(100),
+            }]
+        );
+        let stats = arbiter.stats()["faction_api"];
+        assert_eq!((stats.acquired, stats.contended, stats.in_use), (2, 1, 0));
+
+        let impatient = requires(&arbiter, &["faction_api"], WhenBusy::Fail);
+        let (a, b) = tokio::join!(
+            alpha.run(&negotiations, &mut alpha_state),
+            impatient.run(&negotiations, &mut beta_state)
+        );
+        a.unwrap();
+        assert_eq!(b.unwrap_err().to_string(), "resource `faction_api` is busy");
+        assert_eq!(arbiter.stats()["faction_api"].refused, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_resources_are_taken_in_alphabetical_order() {
+        let arbiter = Arc::new(
+            ResourceArbiter::new()
+                .with_resource("market", 2)
+                .with_resource("faction_api", 1),
+        );
+        let recorder = Recorder::default();
+        let bt = requires(&arbiter, &["market", "faction_api"], WhenBusy::Wait);
+        let mut instance = TreeInstance::new(bt).with_observer(recorder.clone());
+
+        let negotiations = Arc::new(Mutex::new(Negotiations::default()));
+        instance.run(&negotiations, &mut ()).await.unwrap();
+        let acquired: Vec<_> = recorder
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|event| match event {
+                TreeEvent::ResourceAcquired { resource, .. } => resource.clone(),
+                other => panic!("unexpected event {:?}", other),
+            })
+            .collect();
+        assert_eq!(acquired, ["faction_api", "market"]);
+
+        let bt = requires(&arbiter, &["fuel_depot"], WhenBusy::Wait);
+        let err = bt.run(&negotiations, &mut ()).await.unwrap_err();
+        assert_eq!(err.to_string(), "unknown resource `fuel_depot`");
+    }
+}
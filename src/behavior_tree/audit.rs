@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// How much an audited instance records, see `TreeInstance::with_audit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditOptions {
+    /// The most changes one `StateChanged` event lists; the rest are dropped.
+    pub max_changes: usize,
+    /// Audits every `sample_every`th action run, `1` audits them all.
+    pub sample_every: u32,
+}
+
+impl Default for AuditOptions {
+    fn default() -> Self {
+        Self {
+            max_changes: 64,
+            sample_every: 1,
+        }
+    }
+}
+
+/// A value of the state an action changed. `old` is `None` for added values, `new` for removed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateChange {
+    /// JSON pointer to the value, e.g. `/cargo/0/units`.
+    pub pointer: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+pub(crate) struct Auditor<S> {
+    serialize: fn(&S) -> Value,
+    options: AuditOptions,
+    runs: u64,
+}
+
+impl<S> Auditor<S> {
+    pub(crate) fn new(serialize: fn(&S) -> Value, options: AuditOptions) -> Self {
+        Self {
+            serialize,
+            options,
+            runs: 0,
+        }
+    }
+
+    /// The state before an action runs, `None` if this run isn't sampled.
+    pub(crate) fn before(&mut self, state: &S) -> Option<Value> {
+        let sampled = self
+            .runs
+            .is_multiple_of(u64::from(self.options.sample_every.max(1)));
+        self.runs += 1;
+        sampled.then(|| (self.serialize)(state))
+    }
+
+    /// What the action changed, and whether more changed than the options allow to list.
+    pub(crate) fn changes(&self, before: &Value, state: &S) -> (Vec<StateChange>, bool) {
+        let mut changes = vec![];
+        let truncated = diff(
+            before,
+            &(self.serialize)(state),
+            &mut String::new(),
+            &mut changes,
+            self.options.max_changes,
+        );
+        (changes, truncated)
+    }
+}
+
+/// Every change from `old` to `new`.
+pub(crate) fn changes(old: &Value, new: &Value) -> Vec<StateChange> {
+    let mut changes = vec![];
+    diff(old, new, &mut String::new(), &mut changes, usize::MAX);
+    changes
+}
+
+// collects the changes from `old` to `new` below `pointer`, returns whether some didn't fit
+fn diff(
+    old: &Value,
+    new: &Value,
+    pointer: &mut String,
+    changes: &mut Vec<StateChange>,
+    max: usize,
+) -> bool {
+    let mut below = |segment: &str, old: Option<&Value>, new: Option<&Value>| {
+        let len = pointer.len();
+        pointer.push('/');
+        pointer.push_str(&segment.replace('~', "~0").replace('/', "~1"));
+        let truncated = match (old, new) {
+            (Some(old), Some(new)) => diff(old, new, pointer, changes, max),
+            _ => push(pointer, old, new, changes, max),
+        };
+        pointer.truncate(len);
+        truncated
+    };
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut keys: Vec<_> = old.keys().chain(new.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            keys.into_iter()
+                .any(|key| below(key, old.get(key), new.get(key)))
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            (0..old.len().max(new.len())).any(|i| below(&i.to_string(), old.get(i), new.get(i)))
+        }
+        _ if old == new => false,
+        _ => push(pointer, Some(old), Some(new), changes, max),
+    }
+}
+
+fn push(
+    pointer: &str,
+    old: Option<&Value>,
+    new: Option<&Value>,
+    changes: &mut Vec<StateChange>,
+    max: usize,
+) -> bool {
+    if changes.len() == max {
+        return true;
+    }
+    changes.push(StateChange {
+        pointer: pointer.to_string(),
+        old: old.cloned(),
+        new: new.cloned(),
+    });
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::audit::{AuditOptions, Auditor, StateChange};
+    use serde_json::{json, Value};
+
+    fn auditor(max_changes: usize) -> Auditor<Value> {
+        Auditor::new(
+            Value::clone,
+            AuditOptions {
+                max_changes,
+                sample_every: 1,
+            },
+        )
+    }
+
+    fn change(pointer: &str, old: Option<Value>, new: Option<Value>) -> StateChange {
+        StateChange {
+            pointer: pointer.to_string(),
+            old,
+            new,
+        }
+    }
+
+    #[test]
+    fn test_changes_are_listed_by_pointer() {
+        let before = json!({"fuel": 10, "cargo": [{"units": 3}], "route": {"a/b": 1}, "at": "X1"});
+        let after =
+            json!({"fuel": 12, "cargo": [{"units": 3}, {"units": 1}], "route": {}, "at": "X1"});
+
+        let (changes, truncated) = auditor(10).changes(&before, &after);
+        assert!(!truncated);
+        assert_eq!(
+            changes,
+            vec![
+                change("/cargo/1", None, Some(json!({"units": 1}))),
+                change("/fuel", Some(json!(10)), Some(json!(12))),
+                change("/route/a~1b", Some(json!(1)), None),
+            ]
+        );
+
+        let (changes, truncated) = auditor(1).changes(&before, &after);
+        assert!(truncated);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(auditor(1).changes(&before, &before), (vec![], false));
+    }
+
+    #[test]
+    fn test_sampling() {
+        let mut auditor = Auditor::new(
+            Value::clone,
+            AuditOptions {
+                max_changes: 10,
+                sample_every: 3,
+            },
+        );
+        let sampled: Vec<_> = (0..7)
+            .map(|_| auditor.before(&json!(1)).is_some())
+            .collect();
+        assert_eq!(sampled, [true, false, false, true, false, false, true]);
+    }
+}
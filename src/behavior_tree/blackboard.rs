@@ -0,0 +1,213 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A single value stored on the blackboard.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+pub enum BlackboardValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl BlackboardValue {
+    /// The value as a number, for ints and floats.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            BlackboardValue::Int(i) => Some(*i as f64),
+            BlackboardValue::Float(x) => Some(*x),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for BlackboardValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlackboardValue::Bool(b) => write!(f, "{}", b),
+            BlackboardValue::Int(i) => write!(f, "{}", i),
+            BlackboardValue::Float(x) => write!(f, "{}", x),
+            BlackboardValue::String(s) => write!(f, "{:?}", s),
+        }
+    }
+}
+
+impl From<bool> for BlackboardValue {
+    fn from(value: bool) -> Self {
+        BlackboardValue::Bool(value)
+    }
+}
+
+impl From<i32> for BlackboardValue {
+    fn from(value: i32) -> Self {
+        BlackboardValue::Int(value.into())
+    }
+}
+
+impl From<i64> for BlackboardValue {
+    fn from(value: i64) -> Self {
+        BlackboardValue::Int(value)
+    }
+}
+
+impl From<f64> for BlackboardValue {
+    fn from(value: f64) -> Self {
+        BlackboardValue::Float(value)
+    }
+}
+
+impl From<&str> for BlackboardValue {
+    fn from(value: &str) -> Self {
+        BlackboardValue::String(value.to_string())
+    }
+}
+
+impl From<String> for BlackboardValue {
+    fn from(value: String) -> Self {
+        BlackboardValue::String(value)
+    }
+}
+
+/// Named values shared by all nodes of a tree instance.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Blackboard(BTreeMap<String, BlackboardValue>);
+
+impl Blackboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&BlackboardValue> {
+        self.0.get(key)
+    }
+
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<BlackboardValue>) {
+        self.0.insert(key.into(), value.into());
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<BlackboardValue> {
+        self.0.remove(key)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.0.contains_key(key)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
+
+    /// Inserts all entries of `other`, overwriting existing keys.
+    pub fn extend(&mut self, other: Blackboard) {
+        self.0.extend(other.0);
+    }
+
+    /// Replaces every `${key}` in `template` with the value stored under `key`. Strings are
+    /// inserted without quotes, keys that aren't set render as `<unset:key>`.
+    pub fn interpolate(&self, template: &str) -> String {
+        let mut out = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some((before, key, after)) = split_placeholder(rest) {
+            out.push_str(before);
+            match self.get(key) {
+                Some(BlackboardValue::String(s)) => out.push_str(s),
+                Some(value) => out.push_str(&value.to_string()),
+                None => {
+                    out.push_str("<unset:");
+                    out.push_str(key);
+                    out.push('>');
+                }
+            }
+            rest = after;
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+/// The keys of all `${key}` placeholders in `template`.
+pub fn template_keys(template: &str) -> Vec<&str> {
+    let mut keys = vec![];
+    let mut rest = template;
+    while let Some((_, key, after)) = split_placeholder(rest) {
+        keys.push(key);
+        rest = after;
+    }
+    keys
+}
+
+/// `template` with the keys of its placeholders that `rename` gives a new name for renamed.
+pub fn rename_template_keys(template: &str, rename: &dyn Fn(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some((before, key, after)) = split_placeholder(rest) {
+        out.push_str(before);
+        out.push_str("${");
+        out.push_str(&rename(key).unwrap_or_else(|| key.to_string()));
+        out.push('}');
+        rest = after;
+    }
+    out.push_str(rest);
+    out
+}
+
+// (text before, key, text after) of the first complete `${key}`
+fn split_placeholder(s: &str) -> Option<(&str, &str, &str)> {
+    let start = s.find("${")?;
+    let len = s[start + 2..].find('}')?;
+    Some((
+        &s[..start],
+        &s[start + 2..start + 2 + len],
+        &s[start + 3 + len..],
+    ))
+}
+
+impl<K, V> FromIterator<(K, V)> for Blackboard
+where
+    K: Into<String>,
+    V: Into<BlackboardValue>,
+{
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        Self(
+            iter.into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::blackboard::{template_keys, Blackboard, BlackboardValue};
+
+    #[test]
+    fn test_interpolate() {
+        let blackboard = Blackboard::from_iter([
+            ("ship", BlackboardValue::from("BOT-1")),
+            ("fuel", BlackboardValue::Int(30)),
+        ]);
+
+        assert_eq!(
+            blackboard.interpolate("${ship} has ${fuel} fuel, heading to ${target}"),
+            "BOT-1 has 30 fuel, heading to <unset:target>"
+        );
+        assert_eq!(
+            blackboard.interpolate("no ${placeholder"),
+            "no ${placeholder"
+        );
+        assert_eq!(
+            template_keys("${ship} to ${target}"),
+            vec!["ship", "target"]
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use crate::behavior_tree::{ActionContext, Actionable, Behavior, BoxFuture, Response};
+use std::fmt;
+
+/// Object-safe form of [`Actionable`], so actions of different types can share one tree. Every
+/// `Actionable` implements it.
+pub trait DynActionable<Args, State, E>: Send + Sync {
+    /// Runs the action like [`Actionable::run_with`].
+    fn run_boxed<'a>(
+        &'a self,
+        cx: &'a ActionContext,
+        args: &'a Args,
+        state: &'a mut State,
+    ) -> BoxFuture<'a, Result<Response, E>>;
+
+    fn name(&self) -> String;
+
+    fn is_read_only(&self) -> bool;
+
+    fn clone_boxed(&self) -> BoxedAction<Args, State, E>;
+}
+
+pub type BoxedAction<Args, State, E> = Box<dyn DynActionable<Args, State, E>>;
+
+/// A tree whose actions can be of any type with the given args, state and error.
+pub type BoxedBehavior<Args, State, E> = Behavior<BoxedAction<Args, State, E>>;
+
+impl<A> DynActionable<A::ActionArgs, A::ActionState, A::ActionError> for A
+where
+    A: Actionable + 'static,
+{
+    fn run_boxed<'a>(
+        &'a self,
+        cx: &'a ActionContext,
+        args: &'a A::ActionArgs,
+        state: &'a mut A::ActionState,
+    ) -> BoxFuture<'a, Result<Response, A::ActionError>> {
+        Box::pin(self.run_with(cx, args, state))
+    }
+
+    fn name(&self) -> String {
+        Actionable::name(self)
+    }
+
+    fn is_read_only(&self) -> bool {
+        Actionable::is_read_only(self)
+    }
+
+    fn clone_boxed(&self) -> BoxedAction<A::ActionArgs, A::ActionState, A::ActionError> {
+        Box::new(self.clone())
+    }
+}
+
+// the box itself is an `Actionable` and so a `DynActionable` too; the impls below call through
+// to the boxed action with `**self` instead of recursing into the box's own impls
+impl<Args, State, E> Clone for BoxedAction<Args, State, E> {
+    fn clone(&self) -> Self {
+        (**self).clone_boxed()
+    }
+}
+
+impl<Args, State, E> fmt::Debug for dyn DynActionable<Args, State, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name())
+    }
+}
+
+impl<Args, State, E> Actionable for BoxedAction<Args, State, E>
+where
+    Args: Send + Sync + 'static,
+    State: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    type ActionError = E;
+    type ActionArgs = Args;
+    type ActionState = State;
+
+    async fn run(&self, args: &Args, state: &mut State) -> Result<Response, E> {
+        self.run_with(&ActionContext::default(), args, state).await
+    }
+
+    async fn run_with(
+        &self,
+        cx: &ActionContext,
+        args: &Args,
+        state: &mut State,
+    ) -> Result<Response, E> {
+        (**self).run_boxed(cx, args, state).await
+    }
+
+    fn name(&self) -> String {
+        (**self).name()
+    }
+
+    fn is_read_only(&self) -> bool {
+        (**self).is_read_only()
+    }
+}
+
+impl<A> Behavior<A>
+where
+    A: Actionable + 'static,
+{
+    /// Boxes every action, so the tree can be combined with trees of other action types.
+    pub fn into_boxed(self) -> BoxedBehavior<A::ActionArgs, A::ActionState, A::ActionError> {
+        self.map_actions(
+            &mut |_, action| Behavior::Action(Box::new(action) as BoxedAction<_, _, _>),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::boxed::{BoxedAction, BoxedBehavior};
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{Actionable, Behavior, Response};
+
+    #[derive(Clone, Debug)]
+    struct Refuel;
+
+    #[derive(Clone, Debug)]
+    struct Jump {
+        fuel: u32,
+    }
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Ship {
+        fuel: u32,
+        jumps: u32,
+    }
+
+    impl Actionable for Refuel {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = Ship;
+
+        async fn run(&self, _: &(), ship: &mut Ship) -> Result<Response, String> {
+            ship.fuel = 10;
+            Ok(Response::Success)
+        }
+    }
+
+    impl Actionable for Jump {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = Ship;
+
+        async fn run(&self, _: &(), ship: &mut Ship) -> Result<Response, String> {
+            if ship.fuel < self.fuel {
+                return Ok(Response::Failure);
+            }
+            ship.fuel -= self.fuel;
+            ship.jumps += 1;
+            Ok(Response::Success)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mixed_action_types() {
+        let jump: Behavior<Jump> = Select(vec![Action(Jump { fuel: 4 })]);
+        let bt: BoxedBehavior<(), Ship, String> = Sequence(vec![
+            Action(Box::new(Refuel)),
+            jump.into_boxed(),
+            Action(Box::new(Jump { fuel: 4 })),
+            Invert(Box::new(Action(Box::new(Jump { fuel: 4 })))),
+        ]);
+        let mut ship = Ship::default();
+
+        bt.run(&(), &mut ship).await.unwrap();
+        assert_eq!(ship, Ship { fuel: 2, jumps: 2 });
+
+        let action: BoxedAction<(), Ship, String> = Box::new(Jump { fuel: 4 });
+        assert_eq!(format!("{:?}", Action(action.clone())), "Action(Jump)");
+        assert_eq!(action.name(), "Jump");
+    }
+}
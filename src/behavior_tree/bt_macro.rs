@@ -0,0 +1,126 @@
+/// Builds a [`Behavior`](crate::behavior_tree::Behavior) from a compact description of the
+/// tree, so nested trees read top to bottom without the `vec!`s and `Box::new`s.
+///
+/// ```ignore
+/// let tree: Behavior<ShipAction> = bt!(select [
+///     sequence [
+///         action(ShipAction::IsDocked),
+///         invert(action(ShipAction::HasCargo)),
+///         action(ShipAction::Undock),
+///     ],
+///     while (action(ShipAction::HasFuel)) { action(ShipAction::Mine) },
+///     { fallback_tree() },
+/// ]);
+/// ```
+///
+/// `select [..]` and `sequence [..]` take their children separated by commas, `invert (..)`
+/// exactly one child, and `while (..) { .. }` one condition and one action. `fail` is an
+/// `AlwaysFail`, and any other node is written as an expression in braces.
+#[macro_export]
+macro_rules! bt {
+    (action ($action:expr)) => {
+        $crate::behavior_tree::Behavior::Action($action)
+    };
+    (fail) => {
+        $crate::behavior_tree::Behavior::AlwaysFail
+    };
+    ({ $node:expr }) => {
+        $node
+    };
+    (select [$($children:tt)*]) => {
+        $crate::behavior_tree::Behavior::Select($crate::bt!(@children [] [] $($children)*))
+    };
+    (sequence [$($children:tt)*]) => {
+        $crate::behavior_tree::Behavior::Sequence($crate::bt!(@children [] [] $($children)*))
+    };
+    (invert ($($child:tt)+)) => {
+        $crate::behavior_tree::Behavior::Invert(::std::boxed::Box::new($crate::bt!($($child)+)))
+    };
+    (while ($($condition:tt)+) { $($action:tt)+ }) => {
+        $crate::behavior_tree::Behavior::While {
+            condition: ::std::boxed::Box::new($crate::bt!($($condition)+)),
+            action: ::std::boxed::Box::new($crate::bt!($($action)+)),
+        }
+    };
+    (while $($rest:tt)*) => {
+        ::std::compile_error!("`while` takes one condition and one action: `while (condition) { action }`")
+    };
+
+    // the children of a list split at the commas: the children done, the tokens of the one
+    // being read, and the tokens left
+    (@children [$($done:expr,)*] []) => {
+        ::std::vec![$($done,)*]
+    };
+    (@children [$($done:expr,)*] [$($child:tt)+]) => {
+        ::std::vec![$($done,)* $crate::bt!($($child)+)]
+    };
+    (@children [$($done:expr,)*] [] , $($rest:tt)*) => {
+        ::std::compile_error!("empty child in a `bt!` list")
+    };
+    (@children [$($done:expr,)*] [$($child:tt)+] , $($rest:tt)*) => {
+        $crate::bt!(@children [$($done,)* $crate::bt!($($child)+),] [] $($rest)*)
+    };
+    (@children [$($done:expr,)*] [$($child:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::bt!(@children [$($done,)*] [$($child)* $next] $($rest)*)
+    };
+
+    ($($node:tt)*) => {
+        ::std::compile_error!(::std::concat!(
+            "expected a `bt!` node: action(..), fail, { .. }, select [..], sequence [..], ",
+            "invert (..) or while (..) { .. }; got `",
+            ::std::stringify!($($node)*),
+            "`"
+        ))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::Behavior;
+    use crate::behavior_tree::Behavior::*;
+
+    // only ever compared through `Debug`
+    #[allow(dead_code)]
+    #[derive(Clone, Debug)]
+    enum Ship {
+        IsDocked,
+        HasCargo,
+        Undock,
+        Mine { tons: u32 },
+    }
+
+    #[test]
+    fn test_bt_builds_the_same_tree_as_the_variants() {
+        let fallback: Behavior<Ship> = Action(Ship::Undock);
+        let built: Behavior<Ship> = bt!(select [
+            sequence [
+                action(Ship::IsDocked),
+                invert(action(Ship::HasCargo)),
+                action(Ship::Mine { tons: 2 + 3 }),
+            ],
+            while (invert(fail)) { sequence [action(Ship::Undock)] },
+            { fallback.clone() },
+            sequence [],
+        ]);
+        let expected = Select(vec![
+            Sequence(vec![
+                Action(Ship::IsDocked),
+                Invert(Box::new(Action(Ship::HasCargo))),
+                Action(Ship::Mine { tons: 5 }),
+            ]),
+            While {
+                condition: Box::new(Invert(Box::new(AlwaysFail))),
+                action: Box::new(Sequence(vec![Action(Ship::Undock)])),
+            },
+            fallback,
+            Sequence(vec![]),
+        ]);
+        assert_eq!(format!("{:?}", built), format!("{:?}", expected));
+
+        let single: Behavior<Ship> = bt!(sequence[action(Ship::Undock)]);
+        assert_eq!(
+            format!("{:?}", single),
+            format!("{:?}", Sequence(vec![Action(Ship::Undock)]))
+        );
+    }
+}
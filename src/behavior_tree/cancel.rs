@@ -0,0 +1,62 @@
+use crate::behavior_tree::sync::Notify;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Why a token was cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CancelReason {
+    // `cancel` was called
+    Requested,
+    // a `GracefulTimeout` reached its soft deadline and asks its child to wrap up
+    TimedOut,
+}
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    reason: Mutex<Option<CancelReason>>,
+    notify: Notify,
+}
+
+/// Handle to stop a running tree from the outside. Clones share the same cancellation.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancel_with(CancelReason::Requested);
+    }
+
+    /// Cancels the token, unless it already is, giving `reason` as the reason.
+    pub fn cancel_with(&self, reason: CancelReason) {
+        self.inner.reason.lock().unwrap().get_or_insert(reason);
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+    }
+
+    /// Why the token was cancelled, `None` if it isn't.
+    pub fn reason(&self) -> Option<CancelReason> {
+        *self.inner.reason.lock().unwrap()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Completes once the token is cancelled, right away if it already is.
+    pub async fn cancelled(&self) {
+        loop {
+            let notified = self.inner.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
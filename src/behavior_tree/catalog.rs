@@ -0,0 +1,614 @@
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+use serde::{forward_to_deserialize_any, Serialize};
+use std::cell::RefCell;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ActionKind {
+    Unit,
+    Newtype,
+    Tuple,
+    Struct,
+}
+
+/// Name and payload shape of one action.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ActionDescriptor {
+    pub name: String,
+    // (field name, type name), newtype and tuple fields are named by their position
+    pub fields: Vec<(String, String)>,
+    pub kind: ActionKind,
+}
+
+/// Lists the actions of an action type.
+///
+/// Every enum that implements `Deserialize` gets this for free: the descriptors are collected by
+/// deserializing each variant from a recording deserializer. Type names are the ones serde
+/// reports, so they are primitive names, `String`, `Option<..>`, `Vec<..>` and `Map<..>`, and the
+/// names of nested structs and enums.
+pub trait DescribeActions {
+    fn describe_actions() -> Vec<ActionDescriptor>;
+}
+
+impl<A: DeserializeOwned> DescribeActions for A {
+    fn describe_actions() -> Vec<ActionDescriptor> {
+        let variants = RefCell::new(&[][..]);
+        let mut actions = vec![];
+        let mut index = 0;
+        loop {
+            let descriptor = RefCell::new(None);
+            let _ = A::deserialize(ActionsProbe {
+                index,
+                variants: &variants,
+                descriptor: &descriptor,
+            });
+            match descriptor.into_inner() {
+                Some(action) => actions.push(action),
+                None => break,
+            }
+            index += 1;
+            if index >= variants.borrow().len() {
+                break;
+            }
+        }
+        actions
+    }
+}
+
+/// The actions of an action type, for tooling that needs to know which action names are valid.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct ActionCatalog {
+    actions: Vec<ActionDescriptor>,
+}
+
+impl ActionCatalog {
+    pub fn of<A: DescribeActions>() -> Self {
+        Self {
+            actions: A::describe_actions(),
+        }
+    }
+
+    pub fn actions(&self) -> &[ActionDescriptor] {
+        &self.actions
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ActionDescriptor> {
+        self.actions.iter().find(|action| action.name == name)
+    }
+
+    /// The action name closest to `name`, if it is close enough to be a typo of it.
+    pub fn suggest(&self, name: &str) -> Option<&str> {
+        let max_distance = (name.chars().count() / 3).max(1);
+        self.actions
+            .iter()
+            .map(|action| (edit_distance(name, &action.name), action.name.as_str()))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, name)| name)
+    }
+}
+
+// optimal string alignment distance: insertions, deletions, substitutions and transpositions
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    rows[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = best;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+// nested types are only described this deep, which also stops recursive types
+const MAX_DEPTH: usize = 8;
+
+#[derive(Debug)]
+struct ProbeError(String);
+
+impl fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ProbeError {}
+
+impl de::Error for ProbeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        ProbeError(msg.to_string())
+    }
+}
+
+// deserializes the action enum as its variant `index`
+struct ActionsProbe<'a> {
+    index: usize,
+    variants: &'a RefCell<&'static [&'static str]>,
+    descriptor: &'a RefCell<Option<ActionDescriptor>>,
+}
+
+impl<'de> de::Deserializer<'de> for ActionsProbe<'_> {
+    type Error = ProbeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, ProbeError> {
+        Err(de::Error::custom("actions have to be an enum"))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        *self.variants.borrow_mut() = variants;
+        let Some(name) = variants.get(self.index) else {
+            return Err(de::Error::custom("no such variant"));
+        };
+        visitor.visit_enum(VariantProbe {
+            index: self.index,
+            name,
+            descriptor: self.descriptor,
+            depth: 0,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map struct identifier
+        ignored_any
+    }
+}
+
+// picks one variant of an enum and records its shape
+struct VariantProbe<'a> {
+    index: usize,
+    name: &'static str,
+    descriptor: &'a RefCell<Option<ActionDescriptor>>,
+    depth: usize,
+}
+
+impl VariantProbe<'_> {
+    fn record(&self, kind: ActionKind, fields: Vec<(String, String)>) {
+        *self.descriptor.borrow_mut() = Some(ActionDescriptor {
+            name: self.name.to_string(),
+            fields,
+            kind,
+        });
+    }
+}
+
+impl<'de> EnumAccess<'de> for VariantProbe<'_> {
+    type Error = ProbeError;
+    type Variant = Self;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> Result<(S::Value, Self), ProbeError> {
+        let index = IntoDeserializer::<ProbeError>::into_deserializer(self.index as u32);
+        Ok((seed.deserialize(index)?, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for VariantProbe<'_> {
+    type Error = ProbeError;
+
+    fn unit_variant(self) -> Result<(), ProbeError> {
+        self.record(ActionKind::Unit, vec![]);
+        Ok(())
+    }
+
+    fn newtype_variant_seed<S: DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> Result<S::Value, ProbeError> {
+        let ty = unknown_type();
+        let result = seed.deserialize(TypeProbe::new(&ty, self.depth));
+        self.record(
+            ActionKind::Newtype,
+            vec![("0".to_string(), ty.into_inner())],
+        );
+        result
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        let types: Vec<_> = (0..len).map(|_| unknown_type()).collect();
+        let result = visitor.visit_seq(ElementsProbe::new(&types, self.depth));
+        let fields = types
+            .into_iter()
+            .enumerate()
+            .map(|(i, ty)| (i.to_string(), ty.into_inner()))
+            .collect();
+        self.record(ActionKind::Tuple, fields);
+        result
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        let types: Vec<_> = fields.iter().map(|_| unknown_type()).collect();
+        let result = visitor.visit_map(FieldsProbe::new(fields, &types, self.depth));
+        let fields = fields
+            .iter()
+            .zip(types)
+            .map(|(name, ty)| (name.to_string(), ty.into_inner()))
+            .collect();
+        self.record(ActionKind::Struct, fields);
+        result
+    }
+}
+
+fn unknown_type() -> RefCell<String> {
+    RefCell::new("?".to_string())
+}
+
+// hands out the fields of a struct, each deserialized from a `TypeProbe`
+struct FieldsProbe<'a> {
+    fields: &'static [&'static str],
+    types: &'a [RefCell<String>],
+    next: usize,
+    depth: usize,
+}
+
+impl<'a> FieldsProbe<'a> {
+    fn new(fields: &'static [&'static str], types: &'a [RefCell<String>], depth: usize) -> Self {
+        Self {
+            fields,
+            types,
+            next: 0,
+            depth,
+        }
+    }
+}
+
+impl<'de> MapAccess<'de> for FieldsProbe<'_> {
+    type Error = ProbeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, ProbeError> {
+        match self.fields.get(self.next) {
+            Some(field) => seed.deserialize(field.into_deserializer()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, ProbeError> {
+        let ty = &self.types[self.next];
+        self.next += 1;
+        seed.deserialize(TypeProbe::new(ty, self.depth))
+    }
+}
+
+// hands out tuple elements, each deserialized from a `TypeProbe`
+struct ElementsProbe<'a> {
+    types: &'a [RefCell<String>],
+    next: usize,
+    depth: usize,
+}
+
+impl<'a> ElementsProbe<'a> {
+    fn new(types: &'a [RefCell<String>], depth: usize) -> Self {
+        Self {
+            types,
+            next: 0,
+            depth,
+        }
+    }
+}
+
+impl<'de> SeqAccess<'de> for ElementsProbe<'_> {
+    type Error = ProbeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, ProbeError> {
+        let Some(ty) = self.types.get(self.next) else {
+            return Ok(None);
+        };
+        self.next += 1;
+        seed.deserialize(TypeProbe::new(ty, self.depth)).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.types.len() - self.next)
+    }
+}
+
+// a map with a single entry
+struct EntryProbe<'a> {
+    key: &'a RefCell<String>,
+    value: &'a RefCell<String>,
+    done: bool,
+    depth: usize,
+}
+
+impl<'de> MapAccess<'de> for EntryProbe<'_> {
+    type Error = ProbeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, ProbeError> {
+        if self.done {
+            return Ok(None);
+        }
+        self.done = true;
+        seed.deserialize(TypeProbe::new(self.key, self.depth))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, ProbeError> {
+        seed.deserialize(TypeProbe::new(self.value, self.depth))
+    }
+}
+
+// deserializes a placeholder value of whatever type is asked for and records that type's name
+struct TypeProbe<'a> {
+    ty: &'a RefCell<String>,
+    depth: usize,
+}
+
+impl<'a> TypeProbe<'a> {
+    fn new(ty: &'a RefCell<String>, depth: usize) -> Self {
+        Self {
+            ty,
+            depth: depth + 1,
+        }
+    }
+
+    fn name(&self, name: impl Into<String>) -> Result<(), ProbeError> {
+        *self.ty.borrow_mut() = name.into();
+        if self.depth > MAX_DEPTH {
+            return Err(de::Error::custom("type is nested too deep"));
+        }
+        Ok(())
+    }
+}
+
+macro_rules! probe_primitives {
+    ($($method:ident => $visit:ident($($value:expr)?): $name:literal,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+                self.name($name)?;
+                visitor.$visit($($value)?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for TypeProbe<'_> {
+    type Error = ProbeError;
+
+    probe_primitives! {
+        deserialize_bool => visit_bool(false): "bool",
+        deserialize_i8 => visit_i8(0): "i8",
+        deserialize_i16 => visit_i16(0): "i16",
+        deserialize_i32 => visit_i32(0): "i32",
+        deserialize_i64 => visit_i64(0): "i64",
+        deserialize_i128 => visit_i128(0): "i128",
+        deserialize_u8 => visit_u8(0): "u8",
+        deserialize_u16 => visit_u16(0): "u16",
+        deserialize_u32 => visit_u32(0): "u32",
+        deserialize_u64 => visit_u64(0): "u64",
+        deserialize_u128 => visit_u128(0): "u128",
+        deserialize_f32 => visit_f32(0.0): "f32",
+        deserialize_f64 => visit_f64(0.0): "f64",
+        deserialize_char => visit_char(' '): "char",
+        deserialize_str => visit_str(""): "String",
+        deserialize_string => visit_str(""): "String",
+        deserialize_bytes => visit_bytes(&[]): "bytes",
+        deserialize_byte_buf => visit_bytes(&[]): "bytes",
+        deserialize_unit => visit_unit(): "()",
+        deserialize_identifier => visit_str(""): "String",
+        deserialize_ignored_any => visit_unit(): "any",
+        deserialize_any => visit_unit(): "any",
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        self.name("Option")?;
+        let inner = unknown_type();
+        let result = visitor.visit_some(TypeProbe::new(&inner, self.depth));
+        self.name(format!("Option<{}>", inner.into_inner()))?;
+        result
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        self.name(name)?;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        self.name(name)?;
+        let inner = unknown_type();
+        visitor.visit_newtype_struct(TypeProbe::new(&inner, self.depth))
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        self.name("Vec")?;
+        let element = [unknown_type()];
+        let result = visitor.visit_seq(ElementsProbe::new(&element, self.depth));
+        let [element] = element;
+        self.name(format!("Vec<{}>", element.into_inner()))?;
+        result
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        self.name("tuple")?;
+        let types: Vec<_> = (0..len).map(|_| unknown_type()).collect();
+        let result = visitor.visit_seq(ElementsProbe::new(&types, self.depth));
+        let names: Vec<_> = types.into_iter().map(RefCell::into_inner).collect();
+        self.name(format!("({})", names.join(", ")))?;
+        result
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        self.name(name)?;
+        let types: Vec<_> = (0..len).map(|_| unknown_type()).collect();
+        visitor.visit_seq(ElementsProbe::new(&types, self.depth))
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ProbeError> {
+        self.name("Map")?;
+        let (key, value) = (unknown_type(), unknown_type());
+        let result = visitor.visit_map(EntryProbe {
+            key: &key,
+            value: &value,
+            done: false,
+            depth: self.depth,
+        });
+        self.name(format!("Map<{}, {}>", key.into_inner(), value.into_inner()))?;
+        result
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        self.name(name)?;
+        let types: Vec<_> = fields.iter().map(|_| unknown_type()).collect();
+        visitor.visit_map(FieldsProbe::new(fields, &types, self.depth))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        self.name(name)?;
+        let Some(first) = variants.first() else {
+            return Err(de::Error::custom("enum without variants"));
+        };
+        visitor.visit_enum(VariantProbe {
+            index: 0,
+            name: first,
+            descriptor: &RefCell::new(None),
+            depth: self.depth,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::catalog::{ActionCatalog, ActionDescriptor, ActionKind};
+    use serde::Deserialize;
+
+    #[allow(unused)]
+    #[derive(Deserialize)]
+    enum ShipAction {
+        Dock,
+        Wave,
+        Refuel(u32),
+        Trade {
+            good: String,
+            units: u32,
+            max_price: Option<f64>,
+        },
+        Jump(String, Vec<Waypoint>),
+    }
+
+    #[allow(unused)]
+    #[derive(Deserialize)]
+    struct Waypoint {
+        symbol: String,
+    }
+
+    fn fields(fields: &[(&str, &str)]) -> Vec<(String, String)> {
+        fields
+            .iter()
+            .map(|(name, ty)| (name.to_string(), ty.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_catalog_describes_every_variant() {
+        let catalog = ActionCatalog::of::<ShipAction>();
+
+        let names: Vec<_> = catalog.actions().iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["Dock", "Wave", "Refuel", "Trade", "Jump"]);
+        assert_eq!(catalog.get("Dock").unwrap().kind, ActionKind::Unit);
+        assert_eq!(
+            catalog.get("Refuel"),
+            Some(&ActionDescriptor {
+                name: "Refuel".to_string(),
+                fields: fields(&[("0", "u32")]),
+                kind: ActionKind::Newtype,
+            })
+        );
+        assert_eq!(
+            catalog.get("Trade"),
+            Some(&ActionDescriptor {
+                name: "Trade".to_string(),
+                fields: fields(&[
+                    ("good", "String"),
+                    ("units", "u32"),
+                    ("max_price", "Option<f64>")
+                ]),
+                kind: ActionKind::Struct,
+            })
+        );
+        assert_eq!(
+            catalog.get("Jump").unwrap().fields,
+            fields(&[("0", "String"), ("1", "Vec<Waypoint>")])
+        );
+    }
+
+    #[test]
+    fn test_suggest_close_names() {
+        let catalog = ActionCatalog::of::<ShipAction>();
+
+        assert_eq!(catalog.suggest("Wav"), Some("Wave"));
+        assert_eq!(catalog.suggest("Rfeuel"), Some("Refuel"));
+        assert_eq!(catalog.suggest("Mine"), None);
+    }
+}
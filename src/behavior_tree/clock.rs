@@ -0,0 +1,516 @@
+use crate::behavior_tree::blackboard::BlackboardValue;
+use crate::behavior_tree::BoxFuture;
+#[cfg(not(feature = "tokio"))]
+use std::collections::BTreeMap;
+use std::fmt;
+#[cfg(not(feature = "tokio"))]
+use std::future::Future;
+#[cfg(not(feature = "tokio"))]
+use std::pin::Pin;
+use std::sync::Arc;
+#[cfg(not(feature = "tokio"))]
+use std::sync::{Condvar, Mutex, OnceLock};
+#[cfg(not(feature = "tokio"))]
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Source of the wall-clock time that time-based nodes like `SleepUntil` measure against.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+/// The operating system's clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Wall-clock time driven by tokio's clock, starting at a fixed time. It stands still under
+/// `tokio::time::pause` and moves with `tokio::time::advance`, so timestamps work in tests.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy)]
+pub struct TokioClock {
+    start: SystemTime,
+    anchor: tokio::time::Instant,
+}
+
+#[cfg(feature = "tokio")]
+impl TokioClock {
+    pub fn starting_at(start: SystemTime) -> Self {
+        Self {
+            start,
+            anchor: tokio::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Clock for TokioClock {
+    fn now(&self) -> SystemTime {
+        self.start + self.anchor.elapsed()
+    }
+}
+
+/// The timer nodes wait and measure time with: `Timeout`, `GracefulTimeout`, `StallGuard`,
+/// `Cooldown`, `Jitter`, `SleepUntil`, `Retry` backoffs and `Join` timeouts. Apart from `Spawn` nodes it is
+/// the only part of running a tree tied to an executor; set one for executors other than tokio
+/// with `TreeInstance::with_sleeper`.
+pub trait Sleeper: Send + Sync {
+    /// The current moment on the timer's timeline.
+    fn now(&self) -> Instant;
+
+    /// Completes at `deadline`, right away if it passed.
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'static, ()>;
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        self.sleep_until(self.now() + duration)
+    }
+}
+
+impl fmt::Debug for dyn Sleeper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Sleeper")
+    }
+}
+
+/// Waits on tokio's timer, so it stands still under `tokio::time::pause` like [`TokioClock`].
+/// The default sleeper with the `tokio` feature.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioSleeper;
+
+#[cfg(feature = "tokio")]
+impl Sleeper for TokioSleeper {
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep_until(deadline.into()))
+    }
+}
+
+/// Waits on a timer thread shared by all of its sleeps, which works under any executor. The
+/// default sleeper without the `tokio` feature; the thread starts with the first sleep that
+/// doesn't end right away, and a sleep dropped before its deadline is taken off its queue.
+#[cfg(not(feature = "tokio"))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadSleeper;
+
+#[cfg(not(feature = "tokio"))]
+impl Sleeper for ThreadSleeper {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'static, ()> {
+        Box::pin(ThreadSleep {
+            deadline,
+            key: None,
+        })
+    }
+}
+
+// the sleeps waiting on the timer thread, in deadline order; the ids tell sleeps with the same
+// deadline apart
+#[cfg(not(feature = "tokio"))]
+#[derive(Default)]
+struct Timers {
+    next_id: u64,
+    sleeps: BTreeMap<(Instant, u64), Waker>,
+}
+
+#[cfg(not(feature = "tokio"))]
+struct TimerThread {
+    timers: Mutex<Timers>,
+    // notified when a sleep is queued
+    queued: Condvar,
+}
+
+#[cfg(not(feature = "tokio"))]
+fn timer_thread() -> &'static TimerThread {
+    static THREAD: OnceLock<TimerThread> = OnceLock::new();
+    THREAD.get_or_init(|| {
+        std::thread::Builder::new()
+            .name("behavior-tree-timer".to_string())
+            .spawn(run_timers)
+            .expect("failed to start the timer thread");
+        TimerThread {
+            timers: Mutex::default(),
+            queued: Condvar::new(),
+        }
+    })
+}
+
+// wakes the sleeps whose deadline passed, waiting for the next deadline or sleep in between
+#[cfg(not(feature = "tokio"))]
+fn run_timers() {
+    let thread = timer_thread();
+    let mut timers = thread.timers.lock().unwrap();
+    loop {
+        let now = Instant::now();
+        let mut due = vec![];
+        while let Some(sleep) = timers.sleeps.first_entry() {
+            if sleep.key().0 > now {
+                break;
+            }
+            due.push(sleep.remove());
+        }
+        if !due.is_empty() {
+            // the wakers may poll right away, which takes the lock
+            drop(timers);
+            due.into_iter().for_each(Waker::wake);
+            timers = thread.timers.lock().unwrap();
+            continue;
+        }
+        timers = match timers.sleeps.keys().next() {
+            Some((deadline, _)) => {
+                let wait = deadline.saturating_duration_since(now);
+                thread.queued.wait_timeout(timers, wait).unwrap().0
+            }
+            None => thread.queued.wait(timers).unwrap(),
+        };
+    }
+}
+
+#[cfg(not(feature = "tokio"))]
+struct ThreadSleep {
+    deadline: Instant,
+    // where the sleep is queued, once it is
+    key: Option<(Instant, u64)>,
+}
+
+#[cfg(not(feature = "tokio"))]
+impl Future for ThreadSleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let passed = Instant::now() >= self.deadline;
+        if passed && self.key.is_none() {
+            return Poll::Ready(());
+        }
+        let thread = timer_thread();
+        let mut timers = thread.timers.lock().unwrap();
+        if let Some(key) = self.key {
+            // the thread takes the sleeps it wakes off the queue
+            match timers.sleeps.get_mut(&key) {
+                Some(waker) if !passed => waker.clone_from(cx.waker()),
+                _ => {
+                    timers.sleeps.remove(&key);
+                    self.key = None;
+                    return Poll::Ready(());
+                }
+            }
+            return Poll::Pending;
+        }
+        let key = (self.deadline, timers.next_id);
+        timers.next_id += 1;
+        timers.sleeps.insert(key, cx.waker().clone());
+        self.key = Some(key);
+        thread.queued.notify_one();
+        Poll::Pending
+    }
+}
+
+#[cfg(not(feature = "tokio"))]
+impl Drop for ThreadSleep {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            if let Ok(mut timers) = timer_thread().timers.lock() {
+                timers.sleeps.remove(&key);
+            }
+        }
+    }
+}
+
+pub(crate) fn default_sleeper() -> Arc<dyn Sleeper> {
+    #[cfg(feature = "tokio")]
+    let sleeper = TokioSleeper;
+    #[cfg(not(feature = "tokio"))]
+    let sleeper = ThreadSleeper;
+    Arc::new(sleeper)
+}
+
+/// Reads a timestamp stored as an RFC3339 string or as milliseconds since the unix epoch.
+pub fn parse_timestamp(value: &BlackboardValue) -> Result<SystemTime, String> {
+    match value {
+        BlackboardValue::Int(millis) => Ok(from_epoch_millis(*millis)),
+        BlackboardValue::String(s) => parse_rfc3339(s)
+            .map(from_epoch_millis)
+            .ok_or_else(|| format!("{:?} is not an RFC3339 timestamp", s)),
+        other => Err(format!(
+            "expected an RFC3339 string or epoch millis, got {}",
+            other
+        )),
+    }
+}
+
+/// (De)serializes a `Duration` as whole milliseconds.
+#[cfg(feature = "serde")]
+pub(crate) mod duration_millis {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_millis() as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+
+    // for optional durations, `null` if unset
+    pub mod option {
+        use serde::{Deserialize, Deserializer, Serializer};
+        use std::time::Duration;
+
+        pub fn serialize<S: Serializer>(
+            duration: &Option<Duration>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match duration {
+                Some(duration) => serializer.serialize_some(&(duration.as_millis() as u64)),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Duration>, D::Error> {
+            Option::<u64>::deserialize(deserializer).map(|millis| millis.map(Duration::from_millis))
+        }
+    }
+}
+
+fn from_epoch_millis(millis: i64) -> SystemTime {
+    let offset = Duration::from_millis(millis.unsigned_abs());
+    if millis < 0 {
+        UNIX_EPOCH - offset
+    } else {
+        UNIX_EPOCH + offset
+    }
+}
+
+// milliseconds since the epoch of `YYYY-MM-DDTHH:MM:SS[.fraction](Z|+HH:MM|-HH:MM)`
+fn parse_rfc3339(s: &str) -> Option<i64> {
+    let b = s.as_bytes();
+    if b.len() < 20
+        || b[4] != b'-'
+        || b[7] != b'-'
+        || !matches!(b[10], b'T' | b't' | b' ')
+        || b[13] != b':'
+        || b[16] != b':'
+    {
+        return None;
+    }
+    let (year, month, day) = (
+        digits(s.get(0..4)?)?,
+        digits(s.get(5..7)?)?,
+        digits(s.get(8..10)?)?,
+    );
+    let (hour, minute, second) = (
+        digits(s.get(11..13)?)?,
+        digits(s.get(14..16)?)?,
+        digits(s.get(17..19)?)?,
+    );
+    if !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+
+    let mut rest = s.get(19..)?;
+    let mut millis = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let len = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if len == 0 {
+            return None;
+        }
+        // anything finer than milliseconds is dropped
+        millis = digits(&format!("{:0<3}", &fraction[..len.min(3)]))?;
+        rest = &fraction[len..];
+    }
+    let offset_minutes = match rest {
+        "Z" | "z" => 0,
+        _ => {
+            let sign = match rest.as_bytes().first()? {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            if rest.len() != 6 || rest.as_bytes()[3] != b':' {
+                return None;
+            }
+            sign * (digits(rest.get(1..3)?)? * 60 + digits(rest.get(4..6)?)?)
+        }
+    };
+
+    let seconds = days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second
+        - offset_minutes * 60;
+    Some(seconds * 1000 + millis)
+}
+
+fn digits(s: &str) -> Option<i64> {
+    if s.is_empty() || !s.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// days since 1970-01-01 of a proleptic gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::blackboard::BlackboardValue;
+    use crate::behavior_tree::clock::{parse_timestamp, Sleeper};
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{Actionable, BoxFuture, Response, TreeInstance};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant, UNIX_EPOCH};
+
+    fn millis(value: impl Into<BlackboardValue>) -> Result<u128, String> {
+        parse_timestamp(&value.into()).map(|t| t.duration_since(UNIX_EPOCH).unwrap().as_millis())
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(millis("1970-01-01T00:00:00Z"), Ok(0));
+        assert_eq!(millis("2024-02-29T12:30:05.25Z"), Ok(1_709_209_805_250));
+        assert_eq!(
+            millis("2024-02-29T14:30:05.250+02:00"),
+            Ok(1_709_209_805_250)
+        );
+        assert_eq!(millis(1_709_209_805_250i64), Ok(1_709_209_805_250));
+        assert_eq!(
+            parse_timestamp(&BlackboardValue::Int(-1_000)),
+            Ok(UNIX_EPOCH - Duration::from_secs(1))
+        );
+
+        for invalid in ["2023-02-29T00:00:00Z", "2024-01-01 10:00", "tomorrow"] {
+            assert_eq!(
+                millis(invalid),
+                Err(format!("{:?} is not an RFC3339 timestamp", invalid))
+            );
+        }
+        assert!(millis(true).is_err());
+    }
+
+    // never returns
+    #[derive(Clone, Debug)]
+    struct Hang;
+
+    impl Actionable for Hang {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = ();
+
+        async fn run(&self, _: &(), _: &mut ()) -> Result<Response, String> {
+            std::future::pending().await
+        }
+    }
+
+    // a timer whose sleeps end right away, noting how long they were
+    #[derive(Clone)]
+    struct NoWait {
+        start: Instant,
+        sleeps: Arc<Mutex<Vec<Duration>>>,
+    }
+
+    impl Sleeper for NoWait {
+        fn now(&self) -> Instant {
+            self.start
+        }
+
+        fn sleep_until(&self, deadline: Instant) -> BoxFuture<'static, ()> {
+            let slept = deadline.saturating_duration_since(self.now());
+            self.sleeps.lock().unwrap().push(slept);
+            Box::pin(async {})
+        }
+    }
+
+    #[test]
+    fn test_trees_wait_on_the_sleeper_of_their_instance() {
+        let sleeper = NoWait {
+            start: Instant::now(),
+            sleeps: Arc::default(),
+        };
+        let bt = Timeout {
+            child: Box::new(Action(Hang)),
+            duration: Duration::from_secs(3_600),
+        };
+        let mut instance = TreeInstance::new(bt).with_sleeper(sleeper.clone());
+        // no timer of tokio's to wait on
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let result = runtime.block_on(instance.run(&(), &mut ())).unwrap();
+        assert_eq!(result, Response::Failure);
+        assert_eq!(
+            *sleeper.sleeps.lock().unwrap(),
+            [Duration::from_secs(3_600)]
+        );
+    }
+
+    #[cfg(not(feature = "tokio"))]
+    #[test]
+    fn test_thread_sleeps_share_the_timer_thread() {
+        use crate::behavior_tree::clock::{timer_thread, ThreadSleep, ThreadSleeper};
+
+        let queued = |key| {
+            timer_thread()
+                .timers
+                .lock()
+                .unwrap()
+                .sleeps
+                .contains_key(&key)
+        };
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            // a sleep dropped before its deadline leaves the queue
+            let mut sleep = ThreadSleep {
+                deadline: Instant::now() + Duration::from_secs(3_600),
+                key: None,
+            };
+            assert!(futures_util::poll!(&mut sleep).is_pending());
+            let key = sleep.key.unwrap();
+            assert!(queued(key));
+            drop(sleep);
+            assert!(!queued(key));
+
+            let started = Instant::now();
+            tokio::join!(
+                ThreadSleeper.sleep(Duration::from_millis(30)),
+                ThreadSleeper.sleep(Duration::from_millis(10)),
+            );
+            let elapsed = started.elapsed();
+            assert!(elapsed >= Duration::from_millis(30), "{:?}", elapsed);
+            assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+        });
+    }
+}
@@ -0,0 +1,275 @@
+use crate::behavior_tree::blackboard::{Blackboard, BlackboardValue};
+use crate::behavior_tree::expr::FieldAccessFn;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CompareOp {
+    #[cfg_attr(feature = "serde", serde(rename = "=="))]
+    Eq,
+    #[cfg_attr(feature = "serde", serde(rename = "!="))]
+    Ne,
+    #[cfg_attr(feature = "serde", serde(rename = "<"))]
+    Lt,
+    #[cfg_attr(feature = "serde", serde(rename = "<="))]
+    Le,
+    #[cfg_attr(feature = "serde", serde(rename = ">"))]
+    Gt,
+    #[cfg_attr(feature = "serde", serde(rename = ">="))]
+    Ge,
+}
+
+impl CompareOp {
+    /// Compares two values, `None` if their types can't be compared.
+    /// Ints and floats compare as numbers, strings compare with strings and bools only support
+    /// `==` and `!=`.
+    pub fn apply(&self, left: &BlackboardValue, right: &BlackboardValue) -> Option<bool> {
+        let ordering = match (left, right) {
+            (BlackboardValue::Bool(a), BlackboardValue::Bool(b)) => match self {
+                CompareOp::Eq | CompareOp::Ne => a.cmp(b),
+                _ => return None,
+            },
+            (BlackboardValue::String(a), BlackboardValue::String(b)) => a.cmp(b),
+            (BlackboardValue::Int(a), BlackboardValue::Int(b)) => a.cmp(b),
+            _ => left.as_f64()?.partial_cmp(&right.as_f64()?)?,
+        };
+        Some(match self {
+            CompareOp::Eq => ordering == Ordering::Equal,
+            CompareOp::Ne => ordering != Ordering::Equal,
+            CompareOp::Lt => ordering.is_lt(),
+            CompareOp::Le => ordering.is_le(),
+            CompareOp::Gt => ordering.is_gt(),
+            CompareOp::Ge => ordering.is_ge(),
+        })
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            CompareOp::Eq => "==",
+            CompareOp::Ne => "!=",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+        }
+    }
+}
+
+impl fmt::Display for CompareOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.symbol())
+    }
+}
+
+/// One side of a `Compare` node.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ValueRef {
+    Key(String),
+    Literal(BlackboardValue),
+    // a named accessor registered in the instance's `AccessorRegistry`
+    Accessor(String),
+}
+
+impl ValueRef {
+    pub fn resolve<S>(
+        &self,
+        blackboard: &Blackboard,
+        accessors: &AccessorRegistry<S>,
+        state: &S,
+    ) -> Result<BlackboardValue, String> {
+        match self {
+            ValueRef::Key(key) => blackboard
+                .get(key)
+                .cloned()
+                .ok_or_else(|| format!("blackboard key `{}` is not set", key)),
+            ValueRef::Literal(value) => Ok(value.clone()),
+            ValueRef::Accessor(name) => accessors
+                .read(name, state)
+                .ok_or_else(|| format!("no accessor named `{}` is registered", name)),
+        }
+    }
+}
+
+impl fmt::Display for ValueRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueRef::Key(key) => write!(f, "bb.{}", key),
+            ValueRef::Literal(value) => write!(f, "{}", value),
+            ValueRef::Accessor(name) => write!(f, "{}()", name),
+        }
+    }
+}
+
+pub type Accessor<S> = fn(&S) -> BlackboardValue;
+
+/// Named functions reading values out of an `ActionState`, used by `Compare` nodes.
+pub struct AccessorRegistry<S> {
+    accessors: HashMap<String, Accessor<S>>,
+    fallback: Option<FieldAccessFn<S>>,
+}
+
+// `derive` would require `S: Clone`
+impl<S> Clone for AccessorRegistry<S> {
+    fn clone(&self) -> Self {
+        Self {
+            accessors: self.accessors.clone(),
+            fallback: self.fallback,
+        }
+    }
+}
+
+impl<S> AccessorRegistry<S> {
+    pub fn new() -> Self {
+        Self {
+            accessors: HashMap::new(),
+            fallback: None,
+        }
+    }
+
+    /// Resolves names that aren't registered with `fallback`, e.g. paths into a dynamic state.
+    pub fn with_fallback(mut self, fallback: FieldAccessFn<S>) -> Self {
+        self.fallback = Some(fallback);
+        self
+    }
+
+    pub fn register(mut self, name: impl Into<String>, accessor: Accessor<S>) -> Self {
+        self.accessors.insert(name.into(), accessor);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<Accessor<S>> {
+        self.accessors.get(name).copied()
+    }
+
+    /// Reads the value `name` stands for out of `state`.
+    pub fn read(&self, name: &str, state: &S) -> Option<BlackboardValue> {
+        match self.get(name) {
+            Some(accessor) => Some(accessor(state)),
+            None => self.fallback.and_then(|fallback| fallback(state, name)),
+        }
+    }
+}
+
+impl<S> Default for AccessorRegistry<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Resolves both sides and compares them, naming the offending side in errors.
+pub(crate) fn compare<S>(
+    left: &ValueRef,
+    op: CompareOp,
+    right: &ValueRef,
+    blackboard: &Blackboard,
+    accessors: &AccessorRegistry<S>,
+    state: &S,
+) -> Result<bool, String> {
+    let l = left.resolve(blackboard, accessors, state)?;
+    let r = right.resolve(blackboard, accessors, state)?;
+    op.apply(&l, &r)
+        .ok_or_else(|| format!("cannot compare {} ({}) {} {} ({})", left, l, op, right, r))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::blackboard::{Blackboard, BlackboardValue};
+    use crate::behavior_tree::compare::{compare, AccessorRegistry, CompareOp, ValueRef};
+
+    struct Ship {
+        fuel: i64,
+        capacity: i64,
+        name: String,
+        docked: bool,
+    }
+
+    fn registry() -> AccessorRegistry<Ship> {
+        AccessorRegistry::new()
+            .register("fuel_percent", |s: &Ship| {
+                BlackboardValue::Float(s.fuel as f64 * 100.0 / s.capacity as f64)
+            })
+            .register("name", |s: &Ship| s.name.clone().into())
+            .register("docked", |s: &Ship| s.docked.into())
+    }
+
+    fn check(left: ValueRef, op: CompareOp, right: ValueRef) -> Result<bool, String> {
+        let ship = Ship {
+            fuel: 30,
+            capacity: 100,
+            name: "BOT-1".to_string(),
+            docked: true,
+        };
+        let blackboard = Blackboard::from_iter([
+            ("min_fuel", BlackboardValue::Int(25)),
+            ("home", BlackboardValue::from("X1-ABC")),
+        ]);
+        compare(&left, op, &right, &blackboard, &registry(), &ship)
+    }
+
+    fn accessor(name: &str) -> ValueRef {
+        ValueRef::Accessor(name.to_string())
+    }
+
+    fn key(name: &str) -> ValueRef {
+        ValueRef::Key(name.to_string())
+    }
+
+    #[test]
+    fn test_numeric() {
+        let fuel = || accessor("fuel_percent");
+        let literal = |i: i64| ValueRef::Literal(i.into());
+
+        assert_eq!(check(fuel(), CompareOp::Gt, key("min_fuel")), Ok(true));
+        assert_eq!(check(fuel(), CompareOp::Le, literal(25)), Ok(false));
+        assert_eq!(check(fuel(), CompareOp::Eq, literal(30)), Ok(true));
+        assert_eq!(check(literal(2), CompareOp::Lt, literal(3)), Ok(true));
+    }
+
+    #[test]
+    fn test_string_and_bool() {
+        let literal = |s: &str| ValueRef::Literal(s.into());
+
+        assert_eq!(
+            check(accessor("name"), CompareOp::Eq, literal("BOT-1")),
+            Ok(true)
+        );
+        assert_eq!(check(key("home"), CompareOp::Lt, literal("X2")), Ok(true));
+        assert_eq!(
+            check(
+                accessor("docked"),
+                CompareOp::Ne,
+                ValueRef::Literal(true.into())
+            ),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn test_errors_name_the_culprit() {
+        assert_eq!(
+            check(accessor("cargo"), CompareOp::Gt, key("min_fuel")),
+            Err("no accessor named `cargo` is registered".to_string())
+        );
+        assert_eq!(
+            check(key("max_fuel"), CompareOp::Gt, key("min_fuel")),
+            Err("blackboard key `max_fuel` is not set".to_string())
+        );
+        assert_eq!(
+            check(accessor("name"), CompareOp::Gt, key("min_fuel")),
+            Err("cannot compare name() (\"BOT-1\") > bb.min_fuel (25)".to_string())
+        );
+        assert_eq!(
+            check(
+                accessor("docked"),
+                CompareOp::Lt,
+                ValueRef::Literal(true.into())
+            ),
+            Err("cannot compare docked() (true) < true (true)".to_string())
+        );
+    }
+}
@@ -0,0 +1,265 @@
+use crate::behavior_tree::{ActionContext, Actionable, Response};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// An action of one of two action types that share their args, state and error, for trees using
+/// both. Convert trees of either side with `map_action(Either::Left)` or
+/// `map_action(Either::Right)`.
+///
+/// It (de)serializes untagged, as the action it holds. When both sides have an action of the
+/// same name and shape, loading picks the left one.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(untagged))]
+pub enum Either<A, B> {
+    Left(A),
+    Right(B),
+}
+
+impl<A, B> Actionable for Either<A, B>
+where
+    A: Actionable,
+    B: Actionable<
+        ActionArgs = A::ActionArgs,
+        ActionState = A::ActionState,
+        ActionError = A::ActionError,
+    >,
+{
+    type ActionError = A::ActionError;
+    type ActionArgs = A::ActionArgs;
+    type ActionState = A::ActionState;
+
+    async fn run(
+        &self,
+        args: &Self::ActionArgs,
+        state: &mut Self::ActionState,
+    ) -> Result<Response, Self::ActionError> {
+        match self {
+            Either::Left(action) => action.run(args, state).await,
+            Either::Right(action) => action.run(args, state).await,
+        }
+    }
+
+    async fn run_with(
+        &self,
+        cx: &ActionContext,
+        args: &Self::ActionArgs,
+        state: &mut Self::ActionState,
+    ) -> Result<Response, Self::ActionError> {
+        match self {
+            Either::Left(action) => action.run_with(cx, args, state).await,
+            Either::Right(action) => action.run_with(cx, args, state).await,
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Either::Left(action) => action.name(),
+            Either::Right(action) => action.name(),
+        }
+    }
+
+    fn is_read_only(&self) -> bool {
+        match self {
+            Either::Left(action) => action.is_read_only(),
+            Either::Right(action) => action.is_read_only(),
+        }
+    }
+}
+
+/// Generates an enum with one variant per action type, named like the type, which runs the
+/// action it holds. All action types have to share their args, state and error.
+///
+/// ```ignore
+/// compose_actions!(
+///     #[derive(Serialize, Deserialize)]
+///     #[serde(untagged)]
+///     pub Combined = NavAction | TradeAction
+/// );
+/// let tree: Behavior<Combined> = nav_tree.map_action(Combined::from);
+/// ```
+///
+/// Attributes are put on the enum. Like [`Either`], combined enums are meant to be untagged, so
+/// tree files show the actions as they are.
+#[macro_export]
+macro_rules! compose_actions {
+    ($(#[$meta:meta])* $vis:vis $name:ident = $first:ident $(| $rest:ident)+) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone)]
+        $vis enum $name {
+            $first($first),
+            $($rest($rest),)+
+        }
+
+        impl $crate::behavior_tree::Actionable for $name {
+            type ActionError = <$first as $crate::behavior_tree::Actionable>::ActionError;
+            type ActionArgs = <$first as $crate::behavior_tree::Actionable>::ActionArgs;
+            type ActionState = <$first as $crate::behavior_tree::Actionable>::ActionState;
+
+            async fn run(
+                &self,
+                args: &Self::ActionArgs,
+                state: &mut Self::ActionState,
+            ) -> Result<$crate::behavior_tree::Response, Self::ActionError> {
+                match self {
+                    $name::$first(action) => action.run(args, state).await,
+                    $($name::$rest(action) => action.run(args, state).await,)+
+                }
+            }
+
+            async fn run_with(
+                &self,
+                cx: &$crate::behavior_tree::ActionContext,
+                args: &Self::ActionArgs,
+                state: &mut Self::ActionState,
+            ) -> Result<$crate::behavior_tree::Response, Self::ActionError> {
+                match self {
+                    $name::$first(action) => action.run_with(cx, args, state).await,
+                    $($name::$rest(action) => action.run_with(cx, args, state).await,)+
+                }
+            }
+
+            fn name(&self) -> String {
+                match self {
+                    $name::$first(action) => action.name(),
+                    $($name::$rest(action) => action.name(),)+
+                }
+            }
+
+            fn is_read_only(&self) -> bool {
+                match self {
+                    $name::$first(action) => action.is_read_only(),
+                    $($name::$rest(action) => action.is_read_only(),)+
+                }
+            }
+        }
+
+        impl From<$first> for $name {
+            fn from(action: $first) -> Self {
+                $name::$first(action)
+            }
+        }
+
+        $(impl From<$rest> for $name {
+            fn from(action: $rest) -> Self {
+                $name::$rest(action)
+            }
+        })+
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::compose::Either;
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{Actionable, Behavior, Response};
+    #[cfg(feature = "serde")]
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, Debug, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    enum NavAction {
+        Jump { to: String },
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    enum TradeAction {
+        Sell,
+    }
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Ship {
+        at: String,
+        sold_at: Vec<String>,
+    }
+
+    impl Actionable for NavAction {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = Ship;
+
+        async fn run(&self, _: &(), ship: &mut Ship) -> Result<Response, String> {
+            match self {
+                NavAction::Jump { to } => ship.at = to.clone(),
+            }
+            Ok(Response::Success)
+        }
+    }
+
+    impl Actionable for TradeAction {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = Ship;
+
+        async fn run(&self, _: &(), ship: &mut Ship) -> Result<Response, String> {
+            match self {
+                TradeAction::Sell => ship.sold_at.push(ship.at.clone()),
+            }
+            Ok(Response::Success)
+        }
+    }
+
+    fn jump(to: &str) -> Behavior<NavAction> {
+        Action(NavAction::Jump { to: to.to_string() })
+    }
+
+    crate::compose_actions!(
+        #[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(untagged))]
+        Combined = NavAction | TradeAction
+    );
+
+    #[tokio::test]
+    async fn test_either() {
+        let bt: Behavior<Either<NavAction, TradeAction>> = Sequence(vec![
+            jump("X1-A").map_action(Either::Left),
+            Action(Either::Right(TradeAction::Sell)),
+            jump("X1-B").map_action(Either::Left),
+        ]);
+        let mut ship = Ship::default();
+
+        bt.run(&(), &mut ship).await.unwrap();
+        assert_eq!(ship.at, "X1-B");
+        assert_eq!(ship.sold_at, vec!["X1-A"]);
+    }
+
+    #[tokio::test]
+    async fn test_compose_actions() {
+        let bt: Behavior<Combined> = Sequence(vec![
+            jump("X1-A").map_action(Combined::from),
+            Action(TradeAction::Sell.into()),
+            Action(
+                NavAction::Jump {
+                    to: "X1-B".to_string(),
+                }
+                .into(),
+            ),
+            Action(Combined::TradeAction(TradeAction::Sell)),
+        ]);
+        let mut ship = Ship::default();
+
+        bt.run(&(), &mut ship).await.unwrap();
+        assert_eq!(ship.sold_at, vec!["X1-A", "X1-B"]);
+        assert_eq!(Combined::from(TradeAction::Sell).name(), "TradeAction");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_combined_actions_serialize_untagged() {
+        let bt: Behavior<Either<NavAction, TradeAction>> = Sequence(vec![
+            jump("X1-A").map_action(Either::Left),
+            Action(Either::Right(TradeAction::Sell)),
+        ]);
+        let json = r#"{"Sequence":[{"Action":{"Jump":{"to":"X1-A"}}},{"Action":"Sell"}]}"#;
+        assert_eq!(serde_json::to_string(&bt).unwrap(), json);
+
+        let combined: Behavior<Combined> = serde_json::from_str(json).unwrap();
+        assert_eq!(serde_json::to_string(&combined).unwrap(), json);
+        match combined {
+            Sequence(children) => assert!(matches!(
+                &children[1],
+                Action(Combined::TradeAction(TradeAction::Sell))
+            )),
+            _ => unreachable!(),
+        }
+    }
+}
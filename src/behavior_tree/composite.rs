@@ -0,0 +1,379 @@
+use crate::behavior_tree::blackboard::Blackboard;
+use crate::behavior_tree::decorator::Unresolved;
+use crate::behavior_tree::instance::RunContext;
+#[cfg(feature = "serde")]
+use crate::behavior_tree::loader::LoadError;
+use crate::behavior_tree::observer::TreeEvent;
+use crate::behavior_tree::{Actionable, Behavior, BehaviorError, BoxFuture, Response, TreeRng};
+#[cfg(feature = "serde")]
+use serde_json::Value;
+#[cfg(feature = "serde")]
+use std::collections::BTreeMap;
+use std::fmt;
+#[cfg(feature = "serde")]
+use std::sync::Arc;
+
+/// Decides which children of a `Composite` node run, in which order, and what the node results
+/// in, without adding a node kind.
+pub trait CompositeNode<A>: Send + Sync {
+    /// The name the composite is saved under and registered with in a `CompositeRegistry`.
+    fn name(&self) -> String;
+
+    /// What the registry's factory needs to build the composite again.
+    #[cfg(feature = "serde")]
+    fn params(&self) -> Value {
+        Value::Null
+    }
+
+    /// Runs the node. `children` runs the children of the node by index.
+    fn run<'a>(
+        &'a self,
+        children: Children<'a, A>,
+        args: &'a A::ActionArgs,
+        state: &'a mut A::ActionState,
+    ) -> BoxFuture<'a, Result<Response, BehaviorError<A::ActionError>>>
+    where
+        A: Actionable;
+
+    /// Whether this only stands in for a composite a `CompositeRegistry` has to build.
+    fn is_placeholder(&self) -> bool {
+        false
+    }
+}
+
+impl<A> fmt::Debug for dyn CompositeNode<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name())
+    }
+}
+
+/// Handle a [`CompositeNode`] runs its children with. Children run at their own node paths,
+/// so breakpoints, observers and node memory work as below the built-in composites. A child
+/// answering `Running` or failing fatally should usually end the node with that result.
+pub struct Children<'a, A: Actionable> {
+    children: &'a [Behavior<A>],
+    ctx: &'a mut RunContext<A>,
+}
+
+impl<'a, A: Actionable> Children<'a, A> {
+    pub(crate) fn new(children: &'a [Behavior<A>], ctx: &'a mut RunContext<A>) -> Self {
+        Self { children, ctx }
+    }
+
+    pub fn len(&self) -> usize {
+        self.children.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Behavior<A>> {
+        self.children.get(index)
+    }
+
+    /// Runs the child at `index`. Panics if there is no such child.
+    pub async fn run(
+        &mut self,
+        index: usize,
+        args: &A::ActionArgs,
+        state: &mut A::ActionState,
+    ) -> Result<Response, BehaviorError<A::ActionError>> {
+        self.children[index]
+            .run_child(index, self.ctx, args, state)
+            .await
+    }
+
+    /// The path of the `Composite` node.
+    pub fn path(&self) -> &[usize] {
+        &self.ctx.path
+    }
+
+    pub fn blackboard(&self) -> &Blackboard {
+        &self.ctx.blackboard
+    }
+
+    /// The rng of the instance, so random choices replay with its seed.
+    pub fn rng(&mut self) -> &mut TreeRng {
+        &mut self.ctx.rng
+    }
+
+    /// Tells the observers of the instance about `event`, as if the `Composite` node emitted it.
+    pub fn emit(&mut self, event: TreeEvent) {
+        self.ctx.emit(event)
+    }
+}
+
+impl<A> CompositeNode<A> for Unresolved {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    #[cfg(feature = "serde")]
+    fn params(&self) -> Value {
+        self.params.clone()
+    }
+
+    fn run<'a>(
+        &'a self,
+        _: Children<'a, A>,
+        _: &'a A::ActionArgs,
+        _: &'a mut A::ActionState,
+    ) -> BoxFuture<'a, Result<Response, BehaviorError<A::ActionError>>>
+    where
+        A: Actionable,
+    {
+        Box::pin(async move { Err(self.fail()) })
+    }
+
+    fn is_placeholder(&self) -> bool {
+        true
+    }
+}
+
+impl Unresolved {
+    pub(crate) fn of_composite<A>(node: &dyn CompositeNode<A>) -> Self {
+        Self {
+            name: node.name(),
+            #[cfg(feature = "serde")]
+            params: node.params(),
+        }
+    }
+}
+
+// saves a composite as `{"name": ..., "params": ...}`, loads it as an `Unresolved`
+#[cfg(feature = "serde")]
+pub(crate) mod serde_composite {
+    use crate::behavior_tree::composite::CompositeNode;
+    use crate::behavior_tree::decorator::serde_decorator::Saved;
+    use crate::behavior_tree::decorator::Unresolved;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::sync::Arc;
+
+    pub fn serialize<A, S: Serializer>(
+        node: &Arc<dyn CompositeNode<A>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Saved {
+            name: node.name(),
+            params: node.params(),
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, A, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Arc<dyn CompositeNode<A>>, D::Error> {
+        let Saved { name, params } = Saved::deserialize(deserializer)?;
+        Ok(Arc::new(Unresolved { name, params }))
+    }
+}
+
+#[cfg(feature = "serde")]
+type Factory<A> = Box<dyn Fn(&Value) -> Result<Arc<dyn CompositeNode<A>>, String> + Send + Sync>;
+
+/// Builds composites from the name and params they were saved with.
+#[cfg(feature = "serde")]
+pub struct CompositeRegistry<A> {
+    factories: BTreeMap<String, Factory<A>>,
+}
+
+#[cfg(feature = "serde")]
+impl<A> Default for CompositeRegistry<A> {
+    fn default() -> Self {
+        Self {
+            factories: BTreeMap::new(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<A> CompositeRegistry<A> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        factory: impl Fn(&Value) -> Result<Arc<dyn CompositeNode<A>>, String> + Send + Sync + 'static,
+    ) {
+        self.factories.insert(name.into(), Box::new(factory));
+    }
+
+    /// Builds every composite in `tree` that has a factory registered under its name. Fails if
+    /// a composite that is only a placeholder has none, or if a factory rejects the params.
+    pub fn resolve(&self, tree: &mut Behavior<A>) -> Result<(), LoadError> {
+        self.resolve_at(tree, &mut vec![])
+    }
+
+    fn resolve_at(&self, node: &mut Behavior<A>, path: &mut Vec<usize>) -> Result<(), LoadError> {
+        if let Behavior::Composite { node, .. } = node {
+            let name = node.name();
+            let invalid = |message| LoadError::InvalidNode {
+                message,
+                path: path.clone(),
+            };
+            match self.factories.get(&name) {
+                Some(factory) => {
+                    *node = factory(&node.params())
+                        .map_err(|err| invalid(format!("composite `{}`: {}", name, err)))?;
+                }
+                None if node.is_placeholder() => {
+                    let known: Vec<_> = self.factories.keys().map(String::as_str).collect();
+                    return Err(invalid(format!(
+                        "unknown composite `{}`, registered: [{}]",
+                        name,
+                        known.join(", ")
+                    )));
+                }
+                None => {}
+            }
+        }
+        for (i, child) in node.children_mut().into_iter().enumerate() {
+            path.push(i);
+            self.resolve_at(child, path)?;
+            path.pop();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::composite::{Children, CompositeNode};
+    use crate::behavior_tree::debugger::DebugController;
+    use crate::behavior_tree::runner::Runner;
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{
+        Actionable, Behavior, BehaviorError, BoxFuture, Response, TreeInstance,
+    };
+    use std::sync::Arc;
+
+    // visits a stop, or waits there forever
+    #[derive(Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    enum Stop {
+        Visit(u32),
+        Closed,
+        Wait,
+    }
+
+    impl Actionable for Stop {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = Vec<u32>;
+
+        async fn run(&self, _: &(), visited: &mut Vec<u32>) -> Result<Response, String> {
+            match self {
+                Stop::Visit(stop) => {
+                    visited.push(*stop);
+                    Ok(Response::Success)
+                }
+                Stop::Closed => Ok(Response::Failure),
+                Stop::Wait => Ok(Response::Running),
+            }
+        }
+    }
+
+    // tries its children like a `Select`, the last one first
+    struct Backwards;
+
+    impl<A> CompositeNode<A> for Backwards {
+        fn name(&self) -> String {
+            "backwards".to_string()
+        }
+
+        fn run<'a>(
+            &'a self,
+            mut children: Children<'a, A>,
+            args: &'a A::ActionArgs,
+            state: &'a mut A::ActionState,
+        ) -> BoxFuture<'a, Result<Response, BehaviorError<A::ActionError>>>
+        where
+            A: Actionable,
+        {
+            Box::pin(async move {
+                for i in (0..children.len()).rev() {
+                    match children.run(i, args, state).await? {
+                        Response::Failure => {}
+                        response => return Ok(response),
+                    }
+                }
+                Ok(Response::Failure)
+            })
+        }
+    }
+
+    fn backwards(children: Vec<Behavior<Stop>>) -> Behavior<Stop> {
+        Composite {
+            node: Arc::new(Backwards),
+            children,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_composites_run_their_children_by_index() {
+        let mut visited = vec![];
+        let bt = backwards(vec![Action(Stop::Visit(1)), Action(Stop::Visit(2))]);
+        TreeInstance::new(bt).run(&(), &mut visited).await.unwrap();
+        assert_eq!(visited, [2]);
+
+        let bt = backwards(vec![Action(Stop::Visit(1)), Action(Stop::Closed)]);
+        TreeInstance::new(bt).run(&(), &mut visited).await.unwrap();
+        assert_eq!(visited, [2, 1]);
+
+        let bt = backwards(vec![Action(Stop::Closed), Action(Stop::Closed)]);
+        let result = TreeInstance::new(bt).run(&(), &mut visited).await;
+        assert_eq!(result.unwrap(), Response::Failure);
+    }
+
+    #[tokio::test]
+    async fn test_running_and_paths_through_a_composite() {
+        let mut visited = vec![];
+        let response = TreeInstance::new(backwards(vec![Action(Stop::Wait)]))
+            .run(&(), &mut visited)
+            .await
+            .unwrap();
+        assert_eq!(response, Response::Running);
+
+        let bt = Sequence(vec![
+            backwards(vec![Action(Stop::Visit(1))]),
+            Action(Stop::Visit(2)),
+        ]);
+        let debugger = DebugController::new();
+        debugger.add_breakpoint(vec![0, 0]);
+        let mut runner = Runner::new(TreeInstance::new(bt)).with_debugger(debugger.clone());
+        let mut tick = Box::pin(runner.tick(&(), &mut visited));
+        tokio::select! {
+            _ = &mut tick => panic!("the breakpoint below the composite wasn't hit"),
+            paused = debugger.wait_until_paused() => assert_eq!(paused.path, vec![0, 0]),
+        }
+        debugger.resume();
+        tick.await.unwrap();
+        assert_eq!(visited, [1, 2]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn test_saved_composites_are_built_by_the_registry() {
+        use crate::behavior_tree::composite::CompositeRegistry;
+
+        let bt = backwards(vec![Action(Stop::Visit(1)), Action(Stop::Visit(2))]);
+        let json = serde_json::to_string(&bt).unwrap();
+        assert_eq!(
+            json,
+            r#"{"Composite":{"node":{"name":"backwards"},"children":[{"Action":{"Visit":1}},{"Action":{"Visit":2}}]}}"#
+        );
+
+        let mut loaded: Behavior<Stop> = serde_json::from_str(&json).unwrap();
+        let mut visited = vec![];
+        assert!(loaded.run(&(), &mut visited).await.is_err());
+
+        let mut registry = CompositeRegistry::new();
+        registry.register("backwards", |_| Ok(Arc::new(Backwards)));
+        registry.resolve(&mut loaded).unwrap();
+        loaded.run(&(), &mut visited).await.unwrap();
+        assert_eq!(visited, [2]);
+    }
+}
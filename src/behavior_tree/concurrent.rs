@@ -0,0 +1,239 @@
+use crate::behavior_tree::instance::RunContext;
+use crate::behavior_tree::{Actionable, Behavior, BehaviorError, BoxFuture, NodePath, Response};
+use std::collections::VecDeque;
+use std::future::poll_fn;
+use std::task::Poll;
+
+type ChildResult<E> = Result<Response, BehaviorError<E>>;
+
+/// The results of read-only conditions among the children of a `Select` or `Sequence` that ran
+/// concurrently, waiting for the node to get to them.
+pub(crate) struct Batch<E> {
+    results: VecDeque<(usize, Result<Response, E>)>,
+}
+
+impl<E> Batch<E> {
+    pub(crate) fn new() -> Self {
+        Self {
+            results: VecDeque::new(),
+        }
+    }
+
+    /// Runs child `index` of `children`. With concurrent conditions enabled, reaching the first
+    /// of two or more adjacent read-only actions runs all of them at once, each on a clone of
+    /// the state, and keeps the results of the others for when the node gets to them. Each
+    /// child still runs as a node when the node gets to it, with its toggles, observers and
+    /// audit, only its action isn't run again.
+    pub(crate) async fn run<A>(
+        &mut self,
+        children: &[Behavior<A>],
+        index: usize,
+        ctx: &mut RunContext<A>,
+        args: &A::ActionArgs,
+        state: &mut A::ActionState,
+    ) -> ChildResult<E>
+    where
+        A: Actionable<ActionError = E>,
+    {
+        if self.results.front().is_none_or(|(i, _)| *i != index) {
+            self.results.clear();
+            let conditions = read_only_run(children, index, ctx);
+            match ctx.concurrent_conditions {
+                Some(clone) if conditions.len() > 1 && !ctx.is_instrumented() => {
+                    let mut copies: Vec<_> = conditions.iter().map(|_| clone(state)).collect();
+                    let futures =
+                        conditions
+                            .iter()
+                            .zip(&mut copies)
+                            .map(|((path, action), copy)| {
+                                Box::pin(ctx.run_scoped(path, action, args, copy))
+                                    as BoxFuture<'_, _>
+                            });
+                    let results = join_all(futures.collect()).await;
+                    self.results = (index..).zip(results).collect();
+                }
+                _ => return children[index].run_child(index, ctx, args, state).await,
+            }
+        }
+        let (_, result) = self
+            .results
+            .pop_front()
+            .expect("the batch starts at `index`");
+        ctx.batched = Some(result);
+        let result = children[index].run_child(index, ctx, args, state).await;
+        // a cancelled run doesn't get to the action
+        ctx.batched = None;
+        result
+    }
+}
+
+// the paths and actions of the read-only `Action` leaves from child `index` of `children` on
+// that are enabled, none if the run is cancelled
+fn read_only_run<'a, A: Actionable>(
+    children: &'a [Behavior<A>],
+    index: usize,
+    ctx: &RunContext<A>,
+) -> Vec<(NodePath, &'a A)> {
+    if ctx.cancellation.is_cancelled() {
+        return vec![];
+    }
+    (index..)
+        .zip(&children[index..])
+        .map_while(|(i, child)| match child {
+            Behavior::Action(action) if action.is_read_only() => {
+                let mut path = ctx.path.clone();
+                path.push(i);
+                let enabled = ctx.toggles.check(&path, child).is_none();
+                enabled.then_some((path, action))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+async fn join_all<T>(futures: Vec<BoxFuture<'_, T>>) -> Vec<T> {
+    let mut pending: Vec<_> = futures.into_iter().map(Some).collect();
+    let mut outputs: Vec<Option<T>> = pending.iter().map(|_| None).collect();
+    poll_fn(|cx| {
+        let mut done = true;
+        for (future, output) in pending.iter_mut().zip(&mut outputs) {
+            if let Some(running) = future {
+                match running.as_mut().poll(cx) {
+                    Poll::Ready(value) => {
+                        *output = Some(value);
+                        *future = None;
+                    }
+                    Poll::Pending => done = false,
+                }
+            }
+        }
+        if done {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await;
+    outputs.into_iter().flatten().collect()
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use crate::behavior_tree::observer::RecordingObserver;
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{Actionable, Behavior, NodePath, Response, TreeInstance};
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    // a condition that takes 100ms to check, like a request to a server
+    #[derive(Clone, Debug)]
+    enum Remote {
+        IsAbove(u32),
+        Add(u32),
+    }
+
+    impl Actionable for Remote {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = u32;
+
+        async fn run(&self, _: &(), value: &mut u32) -> Result<Response, String> {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            match self {
+                Remote::IsAbove(limit) if *value > *limit => Ok(Response::Success),
+                Remote::IsAbove(_) => Ok(Response::Failure),
+                Remote::Add(amount) => {
+                    *value += amount;
+                    Ok(Response::Success)
+                }
+            }
+        }
+
+        fn is_read_only(&self) -> bool {
+            matches!(self, Remote::IsAbove(_))
+        }
+    }
+
+    fn is_above(limit: u32) -> Behavior<Remote> {
+        Action(Remote::IsAbove(limit))
+    }
+
+    struct Timed {
+        result: String,
+        value: u32,
+        visited: Vec<NodePath>,
+        millis: u128,
+    }
+
+    async fn run_timed(bt: &Behavior<Remote>, concurrent: bool, mut value: u32) -> Timed {
+        let mut instance = TreeInstance::new(bt.clone()).with_history(1);
+        if concurrent {
+            instance = instance.with_concurrent_conditions();
+        }
+        let start = Instant::now();
+        let result = instance.run(&(), &mut value).await;
+        Timed {
+            result: format!("{:?}", result),
+            value,
+            visited: instance.history()[0].last_run.visited.clone(),
+            millis: start.elapsed().as_millis(),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_concurrent_conditions_match_sequential_runs() {
+        let trees = [
+            Select(vec![is_above(8), is_above(6), is_above(4), is_above(2)]),
+            Select(vec![is_above(8), is_above(9)]),
+            Sequence(vec![
+                is_above(1),
+                is_above(2),
+                Action(Remote::Add(5)),
+                is_above(6),
+                is_above(9),
+            ]),
+            Sequence(vec![is_above(1), is_above(7), is_above(2)]),
+        ];
+        for bt in &trees {
+            for value in [3, 5, 10] {
+                let sequential = run_timed(bt, false, value).await;
+                let concurrent = run_timed(bt, true, value).await;
+                assert_eq!(
+                    concurrent.result, sequential.result,
+                    "{:?} from {}",
+                    bt, value
+                );
+                assert_eq!(concurrent.value, sequential.value);
+                assert_eq!(concurrent.visited, sequential.visited);
+            }
+        }
+
+        let sequential = run_timed(&trees[0], false, 3).await;
+        let concurrent = run_timed(&trees[0], true, 3).await;
+        assert_eq!((sequential.millis, concurrent.millis), (400, 100));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_concurrent_conditions_run_as_nodes() {
+        let bt = Select(vec![is_above(8), is_above(6), is_above(4), is_above(2)]);
+        let mut runs = vec![];
+        for concurrent in [false, true] {
+            let observer = RecordingObserver::default();
+            let mut instance = TreeInstance::new(bt.clone()).with_observer(observer.clone());
+            if concurrent {
+                instance = instance.with_concurrent_conditions();
+            }
+            instance.set_enabled(vec![2], false);
+            let start = Instant::now();
+            let result = instance.run(&(), &mut 3).await;
+            let millis = start.elapsed().as_millis();
+            runs.push((format!("{:?}", result), observer.events(), millis));
+        }
+        let (sequential, concurrent) = (&runs[0], &runs[1]);
+        assert_eq!(concurrent.0, sequential.0);
+        assert_eq!(concurrent.1, sequential.1);
+        assert_eq!(concurrent.1.len(), 10);
+        // the disabled child ends the batch
+        assert_eq!((sequential.2, concurrent.2), (300, 200));
+    }
+}
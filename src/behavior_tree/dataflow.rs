@@ -0,0 +1,284 @@
+use crate::behavior_tree::blackboard::Blackboard;
+use crate::behavior_tree::Behavior;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// A stage of a `Pipeline` node: a subtree with the blackboard keys it reads and the ones it
+/// writes for the stages after it.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PipelineStage<A> {
+    pub name: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub inputs: Vec<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub outputs: Vec<String>,
+    pub branch: Behavior<A>,
+}
+
+impl<A> PipelineStage<A> {
+    pub fn new<K: Into<String>>(
+        name: impl Into<String>,
+        inputs: impl IntoIterator<Item = K>,
+        outputs: impl IntoIterator<Item = K>,
+        branch: Behavior<A>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            inputs: inputs.into_iter().map(Into::into).collect(),
+            outputs: outputs.into_iter().map(Into::into).collect(),
+            branch,
+        }
+    }
+}
+
+/// The inputs of the stages no earlier stage outputs, which the blackboard has to provide.
+pub(crate) fn external_inputs<A>(stages: &[PipelineStage<A>]) -> Vec<&str> {
+    let mut written = BTreeSet::new();
+    let mut external = vec![];
+    for stage in stages {
+        for input in &stage.inputs {
+            if !written.contains(input.as_str()) && !external.contains(&input.as_str()) {
+                external.push(input.as_str());
+            }
+        }
+        written.extend(stage.outputs.iter().map(String::as_str));
+    }
+    external
+}
+
+/// The first stage reading a key that no earlier stage outputs and that `provided` says the
+/// blackboard doesn't hold, with that key.
+#[cfg(feature = "serde")]
+pub(crate) fn unwired_input<A>(
+    stages: &[PipelineStage<A>],
+    provided: impl Fn(&str) -> bool,
+) -> Option<(&str, &str)> {
+    let mut written = BTreeSet::new();
+    for stage in stages {
+        if let Some(input) = stage
+            .inputs
+            .iter()
+            .find(|input| !written.contains(input.as_str()) && !provided(input))
+        {
+            return Some((&stage.name, input));
+        }
+        written.extend(stage.outputs.iter().map(String::as_str));
+    }
+    None
+}
+
+/// Puts back the keys a stage changed without declaring them as outputs, as they were
+/// `before` it ran. Returns those keys.
+pub(crate) fn revert_undeclared(
+    before: &Blackboard,
+    after: &mut Blackboard,
+    outputs: &[String],
+) -> Vec<String> {
+    let keys: BTreeSet<String> = before
+        .keys()
+        .chain(after.keys())
+        .map(String::from)
+        .collect();
+    let mut reverted = vec![];
+    for key in keys {
+        if outputs.contains(&key) || before.get(&key) == after.get(&key) {
+            continue;
+        }
+        match before.get(&key) {
+            Some(value) => after.set(key.clone(), value.clone()),
+            None => {
+                after.remove(&key);
+            }
+        }
+        reverted.push(key);
+    }
+    reverted
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::compare::ValueRef;
+    use crate::behavior_tree::dataflow::PipelineStage;
+    use crate::behavior_tree::observer::{BehaviorObserver, TreeEvent};
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{Actionable, Behavior, Response, TreeInstance};
+    #[cfg(feature = "serde")]
+    use serde::{Deserialize, Serialize};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    struct Noop;
+
+    impl Actionable for Noop {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = ();
+
+        async fn run(&self, _: &(), _: &mut ()) -> Result<Response, String> {
+            Ok(Response::Success)
+        }
+    }
+
+    fn set<A>(key: &str, value: &str) -> Behavior<A> {
+        SetKey {
+            key: key.to_string(),
+            value: ValueRef::Literal(value.into()),
+        }
+    }
+
+    fn copy(key: &str, from: &str) -> Behavior<Noop> {
+        SetKey {
+            key: key.to_string(),
+            value: ValueRef::Key(from.to_string()),
+        }
+    }
+
+    // scan the market, plan a route from it, then fly the route
+    fn trade_run(plan_outputs: &[&str]) -> Behavior<Noop> {
+        Pipeline(vec![
+            PipelineStage::new("scan", ["system"], ["market"], set("market", "X1-M1")),
+            PipelineStage::new(
+                "plan",
+                vec!["market"],
+                plan_outputs.to_vec(),
+                Sequence(vec![copy("route", "market"), set("scratch", "tmp")]),
+            ),
+            PipelineStage::new(
+                "fly",
+                ["route"],
+                ["arrived"],
+                Sequence(vec![copy("arrived", "route")]),
+            ),
+        ])
+    }
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<TreeEvent>>>);
+
+    impl<A> BehaviorObserver<A> for Recorder {
+        fn on_event(&mut self, _: &[usize], event: &TreeEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stages_pass_their_outputs_on() {
+        let recorder = Recorder::default();
+        let mut instance = TreeInstance::new(trade_run(&["route"])).with_observer(recorder.clone());
+        instance.blackboard_mut().set("system", "X1");
+        instance.run(&(), &mut ()).await.unwrap();
+        assert_eq!(instance.blackboard().get("arrived"), Some(&"X1-M1".into()));
+        // only declared outputs survive their stage
+        assert!(!instance.blackboard().contains_key("scratch"));
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [TreeEvent::UndeclaredWrite {
+                stage: "plan".to_string(),
+                key: "scratch".to_string()
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_inputs_fail_the_stage() {
+        let recorder = Recorder::default();
+        let mut instance = TreeInstance::new(trade_run(&[])).with_observer(recorder.clone());
+        instance.blackboard_mut().set("system", "X1");
+        let err = instance.run(&(), &mut ()).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "pipeline stage `fly` reads `route`, which is not set"
+        );
+        let undeclared: Vec<_> = recorder
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|event| match event {
+                TreeEvent::UndeclaredWrite { key, .. } => key.clone(),
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect();
+        assert_eq!(undeclared, ["route", "scratch"]);
+    }
+
+    #[tokio::test]
+    async fn test_failing_stages_keep_their_errors() {
+        let bt: Behavior<Noop> = Pipeline(vec![PipelineStage::new(
+            "dock",
+            Vec::<String>::new(),
+            [],
+            Subtree {
+                name: "dock".to_string(),
+                remapping: Default::default(),
+            },
+        )]);
+        let err = TreeInstance::new(bt).run(&(), &mut ()).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "pipeline stage `dock` failed ([0]: subtree `dock` was never resolved by a \
+             SubtreeRegistry)"
+        );
+    }
+
+    // counts its runs, and returns `Running` on the first
+    #[derive(Clone, Debug)]
+    struct Dock;
+
+    impl Actionable for Dock {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = u32;
+
+        async fn run(&self, _: &(), runs: &mut u32) -> Result<Response, String> {
+            *runs += 1;
+            match *runs {
+                1 => Ok(Response::Running),
+                _ => Ok(Response::Success),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_running_stages_resume_where_they_left_off() {
+        let bt = Pipeline(vec![
+            PipelineStage::new("scan", [], ["market"], set("market", "X1-M1")),
+            PipelineStage::new("dock", ["market"], [], Action(Dock)),
+        ]);
+        let mut instance = TreeInstance::new(bt);
+        let mut runs = 0;
+        let result = instance.run(&(), &mut runs).await.unwrap();
+        assert_eq!(result, Response::Running);
+
+        // a rerun of `scan` would overwrite the market again
+        instance.blackboard_mut().set("market", "X1-M2");
+        let result = instance.run(&(), &mut runs).await.unwrap();
+        assert_eq!(result, Response::Success);
+        assert_eq!(runs, 2);
+        assert_eq!(instance.blackboard().get("market"), Some(&"X1-M2".into()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_miswired_pipelines_fail_validation() {
+        use crate::behavior_tree::loader::LoadedTree;
+
+        let tree = |plan_outputs: &[&str]| {
+            serde_json::json!({
+                "runtime_keys": ["system"],
+                "tree": {"Sequence": [serde_json::to_value(trade_run(plan_outputs)).unwrap()]},
+            })
+            .to_string()
+        };
+        LoadedTree::<Noop>::from_json(&tree(&["route"])).unwrap();
+        let err = LoadedTree::<Noop>::from_json(&tree(&[])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "pipeline stage `fly` at [0] reads `route`, which no earlier stage outputs and the \
+             blackboard doesn't declare"
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use crate::behavior_tree::sync::Notify;
+use crate::behavior_tree::NodePath;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+/// Where a paused tree is waiting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PausedAt {
+    pub path: NodePath,
+    // the label of the `Breakpoint` node, `None` for path breakpoints
+    pub label: Option<String>,
+}
+
+#[derive(Default)]
+struct DebugState {
+    breakpoints: HashSet<NodePath>,
+    paused: Option<PausedAt>,
+}
+
+#[derive(Default)]
+struct Inner {
+    state: Mutex<DebugState>,
+    resumed: Notify,
+    paused: Notify,
+}
+
+/// Handle to pause a running tree at breakpoints and resume it, shared between the runner and
+/// whoever is debugging it.
+#[derive(Clone, Default)]
+pub struct DebugController {
+    inner: Arc<Inner>,
+}
+
+impl DebugController {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pauses the tree whenever the node at `path` is about to run.
+    pub fn add_breakpoint(&self, path: NodePath) {
+        self.inner.state.lock().unwrap().breakpoints.insert(path);
+    }
+
+    pub fn remove_breakpoint(&self, path: &[usize]) {
+        self.inner.state.lock().unwrap().breakpoints.remove(path);
+    }
+
+    pub fn has_breakpoint(&self, path: &[usize]) -> bool {
+        self.inner.state.lock().unwrap().breakpoints.contains(path)
+    }
+
+    pub fn paused_at(&self) -> Option<PausedAt> {
+        self.inner.state.lock().unwrap().paused.clone()
+    }
+
+    /// Waits until the tree is paused at a breakpoint.
+    pub async fn wait_until_paused(&self) -> PausedAt {
+        loop {
+            let notified = self.inner.paused.notified();
+            if let Some(paused) = self.paused_at() {
+                return paused;
+            }
+            notified.await;
+        }
+    }
+
+    /// Lets a paused tree continue. Does nothing if the tree isn't paused.
+    pub fn resume(&self) {
+        let resumed = self.inner.state.lock().unwrap().paused.take().is_some();
+        if resumed {
+            self.inner.resumed.notify_waiters();
+        }
+    }
+
+    pub(crate) async fn pause(&self, paused: PausedAt) {
+        self.inner.state.lock().unwrap().paused = Some(paused);
+        self.inner.paused.notify_waiters();
+        loop {
+            let resumed = self.inner.resumed.notified();
+            if self.paused_at().is_none() {
+                return;
+            }
+            resumed.await;
+        }
+    }
+}
@@ -0,0 +1,383 @@
+use crate::behavior_tree::blackboard::Blackboard;
+use crate::behavior_tree::clock::Sleeper;
+use crate::behavior_tree::instance::RunContext;
+#[cfg(feature = "serde")]
+use crate::behavior_tree::loader::LoadError;
+use crate::behavior_tree::observer::TreeEvent;
+use crate::behavior_tree::{Actionable, Behavior, BehaviorError, BoxFuture, Response};
+#[cfg(feature = "serde")]
+use serde_json::Value;
+#[cfg(feature = "serde")]
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Wraps custom logic around the child of a `Decorated` node, without adding a node kind.
+pub trait Decorator<A>: Send + Sync {
+    /// The name the decorator is saved under and registered with in a `DecoratorRegistry`.
+    fn name(&self) -> String;
+
+    /// What the registry's factory needs to build the decorator again.
+    #[cfg(feature = "serde")]
+    fn params(&self) -> Value {
+        Value::Null
+    }
+
+    /// Runs the node. `child` runs the decorated child, as many times as the decorator wants.
+    fn decorate<'a>(
+        &'a self,
+        child: Executor<'a, A>,
+        args: &'a A::ActionArgs,
+        state: &'a mut A::ActionState,
+    ) -> BoxFuture<'a, Result<Response, BehaviorError<A::ActionError>>>
+    where
+        A: Actionable;
+
+    /// Whether this only stands in for a decorator a `DecoratorRegistry` has to build.
+    fn is_placeholder(&self) -> bool {
+        false
+    }
+}
+
+impl<A> fmt::Debug for dyn Decorator<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name())
+    }
+}
+
+/// Handle a [`Decorator`] runs its child with.
+pub struct Executor<'a, A: Actionable> {
+    child: &'a Behavior<A>,
+    ctx: &'a mut RunContext<A>,
+}
+
+impl<'a, A: Actionable> Executor<'a, A> {
+    pub(crate) fn new(child: &'a Behavior<A>, ctx: &'a mut RunContext<A>) -> Self {
+        Self { child, ctx }
+    }
+
+    pub async fn run(
+        &mut self,
+        args: &A::ActionArgs,
+        state: &mut A::ActionState,
+    ) -> Result<Response, BehaviorError<A::ActionError>> {
+        self.child.run_child(0, self.ctx, args, state).await
+    }
+
+    pub fn child(&self) -> &Behavior<A> {
+        self.child
+    }
+
+    /// The path of the `Decorated` node.
+    pub fn path(&self) -> &[usize] {
+        &self.ctx.path
+    }
+
+    pub fn blackboard(&self) -> &Blackboard {
+        &self.ctx.blackboard
+    }
+
+    /// Tells the observers of the instance about `event`, as if the `Decorated` node emitted it.
+    pub fn emit(&mut self, event: TreeEvent) {
+        self.ctx.emit(event)
+    }
+
+    /// The timer of the instance, for decorators that wait or measure time.
+    pub fn sleeper(&self) -> Arc<dyn Sleeper> {
+        self.ctx.sleeper.clone()
+    }
+}
+
+/// A decorator or composite known only by name, e.g. after loading a tree or converting its
+/// actions. Fails when run.
+#[derive(Debug, Clone)]
+pub(crate) struct Unresolved {
+    pub(crate) name: String,
+    #[cfg(feature = "serde")]
+    pub(crate) params: Value,
+}
+
+impl Unresolved {
+    pub(crate) fn of<A>(decorator: &dyn Decorator<A>) -> Self {
+        Self {
+            name: decorator.name(),
+            #[cfg(feature = "serde")]
+            params: decorator.params(),
+        }
+    }
+
+    pub(crate) fn fail<E>(&self) -> BehaviorError<E> {
+        BehaviorError::failed(format!(
+            "`{}` has to be built by a registry before it can run",
+            self.name
+        ))
+    }
+}
+
+impl<A> Decorator<A> for Unresolved {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    #[cfg(feature = "serde")]
+    fn params(&self) -> Value {
+        self.params.clone()
+    }
+
+    fn decorate<'a>(
+        &'a self,
+        _: Executor<'a, A>,
+        _: &'a A::ActionArgs,
+        _: &'a mut A::ActionState,
+    ) -> BoxFuture<'a, Result<Response, BehaviorError<A::ActionError>>>
+    where
+        A: Actionable,
+    {
+        Box::pin(async move { Err(self.fail()) })
+    }
+
+    fn is_placeholder(&self) -> bool {
+        true
+    }
+}
+
+// saves a decorator as `{"name": ..., "params": ...}`, loads it as an `Unresolved`
+#[cfg(feature = "serde")]
+pub(crate) mod serde_decorator {
+    use crate::behavior_tree::decorator::{Decorator, Unresolved};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde_json::Value;
+    use std::sync::Arc;
+
+    #[derive(Serialize, Deserialize)]
+    pub(crate) struct Saved {
+        pub(crate) name: String,
+        #[serde(default, skip_serializing_if = "Value::is_null")]
+        pub(crate) params: Value,
+    }
+
+    pub fn serialize<A, S: Serializer>(
+        decorator: &Arc<dyn Decorator<A>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Saved {
+            name: decorator.name(),
+            params: decorator.params(),
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, A, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Arc<dyn Decorator<A>>, D::Error> {
+        let Saved { name, params } = Saved::deserialize(deserializer)?;
+        Ok(Arc::new(Unresolved { name, params }))
+    }
+}
+
+#[cfg(feature = "serde")]
+type Factory<A> = Box<dyn Fn(&Value) -> Result<Arc<dyn Decorator<A>>, String> + Send + Sync>;
+
+/// Builds decorators from the name and params they were saved with.
+#[cfg(feature = "serde")]
+pub struct DecoratorRegistry<A> {
+    factories: BTreeMap<String, Factory<A>>,
+}
+
+#[cfg(feature = "serde")]
+impl<A> Default for DecoratorRegistry<A> {
+    fn default() -> Self {
+        Self {
+            factories: BTreeMap::new(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<A> DecoratorRegistry<A> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        factory: impl Fn(&Value) -> Result<Arc<dyn Decorator<A>>, String> + Send + Sync + 'static,
+    ) {
+        self.factories.insert(name.into(), Box::new(factory));
+    }
+
+    /// Builds every decorator in `tree` that has a factory registered under its name. Fails if
+    /// a decorator that is only a placeholder has none, or if a factory rejects the params.
+    pub fn resolve(&self, tree: &mut Behavior<A>) -> Result<(), LoadError> {
+        self.resolve_at(tree, &mut vec![])
+    }
+
+    fn resolve_at(&self, node: &mut Behavior<A>, path: &mut Vec<usize>) -> Result<(), LoadError> {
+        if let Behavior::Decorated { decorator, .. } = node {
+            let name = decorator.name();
+            let invalid = |message| LoadError::InvalidNode {
+                message,
+                path: path.clone(),
+            };
+            match self.factories.get(&name) {
+                Some(factory) => {
+                    *decorator = factory(&decorator.params())
+                        .map_err(|err| invalid(format!("decorator `{}`: {}", name, err)))?;
+                }
+                None if decorator.is_placeholder() => {
+                    let known: Vec<_> = self.factories.keys().map(String::as_str).collect();
+                    return Err(invalid(format!(
+                        "unknown decorator `{}`, registered: [{}]",
+                        name,
+                        known.join(", ")
+                    )));
+                }
+                None => {}
+            }
+        }
+        for (i, child) in node.children_mut().into_iter().enumerate() {
+            path.push(i);
+            self.resolve_at(child, path)?;
+            path.pop();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::decorator::{Decorator, Executor};
+    use crate::behavior_tree::observer::{LogLevel, TreeEvent};
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{
+        Actionable, Behavior, BehaviorError, BoxFuture, NodePath, Response, TreeInstance,
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    struct Increase;
+
+    impl Actionable for Increase {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = u32;
+
+        async fn run(&self, _: &(), count: &mut u32) -> Result<Response, String> {
+            *count += 1;
+            Ok(Response::Success)
+        }
+    }
+
+    // logs before running its child `times` times in a row
+    struct LogAndRepeat {
+        times: u32,
+    }
+
+    impl<A: Actionable> Decorator<A> for LogAndRepeat {
+        fn name(&self) -> String {
+            "log_and_repeat".to_string()
+        }
+
+        #[cfg(feature = "serde")]
+        fn params(&self) -> serde_json::Value {
+            serde_json::json!({ "times": self.times })
+        }
+
+        fn decorate<'a>(
+            &'a self,
+            mut child: Executor<'a, A>,
+            args: &'a A::ActionArgs,
+            state: &'a mut A::ActionState,
+        ) -> BoxFuture<'a, Result<Response, BehaviorError<A::ActionError>>> {
+            Box::pin(async move {
+                child.emit(TreeEvent::LogEmitted {
+                    level: LogLevel::Info,
+                    message: format!("running {} times", self.times),
+                });
+                for _ in 1..self.times {
+                    child.run(args, state).await?;
+                }
+                child.run(args, state).await
+            })
+        }
+    }
+
+    fn log_and_repeat(times: u32, child: Behavior<Increase>) -> Behavior<Increase> {
+        Decorated {
+            decorator: Arc::new(LogAndRepeat { times }),
+            child: Box::new(child),
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<(NodePath, TreeEvent)>>>);
+
+    impl<A> crate::behavior_tree::observer::BehaviorObserver<A> for Recorder {
+        fn on_event(&mut self, path: &[usize], event: &TreeEvent) {
+            self.0.lock().unwrap().push((path.to_vec(), event.clone()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decorator_runs_child_twice() {
+        let bt = Sequence(vec![
+            Action(Increase),
+            log_and_repeat(2, Sequence(vec![Action(Increase), Action(Increase)])),
+        ]);
+        let recorder = Recorder::default();
+        let mut instance = TreeInstance::new(bt).with_observer(recorder.clone());
+        let mut count = 0;
+
+        instance.run(&(), &mut count).await.unwrap();
+        assert_eq!(count, 5);
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![(
+                vec![1],
+                TreeEvent::LogEmitted {
+                    level: LogLevel::Info,
+                    message: "running 2 times".to_string(),
+                }
+            )]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn test_saved_decorators_are_built_by_the_registry() {
+        use crate::behavior_tree::decorator::DecoratorRegistry;
+
+        let bt = Sequence(vec![log_and_repeat(3, Action(Increase))]);
+        let json = serde_json::to_string(&bt).unwrap();
+        assert_eq!(
+            json,
+            r#"{"Sequence":[{"Decorated":{"decorator":{"name":"log_and_repeat","params":{"times":3}},"child":{"Action":null}}}]}"#
+        );
+
+        let mut loaded: Behavior<Increase> = serde_json::from_str(&json).unwrap();
+        let mut count = 0;
+        let err = loaded.children()[0].run(&(), &mut count).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`log_and_repeat` has to be built by a registry before it can run"
+        );
+
+        let mut registry = DecoratorRegistry::new();
+        assert_eq!(
+            registry.resolve(&mut loaded).unwrap_err().to_string(),
+            "invalid node at [0]: unknown decorator `log_and_repeat`, registered: []"
+        );
+        registry.register("log_and_repeat", |params| {
+            let times = params["times"].as_u64().ok_or("`times` is missing")?;
+            Ok(Arc::new(LogAndRepeat {
+                times: times as u32,
+            }) as Arc<dyn Decorator<_>>)
+        });
+        registry.resolve(&mut loaded).unwrap();
+
+        loaded.run(&(), &mut count).await.unwrap();
+        assert_eq!(count, 3);
+    }
+}
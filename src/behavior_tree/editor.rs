@@ -0,0 +1,419 @@
+use crate::behavior_tree::{Behavior, NodePath};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::mem;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum EditError {
+    #[error("no node at {0:?}")]
+    NoNode(NodePath),
+    #[error("the node at {0:?} doesn't take a list of children")]
+    NoChildList(NodePath),
+    #[error("index {index} is out of range for the children of {parent:?}")]
+    IndexOutOfRange { parent: NodePath, index: usize },
+    #[error("the root can't be removed or moved")]
+    Root,
+}
+
+/// A change to a tree, by node path.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TreeEdit<A> {
+    Replace {
+        path: NodePath,
+        node: Behavior<A>,
+    },
+    // adds `node` as child `index` of the node at `parent`, which takes a list of children
+    Insert {
+        parent: NodePath,
+        index: usize,
+        node: Behavior<A>,
+    },
+    Remove {
+        path: NodePath,
+    },
+    // moves the subtree at `from` to child `index` of `parent`, a path in the tree without the
+    // subtree
+    Graft {
+        from: NodePath,
+        parent: NodePath,
+        index: usize,
+    },
+}
+
+impl<A> TreeEdit<A> {
+    /// Applies the edit to `tree`, returning the edit that reverts it. `tree` is left unchanged
+    /// if the edit doesn't apply.
+    pub fn apply(self, tree: &mut Behavior<A>) -> Result<TreeEdit<A>, EditError> {
+        match self {
+            TreeEdit::Replace { path, node } => {
+                let slot = tree
+                    .node_at_mut(&path)
+                    .ok_or_else(|| EditError::NoNode(path.clone()))?;
+                let node = mem::replace(slot, node);
+                Ok(TreeEdit::Replace { path, node })
+            }
+            TreeEdit::Insert {
+                parent,
+                index,
+                node,
+            } => {
+                insert(tree, &parent, index, node).map_err(|(err, _)| err)?;
+                let mut path = parent;
+                path.push(index);
+                Ok(TreeEdit::Remove { path })
+            }
+            TreeEdit::Remove { path } => {
+                let (index, parent) = path.split_last().ok_or(EditError::Root)?;
+                let children = child_list(tree, parent)?;
+                if *index >= children.len() {
+                    return Err(EditError::NoNode(path));
+                }
+                Ok(TreeEdit::Insert {
+                    node: children.remove(*index),
+                    parent: parent.to_vec(),
+                    index: *index,
+                })
+            }
+            TreeEdit::Graft {
+                from,
+                parent,
+                index,
+            } => {
+                let TreeEdit::Insert {
+                    parent: old_parent,
+                    index: old_index,
+                    node,
+                } = TreeEdit::Remove { path: from }.apply(tree)?
+                else {
+                    unreachable!("reverting a removal inserts")
+                };
+                if let Err((err, node)) = insert(tree, &parent, index, node) {
+                    let restored = insert(tree, &old_parent, old_index, node);
+                    assert!(restored.is_ok(), "the subtree was just removed from there");
+                    return Err(err);
+                }
+                let mut from = parent;
+                from.push(index);
+                Ok(TreeEdit::Graft {
+                    from,
+                    parent: old_parent,
+                    index: old_index,
+                })
+            }
+        }
+    }
+}
+
+// an edit that can be undone, and the states of the tree before and after it
+struct Step<A> {
+    // the edit going from `before` to `after` on the redo stack, back on the undo stack
+    edit: TreeEdit<A>,
+    before: u64,
+    after: u64,
+}
+
+/// A tree being edited, with undo and redo of the edits.
+///
+/// Every state of the tree gets a number, so the editor can tell whether the tree is the one
+/// saved last. Undo keeps the last `depth` edits. New edits after an undo drop the edits that
+/// could have been redone.
+pub struct TreeEditor<A> {
+    tree: Behavior<A>,
+    undo: VecDeque<Step<A>>,
+    redo: Vec<Step<A>>,
+    depth: usize,
+    state: u64,
+    saved: u64,
+    next_state: u64,
+    // the edits applied to the tree since the last `take_edits`, undos and redos included
+    edits: Vec<TreeEdit<A>>,
+}
+
+impl<A: Clone> TreeEditor<A> {
+    pub fn new(tree: Behavior<A>) -> Self {
+        Self {
+            tree,
+            undo: VecDeque::new(),
+            redo: vec![],
+            depth: 100,
+            state: 0,
+            saved: 0,
+            next_state: 1,
+            edits: vec![],
+        }
+    }
+
+    /// Keeps only the last `depth` edits for undo, 100 by default.
+    pub fn with_depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self.undo.drain(..self.undo.len().saturating_sub(depth));
+        self
+    }
+
+    pub fn tree(&self) -> &Behavior<A> {
+        &self.tree
+    }
+
+    pub fn into_tree(self) -> Behavior<A> {
+        self.tree
+    }
+
+    /// Applies `edit` and makes it the one `undo` reverts next.
+    pub fn apply(&mut self, edit: TreeEdit<A>) -> Result<(), EditError> {
+        let applied = edit.clone();
+        let inverse = edit.apply(&mut self.tree)?;
+        self.edits.push(applied);
+        self.redo.clear();
+        let after = self.next_state;
+        self.next_state += 1;
+        self.undo.push_back(Step {
+            edit: inverse,
+            before: self.state,
+            after,
+        });
+        if self.undo.len() > self.depth {
+            self.undo.pop_front();
+        }
+        self.state = after;
+        Ok(())
+    }
+
+    pub fn replace(&mut self, path: &[usize], node: Behavior<A>) -> Result<(), EditError> {
+        self.apply(TreeEdit::Replace {
+            path: path.to_vec(),
+            node,
+        })
+    }
+
+    pub fn insert(
+        &mut self,
+        parent: &[usize],
+        index: usize,
+        node: Behavior<A>,
+    ) -> Result<(), EditError> {
+        self.apply(TreeEdit::Insert {
+            parent: parent.to_vec(),
+            index,
+            node,
+        })
+    }
+
+    pub fn remove(&mut self, path: &[usize]) -> Result<(), EditError> {
+        self.apply(TreeEdit::Remove {
+            path: path.to_vec(),
+        })
+    }
+
+    pub fn graft(
+        &mut self,
+        from: &[usize],
+        parent: &[usize],
+        index: usize,
+    ) -> Result<(), EditError> {
+        self.apply(TreeEdit::Graft {
+            from: from.to_vec(),
+            parent: parent.to_vec(),
+            index,
+        })
+    }
+
+    /// Reverts the last edit not undone yet. Returns whether there was one.
+    pub fn undo(&mut self) -> bool {
+        let Some(step) = self.undo.pop_back() else {
+            return false;
+        };
+        let redo = self.revert(step.edit);
+        self.state = step.before;
+        self.redo.push(Step { edit: redo, ..step });
+        true
+    }
+
+    /// Applies the last undone edit again. Returns whether there was one.
+    pub fn redo(&mut self) -> bool {
+        let Some(step) = self.redo.pop() else {
+            return false;
+        };
+        let undo = self.revert(step.edit);
+        self.state = step.after;
+        self.undo.push_back(Step { edit: undo, ..step });
+        true
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Marks the tree as it is now as saved.
+    pub fn savepoint(&mut self) {
+        self.saved = self.state;
+    }
+
+    /// Whether the tree differs from the one at the last savepoint, or from the tree the editor
+    /// started with. Undoing back to the savepoint makes it clean again.
+    pub fn dirty(&self) -> bool {
+        self.state != self.saved
+    }
+
+    /// The edits applied since the last call, oldest first, for an edit log. Undos and redos
+    /// show up as the edits they applied.
+    pub fn take_edits(&mut self) -> Vec<TreeEdit<A>> {
+        mem::take(&mut self.edits)
+    }
+
+    // applies an edit from one of the stacks, which always applies as it reverts an edit made
+    // to the tree in the state it is in now
+    fn revert(&mut self, edit: TreeEdit<A>) -> TreeEdit<A> {
+        self.edits.push(edit.clone());
+        match edit.apply(&mut self.tree) {
+            Ok(inverse) => inverse,
+            Err(err) => unreachable!("reverting an edit failed: {}", err),
+        }
+    }
+}
+
+// hands `node` back if it can't be inserted
+fn insert<A>(
+    tree: &mut Behavior<A>,
+    parent: &[usize],
+    index: usize,
+    node: Behavior<A>,
+) -> Result<(), (EditError, Behavior<A>)> {
+    let children = match child_list(tree, parent) {
+        Ok(children) => children,
+        Err(err) => return Err((err, node)),
+    };
+    if index > children.len() {
+        let err = EditError::IndexOutOfRange {
+            parent: parent.to_vec(),
+            index,
+        };
+        return Err((err, node));
+    }
+    children.insert(index, node);
+    Ok(())
+}
+
+fn child_list<'a, A>(
+    tree: &'a mut Behavior<A>,
+    parent: &[usize],
+) -> Result<&'a mut Vec<Behavior<A>>, EditError> {
+    tree.node_at_mut(parent)
+        .ok_or_else(|| EditError::NoNode(parent.to_vec()))?
+        .child_list_mut()
+        .ok_or_else(|| EditError::NoChildList(parent.to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::editor::{EditError, TreeEdit, TreeEditor};
+    use crate::behavior_tree::Behavior;
+    use crate::behavior_tree::Behavior::*;
+
+    #[derive(Clone, Debug)]
+    enum Step {
+        Dock,
+        Refuel,
+        Sell,
+        Scan,
+    }
+
+    fn route() -> Behavior<Step> {
+        Sequence(vec![
+            Action(Step::Dock),
+            Select(vec![Action(Step::Refuel), AlwaysFail]),
+            Action(Step::Sell),
+        ])
+    }
+
+    fn shape(tree: &Behavior<Step>) -> String {
+        format!("{:?}", tree)
+    }
+
+    fn edit(editor: &mut TreeEditor<Step>) {
+        editor.replace(&[2], Action(Step::Scan)).unwrap();
+        editor.insert(&[1], 0, Action(Step::Sell)).unwrap();
+        editor.remove(&[1, 2]).unwrap();
+        // the refuel moves out of the select, to the end of the sequence
+        editor.graft(&[1, 1], &[], 3).unwrap();
+    }
+
+    #[test]
+    fn test_undo_and_redo_all_edits() {
+        let mut editor = TreeEditor::new(route());
+        edit(&mut editor);
+        let edited = Sequence(vec![
+            Action(Step::Dock),
+            Select(vec![Action(Step::Sell)]),
+            Action(Step::Scan),
+            Action(Step::Refuel),
+        ]);
+        assert_eq!(shape(editor.tree()), shape(&edited));
+        assert!(editor.dirty());
+
+        while editor.undo() {}
+        assert_eq!(shape(editor.tree()), shape(&route()));
+        assert!(!editor.dirty());
+        assert!(!editor.can_undo());
+
+        while editor.redo() {}
+        assert_eq!(shape(editor.tree()), shape(&edited));
+        // 4 edits, 4 undos and 4 redos
+        let edits = editor.take_edits();
+        assert_eq!(edits.len(), 12);
+        assert!(matches!(&edits[4], TreeEdit::Graft { from, .. } if *from == [3]));
+        assert!(editor.take_edits().is_empty());
+    }
+
+    #[test]
+    fn test_history_depth_and_redo_truncation() {
+        let mut editor = TreeEditor::new(route()).with_depth(3);
+        edit(&mut editor);
+        let mut undone = 0;
+        while editor.undo() {
+            undone += 1;
+        }
+        assert_eq!(undone, 3);
+        // the first edit is out of the history
+        assert_eq!(shape(editor.tree().node_at(&[2]).unwrap()), "Action(Scan)");
+
+        editor.redo();
+        editor.savepoint();
+        editor.remove(&[0]).unwrap();
+        assert!(!editor.can_redo());
+        assert!(editor.dirty());
+        editor.undo();
+        assert!(!editor.dirty());
+        assert!(editor.redo());
+        assert!(editor.dirty());
+    }
+
+    #[test]
+    fn test_invalid_edits_leave_the_tree_alone() {
+        let mut editor = TreeEditor::new(route());
+        assert_eq!(editor.remove(&[]), Err(EditError::Root));
+        assert_eq!(
+            editor.insert(&[0], 0, AlwaysFail),
+            Err(EditError::NoChildList(vec![0]))
+        );
+        assert_eq!(
+            editor.graft(&[1, 0], &[1], 5),
+            Err(EditError::IndexOutOfRange {
+                parent: vec![1],
+                index: 5
+            })
+        );
+        assert_eq!(
+            editor.replace(&[7], AlwaysFail),
+            Err(EditError::NoNode(vec![7]))
+        );
+        assert_eq!(shape(editor.tree()), shape(&route()));
+        assert!(!editor.can_undo());
+        assert!(!editor.dirty());
+    }
+}
@@ -0,0 +1,230 @@
+use crate::behavior_tree::blackboard::BlackboardValue;
+use crate::behavior_tree::Behavior;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A branch of an `Experiment` node. Its `weight` is its share of the units relative to the
+/// weights of the other variants.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Variant<A> {
+    pub name: String,
+    #[cfg_attr(feature = "serde", serde(default = "default_weight"))]
+    pub weight: u32,
+    pub branch: Behavior<A>,
+}
+
+#[cfg(feature = "serde")]
+fn default_weight() -> u32 {
+    1
+}
+
+impl<A> Variant<A> {
+    pub fn new(name: impl Into<String>, weight: u32, branch: Behavior<A>) -> Self {
+        Self {
+            name: name.into(),
+            weight,
+            branch,
+        }
+    }
+}
+
+/// Why variants can't make up an experiment.
+pub(crate) fn config_error<A>(variants: &[Variant<A>]) -> Option<String> {
+    if variants.is_empty() {
+        return Some("experiment has no variants".to_string());
+    }
+    if variants.iter().all(|variant| variant.weight == 0) {
+        return Some("experiment variants all have weight 0".to_string());
+    }
+    variants.iter().enumerate().find_map(|(i, variant)| {
+        variants[..i]
+            .iter()
+            .any(|earlier| earlier.name == variant.name)
+            .then(|| format!("experiment has two variants named `{}`", variant.name))
+    })
+}
+
+/// The unit as text, the way it is hashed and reported: strings without quotes.
+pub(crate) fn unit_name(unit: &BlackboardValue) -> String {
+    match unit {
+        BlackboardValue::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// The index of the variant `unit` is assigned to. The unit and salt are hashed to a point in
+/// `[0, 1)`, and the variants split that range by weight, in order. The hash doesn't depend on
+/// the process, so a unit keeps its variant across restarts as long as the salt and weights
+/// stay the same. Raising the weight of the last variant only moves units into it.
+///
+/// `variants` must pass `config_error`.
+pub(crate) fn assign<A>(variants: &[Variant<A>], salt: &str, unit: &BlackboardValue) -> usize {
+    let total: u64 = variants
+        .iter()
+        .map(|variant| u64::from(variant.weight))
+        .sum();
+    let hash = stable_hash(salt, &unit_name(unit));
+    // `hash * total / 2^64`, uniform in `[0, total)`
+    let bucket = ((u128::from(hash) * u128::from(total)) >> 64) as u64;
+    let mut end = 0;
+    for (i, variant) in variants.iter().enumerate() {
+        end += u64::from(variant.weight);
+        if bucket < end {
+            return i;
+        }
+    }
+    unreachable!("the buckets cover `[0, total)`")
+}
+
+// FNV-1a of `salt`, a separator and `unit`, finished with the SplitMix64 mixer so that similar
+// units land far apart
+fn stable_hash(salt: &str, unit: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let bytes = salt.bytes().chain([0xff]).chain(unit.bytes());
+    for byte in bytes {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^ (hash >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::compare::ValueRef;
+    use crate::behavior_tree::experiment::Variant;
+    use crate::behavior_tree::observer::{BehaviorObserver, TreeEvent};
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{Actionable, Behavior, NodeMemory, Response, TreeInstance};
+    use std::sync::{Arc, Mutex};
+
+    // records which strategy traded
+    #[derive(Clone, Debug)]
+    struct Trade(&'static str);
+
+    impl Actionable for Trade {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = Vec<&'static str>;
+
+        async fn run(&self, _: &(), trades: &mut Vec<&'static str>) -> Result<Response, String> {
+            trades.push(self.0);
+            Ok(Response::Success)
+        }
+    }
+
+    fn rollout(new_weight: u32) -> Behavior<Trade> {
+        Experiment {
+            key: ValueRef::Key("ship".to_string()),
+            variants: vec![
+                Variant::new("current", 100 - new_weight, Action(Trade("current"))),
+                Variant::new("arbitrage", new_weight, Action(Trade("arbitrage"))),
+            ],
+            salt: "trading-2024".to_string(),
+        }
+    }
+
+    async fn strategy_of(bt: &Behavior<Trade>, ship: &str) -> &'static str {
+        let mut instance = TreeInstance::new(bt.clone());
+        instance.blackboard_mut().set("ship", ship);
+        let mut trades = vec![];
+        instance.run(&(), &mut trades).await.unwrap();
+        trades[0]
+    }
+
+    fn ships() -> impl Iterator<Item = String> {
+        (0..1000).map(|i| format!("SHIP-{}", i))
+    }
+
+    #[tokio::test]
+    async fn test_units_keep_their_variant() {
+        let bt = rollout(50);
+        let mut assigned = vec![];
+        for ship in ["SHIP-1", "SHIP-2", "SHIP-3", "SHIP-4", "SHIP-5"] {
+            assigned.push(strategy_of(&bt, ship).await);
+            assert_eq!(strategy_of(&bt, ship).await, *assigned.last().unwrap());
+        }
+        assert_eq!(
+            assigned,
+            ["arbitrage", "current", "arbitrage", "current", "arbitrage"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_weights_split_the_units() {
+        let (ten_percent, half) = (rollout(10), rollout(50));
+        let mut counts = [0, 0];
+        for ship in ships() {
+            let before = strategy_of(&ten_percent, &ship).await;
+            let after = strategy_of(&half, &ship).await;
+            if before == "arbitrage" {
+                counts[0] += 1;
+                // raising its weight only adds ships to the last variant
+                assert_eq!(after, "arbitrage", "{}", ship);
+            }
+            if after == "arbitrage" {
+                counts[1] += 1;
+            }
+        }
+        assert!((70..130).contains(&counts[0]), "{:?}", counts);
+        assert!((450..550).contains(&counts[1]), "{:?}", counts);
+    }
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<TreeEvent>>>);
+
+    impl<A> BehaviorObserver<A> for Recorder {
+        fn on_event(&mut self, _: &[usize], event: &TreeEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_assignments_are_reported() {
+        let recorder = Recorder::default();
+        let mut instance = TreeInstance::new(rollout(50)).with_observer(recorder.clone());
+        let mut trades = vec![];
+        for ship in ["SHIP-1", "SHIP-2", "SHIP-1"] {
+            instance.blackboard_mut().set("ship", ship);
+            instance.run(&(), &mut trades).await.unwrap();
+        }
+
+        let assigned = |unit: &str, variant: &str| TreeEvent::VariantAssigned {
+            unit: unit.to_string(),
+            variant: variant.to_string(),
+        };
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![
+                assigned("SHIP-1", "arbitrage"),
+                assigned("SHIP-2", "current"),
+                assigned("SHIP-1", "arbitrage"),
+            ]
+        );
+        let Some(NodeMemory::Variants(stats)) = instance.memory(&[]) else {
+            panic!("no stats recorded");
+        };
+        let successes: Vec<_> = stats.iter().map(|s| s.successes).collect();
+        assert_eq!(successes, [1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_experiments_fail() {
+        let bt = Experiment {
+            key: ValueRef::Key("ship".to_string()),
+            variants: vec![Variant::new("current", 0, Action(Trade("current")))],
+            salt: String::new(),
+        };
+        assert_eq!(
+            bt.config_error().as_deref(),
+            Some("experiment variants all have weight 0")
+        );
+        let err = rollout(50).run(&(), &mut vec![]).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "experiment unit bb.ship: blackboard key `ship` is not set"
+        );
+    }
+}
@@ -0,0 +1,536 @@
+//! Drawing trees with Graphviz and Mermaid.
+//!
+//! Nodes are named after their path, `n` for the root and `n_0_2` for the third child of its
+//! first child, so the same tree always draws the same. Actions are labelled with their
+//! serialized form: `Dock` for a unit variant, `Refuel{station=X1-A1,units=5}` for a struct
+//! variant. Other nodes are labelled with their kind and what they are configured with, like the
+//! nodes of a [report](crate::behavior_tree::report).
+//!
+//! The shape of a node tells its kind apart: sequences are boxes, selects diamonds, loops
+//! hexagons, parallel nodes parallelograms, decorators notes and actions ellipses. The edges to
+//! the children of a node with several are labelled with their index, or with what they are for,
+//! like `condition` and `action` for a `While`.
+//!
+//! The `_annotated` versions color the nodes by how they last ran, as [`TreeStats`] collected
+//! it: green for success, yellow for running, red for failures and errors.
+
+use crate::behavior_tree::replay::Outcome;
+use crate::behavior_tree::report::{compact, label, outcome_name, TreeStats};
+use crate::behavior_tree::Behavior;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt::Write;
+
+impl<A: Serialize> Behavior<A> {
+    /// The tree as a Graphviz `digraph`, see [`to_dot`].
+    pub fn to_dot(&self) -> String {
+        to_dot(self)
+    }
+
+    /// The tree as a Mermaid flowchart, see [`to_mermaid`].
+    pub fn to_mermaid(&self) -> String {
+        to_mermaid(self)
+    }
+}
+
+/// The tree as a Graphviz `digraph`, for `dot -Tsvg`.
+pub fn to_dot<A: Serialize>(tree: &Behavior<A>) -> String {
+    dot(tree, None)
+}
+
+/// Like [`to_dot`], with the nodes colored by their last outcome in `stats`.
+pub fn to_dot_annotated<A: Serialize>(tree: &Behavior<A>, stats: &TreeStats) -> String {
+    dot(tree, Some(stats))
+}
+
+/// The tree as a Mermaid flowchart, top to bottom.
+pub fn to_mermaid<A: Serialize>(tree: &Behavior<A>) -> String {
+    mermaid(tree, None)
+}
+
+/// Like [`to_mermaid`], with the nodes colored by their last outcome in `stats`.
+pub fn to_mermaid_annotated<A: Serialize>(tree: &Behavior<A>, stats: &TreeStats) -> String {
+    mermaid(tree, Some(stats))
+}
+
+fn dot<A: Serialize>(tree: &Behavior<A>, stats: Option<&TreeStats>) -> String {
+    let mut dot = "digraph behavior_tree {\n".to_string();
+    walk(tree, &mut vec![], None, &mut |id, path, node, edge| {
+        let shape = shape(node);
+        let _ = write!(
+            dot,
+            "    {} [label=\"{}\", shape={}",
+            id,
+            dot_escape(&node_label(node)),
+            shape.dot()
+        );
+        let rounded = if shape == Shape::Rounded {
+            "rounded,"
+        } else {
+            ""
+        };
+        match last_outcome(stats, path) {
+            Some(outcome) => {
+                let _ = write!(
+                    dot,
+                    ", style=\"{}filled\", fillcolor=\"{}\"",
+                    rounded,
+                    color(outcome)
+                );
+            }
+            None if shape == Shape::Rounded => dot.push_str(", style=rounded"),
+            None => {}
+        }
+        dot.push_str("];\n");
+        if let Some((parent, edge_label)) = edge {
+            match edge_label {
+                Some(edge_label) => {
+                    let edge_label = dot_escape(&edge_label);
+                    let _ = writeln!(dot, "    {} -> {} [label=\"{}\"];", parent, id, edge_label);
+                }
+                None => {
+                    let _ = writeln!(dot, "    {} -> {};", parent, id);
+                }
+            }
+        }
+    });
+    dot.push_str("}\n");
+    dot
+}
+
+fn mermaid<A: Serialize>(tree: &Behavior<A>, stats: Option<&TreeStats>) -> String {
+    let mut mermaid = "flowchart TD\n".to_string();
+    let mut classes = vec![];
+    walk(tree, &mut vec![], None, &mut |id, path, node, edge| {
+        let (open, close) = shape(node).mermaid();
+        let node_label = mermaid_escape(&node_label(node));
+        let _ = writeln!(mermaid, "    {}{}\"{}\"{}", id, open, node_label, close);
+        if let Some(outcome) = last_outcome(stats, path) {
+            classes.push((id.to_string(), outcome));
+        }
+        if let Some((parent, edge_label)) = edge {
+            match edge_label {
+                Some(edge_label) => {
+                    let edge_label = mermaid_escape(&edge_label);
+                    let _ = writeln!(mermaid, "    {} -->|\"{}\"| {}", parent, edge_label, id);
+                }
+                None => {
+                    let _ = writeln!(mermaid, "    {} --> {}", parent, id);
+                }
+            }
+        }
+    });
+    if stats.is_some() {
+        for outcome in [
+            Outcome::Success,
+            Outcome::Running,
+            Outcome::Failure,
+            Outcome::Failed,
+        ] {
+            let name = outcome_name(outcome);
+            let _ = writeln!(mermaid, "    classDef {} fill:{}", name, color(outcome));
+        }
+    }
+    for (id, outcome) in classes {
+        let _ = writeln!(mermaid, "    class {} {}", id, outcome_name(outcome));
+    }
+    mermaid
+}
+
+fn last_outcome(stats: Option<&TreeStats>, path: &[usize]) -> Option<Outcome> {
+    stats?.nodes.get(path)?.last
+}
+
+fn color(outcome: Outcome) -> &'static str {
+    match outcome {
+        Outcome::Success => "#b7e4c7",
+        Outcome::Running => "#ffe08a",
+        Outcome::Failure => "#f4b6b6",
+        Outcome::Failed => "#e06666",
+    }
+}
+
+// calls `f` with the id and path of every node, parents before their children, and the id of
+// its parent with the label of the edge from there
+fn walk<A>(
+    node: &Behavior<A>,
+    path: &mut Vec<usize>,
+    edge: Option<(&str, Option<String>)>,
+    f: &mut impl FnMut(&str, &[usize], &Behavior<A>, Option<(&str, Option<String>)>),
+) {
+    let id = node_id(path);
+    f(&id, path, node, edge);
+    let children = node.children();
+    let mut edge_labels = edge_labels(node).into_iter();
+    for (i, child) in children.into_iter().enumerate() {
+        path.push(i);
+        let edge_label = edge_labels.next().flatten();
+        walk(child, path, Some((&id, edge_label)), f);
+        path.pop();
+    }
+}
+
+fn node_id(path: &[usize]) -> String {
+    let mut id = "n".to_string();
+    for i in path {
+        let _ = write!(id, "_{}", i);
+    }
+    id
+}
+
+fn node_label<A: Serialize>(node: &Behavior<A>) -> String {
+    match node {
+        Behavior::Action(action) => {
+            serde_json::to_value(action).map_or_else(|_| "?".to_string(), |v| action_label(&v))
+        }
+        node => match label(node) {
+            (kind, Some(detail)) => format!("{}: {}", kind, detail),
+            (kind, None) => kind.to_string(),
+        },
+    }
+}
+
+// `Variant` for unit variants, `Variant{field=value}` for struct variants and `Variant(value)`
+// for newtype and tuple variants
+fn action_label(action: &Value) -> String {
+    let Value::Object(variant) = action else {
+        return compact(action);
+    };
+    let mut fields = variant.iter();
+    let (Some((name, value)), None) = (fields.next(), fields.next()) else {
+        return compact(action);
+    };
+    match value {
+        Value::Object(fields) => {
+            let fields: Vec<_> = fields
+                .iter()
+                .map(|(field, value)| format!("{}={}", field, compact(value)))
+                .collect();
+            format!("{}{{{}}}", name, fields.join(","))
+        }
+        Value::Array(values) => {
+            let values: Vec<_> = values.iter().map(compact).collect();
+            format!("{}({})", name, values.join(","))
+        }
+        value => format!("{}({})", name, compact(value)),
+    }
+}
+
+// what the edges to the children of `node` are labelled with, by child
+fn edge_labels<A>(node: &Behavior<A>) -> Vec<Option<String>> {
+    let named = |names: &[&str]| names.iter().map(|name| Some(name.to_string())).collect();
+    match node {
+        Behavior::While { .. } => named(&["condition", "action"]),
+        Behavior::TryCatch { catch, .. } => std::iter::once(Some("body".to_string()))
+            .chain(catch.iter().map(|clause| match clause.codes.as_slice() {
+                [] => Some("catch".to_string()),
+                codes => Some(format!("catch {}", codes.join(", "))),
+            }))
+            .collect(),
+        Behavior::Experiment { variants, .. } => variants
+            .iter()
+            .map(|variant| Some(variant.name.clone()))
+            .collect(),
+        Behavior::Pipeline(stages) => stages
+            .iter()
+            .map(|stage| Some(stage.name.clone()))
+            .collect(),
+        node => {
+            let children = node.children().len();
+            if children > 1 {
+                (0..children).map(|i| Some(i.to_string())).collect()
+            } else {
+                vec![None; children]
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Shape {
+    Box,
+    Diamond,
+    Hexagon,
+    Parallelogram,
+    Note,
+    Ellipse,
+    // conditions and other leaves that aren't actions
+    Rounded,
+}
+
+impl Shape {
+    fn dot(self) -> &'static str {
+        match self {
+            Shape::Box => "box",
+            Shape::Diamond => "diamond",
+            Shape::Hexagon => "hexagon",
+            Shape::Parallelogram => "parallelogram",
+            Shape::Note => "note",
+            Shape::Ellipse => "ellipse",
+            Shape::Rounded => "box",
+        }
+    }
+
+    fn mermaid(self) -> (&'static str, &'static str) {
+        match self {
+            Shape::Box => ("[", "]"),
+            Shape::Diamond => ("{", "}"),
+            Shape::Hexagon => ("{{", "}}"),
+            Shape::Parallelogram => ("[/", "/]"),
+            Shape::Note => (">", "]"),
+            Shape::Ellipse => ("([", "])"),
+            Shape::Rounded => ("(", ")"),
+        }
+    }
+}
+
+fn shape<A>(node: &Behavior<A>) -> Shape {
+    match node {
+        Behavior::Action(_) => Shape::Ellipse,
+        Behavior::Sequence(_)
+        | Behavior::ReactiveSequence(_)
+        | Behavior::LazySequence(_)
+        | Behavior::Pipeline(_)
+        | Behavior::Composite { .. } => Shape::Box,
+        Behavior::Opaque { .. } | Behavior::Subtree { .. } => Shape::Box,
+        Behavior::Select(_)
+        | Behavior::ReactiveSelect(_)
+        | Behavior::LazySelect(_)
+        | Behavior::AdaptiveSelect { .. }
+        | Behavior::RandomSelect(_)
+        | Behavior::WeightedSelect(_)
+        | Behavior::Experiment { .. }
+        | Behavior::TryCatch { .. } => Shape::Diamond,
+        Behavior::While { .. } | Behavior::Repeat { .. } | Behavior::Retry { .. } => Shape::Hexagon,
+        Behavior::Parallel { .. } => Shape::Parallelogram,
+        Behavior::Invert(_)
+        | Behavior::Named { .. }
+        | Behavior::Namespaced { .. }
+        | Behavior::Decorated { .. }
+        | Behavior::Spawn { .. }
+        | Behavior::GracefulTimeout { .. }
+        | Behavior::StallGuard { .. }
+        | Behavior::Timeout { .. }
+        | Behavior::Cooldown { .. }
+        | Behavior::Assert { .. } => Shape::Note,
+        Behavior::CheckKey { .. }
+        | Behavior::SetKey { .. }
+        | Behavior::OnChanged { .. }
+        | Behavior::Expr { .. }
+        | Behavior::Compare { .. }
+        | Behavior::Log { .. }
+        | Behavior::Breakpoint { .. }
+        | Behavior::SleepUntil { .. }
+        | Behavior::Schedule { .. }
+        | Behavior::Jitter { .. }
+        | Behavior::AlwaysFail
+        | Behavior::Join { .. }
+        | Behavior::Throw { .. } => Shape::Rounded,
+    }
+}
+
+// for a quoted DOT string; braces only mean something in record shapes, which aren't used
+fn dot_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// for a quoted Mermaid label, with the characters that could end it or be read as markup
+// written as entity codes
+fn mermaid_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("#quot;"),
+            '#' => escaped.push_str("#35;"),
+            '<' => escaped.push_str("#lt;"),
+            '>' => escaped.push_str("#gt;"),
+            '\n' => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::export::{
+        to_dot, to_dot_annotated, to_mermaid, to_mermaid_annotated,
+    };
+    use crate::behavior_tree::io::from_json_str;
+    use crate::behavior_tree::observer::RecordingObserver;
+    use crate::behavior_tree::report::TreeStats;
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{Actionable, Behavior, Response};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    enum Ship {
+        Dock,
+        Navigate(String),
+        Refuel { station: String, units: u32 },
+    }
+
+    fn patrol() -> Behavior<Ship> {
+        Select(vec![
+            Sequence(vec![
+                Invert(Box::new(Action(Ship::Dock))),
+                Action(Ship::Refuel {
+                    station: "X1-\"A1\" {east}".to_string(),
+                    units: 5,
+                }),
+            ]),
+            While {
+                condition: Box::new(Action(Ship::Navigate("X1-B2".to_string()))),
+                action: Box::new(Action(Ship::Dock)),
+            },
+        ])
+    }
+
+    #[test]
+    fn test_trees_draw_as_dot() {
+        assert_eq!(
+            to_dot(&patrol()),
+            r#"digraph behavior_tree {
+    n [label="Select", shape=diamond];
+    n_0 [label="Sequence", shape=box];
+    n -> n_0 [label="0"];
+    n_0_0 [label="Invert", shape=note];
+    n_0 -> n_0_0 [label="0"];
+    n_0_0_0 [label="Dock", shape=ellipse];
+    n_0_0 -> n_0_0_0;
+    n_0_1 [label="Refuel{station=X1-\"A1\" {east},units=5}", shape=ellipse];
+    n_0 -> n_0_1 [label="1"];
+    n_1 [label="While", shape=hexagon];
+    n -> n_1 [label="1"];
+    n_1_0 [label="Navigate(X1-B2)", shape=ellipse];
+    n_1 -> n_1_0 [label="condition"];
+    n_1_1 [label="Dock", shape=ellipse];
+    n_1 -> n_1_1 [label="action"];
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_trees_draw_as_mermaid() {
+        assert_eq!(
+            to_mermaid(&patrol()),
+            r#"flowchart TD
+    n{"Select"}
+    n_0["Sequence"]
+    n -->|"0"| n_0
+    n_0_0>"Invert"]
+    n_0 -->|"0"| n_0_0
+    n_0_0_0(["Dock"])
+    n_0_0 --> n_0_0_0
+    n_0_1(["Refuel{station=X1-#quot;A1#quot; {east},units=5}"])
+    n_0 -->|"1"| n_0_1
+    n_1{{"While"}}
+    n -->|"1"| n_1
+    n_1_0(["Navigate(X1-B2)"])
+    n_1 -->|"condition"| n_1_0
+    n_1_1(["Dock"])
+    n_1 -->|"action"| n_1_1
+"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_annotated_trees_are_colored_by_the_last_outcome() {
+        #[derive(Clone, Debug, Serialize)]
+        struct Check(bool);
+
+        impl Actionable for Check {
+            type ActionError = String;
+            type ActionArgs = ();
+            type ActionState = ();
+
+            async fn run(&self, _: &(), _: &mut ()) -> Result<Response, String> {
+                Ok(if self.0 {
+                    Response::Success
+                } else {
+                    Response::Failure
+                })
+            }
+        }
+
+        // the second check never runs and stays uncolored, the unresolved subtree fails the
+        // select with an error before the last check runs
+        let bt = Select(vec![
+            Sequence(vec![Action(Check(false)), Action(Check(true))]),
+            Subtree {
+                name: "dock".to_string(),
+                remapping: Default::default(),
+            },
+            Action(Check(true)),
+        ]);
+        let recorder = RecordingObserver::default();
+        bt.run_with_observer(&(), &mut (), recorder.clone())
+            .await
+            .unwrap_err();
+        let mut stats = TreeStats::default();
+        stats.record_exits(&recorder.events());
+
+        assert_eq!(bt.to_dot(), to_dot(&bt),);
+        assert_eq!(
+            to_dot_annotated(&bt, &stats),
+            r##"digraph behavior_tree {
+    n [label="Select", shape=diamond, style="filled", fillcolor="#e06666"];
+    n_0 [label="Sequence", shape=box, style="filled", fillcolor="#f4b6b6"];
+    n -> n_0 [label="0"];
+    n_0_0 [label="false", shape=ellipse, style="filled", fillcolor="#f4b6b6"];
+    n_0 -> n_0_0 [label="0"];
+    n_0_1 [label="true", shape=ellipse];
+    n_0 -> n_0_1 [label="1"];
+    n_1 [label="Subtree: dock", shape=box, style="filled", fillcolor="#e06666"];
+    n -> n_1 [label="1"];
+    n_2 [label="true", shape=ellipse];
+    n -> n_2 [label="2"];
+}
+"##
+        );
+        let mermaid = to_mermaid_annotated(&bt, &stats);
+        assert!(mermaid.starts_with(&bt.to_mermaid()), "{}", mermaid);
+        assert!(mermaid.ends_with(
+            "    classDef success fill:#b7e4c7
+    classDef running fill:#ffe08a
+    classDef failure fill:#f4b6b6
+    classDef failed fill:#e06666
+    class n failed
+    class n_0 failure
+    class n_0_0 failure
+    class n_1 failed
+"
+        ));
+    }
+
+    #[test]
+    fn test_every_node_kind_is_drawn() {
+        let tree: Behavior<Ship> =
+            from_json_str(include_str!("../../fixtures/every_node_kind.json")).unwrap();
+        let mut paths = vec![];
+        tree.walk(&mut |path, _| paths.push(path.to_vec()));
+        let dot = to_dot(&tree);
+        let mermaid = to_mermaid(&tree);
+        for path in paths {
+            let id: String = std::iter::once("n".to_string())
+                .chain(path.iter().map(|i| i.to_string()))
+                .collect::<Vec<_>>()
+                .join("_");
+            assert!(dot.contains(&format!("    {} [label=", id)), "{}", id);
+            assert!(mermaid.lines().any(|line| line
+                .strip_prefix(&format!("    {}", id))
+                .is_some_and(|rest| rest.starts_with(['[', '{', '(', '>']))));
+        }
+        assert_eq!(to_dot(&tree), dot);
+    }
+}
@@ -0,0 +1,737 @@
+use crate::behavior_tree::blackboard::{Blackboard, BlackboardValue};
+use crate::behavior_tree::compare::CompareOp;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Exposes typed fields of an `ActionState` to expressions as `state.<name>`.
+pub trait FieldAccess {
+    fn field(&self, name: &str) -> Option<BlackboardValue>;
+}
+
+pub type FieldAccessFn<S> = fn(&S, &str) -> Option<BlackboardValue>;
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum ExprError {
+    #[error("at column {position}: {message}")]
+    Parse { position: usize, message: String },
+    #[error("expression `{expression}`: {message}")]
+    Eval { expression: String, message: String },
+}
+
+/// A condition written as text, e.g. `bb.fuel > 25 && !state.docked`, parsed when the tree is
+/// built or loaded.
+///
+/// Supported are `bb.<key>` for blackboard values, `state.<field>` for fields exposed through
+/// [`FieldAccess`], number/string/bool literals, `+ - * / %`, comparisons, `&&`, `||` and `!`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "String", into = "String"))]
+pub struct Expression {
+    source: String,
+    ast: Node,
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, ExprError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            pos: 0,
+            end: source.chars().count() + 1,
+            depth: 0,
+        };
+        let ast = parser.or()?;
+        if let Some((position, token)) = parser.tokens.get(parser.pos) {
+            return Err(ExprError::Parse {
+                position: *position,
+                message: format!("unexpected {}", token),
+            });
+        }
+        Ok(Self {
+            source: source.to_string(),
+            ast,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The expression with the blackboard keys `rename` gives a new name for renamed, and the
+    /// rest of the source kept as it is. Fails if a new name isn't a valid key.
+    pub fn rename_keys(&self, rename: &dyn Fn(&str) -> Option<String>) -> Result<Self, ExprError> {
+        let tokens = tokenize(&self.source)?;
+        let chars: Vec<char> = self.source.chars().collect();
+        let mut source = String::with_capacity(self.source.len());
+        // the chars of the old source copied to the new one so far
+        let mut copied = 0;
+        let mut i = 0;
+        while i < tokens.len() {
+            let after_dot = i > 0 && tokens[i - 1].1 == Token::Op(".");
+            if after_dot || tokens[i].1 != Token::Ident("bb".to_string()) {
+                i += 1;
+                continue;
+            }
+            // `bb` followed by `.segment`s
+            let mut segments = vec![];
+            i += 1;
+            while let (Some((_, Token::Op("."))), Some((position, Token::Ident(segment)))) =
+                (tokens.get(i), tokens.get(i + 1))
+            {
+                segments.push((*position, segment.as_str()));
+                i += 2;
+            }
+            let (Some(&(first, _)), Some(&(last, last_segment))) =
+                (segments.first(), segments.last())
+            else {
+                continue;
+            };
+            let key: Vec<_> = segments.iter().map(|(_, segment)| *segment).collect();
+            if let Some(renamed) = rename(&key.join(".")) {
+                source.extend(&chars[copied..first - 1]);
+                source.push_str(&renamed);
+                copied = last - 1 + last_segment.chars().count();
+            }
+        }
+        source.extend(&chars[copied..]);
+        Expression::parse(&source)
+    }
+
+    /// The blackboard keys the expression reads.
+    pub fn blackboard_keys(&self) -> Vec<&str> {
+        fn go<'a>(node: &'a Node, keys: &mut Vec<&'a str>) {
+            match node {
+                Node::Blackboard(key) => keys.push(key),
+                Node::Not(inner) | Node::Neg(inner) => go(inner, keys),
+                Node::Binary(left, _, right) => {
+                    go(left, keys);
+                    go(right, keys);
+                }
+                Node::Literal(_) | Node::State(_) => {}
+            }
+        }
+        let mut keys = vec![];
+        go(&self.ast, &mut keys);
+        keys
+    }
+
+    /// Evaluates the expression; `fields` resolves `state.<field>` accesses.
+    pub fn eval<S>(
+        &self,
+        blackboard: &Blackboard,
+        state: &S,
+        fields: Option<FieldAccessFn<S>>,
+    ) -> Result<BlackboardValue, ExprError> {
+        let env = Env {
+            blackboard,
+            state,
+            fields,
+        };
+        env.eval(&self.ast).map_err(|message| ExprError::Eval {
+            expression: self.source.clone(),
+            message,
+        })
+    }
+}
+
+impl PartialEq for Expression {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl TryFrom<String> for Expression {
+    type Error = ExprError;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        Expression::parse(&source)
+    }
+}
+
+impl From<Expression> for String {
+    fn from(expression: Expression) -> Self {
+        expression.source
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Literal(BlackboardValue),
+    Blackboard(String),
+    State(String),
+    Not(Box<Node>),
+    Neg(Box<Node>),
+    Binary(Box<Node>, BinOp, Box<Node>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Ident(String),
+    Op(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Int(i) => write!(f, "number `{}`", i),
+            Token::Float(x) => write!(f, "number `{}`", x),
+            Token::Str(s) => write!(f, "string {:?}", s),
+            Token::Ident(s) => write!(f, "`{}`", s),
+            Token::Op(op) => write!(f, "`{}`", op),
+        }
+    }
+}
+
+const OPERATORS: [&str; 18] = [
+    "&&", "||", "==", "!=", "<=", ">=", "<", ">", "!", "+", "-", "*", "/", "%", "(", ")", ".", "=",
+];
+
+// positions are 1-based columns
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ExprError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let position = i + 1;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let token = if text.contains('.') {
+                text.parse().map(Token::Float).ok()
+            } else {
+                text.parse().map(Token::Int).ok()
+            };
+            match token {
+                Some(token) => tokens.push((position, token)),
+                None => {
+                    return Err(ExprError::Parse {
+                        position,
+                        message: format!("invalid number `{}`", text),
+                    })
+                }
+            }
+        } else if c == '"' || c == '\'' {
+            i += 1;
+            let start = i;
+            while i < chars.len() && chars[i] != c {
+                i += 1;
+            }
+            if i == chars.len() {
+                return Err(ExprError::Parse {
+                    position,
+                    message: "unterminated string".to_string(),
+                });
+            }
+            tokens.push((position, Token::Str(chars[start..i].iter().collect())));
+            i += 1;
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push((position, Token::Ident(chars[start..i].iter().collect())));
+        } else {
+            let rest: String = chars[i..].iter().take(2).collect();
+            match OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+                Some(&"=") => {
+                    return Err(ExprError::Parse {
+                        position,
+                        message: "unexpected `=`, use `==` to compare".to_string(),
+                    })
+                }
+                Some(op) => {
+                    tokens.push((position, Token::Op(op)));
+                    i += op.len();
+                }
+                None => {
+                    return Err(ExprError::Parse {
+                        position,
+                        message: format!("unexpected character `{}`", c),
+                    })
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// how deep expressions may nest, counting parentheses, prefix operators and the operators of a
+// chain like `a + b + c`; parsing, evaluating and dropping expressions recurse that deep
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'t> {
+    tokens: &'t [(usize, Token)],
+    pos: usize,
+    // column reported for errors at the end of the input
+    end: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some((_, Token::Op(op))) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> ExprError {
+        let position = self
+            .tokens
+            .get(self.pos)
+            .map(|(position, _)| *position)
+            .unwrap_or(self.end);
+        ExprError::Parse {
+            position,
+            message: message.into(),
+        }
+    }
+
+    fn nest(&mut self) -> Result<(), ExprError> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error(format!("expression nests deeper than {} levels", MAX_DEPTH)));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn binary(
+        &mut self,
+        ops: &[(&str, BinOp)],
+        next: fn(&mut Self) -> Result<Node, ExprError>,
+    ) -> Result<Node, ExprError> {
+        let depth = self.depth;
+        let mut left = next(self)?;
+        while let Some(op) = self.peek_op() {
+            let Some((_, bin_op)) = ops.iter().find(|(o, _)| *o == op) else {
+                break;
+            };
+            self.nest()?;
+            self.pos += 1;
+            let right = next(self)?;
+            left = Node::Binary(Box::new(left), *bin_op, Box::new(right));
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn or(&mut self) -> Result<Node, ExprError> {
+        self.binary(&[("||", BinOp::Or)], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Node, ExprError> {
+        self.binary(&[("&&", BinOp::And)], Self::equality)
+    }
+
+    fn equality(&mut self) -> Result<Node, ExprError> {
+        self.binary(&[("==", BinOp::Eq), ("!=", BinOp::Ne)], Self::comparison)
+    }
+
+    fn comparison(&mut self) -> Result<Node, ExprError> {
+        self.binary(
+            &[
+                ("<", BinOp::Lt),
+                ("<=", BinOp::Le),
+                (">", BinOp::Gt),
+                (">=", BinOp::Ge),
+            ],
+            Self::additive,
+        )
+    }
+
+    fn additive(&mut self) -> Result<Node, ExprError> {
+        self.binary(
+            &[("+", BinOp::Add), ("-", BinOp::Sub)],
+            Self::multiplicative,
+        )
+    }
+
+    fn multiplicative(&mut self) -> Result<Node, ExprError> {
+        self.binary(
+            &[("*", BinOp::Mul), ("/", BinOp::Div), ("%", BinOp::Rem)],
+            Self::unary,
+        )
+    }
+
+    fn unary(&mut self) -> Result<Node, ExprError> {
+        let op = self.peek_op();
+        if !matches!(op, Some("!" | "-")) {
+            return self.primary();
+        }
+        self.nest()?;
+        self.pos += 1;
+        let inner = Box::new(self.unary()?);
+        self.depth -= 1;
+        Ok(match op {
+            Some("!") => Node::Not(inner),
+            _ => Node::Neg(inner),
+        })
+    }
+
+    fn primary(&mut self) -> Result<Node, ExprError> {
+        let Some((_, token)) = self.tokens.get(self.pos) else {
+            return Err(self.error("unexpected end of expression"));
+        };
+        let node = match token {
+            Token::Int(i) => Node::Literal(BlackboardValue::Int(*i)),
+            Token::Float(x) => Node::Literal(BlackboardValue::Float(*x)),
+            Token::Str(s) => Node::Literal(BlackboardValue::String(s.clone())),
+            Token::Ident(ident) if ident == "true" => Node::Literal(BlackboardValue::Bool(true)),
+            Token::Ident(ident) if ident == "false" => Node::Literal(BlackboardValue::Bool(false)),
+            Token::Ident(ident) if ident == "bb" || ident == "state" => {
+                self.pos += 1;
+                let field = self.field_path()?;
+                return Ok(if ident == "bb" {
+                    Node::Blackboard(field)
+                } else {
+                    Node::State(field)
+                });
+            }
+            Token::Op("(") => {
+                self.nest()?;
+                self.pos += 1;
+                let inner = self.or()?;
+                if self.peek_op() != Some(")") {
+                    return Err(self.error("expected `)`"));
+                }
+                self.depth -= 1;
+                inner
+            }
+            Token::Ident(ident) => {
+                return Err(self.error(format!(
+                    "unknown name `{}`, expected `bb.<key>` or `state.<field>`",
+                    ident
+                )))
+            }
+            token => return Err(self.error(format!("unexpected {}", token))),
+        };
+        self.pos += 1;
+        Ok(node)
+    }
+
+    // `.a.b.c` after `bb`/`state`, joined as "a.b.c"
+    fn field_path(&mut self) -> Result<String, ExprError> {
+        let mut segments: Vec<&str> = vec![];
+        while self.peek_op() == Some(".") {
+            self.pos += 1;
+            match self.tokens.get(self.pos) {
+                Some((_, Token::Ident(segment))) => segments.push(segment),
+                _ => return Err(self.error("expected a field name after `.`")),
+            }
+            self.pos += 1;
+        }
+        if segments.is_empty() {
+            return Err(self.error("expected `.` and a field name"));
+        }
+        Ok(segments.join("."))
+    }
+}
+
+struct Env<'a, S> {
+    blackboard: &'a Blackboard,
+    state: &'a S,
+    fields: Option<FieldAccessFn<S>>,
+}
+
+impl<S> Env<'_, S> {
+    fn eval(&self, node: &Node) -> Result<BlackboardValue, String> {
+        match node {
+            Node::Literal(value) => Ok(value.clone()),
+            Node::Blackboard(key) => self
+                .blackboard
+                .get(key)
+                .cloned()
+                .ok_or_else(|| format!("blackboard key `{}` is not set", key)),
+            Node::State(field) => {
+                let fields = self.fields.ok_or_else(|| {
+                    format!(
+                        "cannot read `state.{}`: the state does not provide FieldAccess",
+                        field
+                    )
+                })?;
+                fields(self.state, field).ok_or_else(|| format!("unknown state field `{}`", field))
+            }
+            Node::Not(inner) => match self.eval(inner)? {
+                Bool(b) => Ok(Bool(!b)),
+                other => Err(format!("`!` expects a bool, got {}", other)),
+            },
+            Node::Neg(inner) => match self.eval(inner)? {
+                Int(i) => Ok(Int(i.wrapping_neg())),
+                Float(x) => Ok(Float(-x)),
+                other => Err(format!("`-` expects a number, got {}", other)),
+            },
+            Node::Binary(left, BinOp::And, right) => match self.eval(left)? {
+                Bool(false) => Ok(Bool(false)),
+                Bool(true) => self.expect_bool("&&", self.eval(right)?),
+                other => Err(format!("`&&` expects bools, got {}", other)),
+            },
+            Node::Binary(left, BinOp::Or, right) => match self.eval(left)? {
+                Bool(true) => Ok(Bool(true)),
+                Bool(false) => self.expect_bool("||", self.eval(right)?),
+                other => Err(format!("`||` expects bools, got {}", other)),
+            },
+            Node::Binary(left, op, right) => binary(*op, self.eval(left)?, self.eval(right)?),
+        }
+    }
+
+    fn expect_bool(&self, op: &str, value: BlackboardValue) -> Result<BlackboardValue, String> {
+        match value {
+            Bool(b) => Ok(Bool(b)),
+            other => Err(format!("`{}` expects bools, got {}", op, other)),
+        }
+    }
+}
+
+use BlackboardValue::{Bool, Float, Int};
+
+fn binary(
+    op: BinOp,
+    left: BlackboardValue,
+    right: BlackboardValue,
+) -> Result<BlackboardValue, String> {
+    let mismatch = |left: &BlackboardValue, right: &BlackboardValue| {
+        format!("cannot apply `{}` to {} and {}", op.symbol(), left, right)
+    };
+    if let Some(compare) = op.compare_op() {
+        return compare
+            .apply(&left, &right)
+            .map(Bool)
+            .ok_or_else(|| mismatch(&left, &right));
+    }
+    match (op, &left, &right) {
+        (BinOp::Add, BlackboardValue::String(a), BlackboardValue::String(b)) => {
+            Ok(BlackboardValue::String(format!("{}{}", a, b)))
+        }
+        (_, Int(a), Int(b)) => match op {
+            BinOp::Add => Ok(Int(a.wrapping_add(*b))),
+            BinOp::Sub => Ok(Int(a.wrapping_sub(*b))),
+            BinOp::Mul => Ok(Int(a.wrapping_mul(*b))),
+            BinOp::Div | BinOp::Rem if *b == 0 => {
+                Err(format!("division by zero in {} {} 0", a, op.symbol()))
+            }
+            BinOp::Div => Ok(Int(a.wrapping_div(*b))),
+            _ => Ok(Int(a.wrapping_rem(*b))),
+        },
+        _ => match (left.as_f64(), right.as_f64()) {
+            (Some(a), Some(b)) => Ok(Float(match op {
+                BinOp::Add => a + b,
+                BinOp::Sub => a - b,
+                BinOp::Mul => a * b,
+                BinOp::Div => a / b,
+                _ => a % b,
+            })),
+            _ => Err(mismatch(&left, &right)),
+        },
+    }
+}
+
+impl BinOp {
+    fn compare_op(&self) -> Option<CompareOp> {
+        match self {
+            BinOp::Eq => Some(CompareOp::Eq),
+            BinOp::Ne => Some(CompareOp::Ne),
+            BinOp::Lt => Some(CompareOp::Lt),
+            BinOp::Le => Some(CompareOp::Le),
+            BinOp::Gt => Some(CompareOp::Gt),
+            BinOp::Ge => Some(CompareOp::Ge),
+            _ => None,
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            BinOp::Or => "||",
+            BinOp::And => "&&",
+            BinOp::Eq => "==",
+            BinOp::Ne => "!=",
+            BinOp::Lt => "<",
+            BinOp::Le => "<=",
+            BinOp::Gt => ">",
+            BinOp::Ge => ">=",
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Rem => "%",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::blackboard::{Blackboard, BlackboardValue};
+    use crate::behavior_tree::expr::{ExprError, Expression, FieldAccess};
+
+    struct Ship {
+        fuel: i64,
+        docked: bool,
+    }
+
+    impl FieldAccess for Ship {
+        fn field(&self, name: &str) -> Option<BlackboardValue> {
+            match name {
+                "fuel" => Some(self.fuel.into()),
+                "docked" => Some(self.docked.into()),
+                _ => None,
+            }
+        }
+    }
+
+    fn eval(source: &str, ship: &Ship) -> Result<BlackboardValue, ExprError> {
+        let blackboard = Blackboard::from_iter([
+            ("max_price", BlackboardValue::Int(120)),
+            ("ratio", BlackboardValue::Float(0.5)),
+            ("home", BlackboardValue::from("X1-ABC")),
+        ]);
+        Expression::parse(source)?.eval(&blackboard, ship, Some(<Ship as FieldAccess>::field))
+    }
+
+    #[test]
+    fn test_eval_table() {
+        let ship = Ship {
+            fuel: 30,
+            docked: false,
+        };
+        let cases = [
+            ("state.fuel > 25", true),
+            ("state.fuel > 25 && state.docked", false),
+            ("!state.docked || state.fuel < 0", true),
+            ("bb.max_price * bb.ratio == 60", true),
+            ("bb.max_price - state.fuel * 2 >= 60", true),
+            ("(bb.max_price - state.fuel) * 2 >= 190", false),
+            ("bb.home == 'X1-ABC'", true),
+            ("bb.home + \"-1\" != \"X1-ABC-1\"", false),
+            ("-state.fuel < -29.5", true),
+            ("7 % 4 == 3 && 7 / 2 == 3", true),
+            ("\"abc\" < \"abd\"", true),
+        ];
+
+        for (source, expected) in cases {
+            assert_eq!(
+                eval(source, &ship),
+                Ok(BlackboardValue::Bool(expected)),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_errors_report_position() {
+        let cases = [
+            ("bb.fuel >", "at column 10: unexpected end of expression"),
+            (
+                "bb.fuel = 3",
+                "at column 9: unexpected `=`, use `==` to compare",
+            ),
+            ("(1 + 2", "at column 7: expected `)`"),
+            (
+                "fuel > 3",
+                "at column 1: unknown name `fuel`, expected `bb.<key>` or `state.<field>`",
+            ),
+            ("1 2", "at column 3: unexpected number `2`"),
+            ("'open", "at column 1: unterminated string"),
+        ];
+
+        for (source, expected) in cases {
+            let err = Expression::parse(source).unwrap_err();
+            assert_eq!(err.to_string(), expected, "{}", source);
+        }
+    }
+
+    // inputs that overflowed the stack or panicked before expressions had a depth limit
+    #[test]
+    fn test_deep_expressions_are_rejected() {
+        let ship = Ship {
+            fuel: 30,
+            docked: true,
+        };
+        let deep = [
+            format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000)),
+            format!("{}true", "!".repeat(100_000)),
+            format!("1{}", " + 1".repeat(100_000)),
+        ];
+        for source in &deep {
+            let err = Expression::parse(source).unwrap_err();
+            assert!(
+                err.to_string()
+                    .ends_with("expression nests deeper than 128 levels"),
+                "{}",
+                err
+            );
+        }
+
+        let nested = format!("{}1{}", "(".repeat(128), ")".repeat(128));
+        assert_eq!(eval(&nested, &ship), Ok(BlackboardValue::Int(1)));
+        assert_eq!(
+            eval("-(0 - 9223372036854775807 - 1)", &ship),
+            Ok(BlackboardValue::Int(i64::MIN))
+        );
+    }
+
+    #[test]
+    fn test_type_errors_name_expression_and_value() {
+        let ship = Ship {
+            fuel: 30,
+            docked: true,
+        };
+
+        let err = eval("bb.home > 3", &ship).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expression `bb.home > 3`: cannot apply `>` to \"X1-ABC\" and 3"
+        );
+
+        let err = eval("state.fuel && true", &ship).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expression `state.fuel && true`: `&&` expects bools, got 30"
+        );
+
+        let err = eval("bb.missing == 1", &ship).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expression `bb.missing == 1`: blackboard key `missing` is not set"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_parse_error_when_loading() {
+        let err = serde_json::from_str::<Expression>("\"bb.fuel >\"").unwrap_err();
+        assert!(err.to_string().contains("at column 10"), "{}", err);
+    }
+}
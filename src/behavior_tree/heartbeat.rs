@@ -0,0 +1,275 @@
+use crate::behavior_tree::clock::{default_sleeper, Sleeper};
+use crate::behavior_tree::NodePath;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+struct LastBeat {
+    at: Instant,
+    note: Option<String>,
+    // reported stalled by a `StallGuard`, until the next beat
+    stalled: bool,
+}
+
+/// What the instance saw last of a running action.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeartbeatStatus {
+    pub path: NodePath,
+    // since the last heartbeat, or since the action started if it didn't send one yet
+    pub age: Duration,
+    // the last note given with a heartbeat
+    pub note: Option<String>,
+    pub stalled: bool,
+}
+
+/// The last heartbeats of the actions an instance is running, one per action. Clones share the
+/// same heartbeats, so a dashboard can hold one while the instance runs.
+#[derive(Debug, Clone)]
+pub struct Heartbeats {
+    inner: Arc<Mutex<BTreeMap<NodePath, LastBeat>>>,
+    // the timer of the instance, which heartbeats are timed with
+    sleeper: Arc<dyn Sleeper>,
+}
+
+impl Default for Heartbeats {
+    fn default() -> Self {
+        Self::timed_by(default_sleeper())
+    }
+}
+
+impl Heartbeats {
+    pub(crate) fn timed_by(sleeper: Arc<dyn Sleeper>) -> Self {
+        Self {
+            inner: Arc::default(),
+            sleeper,
+        }
+    }
+
+    /// The actions running right now, in path order.
+    pub fn status(&self) -> Vec<HeartbeatStatus> {
+        let now = self.sleeper.now();
+        self.inner
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(path, last)| HeartbeatStatus {
+                path: path.clone(),
+                age: now - last.at,
+                note: last.note.clone(),
+                stalled: last.stalled,
+            })
+            .collect()
+    }
+
+    /// Runs the action at `path`, letting it beat until it stops. Starting counts as the first
+    /// heartbeat.
+    pub(crate) async fn scope<F: Future>(&self, path: &[usize], future: F) -> F::Output {
+        self.inner.lock().unwrap().insert(
+            path.to_vec(),
+            LastBeat {
+                at: self.sleeper.now(),
+                note: None,
+                stalled: false,
+            },
+        );
+        // the action may be dropped midway, by a timeout or a stall
+        let _running = Running(self, path);
+        future.await
+    }
+
+    /// A heartbeat of the action at `path`, with what it is at if `note` is set. Does nothing
+    /// if that action isn't running.
+    pub(crate) fn beat(&self, path: &[usize], note: Option<String>) {
+        if let Some(last) = self.inner.lock().unwrap().get_mut(path) {
+            last.at = self.sleeper.now();
+            last.stalled = false;
+            if note.is_some() {
+                last.note = note;
+            }
+        }
+    }
+
+    /// The action under `prefix` whose last heartbeat is oldest, with that heartbeat. Actions
+    /// already reported stalled don't count until they beat again.
+    pub(crate) fn oldest_under(&self, prefix: &[usize]) -> Option<(NodePath, Instant)> {
+        self.inner
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(path, last)| path.starts_with(prefix) && !last.stalled)
+            .min_by_key(|(_, last)| last.at)
+            .map(|(path, last)| (path.clone(), last.at))
+    }
+
+    fn mark_stalled(&self, path: &[usize]) {
+        if let Some(last) = self.inner.lock().unwrap().get_mut(path) {
+            last.stalled = true;
+        }
+    }
+}
+
+struct Running<'a>(&'a Heartbeats, &'a [usize]);
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        self.0.inner.lock().unwrap().remove(self.1);
+    }
+}
+
+/// Completes once the oldest heartbeat of an action under `prefix` is `stall_after` old, marks
+/// that action stalled and returns it with the age of its heartbeat.
+pub(crate) async fn stalled(
+    heartbeats: &Heartbeats,
+    prefix: &[usize],
+    stall_after: Duration,
+) -> (NodePath, Duration) {
+    loop {
+        let now = heartbeats.sleeper.now();
+        match heartbeats.oldest_under(prefix) {
+            Some((path, at)) if now - at >= stall_after => {
+                heartbeats.mark_stalled(&path);
+                return (path, now - at);
+            }
+            Some((_, at)) => heartbeats.sleeper.sleep_until(at + stall_after).await,
+            // nothing is running, an action starting now can't stall before `stall_after`
+            None => heartbeats.sleeper.sleep(stall_after).await,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use crate::behavior_tree::heartbeat::HeartbeatStatus;
+    use crate::behavior_tree::observer::{BehaviorObserver, TreeEvent};
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{
+        ActionContext, Actionable, Behavior, NodePath, Response, TreeInstance,
+    };
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::time::{sleep, Instant};
+
+    // flies `legs` legs of two seconds, beating after each, then sits silent for `silent` seconds
+    #[derive(Clone, Debug)]
+    struct Haul {
+        legs: u32,
+        silent: u64,
+    }
+
+    impl Actionable for Haul {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = ();
+
+        async fn run(&self, args: &(), state: &mut ()) -> Result<Response, String> {
+            self.run_with(&ActionContext::default(), args, state).await
+        }
+
+        async fn run_with(
+            &self,
+            cx: &ActionContext,
+            _: &(),
+            _: &mut (),
+        ) -> Result<Response, String> {
+            for leg in 0..self.legs {
+                sleep(Duration::from_secs(2)).await;
+                cx.beat_with(format!("leg {}", leg));
+            }
+            sleep(Duration::from_secs(self.silent)).await;
+            Ok(Response::Success)
+        }
+    }
+
+    fn guarded(haul: Haul, cancel: bool) -> Behavior<Haul> {
+        StallGuard {
+            child: Box::new(Action(haul)),
+            stall_after: Duration::from_secs(5),
+            cancel,
+        }
+    }
+
+    type Events = Arc<Mutex<Vec<(NodePath, TreeEvent)>>>;
+
+    #[derive(Clone, Default)]
+    struct Recorder(Events);
+
+    impl<A> BehaviorObserver<A> for Recorder {
+        fn on_event(&mut self, path: &[usize], event: &TreeEvent) {
+            self.0.lock().unwrap().push((path.to_vec(), event.clone()));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_beating_actions_never_stall() {
+        let recorder = Recorder::default();
+        // ten minutes of two second legs
+        let haul = Haul {
+            legs: 300,
+            silent: 0,
+        };
+        let mut instance = TreeInstance::new(guarded(haul, true)).with_observer(recorder.clone());
+        let heartbeats = instance.heartbeats();
+        let (started, mut state) = (Instant::now(), ());
+        let (result, status) = tokio::join!(instance.run(&(), &mut state), async {
+            sleep(Duration::from_secs(7)).await;
+            heartbeats.status()
+        });
+        result.unwrap();
+        assert_eq!(started.elapsed(), Duration::from_secs(600));
+        assert!(recorder.0.lock().unwrap().is_empty());
+        assert_eq!(
+            status,
+            [HeartbeatStatus {
+                path: vec![0],
+                age: Duration::from_secs(1),
+                note: Some("leg 2".to_string()),
+                stalled: false,
+            }]
+        );
+        // only running actions are listed
+        assert!(heartbeats.status().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_silent_actions_stall() {
+        let recorder = Recorder::default();
+        let haul = Haul {
+            legs: 3,
+            silent: 60,
+        };
+        let mut instance =
+            TreeInstance::new(guarded(haul.clone(), true)).with_observer(recorder.clone());
+        let started = Instant::now();
+        let result = instance.run(&(), &mut ()).await.unwrap();
+        // the last heartbeat came after six seconds
+        assert_eq!(started.elapsed(), Duration::from_secs(11));
+        assert_eq!(result, Response::Failure);
+        let stalled = (
+            vec![],
+            TreeEvent::Stalled {
+                path: vec![0],
+                last_heartbeat_age: Duration::from_secs(5),
+            },
+        );
+        let events = recorder.0.lock().unwrap().clone();
+        assert_eq!(events, [stalled]);
+        assert!(instance.heartbeats().status().is_empty());
+
+        // without cancel the stall is only reported
+        let recorder = Recorder::default();
+        let mut instance = TreeInstance::new(guarded(haul, false)).with_observer(recorder.clone());
+        let heartbeats = instance.heartbeats();
+        let (started, mut state) = (Instant::now(), ());
+        let (result, status) = tokio::join!(instance.run(&(), &mut state), async {
+            sleep(Duration::from_secs(20)).await;
+            heartbeats.status()
+        });
+        result.unwrap();
+        assert_eq!(started.elapsed(), Duration::from_secs(66));
+        assert!(status[0].stalled);
+        assert_eq!(status[0].age, Duration::from_secs(14));
+        assert_eq!(*recorder.0.lock().unwrap(), events);
+    }
+}
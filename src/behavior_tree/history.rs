@@ -0,0 +1,117 @@
+use crate::behavior_tree::{NodePath, Response};
+use std::collections::VecDeque;
+
+/// What a tick of an instance ran and how it ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LastRun {
+    /// The nodes in the order they started, a node once per time it ran.
+    pub visited: Vec<NodePath>,
+    /// `None` if the tick ended with an error.
+    pub response: Option<Response>,
+}
+
+/// One tick kept by an instance with history, see `TreeInstance::with_history`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry<S> {
+    /// Counts the runs of the instance from 1, including those that weren't kept.
+    pub tick: u64,
+    /// The state as the tick left it.
+    pub state: S,
+    pub last_run: LastRun,
+}
+
+/// Ring buffer of the last ticks of an instance.
+pub(crate) struct History<S> {
+    capacity: usize,
+    clone: fn(&S) -> S,
+    ticks: u64,
+    entries: VecDeque<HistoryEntry<S>>,
+}
+
+impl<S> History<S> {
+    pub(crate) fn new(capacity: usize, clone: fn(&S) -> S) -> Self {
+        Self {
+            capacity,
+            clone,
+            ticks: 0,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub(crate) fn record(&mut self, state: &S, last_run: LastRun) {
+        self.ticks += 1;
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(HistoryEntry {
+            tick: self.ticks,
+            state: (self.clone)(state),
+            last_run,
+        });
+        self.entries.make_contiguous();
+    }
+
+    pub(crate) fn entries(&self) -> &[HistoryEntry<S>] {
+        // `record` keeps the entries contiguous
+        self.entries.as_slices().0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::history::{HistoryEntry, LastRun};
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{Actionable, Response, TreeInstance};
+
+    #[derive(Clone, Debug)]
+    struct Add(u32);
+
+    impl Actionable for Add {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = u32;
+
+        async fn run(&self, _: &(), total: &mut u32) -> Result<Response, String> {
+            *total += self.0;
+            if total.is_multiple_of(4) {
+                return Ok(Response::Failure);
+            }
+            Ok(Response::Success)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_history_keeps_the_last_ticks() {
+        let bt = Select(vec![Action(Add(1)), Action(Add(10))]);
+        let mut instance = TreeInstance::new(bt).with_history(3);
+        let mut total = 0;
+
+        for _ in 0..10 {
+            let _ = instance.run(&(), &mut total).await;
+        }
+
+        // ticks that hit a multiple of 4 with `Add(1)` fall back to `Add(10)`
+        let mut expected = vec![];
+        let mut total: u32 = 0;
+        for tick in 1..=10 {
+            total += 1;
+            let mut visited = vec![vec![], vec![0]];
+            if total.is_multiple_of(4) {
+                total += 10;
+                visited.push(vec![1]);
+            }
+            expected.push(HistoryEntry {
+                tick,
+                state: total,
+                last_run: LastRun {
+                    visited,
+                    response: Some(Response::Success),
+                },
+            });
+        }
+        assert_eq!(instance.history(), &expected[7..]);
+    }
+}
@@ -0,0 +1,775 @@
+#[cfg(feature = "serde")]
+use crate::behavior_tree::audit::{AuditOptions, Auditor};
+use crate::behavior_tree::blackboard::{Blackboard, BlackboardValue};
+use crate::behavior_tree::cancel::CancellationToken;
+use crate::behavior_tree::clock::{default_sleeper, Clock, Sleeper, SystemClock};
+use crate::behavior_tree::compare::AccessorRegistry;
+use crate::behavior_tree::debugger::{DebugController, PausedAt};
+use crate::behavior_tree::expr::{FieldAccess, FieldAccessFn};
+use crate::behavior_tree::heartbeat::Heartbeats;
+use crate::behavior_tree::history::{History, HistoryEntry, LastRun};
+#[cfg(feature = "serde")]
+use crate::behavior_tree::loader::LoadedTree;
+use crate::behavior_tree::observer::{BehaviorObserver, TreeEvent};
+use crate::behavior_tree::parallel::Merge;
+#[cfg(feature = "serde")]
+use crate::behavior_tree::replay::{Outcome, Trace};
+use crate::behavior_tree::rng::TreeRng;
+use crate::behavior_tree::runner::AssertMode;
+#[cfg(feature = "tokio")]
+use crate::behavior_tree::spawn::Spawner;
+use crate::behavior_tree::toggle::{NodeRef, Toggles};
+use crate::behavior_tree::typed::TypedBlackboard;
+use crate::behavior_tree::{Actionable, AssertionFailed, Behavior, BehaviorError, Response};
+use futures_util::future::{select, Either};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Position of a node in its tree: the child indices taken from the root (`[]`).
+/// `While` uses `0` for its condition and `1` for its action.
+pub type NodePath = Vec<usize>;
+
+/// Success/failure counts of one child of an `AdaptiveSelect` or variant of an `Experiment`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ArmStats {
+    pub successes: u64,
+    pub failures: u64,
+    /// Whether this child of an `AdaptiveSelect` returned `Running`, so the next run resumes it
+    /// instead of choosing again.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "std::ops::Not::not")
+    )]
+    pub running: bool,
+}
+
+impl ArmStats {
+    pub fn attempts(&self) -> u64 {
+        self.successes + self.failures
+    }
+
+    pub fn success_rate(&self) -> f64 {
+        if self.attempts() == 0 {
+            0.0
+        } else {
+            self.successes as f64 / self.attempts() as f64
+        }
+    }
+}
+
+/// Runtime memory a node keeps between runs of the same instance.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum NodeMemory {
+    Adaptive(Vec<ArmStats>),
+    // the counts of the variants of an `Experiment`
+    Variants(Vec<ArmStats>),
+    // the value of the watched key when an `OnChanged` last succeeded
+    LastSeen(Option<BlackboardValue>),
+    // the child a composite returned `Running` from, which the next run resumes at
+    Cursor(usize),
+    // the children of a `Parallel` that finished while others were still running, with why they
+    // failed if they did
+    Finished(Vec<(usize, Option<String>)>),
+    // the attempts a `Retry` used up, or the runs a `Repeat` completed, when its child returned
+    // `Running`
+    Count(usize),
+    // when the child of a `Cooldown` last succeeded or failed, on the instance's sleeper; only
+    // means something in this process, so snapshots leave it out
+    #[cfg_attr(feature = "serde", serde(skip))]
+    FinishedAt(Instant),
+}
+
+type CloneFn<S> = fn(&S) -> S;
+
+// clones the state, and compares two states
+type PurityCheck<S> = (CloneFn<S>, fn(&S, &S) -> bool);
+
+// clones the state for a child of a `Parallel`, and merges the clone back
+pub(crate) type ParallelFns<S> = (CloneFn<S>, fn(&mut S, S));
+
+/// What an action reaches of the run it is part of, handed to it by
+/// [`run_with`](Actionable::run_with). The default one belongs to no run: its token is never
+/// cancelled, its typed blackboard is empty and its beats go nowhere.
+#[derive(Clone, Default)]
+pub struct ActionContext {
+    cancellation: CancellationToken,
+    typed: TypedBlackboard,
+    heartbeats: Heartbeats,
+    path: NodePath,
+}
+
+impl ActionContext {
+    /// The token of the run, so a long action can watch it and wrap up.
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    /// The typed blackboard of the instance, with the namespaces of the `Namespaced` nodes
+    /// above the action.
+    pub fn typed_blackboard(&self) -> &TypedBlackboard {
+        &self.typed
+    }
+
+    /// The path of the `Action` leaf running.
+    pub fn path(&self) -> &[usize] {
+        &self.path
+    }
+
+    /// Tells the instance the action is still making progress.
+    pub fn beat(&self) {
+        self.heartbeats.beat(&self.path, None);
+    }
+
+    /// Like [`beat`](Self::beat), noting what the action is at, e.g. `"docking"`.
+    pub fn beat_with(&self, note: impl Into<String>) {
+        self.heartbeats.beat(&self.path, Some(note.into()));
+    }
+}
+
+/// Everything the evaluator threads through a single run of a tree.
+pub struct RunContext<A: Actionable> {
+    pub(crate) path: NodePath,
+    pub(crate) rng: TreeRng,
+    pub(crate) memory: HashMap<NodePath, NodeMemory>,
+    pub(crate) blackboard: Blackboard,
+    pub(crate) field_access: Option<FieldAccessFn<A::ActionState>>,
+    pub(crate) accessors: AccessorRegistry<A::ActionState>,
+    pub(crate) observers: Vec<Box<dyn BehaviorObserver<A>>>,
+    pub(crate) state_debug: Option<fn(&A::ActionState) -> String>,
+    #[cfg(feature = "serde")]
+    pub(crate) audit: Option<Auditor<A::ActionState>>,
+    #[cfg(feature = "serde")]
+    pub(crate) trace: Option<Trace<A>>,
+    pub(crate) assert_mode: AssertMode,
+    pub(crate) debugger: Option<DebugController>,
+    pub(crate) clock: Arc<dyn Clock>,
+    pub(crate) sleeper: Arc<dyn Sleeper>,
+    pub(crate) cancellation: CancellationToken,
+    pub(crate) purity_check: Option<PurityCheck<A::ActionState>>,
+    // clones the state for each read-only action run concurrently
+    pub(crate) concurrent_conditions: Option<CloneFn<A::ActionState>>,
+    // the result of the next action to run, which ran already in a batch of conditions
+    pub(crate) batched: Option<Result<Response, A::ActionError>>,
+    pub(crate) parallel: Option<ParallelFns<A::ActionState>>,
+    // the paths of the nodes run so far, collected for the history of the instance
+    pub(crate) visited: Option<Vec<NodePath>>,
+    pub(crate) toggles: Toggles,
+    #[cfg(feature = "tokio")]
+    pub(crate) spawner: Option<Spawner<A>>,
+    pub(crate) heartbeats: Heartbeats,
+    pub(crate) typed: TypedBlackboard,
+    // the namespaces of the `Namespaced` nodes running, by path
+    pub(crate) namespaces: BTreeMap<NodePath, String>,
+    // whether the last node to finish passed on an error of a node under it, which observers
+    // were told about already
+    pub(crate) error_reported: bool,
+}
+
+impl<A: Actionable> Default for RunContext<A> {
+    fn default() -> Self {
+        Self {
+            path: vec![],
+            rng: TreeRng::default(),
+            memory: HashMap::new(),
+            blackboard: Blackboard::new(),
+            field_access: None,
+            accessors: AccessorRegistry::new(),
+            observers: vec![],
+            state_debug: None,
+            #[cfg(feature = "serde")]
+            audit: None,
+            #[cfg(feature = "serde")]
+            trace: None,
+            assert_mode: AssertMode::default(),
+            debugger: None,
+            clock: Arc::new(SystemClock),
+            sleeper: default_sleeper(),
+            cancellation: CancellationToken::new(),
+            purity_check: None,
+            concurrent_conditions: None,
+            batched: None,
+            parallel: None,
+            visited: None,
+            toggles: Toggles::default(),
+            #[cfg(feature = "tokio")]
+            spawner: None,
+            heartbeats: Heartbeats::default(),
+            typed: TypedBlackboard::default(),
+            namespaces: BTreeMap::new(),
+            error_reported: false,
+        }
+    }
+}
+
+impl<A: Actionable> RunContext<A> {
+    pub(crate) fn emit(&mut self, event: TreeEvent) {
+        for observer in &mut self.observers {
+            observer.on_event(&self.path, &event);
+        }
+    }
+
+    // passes on what the detached subtrees of `Spawn` nodes sent so far
+    fn deliver_spawn_events(&mut self) {
+        #[cfg(feature = "tokio")]
+        if let Some(spawner) = &self.spawner {
+            for (path, event) in spawner.take_events() {
+                for observer in &mut self.observers {
+                    observer.on_event(&path, &event);
+                }
+            }
+        }
+    }
+
+    /// Whether something watches the action leaves one by one, so they can't run concurrently.
+    pub(crate) fn is_instrumented(&self) -> bool {
+        #[cfg(feature = "serde")]
+        if self.trace.is_some() {
+            return true;
+        }
+        self.debugger.is_some() || self.purity_check.is_some()
+    }
+
+    /// Runs the action of an `Action` leaf, auditing and recording it if the run does.
+    pub(crate) async fn run_action(
+        &mut self,
+        action: &A,
+        args: &A::ActionArgs,
+        state: &mut A::ActionState,
+    ) -> Result<Response, BehaviorError<A::ActionError>> {
+        #[cfg(feature = "serde")]
+        if let Some(Trace::Replay(replayer)) = &mut self.trace {
+            return replayer.step(&self.path, action, state);
+        }
+        #[cfg(feature = "serde")]
+        let audited = self.audit.as_mut().and_then(|audit| audit.before(state));
+        #[cfg(feature = "serde")]
+        let recorded = self.trace.as_ref().and_then(|trace| trace.before(state));
+
+        let unchanged = match self.purity_check {
+            Some((clone, _)) if action.is_read_only() => Some(clone(state)),
+            _ => None,
+        };
+
+        let result = match self.batched.take() {
+            Some(result) => result,
+            None => self.run_scoped(&self.path, action, args, state).await,
+        };
+        let result = result.map_err(BehaviorError::Action);
+
+        if let (Some(unchanged), Some((_, eq))) = (unchanged, self.purity_check) {
+            if !eq(&unchanged, state) {
+                return Err(BehaviorError::AssertionFailed(AssertionFailed {
+                    message: format!("read-only action `{}` changed the state", action.name()),
+                    path: self.path.clone(),
+                    state: self.state_debug.map(|debug| debug(state)),
+                }));
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        if let (Some(before), Some(audit)) = (audited, &self.audit) {
+            let (changes, truncated) = audit.changes(&before, state);
+            if !changes.is_empty() {
+                self.emit(TreeEvent::StateChanged { changes, truncated });
+            }
+        }
+        #[cfg(feature = "serde")]
+        if let (Some(before), Some(trace)) = (recorded, &mut self.trace) {
+            let outcome = match result {
+                Ok(Response::Success) => Outcome::Success,
+                Ok(Response::Running) => Outcome::Running,
+                Ok(Response::Failure) => Outcome::Failure,
+                Err(_) => Outcome::Failed,
+            };
+            trace.record(&self.path, action, outcome, &before, state);
+        }
+        result
+    }
+
+    /// Runs `action`, the leaf at `path`, with an [`ActionContext`] of this run.
+    pub(crate) fn run_scoped<'a>(
+        &self,
+        path: &[usize],
+        action: &'a A,
+        args: &'a A::ActionArgs,
+        state: &'a mut A::ActionState,
+    ) -> impl Future<Output = Result<Response, A::ActionError>> + 'a {
+        let cx = ActionContext {
+            cancellation: self.cancellation.clone(),
+            typed: self.typed_blackboard_at(path),
+            heartbeats: self.heartbeats.clone(),
+            path: path.to_vec(),
+        };
+        async move {
+            let run = action.run_with(&cx, args, state);
+            cx.heartbeats.scope(&cx.path, run).await
+        }
+    }
+
+    /// The typed blackboard with the namespaces of the node running.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn typed_blackboard(&self) -> TypedBlackboard {
+        self.typed_blackboard_at(&self.path)
+    }
+
+    fn typed_blackboard_at(&self, at: &[usize]) -> TypedBlackboard {
+        let namespaces = self.namespaces.iter();
+        namespaces
+            .filter(|(path, _)| at.starts_with(path))
+            .fold(self.typed.clone(), |typed, (_, namespace)| {
+                typed.namespaced(namespace)
+            })
+    }
+
+    /// Sleeps for `duration` unless the run is cancelled first. Returns whether the sleep
+    /// completed.
+    pub(crate) async fn sleep(&mut self, duration: Duration) -> bool {
+        let cancelled = pin!(self.cancellation.cancelled());
+        matches!(
+            select(self.sleeper.sleep(duration), cancelled).await,
+            Either::Left(_)
+        )
+    }
+
+    pub(crate) async fn hit_breakpoint(&mut self, label: Option<String>) {
+        let Some(debugger) = self.debugger.clone() else {
+            return;
+        };
+        self.emit(TreeEvent::BreakpointHit {
+            label: label.clone(),
+            path: self.path.clone(),
+        });
+        debugger
+            .pause(PausedAt {
+                path: self.path.clone(),
+                label,
+            })
+            .await;
+    }
+
+    pub(crate) fn adaptive_stats(&mut self, len: usize) -> &mut Vec<ArmStats> {
+        self.arm_stats(len, NodeMemory::Adaptive)
+    }
+
+    pub(crate) fn variant_stats(&mut self, len: usize) -> &mut Vec<ArmStats> {
+        self.arm_stats(len, NodeMemory::Variants)
+    }
+
+    // the counts `kind` keeps for the node, `len` of them
+    fn arm_stats(
+        &mut self,
+        len: usize,
+        kind: fn(Vec<ArmStats>) -> NodeMemory,
+    ) -> &mut Vec<ArmStats> {
+        let memory = self
+            .memory
+            .entry(self.path.clone())
+            .or_insert_with(|| kind(vec![]));
+        if std::mem::discriminant(memory) != std::mem::discriminant(&kind(vec![])) {
+            *memory = kind(vec![]);
+        }
+        match memory {
+            NodeMemory::Adaptive(stats) | NodeMemory::Variants(stats) => {
+                stats.resize(len, ArmStats::default());
+                stats
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Where the node left off when it last returned `Running`, forgetting it: `0` if it
+    /// didn't or if the node has no `len` children anymore.
+    pub(crate) fn take_cursor(&mut self, len: usize) -> usize {
+        match self.memory.get(&self.path) {
+            Some(NodeMemory::Cursor(i)) => {
+                let i = *i;
+                self.memory.remove(&self.path);
+                if i < len {
+                    i
+                } else {
+                    0
+                }
+            }
+            _ => 0,
+        }
+    }
+
+    /// Makes the next run of the node resume at child `index`.
+    pub(crate) fn set_cursor(&mut self, index: usize) {
+        self.memory
+            .insert(self.path.clone(), NodeMemory::Cursor(index));
+    }
+
+    /// Stops the child `running` of a reactive node, the one it returned `Running` for last,
+    /// if child `decided` came before it: what the child kept is dropped, so it starts over the
+    /// next time it runs.
+    pub(crate) fn preempt(&mut self, running: usize, decided: usize) {
+        if decided >= running {
+            return;
+        }
+        let mut prefix = self.path.clone();
+        prefix.push(running);
+        self.memory.retain(|path, _| !path.starts_with(&prefix));
+        self.emit(TreeEvent::Preempted { child: running });
+    }
+
+    /// The count a `Retry` or `Repeat` node kept when its child returned `Running`, clearing it.
+    pub(crate) fn take_count(&mut self) -> usize {
+        match self.memory.remove(&self.path) {
+            Some(NodeMemory::Count(count)) => count,
+            _ => 0,
+        }
+    }
+
+    pub(crate) fn set_count(&mut self, count: usize) {
+        self.memory
+            .insert(self.path.clone(), NodeMemory::Count(count));
+    }
+
+    /// The value an `OnChanged` node last fired on, `None` if it never ran.
+    pub(crate) fn last_seen(&self) -> Option<&Option<BlackboardValue>> {
+        match self.memory.get(&self.path) {
+            Some(NodeMemory::LastSeen(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn set_last_seen(&mut self, value: Option<BlackboardValue>) {
+        self.memory
+            .insert(self.path.clone(), NodeMemory::LastSeen(value));
+    }
+}
+
+/// Serializable copy of an instance's runtime memory, so learned state survives restarts.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct InstanceSnapshot {
+    pub rng: TreeRng,
+    pub memory: Vec<(NodePath, NodeMemory)>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub disabled: Vec<NodeRef>,
+}
+
+/// A behavior together with the runtime state that persists across its runs.
+pub struct TreeInstance<A: Actionable> {
+    behavior: Behavior<A>,
+    pub(crate) context: RunContext<A>,
+    history: Option<History<A::ActionState>>,
+}
+
+impl<A> TreeInstance<A>
+where
+    A: Actionable,
+{
+    pub fn new(behavior: Behavior<A>) -> Self {
+        Self {
+            behavior,
+            context: RunContext::default(),
+            history: None,
+        }
+    }
+
+    pub fn with_seed(behavior: Behavior<A>, seed: u64) -> Self {
+        Self {
+            behavior,
+            context: RunContext {
+                rng: TreeRng::seeded(seed),
+                ..RunContext::default()
+            },
+            history: None,
+        }
+    }
+
+    /// Creates an instance whose blackboard starts with the values declared in the tree file,
+    /// overwritten by `overrides`.
+    #[cfg(feature = "serde")]
+    pub fn from_loaded(loaded: LoadedTree<A>, overrides: Blackboard) -> Self {
+        let mut instance = Self::new(loaded.behavior);
+        instance.context.blackboard = loaded.blackboard;
+        instance.context.blackboard.extend(overrides);
+        instance
+    }
+
+    /// Lets `Expr` nodes read `state.<field>` through the state's [`FieldAccess`] impl.
+    pub fn with_field_access(mut self) -> Self
+    where
+        A::ActionState: FieldAccess,
+    {
+        self.context.field_access = Some(<A::ActionState as FieldAccess>::field);
+        self
+    }
+
+    /// Sets the accessors `Compare` nodes resolve `ValueRef::Accessor` names with.
+    pub fn with_accessors(mut self, accessors: AccessorRegistry<A::ActionState>) -> Self {
+        self.context.accessors = accessors;
+        self
+    }
+
+    /// Includes the `Debug` output of the state in `AssertionFailed` errors.
+    pub fn with_state_debug(mut self) -> Self
+    where
+        A::ActionState: Debug,
+    {
+        self.context.state_debug = Some(|state| format!("{:?}", state));
+        self
+    }
+
+    /// Compares the serialized state before and after action runs, and emits a
+    /// `TreeEvent::StateChanged` listing what an action changed.
+    #[cfg(feature = "serde")]
+    pub fn with_audit(mut self, options: AuditOptions) -> Self
+    where
+        A::ActionState: Serialize,
+    {
+        self.context.audit = Some(Auditor::new(
+            |state| serde_json::to_value(state).unwrap_or(serde_json::Value::Null),
+            options,
+        ));
+        self
+    }
+
+    /// Keeps the state each of the last `ticks` runs left, with the nodes they ran.
+    pub fn with_history(mut self, ticks: usize) -> Self
+    where
+        A::ActionState: Clone,
+    {
+        self.history = Some(History::new(ticks, A::ActionState::clone));
+        self.context.visited = Some(vec![]);
+        self
+    }
+
+    /// Fails the run with an `AssertionFailed` error when an action declared read-only changes
+    /// the state. Meant for tests and debug builds, it clones the state around those actions.
+    pub fn with_purity_check(mut self) -> Self
+    where
+        A::ActionState: Clone + PartialEq,
+    {
+        self.context.purity_check = Some((A::ActionState::clone, A::ActionState::eq));
+        self
+    }
+
+    /// Lets a `Select` or `Sequence` run adjacent read-only actions among its children
+    /// concurrently, each on a clone of the state, and then look at their results in order as
+    /// if they had run one after the other. Changes such actions make to their clone are lost.
+    /// Observers, audits and toggles see those children as if they had run one by one, and a
+    /// disabled child isn't run. Falls back to running them one by one while debugging,
+    /// recording a trace or checking purity.
+    pub fn with_concurrent_conditions(mut self) -> Self
+    where
+        A::ActionState: Clone,
+    {
+        self.context.concurrent_conditions = Some(A::ActionState::clone);
+        self
+    }
+
+    /// Lets `Parallel` nodes run their children concurrently on the task running the tree. Each
+    /// child runs on a clone of the state and of the blackboard; once it finishes, its state is
+    /// merged into the state of the tree with [`Merge`] and the keys it changed are written to
+    /// the blackboard, in child order. What a child stopped midway changed is lost. The events of
+    /// the children reach the observers when the node returns, but observers aren't told when
+    /// the nodes below the children enter and exit. Their subtrees can't spawn.
+    /// Falls back to running the children one by one while debugging, recording a trace or
+    /// checking purity.
+    pub fn with_parallel(mut self) -> Self
+    where
+        A::ActionState: Clone + Merge,
+    {
+        self.context.parallel = Some((A::ActionState::clone, A::ActionState::merge));
+        self
+    }
+
+    /// Lets `Spawn` nodes start their child on a tokio task of its own. The child runs on clones
+    /// of the args and state; a state that should be shared with the tree, like the ship the
+    /// child moves, has to be a handle such as an `Arc<Mutex<_>>`. The child doesn't see the
+    /// blackboard, toggles or clock of the instance. Its events, and a `SpawnFinished` event
+    /// with its error if it failed, reach the observers on the next run or at
+    /// [`shutdown`](Self::shutdown). The outcome of a task is kept until a `Join` takes it or
+    /// the instance shuts down. Dropping the instance cancels the tasks still running. The tasks
+    /// are tokio tasks, so trees with `Spawn` nodes have to run inside a tokio runtime.
+    #[cfg(feature = "tokio")]
+    pub fn with_spawning(mut self) -> Self
+    where
+        A: 'static,
+        A::ActionArgs: Clone + 'static,
+        A::ActionState: Clone + 'static,
+        A::ActionError: std::fmt::Display,
+    {
+        self.context.spawner = Some(Spawner::new());
+        self
+    }
+
+    /// The ids of the spawned tasks still running.
+    #[cfg(feature = "tokio")]
+    pub fn running_spawns(&self) -> Vec<i64> {
+        self.context
+            .spawner
+            .as_ref()
+            .map_or(vec![], Spawner::running)
+    }
+
+    /// The last heartbeats of the actions the instance is running. The handle stays live, so
+    /// it can be read from elsewhere while the instance runs.
+    pub fn heartbeats(&self) -> Heartbeats {
+        self.context.heartbeats.clone()
+    }
+
+    /// The typed blackboard the actions of the instance share. The handle stays live, so values
+    /// can be set before a run and read after it.
+    pub fn typed_blackboard(&self) -> TypedBlackboard {
+        self.context.typed.clone()
+    }
+
+    /// Lets the actions of the instance share `typed` with whoever else holds it, like the
+    /// actions of another instance.
+    pub fn with_typed_blackboard(mut self, typed: TypedBlackboard) -> Self {
+        self.context.typed = typed;
+        self
+    }
+
+    /// Cancels the spawned tasks still running, waits for them to stop, and passes the events
+    /// they sent on to the observers.
+    #[cfg(feature = "tokio")]
+    pub async fn shutdown(&mut self) {
+        if let Some(spawner) = &mut self.context.spawner {
+            spawner.shutdown().await;
+        }
+        self.context.deliver_spawn_events();
+    }
+
+    /// Sets the clock `SleepUntil` nodes compare their timestamps against.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.context.clock = Arc::new(clock);
+        self
+    }
+
+    /// Sets the timer nodes wait and measure time with, e.g. one of the executor the instance
+    /// runs on. Take the [`heartbeats`](Self::heartbeats) handle after setting it.
+    pub fn with_sleeper(mut self, sleeper: impl Sleeper + 'static) -> Self {
+        let sleeper: Arc<dyn Sleeper> = Arc::new(sleeper);
+        self.context.heartbeats = Heartbeats::timed_by(sleeper.clone());
+        self.context.sleeper = sleeper;
+        self
+    }
+
+    pub fn with_observer(mut self, observer: impl BehaviorObserver<A> + 'static) -> Self {
+        self.context.observers.push(Box::new(observer));
+        self
+    }
+
+    pub fn behavior(&self) -> &Behavior<A> {
+        &self.behavior
+    }
+
+    pub fn blackboard(&self) -> &Blackboard {
+        &self.context.blackboard
+    }
+
+    pub fn blackboard_mut(&mut self) -> &mut Blackboard {
+        &mut self.context.blackboard
+    }
+
+    pub async fn run(
+        &mut self,
+        args: &A::ActionArgs,
+        state: &mut A::ActionState,
+    ) -> Result<Response, BehaviorError<A::ActionError>> {
+        self.run_visiting(args, state).await.0
+    }
+
+    // also returns the nodes the run visited, if the instance collects them
+    pub(crate) async fn run_visiting(
+        &mut self,
+        args: &A::ActionArgs,
+        state: &mut A::ActionState,
+    ) -> (
+        Result<Response, BehaviorError<A::ActionError>>,
+        Option<Vec<NodePath>>,
+    ) {
+        self.context.path.clear();
+        self.context.deliver_spawn_events();
+        let result = self.behavior.run_in(&mut self.context, args, state).await;
+        self.context.deliver_spawn_events();
+        let visited = self.context.visited.as_mut().map(std::mem::take);
+        if let (Some(history), Some(visited)) = (&mut self.history, &visited) {
+            let response = result.as_ref().ok().copied();
+            history.record(
+                state,
+                LastRun {
+                    visited: visited.clone(),
+                    response,
+                },
+            );
+        }
+        (result, visited)
+    }
+
+    /// The kept ticks, oldest first. Empty unless the instance was built `with_history`.
+    pub fn history(&self) -> &[HistoryEntry<A::ActionState>] {
+        self.history.as_ref().map_or(&[], History::entries)
+    }
+
+    pub fn memory(&self, path: &[usize]) -> Option<&NodeMemory> {
+        self.context.memory.get(path)
+    }
+
+    /// Forgets what the nodes remembered from earlier runs, like the children running
+    /// composites resume at, keeping the blackboard and the counts `AdaptiveSelect` and
+    /// `Experiment` nodes learned from.
+    pub fn reset(&mut self) {
+        self.context.memory.retain(|_, memory| match memory {
+            NodeMemory::Adaptive(stats) => {
+                stats.iter_mut().for_each(|arm| arm.running = false);
+                true
+            }
+            NodeMemory::Variants(_) => true,
+            _ => false,
+        });
+    }
+
+    /// The memory of the nodes, the rng and the disabled nodes. Running `Cooldown`s are left
+    /// out and start over in an instance restored from the snapshot.
+    pub fn snapshot(&self) -> InstanceSnapshot {
+        let mut memory: Vec<_> = self
+            .context
+            .memory
+            .iter()
+            .filter(|(_, memory)| !matches!(memory, NodeMemory::FinishedAt(_)))
+            .map(|(path, memory)| (path.clone(), memory.clone()))
+            .collect();
+        memory.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        InstanceSnapshot {
+            rng: self.context.rng.clone(),
+            memory,
+            disabled: self.disabled(),
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: InstanceSnapshot) {
+        self.context.rng = snapshot.rng;
+        self.context.memory = snapshot.memory.into_iter().collect();
+        self.context.toggles = Toggles::default();
+        for node in snapshot.disabled {
+            self.context.toggles.set_enabled(node, false);
+        }
+    }
+
+    /// Switches a node on or off without changing the tree. A disabled node results in its
+    /// `when_disabled` outcome without running its subtree, and emits a `SkippedDisabled` event.
+    pub fn set_enabled(&mut self, node: impl Into<NodeRef>, enabled: bool) {
+        self.context.toggles.set_enabled(node.into(), enabled);
+    }
+
+    /// The nodes switched off with [`set_enabled`](Self::set_enabled), paths first.
+    pub fn disabled(&self) -> Vec<NodeRef> {
+        self.context.toggles.disabled().cloned().collect()
+    }
+}
@@ -0,0 +1,147 @@
+//! Reading and writing bare trees, without the blackboard, runtime keys and definitions of a
+//! tree file (see [`LoadedTree`](crate::behavior_tree::loader::LoadedTree) for those).
+//!
+//! A node is written externally tagged, as its variant name holding its fields:
+//! `{"Sequence": [{"Action": "Dock"}, {"Invert": {"CheckKey": {"key": "fuel", "value": "low"}}}]}`.
+//! Nodes without fields, like `AlwaysFail`, are written as just the name. Durations are given in
+//! milliseconds, and fields with defaults may be left out. `fixtures/every_node_kind.json` has
+//! a node of every kind, written the way [`to_json_string`] writes it.
+//!
+//! A `Select` without children is rejected, as it could only ever fail. A `Sequence` without
+//! children always succeeds.
+
+use crate::behavior_tree::loader::{self, LoadError};
+use crate::behavior_tree::Behavior;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Reads a tree and checks that its nodes are configured correctly. Unknown node kinds are an
+/// error naming the kind.
+///
+/// Trees may nest at most 128 levels deep, the recursion limit of `serde_json`; a node takes
+/// one or two levels. Deeper trees are a [`LoadError::Json`] rather than a stack overflow.
+pub fn from_json_str<A: DeserializeOwned>(json: &str) -> Result<Behavior<A>, LoadError> {
+    let behavior: Behavior<A> = serde_json::from_str(json)?;
+    let mut first_error = None;
+    behavior.walk(&mut |path, node| {
+        if let Some(message) = node.config_error() {
+            first_error.get_or_insert(LoadError::InvalidNode {
+                message,
+                path: path.to_vec(),
+            });
+        }
+    });
+    match first_error {
+        Some(err) => Err(err),
+        None => Ok(behavior),
+    }
+}
+
+impl<A> Behavior<A>
+where
+    A: DeserializeOwned,
+{
+    /// Reads a bare tree, see [`from_json_str`].
+    pub fn from_json(json: &str) -> Result<Self, LoadError> {
+        from_json_str(json)
+    }
+}
+
+/// Writes the tree pretty-printed, with the fields of every node sorted by name so the output
+/// only changes where the tree does. `Opaque` nodes are written back as they were read.
+pub fn to_json_string<A: Serialize>(behavior: &Behavior<A>) -> Result<String, LoadError> {
+    let mut tree = serde_json::to_value(behavior)?;
+    loader::unwrap_opaque_nodes(&mut tree);
+    Ok(serde_json::to_string_pretty(&tree)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::io::{from_json_str, to_json_string};
+    use crate::behavior_tree::loader::{self, LoadError};
+    use crate::behavior_tree::Behavior;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeSet;
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    enum Ship {
+        Dock,
+        Navigate(String),
+    }
+
+    const EVERY_NODE_KIND: &str = include_str!("../../fixtures/every_node_kind.json");
+
+    #[test]
+    fn test_every_node_kind_round_trips() {
+        let tree: Behavior<Ship> = from_json_str(EVERY_NODE_KIND).unwrap();
+        assert_eq!(to_json_string(&tree).unwrap(), EVERY_NODE_KIND.trim_end());
+
+        let json = serde_json::to_string(&tree).unwrap();
+        let reread: Behavior<Ship> = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&reread).unwrap(), json);
+
+        // `Opaque` only stands in for kinds this version doesn't know
+        let mut kinds = BTreeSet::new();
+        tree.walk(&mut |_, node| {
+            let node = serde_json::to_value(node).unwrap();
+            kinds.insert(loader::node_kind(&node).unwrap().to_string());
+        });
+        let expected: BTreeSet<_> = loader::NODE_KINDS
+            .iter()
+            .filter(|&&kind| kind != "Opaque")
+            .map(|kind| kind.to_string())
+            .collect();
+        assert_eq!(kinds, expected);
+    }
+
+    #[test]
+    fn test_unknown_node_kinds_are_named() {
+        let err = from_json_str::<Ship>(r#"{"Sequence": [{"Swarm": []}]}"#).unwrap_err();
+        assert!(matches!(err, LoadError::Json(_)));
+        assert!(
+            err.to_string().contains("unknown variant `Swarm`"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_empty_selects_are_rejected() {
+        let err = from_json_str::<Ship>(r#"{"Invert": {"Select": []}}"#).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid node at [0]: select has no children"
+        );
+        from_json_str::<Ship>(r#"{"Invert": {"Sequence": []}}"#).unwrap();
+    }
+
+    #[test]
+    fn test_missing_fields_are_named() {
+        let err = Behavior::<Ship>::from_json(r#"{"While": {"action": {"Action": "Dock"}}}"#)
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("missing field `condition`"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_deeply_nested_trees_are_rejected() {
+        let nested = |depth: usize| {
+            let inner = r#"{"While": {"condition": "AlwaysFail", "action": {"Invert": "#;
+            format!(
+                "{}\"AlwaysFail\"{}",
+                inner.repeat(depth),
+                "}}}".repeat(depth)
+            )
+        };
+        from_json_str::<Ship>(&nested(40)).unwrap();
+        let err = from_json_str::<Ship>(&nested(100_000)).unwrap_err();
+        assert!(
+            err.to_string().contains("recursion limit exceeded"),
+            "{}",
+            err
+        );
+    }
+}
@@ -0,0 +1,430 @@
+use crate::behavior_tree::blackboard::BlackboardValue;
+use crate::behavior_tree::compare::AccessorRegistry;
+use crate::behavior_tree::expr::FieldAccess;
+use crate::behavior_tree::registry::{ActionRegistry, DynamicAction};
+use crate::behavior_tree::{Actionable, TreeInstance};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The action type of fully dynamic trees: leaves are looked up by name in a [`JsonRegistry`]
+/// and run against a [`JsonState`].
+pub type JsonAction = DynamicAction<(), JsonState, anyhow::Error>;
+
+pub type JsonRegistry = ActionRegistry<(), JsonState, anyhow::Error>;
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum PathError {
+    #[error("invalid path `{path}`: {message}")]
+    Syntax { path: String, message: String },
+    #[error("cannot set `{path}`: `{parent}` is not an object or array")]
+    NotAContainer { path: String, parent: String },
+    #[error("cannot set `{path}`: index {index} is past the end of an array of {len}")]
+    OutOfRange {
+        path: String,
+        index: usize,
+        len: usize,
+    },
+}
+
+/// An `ActionState` that is just a JSON document, for trees run without a Rust state type.
+///
+/// Values are addressed with JSON pointers like `/ship/fuel` or `/cargo/0`: the empty path is
+/// the whole document, every other path starts with `/`, and `~1` and `~0` stand for `/` and `~`
+/// inside a segment. When setting, `-` as the last segment appends to an array.
+///
+/// `Compare` nodes read the state with pointers as accessor names, `Expr` nodes with dotted
+/// fields like `state.ship.fuel`. Both see booleans, strings and numbers, integers as ints and
+/// other numbers as floats; null, arrays and objects can't be read. Strings are never read as
+/// numbers, so comparing `"10"` with `10` fails.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct JsonState(pub Value);
+
+impl JsonState {
+    pub fn new(value: Value) -> Self {
+        Self(value)
+    }
+
+    pub fn into_value(self) -> Value {
+        self.0
+    }
+
+    /// The value at `path`, `None` if there is none.
+    pub fn get(&self, path: &str) -> Result<Option<&Value>, PathError> {
+        let mut value = &self.0;
+        for segment in segments(path)? {
+            let next = match value {
+                Value::Object(map) => map.get(&segment),
+                Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+                _ => None,
+            };
+            match next {
+                Some(next) => value = next,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(value))
+    }
+
+    /// Sets the value at `path`, creating missing objects on the way. Returns the value that was
+    /// there before.
+    pub fn set(&mut self, path: &str, new: Value) -> Result<Option<Value>, PathError> {
+        let segments = segments(path)?;
+        let Some((last, parents)) = segments.split_last() else {
+            return Ok(Some(std::mem::replace(&mut self.0, new)));
+        };
+        let not_a_container = |depth: usize| PathError::NotAContainer {
+            path: path.to_string(),
+            parent: pointer(&segments[..depth]),
+        };
+
+        let mut value = &mut self.0;
+        for (depth, segment) in parents.iter().enumerate() {
+            if value.is_null() {
+                *value = Value::Object(Default::default());
+            }
+            value = match value {
+                Value::Object(map) => map.entry(segment.clone()).or_insert(Value::Null),
+                Value::Array(items) => {
+                    let len = items.len();
+                    let index = array_index(path, segment, len)?;
+                    items.get_mut(index).ok_or(PathError::OutOfRange {
+                        path: path.to_string(),
+                        index,
+                        len,
+                    })?
+                }
+                _ => return Err(not_a_container(depth)),
+            };
+        }
+        if value.is_null() {
+            *value = Value::Object(Default::default());
+        }
+        match value {
+            Value::Object(map) => Ok(map.insert(last.clone(), new)),
+            Value::Array(items) if last == "-" => {
+                items.push(new);
+                Ok(None)
+            }
+            Value::Array(items) => {
+                let len = items.len();
+                match array_index(path, last, len)? {
+                    index if index < len => Ok(Some(std::mem::replace(&mut items[index], new))),
+                    index if index == len => {
+                        items.push(new);
+                        Ok(None)
+                    }
+                    index => Err(PathError::OutOfRange {
+                        path: path.to_string(),
+                        index,
+                        len,
+                    }),
+                }
+            }
+            _ => Err(not_a_container(parents.len())),
+        }
+    }
+
+    /// Reads `path` as a value `Compare` and `Expr` nodes can use.
+    pub fn read(&self, path: &str) -> Result<Option<BlackboardValue>, PathError> {
+        Ok(self.get(path)?.and_then(to_blackboard))
+    }
+
+    /// Accessors resolving every name as a path into the state.
+    pub fn accessors() -> AccessorRegistry<JsonState> {
+        AccessorRegistry::new().with_fallback(|state, path| state.read(path).ok().flatten())
+    }
+}
+
+impl FieldAccess for JsonState {
+    // `state.ship.fuel` in an expression reads `/ship/fuel`
+    fn field(&self, name: &str) -> Option<BlackboardValue> {
+        let path: String = name.split('.').map(|field| format!("/{}", field)).collect();
+        self.read(&path).ok().flatten()
+    }
+}
+
+impl From<Value> for JsonState {
+    fn from(value: Value) -> Self {
+        Self(value)
+    }
+}
+
+impl<A> TreeInstance<A>
+where
+    A: Actionable<ActionState = JsonState>,
+{
+    /// Lets `Compare` and `Expr` nodes read the [`JsonState`] by path.
+    pub fn with_json_state(self) -> Self {
+        self.with_field_access()
+            .with_accessors(JsonState::accessors())
+    }
+}
+
+fn to_blackboard(value: &Value) -> Option<BlackboardValue> {
+    match value {
+        Value::Bool(b) => Some(BlackboardValue::Bool(*b)),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => Some(BlackboardValue::Int(i)),
+            None => n.as_f64().map(BlackboardValue::Float),
+        },
+        Value::String(s) => Some(BlackboardValue::String(s.clone())),
+        Value::Null | Value::Array(_) | Value::Object(_) => None,
+    }
+}
+
+fn segments(path: &str) -> Result<Vec<String>, PathError> {
+    if path.is_empty() {
+        return Ok(vec![]);
+    }
+    let syntax = |message: String| PathError::Syntax {
+        path: path.to_string(),
+        message,
+    };
+    let Some(rest) = path.strip_prefix('/') else {
+        return Err(syntax("must be empty or start with `/`".to_string()));
+    };
+    rest.split('/')
+        .map(|segment| {
+            unescape(segment).ok_or_else(|| {
+                syntax(format!(
+                    "segment `{}` has a `~` not followed by `0` or `1`",
+                    segment
+                ))
+            })
+        })
+        .collect()
+}
+
+fn unescape(segment: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(segment.len());
+    let mut chars = segment.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '~' => match chars.next()? {
+                '0' => unescaped.push('~'),
+                '1' => unescaped.push('/'),
+                _ => return None,
+            },
+            c => unescaped.push(c),
+        }
+    }
+    Some(unescaped)
+}
+
+fn pointer(segments: &[String]) -> String {
+    segments
+        .iter()
+        .map(|segment| format!("/{}", segment.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+fn array_index(path: &str, segment: &str, len: usize) -> Result<usize, PathError> {
+    if segment == "-" {
+        return Ok(len);
+    }
+    let valid = !segment.is_empty()
+        && segment.bytes().all(|c| c.is_ascii_digit())
+        && (segment == "0" || !segment.starts_with('0'));
+    match segment.parse() {
+        Ok(index) if valid => Ok(index),
+        _ => Err(PathError::Syntax {
+            path: path.to_string(),
+            message: format!("`{}` is not an array index", segment),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::blackboard::{Blackboard, BlackboardValue};
+    use crate::behavior_tree::json_state::{JsonAction, JsonRegistry, JsonState, PathError};
+    use crate::behavior_tree::loader::LoadedTree;
+    use crate::behavior_tree::registry::DynamicArgs;
+    use crate::behavior_tree::{Behavior, Response, TreeInstance};
+    use anyhow::anyhow;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    #[derive(Clone, Debug)]
+    struct Set {
+        path: String,
+        value: Value,
+    }
+
+    // adds `amount` to the number at `path`
+    #[derive(Clone, Debug)]
+    struct Add {
+        path: String,
+        amount: i64,
+    }
+
+    impl crate::behavior_tree::Actionable for Set {
+        type ActionError = anyhow::Error;
+        type ActionArgs = ();
+        type ActionState = JsonState;
+
+        async fn run(&self, _: &(), state: &mut JsonState) -> anyhow::Result<Response> {
+            state.set(&self.path, self.value.clone())?;
+            Ok(Response::Success)
+        }
+    }
+
+    impl crate::behavior_tree::Actionable for Add {
+        type ActionError = anyhow::Error;
+        type ActionArgs = ();
+        type ActionState = JsonState;
+
+        async fn run(&self, _: &(), state: &mut JsonState) -> anyhow::Result<Response> {
+            let current = match state.read(&self.path)? {
+                Some(BlackboardValue::Int(i)) => i,
+                other => return Err(anyhow!("`{}` is not an int: {:?}", self.path, other)),
+            };
+            state.set(&self.path, json!(current + self.amount))?;
+            Ok(Response::Success)
+        }
+    }
+
+    fn registry() -> Arc<JsonRegistry> {
+        let mut registry = JsonRegistry::new();
+        registry.register_factory("set", |payload| {
+            let path = payload["path"].as_str().ok_or("`path` is missing")?;
+            Ok(Arc::new(Set {
+                path: path.to_string(),
+                value: payload["value"].clone(),
+            }))
+        });
+        registry.register_factory("add", |payload| {
+            let path = payload["path"].as_str().ok_or("`path` is missing")?;
+            let amount = payload["amount"].as_i64().ok_or("`amount` is missing")?;
+            Ok(Arc::new(Add {
+                path: path.to_string(),
+                amount,
+            }))
+        });
+        Arc::new(registry)
+    }
+
+    #[tokio::test]
+    async fn test_run_dynamic_tree_on_json_state() {
+        let loaded: LoadedTree<JsonAction> =
+            LoadedTree::from_json(include_str!("../../fixtures/dynamic_refuel.json")).unwrap();
+        let mut instance = TreeInstance::from_loaded(loaded, Blackboard::new()).with_json_state();
+        let args = DynamicArgs {
+            registry: registry(),
+            args: (),
+        };
+        let mut state: JsonState =
+            serde_json::from_str(include_str!("../../fixtures/dynamic_refuel_state.json")).unwrap();
+
+        instance.run(&args, &mut state).await.unwrap();
+        assert_eq!(
+            state.into_value(),
+            json!({
+                "ship": {"name": "BOT-1", "fuel": 62, "cargo": ["ore"]},
+                "log": ["refueled", "checked BOT-1"],
+            })
+        );
+    }
+
+    #[test]
+    fn test_paths() {
+        let mut state =
+            JsonState::new(json!({"ship": {"fuel": 12, "a/b": {"~": true}}, "cargo": ["ore"]}));
+        assert_eq!(state.get("/ship/fuel"), Ok(Some(&json!(12))));
+        assert_eq!(state.get("/ship/a~1b/~0"), Ok(Some(&json!(true))));
+        assert_eq!(state.get("/cargo/0"), Ok(Some(&json!("ore"))));
+        assert_eq!(state.get("/cargo/1"), Ok(None));
+        assert_eq!(state.get("/ship/speed"), Ok(None));
+        assert_eq!(state.get("").unwrap(), Some(&state.0.clone()));
+
+        assert_eq!(state.set("/ship/fuel", json!(30)), Ok(Some(json!(12))));
+        assert_eq!(state.set("/route/next", json!("X1-B")), Ok(None));
+        assert_eq!(state.set("/cargo/-", json!("gas")), Ok(None));
+        assert_eq!(state.set("/cargo/0", json!("ice")), Ok(Some(json!("ore"))));
+        assert_eq!(
+            state.0,
+            json!({
+                "ship": {"fuel": 30, "a/b": {"~": true}},
+                "cargo": ["ice", "gas"],
+                "route": {"next": "X1-B"},
+            })
+        );
+
+        let syntax = |path: &str, message: &str| PathError::Syntax {
+            path: path.to_string(),
+            message: message.to_string(),
+        };
+        assert_eq!(
+            state.get("ship/fuel").unwrap_err(),
+            syntax("ship/fuel", "must be empty or start with `/`")
+        );
+        assert_eq!(
+            state.get("/ship/~2").unwrap_err(),
+            syntax(
+                "/ship/~2",
+                "segment `~2` has a `~` not followed by `0` or `1`"
+            )
+        );
+        assert_eq!(
+            state.set("/cargo/01", json!(1)).unwrap_err(),
+            syntax("/cargo/01", "`01` is not an array index")
+        );
+        assert_eq!(
+            state
+                .set("/ship/fuel/max", json!(1))
+                .unwrap_err()
+                .to_string(),
+            "cannot set `/ship/fuel/max`: `/ship/fuel` is not an object or array"
+        );
+        assert_eq!(
+            state.set("/cargo/5", json!(1)).unwrap_err().to_string(),
+            "cannot set `/cargo/5`: index 5 is past the end of an array of 2"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_comparisons_read_typed_values() {
+        let state = JsonState::new(
+            json!({"fuel": 12, "ratio": 0.5, "name": "BOT-1", "count": "10", "cargo": []}),
+        );
+        let check = |json: Value| {
+            let bt: Behavior<JsonAction> = serde_json::from_value(json).unwrap();
+            let mut state = state.clone();
+            async move {
+                let args = DynamicArgs {
+                    registry: registry(),
+                    args: (),
+                };
+                TreeInstance::new(bt)
+                    .with_json_state()
+                    .run(&args, &mut state)
+                    .await
+                    .map_err(|err| err.to_string())
+            }
+        };
+        let compare = |left: &str, op: &str, right: Value| {
+            check(
+                json!({"Compare": {"left": {"Accessor": left}, "op": op, "right": {"Literal": right}}}),
+            )
+        };
+
+        assert!(compare("/fuel", ">", json!(11.5)).await.is_ok());
+        assert!(compare("/ratio", "==", json!(0.5)).await.is_ok());
+        assert!(compare("/name", "==", json!("BOT-1")).await.is_ok());
+        assert!(check(
+            json!({"Expr": {"source": "state.fuel * state.ratio == 6 && state.name != 'X'"}})
+        )
+        .await
+        .is_ok());
+
+        assert_eq!(
+            compare("/count", "==", json!(10)).await,
+            Err("cannot compare /count() (\"10\") == 10 (10)".to_string())
+        );
+        assert_eq!(
+            compare("/cargo", "==", json!(0)).await,
+            Err("no accessor named `/cargo` is registered".to_string())
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use crate::behavior_tree::{Actionable, Behavior};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Makes the children of a `LazySequence` or `LazySelect` node when the node gets to them, so
+/// children that never run are never made.
+///
+/// A tree run starts again at the root on every tick, so a child that is `Running` is made again
+/// on the next tick. Generators should return the same child for the same index and state.
+pub trait ChildGenerator<A>: Send + Sync {
+    /// The child at `index`, `None` if the node has no more children.
+    fn child(&self, index: usize, state: &A::ActionState) -> Option<Behavior<A>>
+    where
+        A: Actionable;
+
+    /// Whether this only stands in for a generator lost converting the actions of the tree.
+    fn is_placeholder(&self) -> bool {
+        false
+    }
+}
+
+impl<A> fmt::Debug for dyn ChildGenerator<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ChildGenerator")
+    }
+}
+
+/// A generator calling `f` with the index of the child and the state.
+pub fn from_fn<A, F>(f: F) -> Arc<dyn ChildGenerator<A>>
+where
+    A: Actionable + 'static,
+    F: FnMut(usize, &A::ActionState) -> Option<Behavior<A>> + Send + 'static,
+{
+    Arc::new(FnGenerator(Mutex::new(f)))
+}
+
+struct FnGenerator<F>(Mutex<F>);
+
+impl<A, F> ChildGenerator<A> for FnGenerator<F>
+where
+    A: Actionable,
+    F: FnMut(usize, &A::ActionState) -> Option<Behavior<A>> + Send,
+{
+    fn child(&self, index: usize, state: &A::ActionState) -> Option<Behavior<A>> {
+        (self.0.lock().unwrap())(index, state)
+    }
+}
+
+// what `map_actions` leaves of a generator, which makes children of the old action type
+pub(crate) struct Unmapped;
+
+impl<A> ChildGenerator<A> for Unmapped {
+    fn child(&self, _: usize, _: &A::ActionState) -> Option<Behavior<A>>
+    where
+        A: Actionable,
+    {
+        None
+    }
+
+    fn is_placeholder(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::lazy;
+    use crate::behavior_tree::observer::{BehaviorObserver, TreeEvent};
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{Actionable, Behavior, NodePath, Response, TreeInstance};
+    use std::sync::{Arc, Mutex};
+
+    // visits a waypoint, failing at the ones listed as blocked
+    #[derive(Clone, Debug)]
+    struct Visit(String);
+
+    #[derive(Default)]
+    struct Route {
+        waypoints: Vec<String>,
+        blocked: Vec<String>,
+        visited: Vec<String>,
+    }
+
+    impl Actionable for Visit {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = Route;
+
+        async fn run(&self, _: &(), route: &mut Route) -> Result<Response, String> {
+            if route.blocked.contains(&self.0) {
+                return Ok(Response::Failure);
+            }
+            route.visited.push(self.0.clone());
+            Ok(Response::Success)
+        }
+    }
+
+    fn route(waypoints: &[&str], blocked: &[&str]) -> Route {
+        Route {
+            waypoints: waypoints.iter().map(|w| w.to_string()).collect(),
+            blocked: blocked.iter().map(|w| w.to_string()).collect(),
+            ..Route::default()
+        }
+    }
+
+    // a visit per waypoint of the route, noting the index of every child made
+    fn visits(generated: Arc<Mutex<Vec<usize>>>) -> Arc<dyn lazy::ChildGenerator<Visit>> {
+        lazy::from_fn(move |i, route: &Route| {
+            let waypoint = route.waypoints.get(i)?;
+            generated.lock().unwrap().push(i);
+            Some(Action(Visit(waypoint.clone())))
+        })
+    }
+
+    #[tokio::test]
+    async fn test_only_the_run_prefix_is_generated() {
+        let generated = Arc::new(Mutex::new(vec![]));
+        let bt = LazySequence(visits(generated.clone()));
+        let mut state = route(&["X1-A1", "X1-A2", "X1-A3", "X1-A4"], &["X1-A2"]);
+        assert_eq!(bt.run(&(), &mut state).await.unwrap(), Response::Failure);
+        assert_eq!(state.visited, ["X1-A1"]);
+        assert_eq!(*generated.lock().unwrap(), [0, 1]);
+
+        let mut state = route(&["X1-A1", "X1-A2"], &[]);
+        bt.run(&(), &mut state).await.unwrap();
+        assert_eq!(state.visited, ["X1-A1", "X1-A2"]);
+    }
+
+    type Events = Arc<Mutex<Vec<(NodePath, TreeEvent)>>>;
+
+    #[derive(Clone, Default)]
+    struct Recorder(Events);
+
+    impl<A> BehaviorObserver<A> for Recorder {
+        fn on_event(&mut self, path: &[usize], event: &TreeEvent) {
+            self.0.lock().unwrap().push((path.to_vec(), event.clone()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_select_falls_back_across_generated_children() {
+        let generated = Arc::new(Mutex::new(vec![]));
+        let bt: Behavior<Visit> = LazySelect(visits(generated.clone()));
+        let mut state = route(&["X1-A1", "X1-A2", "X1-A3", "X1-A4"], &["X1-A1", "X1-A2"]);
+        bt.run(&(), &mut state).await.unwrap();
+        assert_eq!(state.visited, ["X1-A3"]);
+        assert_eq!(*generated.lock().unwrap(), [0, 1, 2]);
+
+        let mut state = route(&["X1-A1"], &["X1-A1"]);
+        let result = bt.run(&(), &mut state).await.unwrap();
+        assert_eq!(result, Response::Failure);
+
+        // generated children run at the path of their index
+        let recorder = Recorder::default();
+        let bt = Sequence(vec![LazySequence(lazy::from_fn(
+            |i, _: &Route| -> Option<Behavior<Visit>> {
+                (i < 2).then(|| Named {
+                    name: format!("leg {}", i),
+                    when_disabled: Default::default(),
+                    child: Box::new(Throw {
+                        code: "E".to_string(),
+                        message: None,
+                    }),
+                })
+            },
+        ))]);
+        let mut instance = TreeInstance::new(bt).with_observer(recorder.clone());
+        instance.set_enabled("leg 0", false);
+        let result = instance.run(&(), &mut route(&[], &[])).await.unwrap();
+        assert_eq!(result, Response::Failure);
+        let paths: Vec<_> = recorder
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(p, _)| p.clone())
+            .collect();
+        assert_eq!(paths, [vec![0, 0]]);
+    }
+}
@@ -0,0 +1,1038 @@
+use crate::behavior_tree::catalog::ActionCatalog;
+use crate::behavior_tree::dataflow;
+use crate::behavior_tree::toggle::WhenDisabled;
+use crate::behavior_tree::{Behavior, Blackboard, NodePath};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LoadError {
+    #[error("invalid tree document: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error(
+        "blackboard key `{key}` used at {path:?} is neither declared in `blackboard` nor listed in `runtime_keys`"
+    )]
+    UndeclaredKey { key: String, path: NodePath },
+    #[error("invalid node at {path:?}: {message}")]
+    InvalidNode { message: String, path: NodePath },
+    #[error("pipeline stage `{stage}` at {path:?} reads `{key}`, which no earlier stage outputs and the blackboard doesn't declare")]
+    UnwiredInput {
+        stage: String,
+        key: String,
+        path: NodePath,
+    },
+    #[error("unknown actions: {}", describe_unknown(.0))]
+    UnknownActions(Vec<UnknownAction>),
+    // `chain` is the aliases being expanded, outermost first, ending with the unknown one
+    #[error("unknown alias `{}` (used via {})", .chain.last().map_or("", String::as_str), .chain.join(" -> "))]
+    UnknownAlias { chain: Vec<String> },
+    #[error("alias definitions are recursive: {}", .chain.join(" -> "))]
+    RecursiveAlias { chain: Vec<String> },
+    #[error("expanding aliases gives more than {MAX_EXPANDED_NODES} nodes")]
+    TooManyNodes,
+}
+
+/// An action in a tree file that doesn't deserialize into the action type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownAction {
+    // the variant name, or the whole payload if it has no recognizable name
+    pub name: String,
+    pub path: NodePath,
+    pub message: String,
+    // a known action with a similar name
+    pub suggestion: Option<String>,
+}
+
+fn describe_unknown(unknown: &[UnknownAction]) -> String {
+    unknown
+        .iter()
+        .map(|action| match &action.suggestion {
+            Some(suggestion) => format!(
+                "`{}` at {:?} (did you mean `{}`?)",
+                action.name, action.path, suggestion
+            ),
+            None => format!("`{}` at {:?}", action.name, action.path),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// What loading a tree does with actions the action type doesn't know.
+pub enum UnknownActions<A> {
+    // fail with a `LoadError::UnknownActions` listing all of them
+    Strict,
+    // replace each with what the function returns for the action's name, so the rest of the
+    // tree can still be inspected
+    Lenient(fn(&str) -> Behavior<A>),
+}
+
+impl<A> UnknownActions<A> {
+    /// Lenient handling with [`unknown_action_placeholder`] as the replacement.
+    pub fn lenient() -> Self {
+        UnknownActions::Lenient(unknown_action_placeholder)
+    }
+}
+
+/// How forgiving [`LoadedTree::from_json_with`] is with trees written for other versions.
+pub struct LoadOptions<A> {
+    pub unknown_actions: UnknownActions<A>,
+    // keep node kinds this version doesn't know as `Behavior::Opaque` instead of failing
+    pub keep_unknown_nodes: bool,
+}
+
+impl<A> LoadOptions<A> {
+    /// Loads whatever can be loaded, for inspecting and rendering trees.
+    pub fn lenient() -> Self {
+        Self {
+            unknown_actions: UnknownActions::lenient(),
+            keep_unknown_nodes: true,
+        }
+    }
+}
+
+impl<A> Default for LoadOptions<A> {
+    fn default() -> Self {
+        Self {
+            unknown_actions: UnknownActions::Strict,
+            keep_unknown_nodes: false,
+        }
+    }
+}
+
+/// Something that doesn't stop a tree from loading but likely stops it from running as intended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadWarning {
+    UnknownNodeKind { kind: String, path: NodePath },
+}
+
+impl fmt::Display for LoadWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadWarning::UnknownNodeKind { kind, path } => {
+                write!(
+                    f,
+                    "node kind `{}` at {:?} is unknown and fails when run",
+                    kind, path
+                )
+            }
+        }
+    }
+}
+
+/// An `AlwaysFail` named after the action it replaces.
+pub fn unknown_action_placeholder<A>(name: &str) -> Behavior<A> {
+    Behavior::Named {
+        name: name.to_string(),
+        when_disabled: WhenDisabled::Fail,
+        child: Box::new(Behavior::AlwaysFail),
+    }
+}
+
+// `"Wave"` and `{"Wave": {...}}` are both named `Wave`
+pub(crate) fn action_name(payload: &Value) -> String {
+    match node_kind(payload) {
+        Some(name) => name.to_string(),
+        None => payload.to_string(),
+    }
+}
+
+// the keys of the `Behavior` variants, keep in sync with it
+pub(crate) const NODE_KINDS: &[&str] = &[
+    "Action",
+    "Invert",
+    "Select",
+    "Sequence",
+    "ReactiveSequence",
+    "ReactiveSelect",
+    "While",
+    "AdaptiveSelect",
+    "RandomSelect",
+    "WeightedSelect",
+    "Parallel",
+    "Experiment",
+    "Pipeline",
+    "CheckKey",
+    "SetKey",
+    "OnChanged",
+    "Expr",
+    "Compare",
+    "Log",
+    "Throw",
+    "TryCatch",
+    "SleepUntil",
+    "Schedule",
+    "GracefulTimeout",
+    "StallGuard",
+    "Retry",
+    "Repeat",
+    "Timeout",
+    "Cooldown",
+    "Jitter",
+    "Breakpoint",
+    "Named",
+    "Namespaced",
+    "AlwaysFail",
+    "Spawn",
+    "Join",
+    "Subtree",
+    "Decorated",
+    "Composite",
+    "Opaque",
+    "Assert",
+];
+
+// the variant name of an externally tagged value: `"Kind"` or `{"Kind": ...}`
+pub(crate) fn node_kind(node: &Value) -> Option<&str> {
+    match node {
+        Value::String(kind) => Some(kind),
+        Value::Object(map) if map.len() == 1 => map.keys().next().map(String::as_str),
+        _ => None,
+    }
+}
+
+fn is_known_node(value: &Value) -> bool {
+    value.is_object() && node_kind(value).is_some_and(|kind| NODE_KINDS.contains(&kind))
+}
+
+// calls `f` with every child node of a node of a known kind, in path order
+fn for_each_child(node: &mut Value, f: &mut impl FnMut(&mut Value)) {
+    let Value::Object(map) = node else {
+        return;
+    };
+    let Some((kind, content)) = map.iter_mut().next() else {
+        return;
+    };
+    let items = |value: Option<&mut Value>, f: &mut dyn FnMut(&mut Value)| {
+        if let Some(Value::Array(items)) = value {
+            items.iter_mut().for_each(f);
+        }
+    };
+    match kind.as_str() {
+        "Invert" => f(content),
+        "Select" | "Sequence" | "ReactiveSequence" | "ReactiveSelect" | "RandomSelect" => {
+            items(Some(content), f)
+        }
+        // `[weight, child]` pairs
+        "WeightedSelect" => items(Some(content), &mut |pair| {
+            pair.get_mut(1).into_iter().for_each(&mut *f)
+        }),
+        "AdaptiveSelect" | "Parallel" | "Composite" | "Opaque" => {
+            items(content.get_mut("children"), f)
+        }
+        "Named" | "Namespaced" | "Decorated" | "Spawn" | "GracefulTimeout" | "StallGuard"
+        | "Retry" | "Repeat" | "Timeout" | "Cooldown" => {
+            content.get_mut("child").into_iter().for_each(f)
+        }
+        "Assert" => content.get_mut("condition").into_iter().for_each(f),
+        "Experiment" => items(content.get_mut("variants"), &mut |variant| {
+            variant.get_mut("branch").into_iter().for_each(&mut *f)
+        }),
+        "Pipeline" => items(Some(content), &mut |stage| {
+            stage.get_mut("branch").into_iter().for_each(&mut *f)
+        }),
+        "While" => {
+            content.get_mut("condition").into_iter().for_each(&mut *f);
+            content.get_mut("action").into_iter().for_each(f);
+        }
+        "TryCatch" => {
+            content.get_mut("try").into_iter().for_each(&mut *f);
+            items(content.get_mut("catch"), &mut |clause| {
+                clause.get_mut("branch").into_iter().for_each(&mut *f)
+            });
+        }
+        _ => {}
+    }
+}
+
+// replaces each node of an unknown kind with an `Opaque` node keeping it as `raw`
+fn wrap_unknown_nodes(node: &mut Value) {
+    match node_kind(node) {
+        Some(kind) if !NODE_KINDS.contains(&kind) => {
+            let kind = kind.to_string();
+            let raw = node.take();
+            let mut children = vec![];
+            if let Value::Object(map) = &raw {
+                map.values()
+                    .for_each(|v| find_known_nodes(v, &mut children));
+            }
+            children.iter_mut().for_each(wrap_unknown_nodes);
+            *node = json!({ "Opaque": { "kind": kind, "raw": raw, "children": children } });
+        }
+        Some(_) => for_each_child(node, &mut wrap_unknown_nodes),
+        None => {}
+    }
+}
+
+fn find_known_nodes(value: &Value, found: &mut Vec<Value>) {
+    if is_known_node(value) {
+        found.push(value.clone());
+        return;
+    }
+    match value {
+        Value::Array(items) => items.iter().for_each(|v| find_known_nodes(v, found)),
+        Value::Object(map) => map.values().for_each(|v| find_known_nodes(v, found)),
+        _ => {}
+    }
+}
+
+// the inverse of `wrap_unknown_nodes`
+pub(crate) fn unwrap_opaque_nodes(node: &mut Value) {
+    if node_kind(node) == Some("Opaque") {
+        if let Some(raw) = node["Opaque"].get_mut("raw") {
+            *node = raw.take();
+        }
+        return;
+    }
+    for_each_child(node, &mut unwrap_opaque_nodes)
+}
+
+/// A tree loaded from a tree file, together with the blackboard values it starts with.
+///
+/// The file format is
+/// ```json
+/// {
+///   "blackboard": { "max_price": 120, "home_system": "X1-ABC" },
+///   "runtime_keys": ["current_waypoint"],
+///   "defs": { "refuel_if_needed": { "Select": [...] } },
+///   "tree": { "Sequence": [{ "Use": "refuel_if_needed" }, ...] }
+/// }
+/// ```
+/// where `blackboard` and `runtime_keys` are optional. `runtime_keys` lists keys the host sets at
+/// runtime, so nodes may reference them without a starting value. An optional
+/// `required_capabilities` list names what the host has to provide to run the tree, see
+/// [`LoadedTree::check_compat_with`].
+///
+/// `defs` names subtrees that `{"Use": name}` nodes anywhere in the tree or in other
+/// definitions stand for. Uses are replaced by copies of their definitions when the tree is
+/// loaded, so [`behavior`](Self::behavior) holds the expanded tree; the definitions are kept as
+/// written for [`to_json_collapsed`](Self::to_json_collapsed).
+///
+/// Documents may nest at most 128 levels deep, the recursion limit of `serde_json`; a node takes
+/// one or two levels. Deeper documents are a [`LoadError::Json`] rather than a stack overflow.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadedTree<A> {
+    #[serde(default, skip_serializing_if = "Blackboard::is_empty")]
+    pub blackboard: Blackboard,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub runtime_keys: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required_capabilities: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub defs: BTreeMap<String, Value>,
+    #[serde(rename = "tree")]
+    pub behavior: Behavior<A>,
+}
+
+impl<A> LoadedTree<A>
+where
+    A: DeserializeOwned,
+{
+    pub fn from_json(json: &str) -> Result<Self, LoadError> {
+        let mut document: Value = serde_json::from_str(json)?;
+        expand_aliases(&mut document)?;
+        let loaded: LoadedTree<A> = serde_json::from_value(document)?;
+        loaded.validate()?;
+        Ok(loaded)
+    }
+
+    /// Like [`from_json`](Self::from_json), with `options` deciding what happens to parts of the
+    /// tree this version or `A` doesn't know.
+    pub fn from_json_with(json: &str, options: LoadOptions<A>) -> Result<Self, LoadError> {
+        let mut document: Value = serde_json::from_str(json)?;
+        expand_aliases(&mut document)?;
+        if options.keep_unknown_nodes {
+            if let Some(tree) = document.get_mut("tree") {
+                wrap_unknown_nodes(tree);
+            }
+        }
+        let untyped: UntypedTree = serde_json::from_value(document)?;
+        untyped.validate()?;
+
+        let unknown = options.unknown_actions;
+        let catalog = ActionCatalog::of::<A>();
+        let mut found = vec![];
+        let behavior = untyped
+            .behavior
+            .map_actions(&mut |path, payload| match A::deserialize(&payload) {
+                Ok(action) => Behavior::Action(action),
+                Err(err) => {
+                    let name = action_name(&payload);
+                    let replacement = match &unknown {
+                        UnknownActions::Strict => Behavior::AlwaysFail,
+                        UnknownActions::Lenient(placeholder) => placeholder(&name),
+                    };
+                    found.push(UnknownAction {
+                        suggestion: catalog.suggest(&name).map(str::to_string),
+                        name,
+                        path: path.to_vec(),
+                        message: err.to_string(),
+                    });
+                    replacement
+                }
+            });
+        if matches!(unknown, UnknownActions::Strict) && !found.is_empty() {
+            return Err(LoadError::UnknownActions(found));
+        }
+        Ok(LoadedTree {
+            blackboard: untyped.blackboard,
+            runtime_keys: untyped.runtime_keys,
+            required_capabilities: untyped.required_capabilities,
+            defs: untyped.defs,
+            behavior,
+        })
+    }
+}
+
+/// Why a tree can't run with a given action type, from [`LoadedTree::check_compat`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Incompatibility {
+    // the action payload at `path` doesn't deserialize into the action type
+    Action { path: NodePath, message: String },
+    MissingCapability(String),
+}
+
+impl fmt::Display for Incompatibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Incompatibility::Action { path, message } => {
+                write!(f, "action at {:?} is incompatible: {}", path, message)
+            }
+            Incompatibility::MissingCapability(name) => {
+                write!(f, "required capability `{}` is not provided", name)
+            }
+        }
+    }
+}
+
+/// A tree whose actions are kept as raw JSON, so it can be checked against an action type before
+/// it is used with it.
+pub type UntypedTree = LoadedTree<Value>;
+
+impl UntypedTree {
+    /// Checks that every action payload deserializes into `A`, reporting all that don't.
+    pub fn check_compat<A: DeserializeOwned>(&self) -> Result<(), Vec<Incompatibility>> {
+        into_result(self.action_incompatibilities::<A>())
+    }
+
+    /// Like [`check_compat`](Self::check_compat), but also checks that every capability the file
+    /// requires is in `capabilities`.
+    pub fn check_compat_with<A: DeserializeOwned>(
+        &self,
+        capabilities: &[&str],
+    ) -> Result<(), Vec<Incompatibility>> {
+        let mut problems = self.action_incompatibilities::<A>();
+        for required in &self.required_capabilities {
+            if !capabilities.contains(&required.as_str()) {
+                problems.push(Incompatibility::MissingCapability(required.clone()));
+            }
+        }
+        into_result(problems)
+    }
+
+    fn action_incompatibilities<A: DeserializeOwned>(&self) -> Vec<Incompatibility> {
+        let mut problems = vec![];
+        self.behavior.walk(&mut |path, node| {
+            if let Behavior::Action(payload) = node {
+                if let Err(err) = A::deserialize(payload) {
+                    problems.push(Incompatibility::Action {
+                        path: path.to_vec(),
+                        message: err.to_string(),
+                    });
+                }
+            }
+        });
+        problems
+    }
+
+    /// Converts the tree to one with typed actions, failing on the first incompatible action.
+    pub fn into_typed<A: DeserializeOwned>(self) -> Result<LoadedTree<A>, LoadError> {
+        Ok(serde_json::from_value(serde_json::to_value(self)?)?)
+    }
+}
+
+fn into_result(problems: Vec<Incompatibility>) -> Result<(), Vec<Incompatibility>> {
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems)
+    }
+}
+
+impl<A: Serialize> LoadedTree<A> {
+    /// Writes the tree in the tree file format. `Opaque` nodes are written back as they were read.
+    pub fn to_json(&self) -> Result<String, LoadError> {
+        let mut document = serde_json::to_value(self)?;
+        if let Some(tree) = document.get_mut("tree") {
+            unwrap_opaque_nodes(tree);
+        }
+        Ok(serde_json::to_string(&document)?)
+    }
+
+    /// Like [`to_json`](Self::to_json), with every subtree that is a copy of a definition in
+    /// `defs` written as a `Use` of it again.
+    pub fn to_json_collapsed(&self) -> Result<String, LoadError> {
+        let mut document = serde_json::to_value(self)?;
+        let mut aliases = Aliases::new(&self.defs);
+        let mut defs = Map::new();
+        for name in self.defs.keys() {
+            let (def, _) = aliases.resolve(name)?;
+            // written the way the tree is, with defaults filled in
+            let def: Behavior<Value> = serde_json::from_value(def)?;
+            defs.insert(name.clone(), serde_json::to_value(def)?);
+        }
+        if let Some(tree) = document.get_mut("tree") {
+            unwrap_opaque_nodes(tree);
+            collapse_uses(tree, &defs);
+        }
+        Ok(serde_json::to_string(&document)?)
+    }
+}
+
+// the most nodes the tree may have once its uses are expanded, so that definitions using each
+// other several times can't blow up
+const MAX_EXPANDED_NODES: usize = 100_000;
+
+// definitions expanded so far, and the ones being expanded
+struct Aliases<'a> {
+    defs: &'a BTreeMap<String, Value>,
+    expanded: BTreeMap<String, (Value, usize)>,
+    chain: Vec<String>,
+    nodes: usize,
+}
+
+impl<'a> Aliases<'a> {
+    fn new(defs: &'a BTreeMap<String, Value>) -> Self {
+        Self {
+            defs,
+            expanded: BTreeMap::new(),
+            chain: vec![],
+            nodes: 0,
+        }
+    }
+
+    // the definition of `name` with its own uses expanded, and its node count
+    fn resolve(&mut self, name: &str) -> Result<(Value, usize), LoadError> {
+        if let Some(expanded) = self.expanded.get(name) {
+            return Ok(expanded.clone());
+        }
+        let mut chain = self.chain.clone();
+        chain.push(name.to_string());
+        if self.chain.iter().any(|used| used == name) {
+            return Err(LoadError::RecursiveAlias { chain });
+        }
+        let Some(def) = self.defs.get(name) else {
+            return Err(LoadError::UnknownAlias { chain });
+        };
+        let mut def = def.clone();
+        let outer = std::mem::replace(&mut self.chain, chain);
+        let before = std::mem::take(&mut self.nodes);
+        let expanded = expand_uses(&mut def, self);
+        let nodes = std::mem::replace(&mut self.nodes, before);
+        self.chain = outer;
+        expanded?;
+        self.expanded.insert(name.to_string(), (def.clone(), nodes));
+        Ok((def, nodes))
+    }
+}
+
+// replaces the `Use` nodes of the tree with their definitions, also checking the definitions
+// that aren't used
+fn expand_aliases(document: &mut Value) -> Result<(), LoadError> {
+    let defs: BTreeMap<String, Value> = match document.get("defs") {
+        Some(defs) => serde_json::from_value(defs.clone())?,
+        None => return Ok(()),
+    };
+    let mut aliases = Aliases::new(&defs);
+    for name in defs.keys() {
+        aliases.resolve(name)?;
+    }
+    match document.get_mut("tree") {
+        Some(tree) => expand_uses(tree, &mut aliases),
+        None => Ok(()),
+    }
+}
+
+fn expand_uses(node: &mut Value, aliases: &mut Aliases) -> Result<(), LoadError> {
+    if let Some(name) = use_target(node) {
+        let (def, nodes) = aliases.resolve(&name)?;
+        aliases.nodes += nodes;
+        *node = def;
+    } else {
+        aliases.nodes += 1;
+        let mut result = Ok(());
+        for_each_child(node, &mut |child| {
+            if result.is_ok() {
+                result = expand_uses(child, aliases);
+            }
+        });
+        result?;
+    }
+    if aliases.nodes > MAX_EXPANDED_NODES {
+        return Err(LoadError::TooManyNodes);
+    }
+    Ok(())
+}
+
+// the alias a `{"Use": name}` node refers to
+fn use_target(node: &Value) -> Option<String> {
+    match node {
+        Value::Object(map) if map.len() == 1 => map.get("Use")?.as_str().map(str::to_string),
+        _ => None,
+    }
+}
+
+// the inverse of `expand_uses`, for subtrees equal to an expanded definition
+fn collapse_uses(node: &mut Value, defs: &Map<String, Value>) {
+    if let Some((name, _)) = defs.iter().find(|(_, def)| *def == node) {
+        *node = json!({ "Use": name });
+        return;
+    }
+    for_each_child(node, &mut |child| collapse_uses(child, defs))
+}
+
+impl<A> LoadedTree<A> {
+    /// Nodes the tree loaded with that will fail when run.
+    pub fn warnings(&self) -> Vec<LoadWarning> {
+        let mut warnings = vec![];
+        self.behavior.walk(&mut |path, node| {
+            if let Behavior::Opaque { kind, .. } = node {
+                warnings.push(LoadWarning::UnknownNodeKind {
+                    kind: kind.clone(),
+                    path: path.to_vec(),
+                });
+            }
+        });
+        warnings
+    }
+
+    /// Checks that every node is configured correctly and that every blackboard key the tree
+    /// reads is either declared, runtime-provided or written by a node of the tree.
+    pub fn validate(&self) -> Result<(), LoadError> {
+        let mut written = vec![];
+        self.behavior
+            .walk(&mut |_, node| written.extend(node.written_keys()));
+        let mut first_error = None;
+        self.behavior.walk(&mut |path, node| {
+            if let Some(message) = node.config_error() {
+                if first_error.is_none() {
+                    first_error = Some(LoadError::InvalidNode {
+                        message,
+                        path: path.to_vec(),
+                    });
+                }
+            }
+            let provided = |key: &str| {
+                self.blackboard.contains_key(key) || self.runtime_keys.iter().any(|k| k == key)
+            };
+            // a stage only gets what the blackboard holds and what the stages before it output
+            if let Behavior::Pipeline(stages) = node {
+                if let Some((stage, key)) = dataflow::unwired_input(stages, provided) {
+                    first_error.get_or_insert(LoadError::UnwiredInput {
+                        stage: stage.to_string(),
+                        key: key.to_string(),
+                        path: path.to_vec(),
+                    });
+                }
+            }
+            for key in node.referenced_keys() {
+                let declared = provided(key) || written.contains(&key);
+                if !declared && first_error.is_none() {
+                    first_error = Some(LoadError::UndeclaredKey {
+                        key: key.to_string(),
+                        path: path.to_vec(),
+                    });
+                }
+            }
+        });
+        match first_error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::blackboard::BlackboardValue;
+    use crate::behavior_tree::loader::{
+        Incompatibility, LoadError, LoadOptions, LoadWarning, LoadedTree, UnknownAction,
+        UntypedTree,
+    };
+    use crate::behavior_tree::{Actionable, Behavior, Blackboard, Response, TreeInstance};
+    use serde::{Deserialize, Serialize};
+    use serde_json::{json, Value};
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    enum MyAction {
+        Buy,
+    }
+
+    impl Actionable for MyAction {
+        type ActionError = anyhow::Error;
+        type ActionArgs = ();
+        type ActionState = u32;
+
+        async fn run(
+            &self,
+            _args: &Self::ActionArgs,
+            state: &mut Self::ActionState,
+        ) -> Result<Response, Self::ActionError> {
+            match self {
+                MyAction::Buy => {
+                    *state += 1;
+                    Ok(Response::Success)
+                }
+            }
+        }
+    }
+
+    const TRADE_ROUTE: &str = include_str!("../../fixtures/trade_route.json");
+
+    #[test]
+    fn test_load_initial_blackboard() {
+        let loaded: LoadedTree<MyAction> = LoadedTree::from_json(TRADE_ROUTE).unwrap();
+
+        assert_eq!(
+            loaded.blackboard.get("max_price"),
+            Some(&BlackboardValue::Int(120))
+        );
+        assert_eq!(loaded.runtime_keys, vec!["current_waypoint".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_check_key_against_file_value() {
+        let loaded: LoadedTree<MyAction> = LoadedTree::from_json(TRADE_ROUTE).unwrap();
+        let mut instance = TreeInstance::from_loaded(loaded, Blackboard::new());
+
+        let mut bought = 0;
+        instance.run(&(), &mut bought).await.unwrap();
+        assert_eq!(bought, 1);
+    }
+
+    #[tokio::test]
+    async fn test_overrides_win_over_file_values() {
+        let loaded: LoadedTree<MyAction> = LoadedTree::from_json(TRADE_ROUTE).unwrap();
+        let overrides = Blackboard::from_iter([("home_system", "X1-XYZ")]);
+        let mut instance = TreeInstance::from_loaded(loaded, overrides);
+
+        assert_eq!(
+            instance.blackboard().get("home_system"),
+            Some(&BlackboardValue::from("X1-XYZ"))
+        );
+        assert_eq!(
+            instance.blackboard().get("max_price"),
+            Some(&BlackboardValue::Int(120))
+        );
+
+        let mut bought = 0;
+        let result = instance.run(&(), &mut bought).await.unwrap();
+        assert_eq!(result, Response::Failure);
+        assert_eq!(bought, 0);
+    }
+
+    #[test]
+    fn test_undeclared_key_is_rejected() {
+        let json = r#"{
+            "blackboard": {"max_price": 120},
+            "tree": {"Sequence": [{"Action": "Buy"}, {"CheckKey": {"key": "home_system", "value": "X1-ABC"}}]}
+        }"#;
+
+        let err = LoadedTree::<MyAction>::from_json(json).unwrap_err();
+        match err {
+            LoadError::UndeclaredKey { key, path } => {
+                assert_eq!(key, "home_system");
+                assert_eq!(path, vec![1]);
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn test_jitter_bounds_are_validated() {
+        let json = r#"{"tree": {"Sequence": [{"Jitter": {"min": 500, "max": 100}}]}}"#;
+
+        let err = LoadedTree::<MyAction>::from_json(json).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid node at [0]: jitter min 500ms is greater than max 100ms"
+        );
+    }
+
+    const RENAMED_ACTION: &str = include_str!("../../fixtures/renamed_action.json");
+
+    #[test]
+    fn test_check_compat_reports_renamed_action() {
+        let tree = UntypedTree::from_json(RENAMED_ACTION).unwrap();
+
+        assert_eq!(
+            tree.check_compat::<MyAction>(),
+            Err(vec![Incompatibility::Action {
+                path: vec![1, 1],
+                message: "unknown variant `Purchase`, expected `Buy`".to_string(),
+            }])
+        );
+        assert!(tree.clone().into_typed::<MyAction>().is_err());
+    }
+
+    #[test]
+    fn test_check_compat_passes_clean_tree() {
+        let tree = UntypedTree::from_json(TRADE_ROUTE).unwrap();
+
+        assert_eq!(tree.check_compat::<MyAction>(), Ok(()));
+        assert_eq!(tree.check_compat_with::<MyAction>(&[]), Ok(()));
+        let typed = tree.into_typed::<MyAction>().unwrap();
+        assert_eq!(typed.runtime_keys, vec!["current_waypoint".to_string()]);
+    }
+
+    #[test]
+    fn test_check_compat_with_capabilities() {
+        let tree = UntypedTree::from_json(RENAMED_ACTION).unwrap();
+
+        let problems = tree
+            .check_compat_with::<MyAction>(&["navigation"])
+            .unwrap_err();
+        assert_eq!(
+            problems.last(),
+            Some(&Incompatibility::MissingCapability("market".to_string()))
+        );
+        assert_eq!(problems.len(), 2);
+        assert_eq!(
+            tree.check_compat_with::<MyAction>(&["market"])
+                .unwrap_err()
+                .len(),
+            1
+        );
+    }
+
+    const UNKNOWN_ACTIONS: &str = include_str!("../../fixtures/unknown_actions.json");
+
+    #[test]
+    fn test_strict_mode_lists_all_unknown_actions() {
+        let err = LoadedTree::<MyAction>::from_json_with(UNKNOWN_ACTIONS, LoadOptions::default())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown actions: `Wav` at [0], `Sell` at [1, 1]"
+        );
+        let LoadError::UnknownActions(unknown) = err else {
+            panic!("unexpected error: {}", err);
+        };
+        assert_eq!(
+            unknown[0],
+            UnknownAction {
+                name: "Wav".to_string(),
+                path: vec![0],
+                message: "unknown variant `Wav`, expected `Buy`".to_string(),
+                suggestion: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_strict_mode_suggests_known_actions() {
+        let json = r#"{"tree": {"Sequence": [{"Action": "Buy"}, {"Action": "Byu"}]}}"#;
+
+        let err = LoadedTree::<MyAction>::from_json_with(json, LoadOptions::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown actions: `Byu` at [1] (did you mean `Buy`?)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lenient_mode_replaces_unknown_actions() {
+        let loaded =
+            LoadedTree::<MyAction>::from_json_with(UNKNOWN_ACTIONS, LoadOptions::lenient())
+                .unwrap();
+
+        let mut names = vec![];
+        loaded.behavior.walk(&mut |path, node| {
+            if let Behavior::Named { name, child, .. } = node {
+                assert!(matches!(**child, Behavior::AlwaysFail));
+                names.push((path.to_vec(), name.clone()));
+            }
+        });
+        assert_eq!(
+            names,
+            vec![
+                (vec![0], "Wav".to_string()),
+                (vec![1, 1], "Sell".to_string())
+            ]
+        );
+
+        // the known `Buy` still runs before the placeholder fails the sequence
+        let mut instance = TreeInstance::from_loaded(loaded, Blackboard::new());
+        let mut bought = 0;
+        let result = instance.run(&(), &mut bought).await.unwrap();
+        assert_eq!(result, Response::Failure);
+        assert_eq!(bought, 1);
+    }
+
+    const UNKNOWN_NODE_KIND: &str = include_str!("../../fixtures/unknown_node_kind.json");
+
+    #[test]
+    fn test_unknown_node_kind_round_trips() {
+        let loaded =
+            LoadedTree::<MyAction>::from_json_with(UNKNOWN_NODE_KIND, LoadOptions::lenient())
+                .unwrap();
+
+        assert_eq!(loaded.to_json().unwrap(), UNKNOWN_NODE_KIND.trim_end());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_node_kind_is_kept_as_opaque() {
+        let loaded =
+            LoadedTree::<MyAction>::from_json_with(UNKNOWN_NODE_KIND, LoadOptions::lenient())
+                .unwrap();
+
+        assert_eq!(
+            loaded.warnings(),
+            vec![LoadWarning::UnknownNodeKind {
+                kind: "Swarm".to_string(),
+                path: vec![0],
+            }]
+        );
+        let Behavior::Sequence(children) = &loaded.behavior else {
+            panic!("unexpected tree: {:?}", loaded.behavior);
+        };
+        let opaque = &children[0];
+        assert_eq!(opaque.children().len(), 2);
+        assert!(matches!(
+            opaque.children()[0],
+            Behavior::Action(MyAction::Buy)
+        ));
+
+        let mut bought = 0;
+        let err = opaque.run(&(), &mut bought).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "node kind `Swarm` is unknown to this version and can't run"
+        );
+        assert_eq!(bought, 0);
+    }
+
+    #[test]
+    fn test_unknown_node_kind_fails_by_default() {
+        let err = LoadedTree::<MyAction>::from_json(UNKNOWN_NODE_KIND).unwrap_err();
+        assert!(matches!(err, LoadError::Json(_)));
+        assert!(err.to_string().contains("unknown variant `Swarm`"));
+    }
+
+    #[test]
+    fn test_deeply_nested_trees_are_rejected() {
+        let nested = |depth: usize| {
+            let tree = format!(
+                "{}\"AlwaysFail\"{}",
+                r#"{"Invert":"#.repeat(depth),
+                "}".repeat(depth)
+            );
+            format!(r#"{{"tree": {}}}"#, tree)
+        };
+        for parse in [
+            |json: &str| UntypedTree::from_json(json).map(|_| ()),
+            |json: &str| UntypedTree::from_json_with(json, LoadOptions::lenient()).map(|_| ()),
+        ] {
+            parse(&nested(120)).unwrap();
+            let err = parse(&nested(100_000)).unwrap_err();
+            assert!(
+                err.to_string().contains("recursion limit exceeded"),
+                "{}",
+                err
+            );
+        }
+    }
+
+    const REFUEL_DEFS: &str = include_str!("../../fixtures/refuel_defs.json");
+
+    #[test]
+    fn test_uses_expand_to_their_definitions() {
+        let loaded = UntypedTree::from_json(REFUEL_DEFS).unwrap();
+        let refuel_if_needed = json!({"Select": [
+            {"Invert": {"CheckKey": {"key": "fuel", "value": "low"}}},
+            {"Sequence": [{"Action": "Dock"}, {"Action": "Refuel"}]},
+        ]});
+        let navigate = |to: &str| json!({"Action": {"Navigate": to}});
+        let expanded: Behavior<Value> = serde_json::from_value(json!({"Sequence": [
+            navigate("X1-A1"),
+            refuel_if_needed,
+            navigate("X1-B2"),
+            refuel_if_needed,
+            navigate("X1-C3"),
+            refuel_if_needed,
+        ]}))
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&loaded.behavior).unwrap(),
+            serde_json::to_value(&expanded).unwrap()
+        );
+
+        let document: Value = serde_json::from_str(REFUEL_DEFS).unwrap();
+        let collapsed: Value = serde_json::from_str(&loaded.to_json_collapsed().unwrap()).unwrap();
+        assert_eq!(collapsed["tree"], document["tree"]);
+        assert_eq!(collapsed["defs"], document["defs"]);
+    }
+
+    #[test]
+    fn test_bad_aliases_are_rejected() {
+        let load = |defs: Value, tree: Value| {
+            let document = json!({"defs": defs, "tree": tree});
+            UntypedTree::from_json(&document.to_string()).unwrap_err()
+        };
+        let err = load(
+            json!({"refuel": {"Sequence": [{"Use": "dock"}]}}),
+            json!({"Use": "refuel"}),
+        );
+        assert_eq!(
+            err.to_string(),
+            "unknown alias `dock` (used via refuel -> dock)"
+        );
+        let err = load(json!({}), json!({"Invert": {"Use": "refuel"}}));
+        assert_eq!(err.to_string(), "unknown alias `refuel` (used via refuel)");
+
+        let err = load(
+            json!({
+                "dock": {"Sequence": [{"Action": "Dock"}, {"Use": "refuel"}]},
+                "refuel": {"Select": [{"Use": "dock"}]},
+            }),
+            json!({"Action": "Wait"}),
+        );
+        assert_eq!(
+            err.to_string(),
+            "alias definitions are recursive: dock -> refuel -> dock"
+        );
+
+        // each level uses the one below it twice
+        let defs: serde_json::Map<String, Value> = (0..20)
+            .map(|i| {
+                let below = json!({"Use": format!("level{}", i + 1)});
+                (format!("level{}", i), json!({"Sequence": [below, below]}))
+            })
+            .chain([("level20".to_string(), json!({"Action": "Wait"}))])
+            .collect();
+        let err = load(Value::Object(defs), json!({"Use": "level0"}));
+        assert_eq!(
+            err.to_string(),
+            "expanding aliases gives more than 100000 nodes"
+        );
+    }
+
+    #[test]
+    fn test_fixtures_round_trip() {
+        let fixtures = [
+            TRADE_ROUTE,
+            RENAMED_ACTION,
+            UNKNOWN_ACTIONS,
+            UNKNOWN_NODE_KIND,
+            REFUEL_DEFS,
+        ];
+        for fixture in fixtures {
+            let loaded = UntypedTree::from_json_with(fixture, LoadOptions::lenient()).unwrap();
+            let json = loaded.to_json().unwrap();
+            let reloaded = UntypedTree::from_json_with(&json, LoadOptions::lenient()).unwrap();
+            assert_eq!(reloaded.to_json().unwrap(), json);
+        }
+    }
+}
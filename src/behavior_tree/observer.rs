@@ -0,0 +1,473 @@
+#[cfg(feature = "serde")]
+use crate::behavior_tree::audit::StateChange;
+use crate::behavior_tree::toggle::WhenDisabled;
+use crate::behavior_tree::{
+    describe_children, Actionable, Behavior, BehaviorError, NodePath, Response,
+};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "tracing")]
+use std::collections::HashMap;
+#[cfg(feature = "tracing")]
+use std::fmt::Debug;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+/// Something noteworthy that happened while a tree was running.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum TreeEvent {
+    LogEmitted {
+        level: LogLevel,
+        message: String,
+    },
+    // `label` is `None` for path breakpoints set on the `DebugController`
+    BreakpointHit {
+        label: Option<String>,
+        path: NodePath,
+    },
+    Thrown {
+        code: String,
+        message: Option<String>,
+    },
+    // the delay a `Jitter` node drew before sleeping
+    JitterChosen {
+        delay: Duration,
+    },
+    // the variant an `Experiment` runs for `unit`, the value of its key
+    VariantAssigned {
+        unit: String,
+        variant: String,
+    },
+    // the node was disabled at runtime and resulted in `outcome` without running
+    SkippedDisabled {
+        outcome: WhenDisabled,
+    },
+    // a `requires` decorator got one of its resources; `waited` is zero if it was free
+    ResourceAcquired {
+        resource: String,
+        waited: Duration,
+    },
+    // a `Pipeline` stage wrote `key` without declaring it as an output; the write was undone
+    UndeclaredWrite {
+        stage: String,
+        key: String,
+    },
+    // a reactive node stopped its running child `child` because a child before it decided it
+    Preempted {
+        child: usize,
+    },
+    // the child of a `Spawn` node finished on its task; `id` is the one stored on the blackboard
+    SpawnFinished {
+        id: i64,
+        error: Option<String>,
+    },
+    // the action at `path` under a `StallGuard` sent no heartbeat for `last_heartbeat_age`
+    Stalled {
+        path: NodePath,
+        last_heartbeat_age: Duration,
+    },
+    // what an action changed in the state of an audited instance; not sent if it changed nothing
+    #[cfg(feature = "serde")]
+    StateChanged {
+        changes: Vec<StateChange>,
+        // more changed than `AuditOptions::max_changes` allows to list
+        truncated: bool,
+    },
+}
+
+impl TreeEvent {
+    /// Whether an [`EventPipeline`](crate::behavior_tree::pipeline::EventPipeline) may drop the
+    /// event when its queue is full: logs below `Error` and chosen jitter delays.
+    pub fn is_droppable(&self) -> bool {
+        match self {
+            TreeEvent::LogEmitted { level, .. } => *level < LogLevel::Error,
+            TreeEvent::JitterChosen { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+/// Receives the events of the tree instances it is registered with.
+pub trait BehaviorObserver<A>: Send {
+    fn on_event(&mut self, path: &[usize], event: &TreeEvent);
+
+    /// Called when the node at `path` starts running. Nodes skipped because the node before
+    /// them decided their parent are never entered.
+    fn on_node_enter(&mut self, _path: &[usize], _node: &Behavior<A>) {}
+
+    /// Called when the node at `path` results in an error of its own, before
+    /// [`on_node_exit`](Self::on_node_exit). The nodes passing the error on don't call it again.
+    fn on_error(
+        &mut self,
+        _path: &[usize],
+        _node: &Behavior<A>,
+        _err: &BehaviorError<A::ActionError>,
+    ) where
+        A: Actionable,
+    {
+    }
+
+    /// Called when the node at `path` finished running, with what it resulted in.
+    fn on_node_exit(
+        &mut self,
+        _path: &[usize],
+        _node: &Behavior<A>,
+        _result: &Result<Response, BehaviorError<A::ActionError>>,
+    ) where
+        A: Actionable,
+    {
+    }
+}
+
+// forwards the events of a run on another task, like a detached or parallel subtree, to the
+// instance it belongs to
+impl<A> BehaviorObserver<A> for mpsc::Sender<(NodePath, TreeEvent)> {
+    fn on_event(&mut self, path: &[usize], event: &TreeEvent) {
+        let _ = self.send((path.to_vec(), event.clone()));
+    }
+}
+
+/// A node starting, failing with an error or finishing, as a [`RecordingObserver`] records it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceEvent {
+    Enter {
+        path: NodePath,
+    },
+    // errors are their message, or just "action error" for the error of an action
+    Error {
+        path: NodePath,
+        message: String,
+    },
+    Exit {
+        path: NodePath,
+        result: Result<Response, String>,
+    },
+}
+
+/// Records the nodes a tree enters and exits, in order. Clones share the same records, so a
+/// test can keep one and register another.
+#[derive(Debug, Clone, Default)]
+pub struct RecordingObserver(Arc<Mutex<Vec<TraceEvent>>>);
+
+impl RecordingObserver {
+    pub fn events(&self) -> Vec<TraceEvent> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl<A> BehaviorObserver<A> for RecordingObserver {
+    fn on_event(&mut self, _: &[usize], _: &TreeEvent) {}
+
+    fn on_node_enter(&mut self, path: &[usize], _: &Behavior<A>) {
+        let path = path.to_vec();
+        self.0.lock().unwrap().push(TraceEvent::Enter { path });
+    }
+
+    fn on_error(&mut self, path: &[usize], _: &Behavior<A>, err: &BehaviorError<A::ActionError>)
+    where
+        A: Actionable,
+    {
+        let (path, message) = (path.to_vec(), error_message(err));
+        self.0
+            .lock()
+            .unwrap()
+            .push(TraceEvent::Error { path, message });
+    }
+
+    fn on_node_exit(
+        &mut self,
+        path: &[usize],
+        _: &Behavior<A>,
+        result: &Result<Response, BehaviorError<A::ActionError>>,
+    ) where
+        A: Actionable,
+    {
+        let result = result.as_ref().copied().map_err(error_message);
+        let path = path.to_vec();
+        self.0
+            .lock()
+            .unwrap()
+            .push(TraceEvent::Exit { path, result });
+    }
+}
+
+// without requiring action errors to be `Display`
+pub(crate) fn error_message<E>(err: &BehaviorError<E>) -> String {
+    match err {
+        BehaviorError::Action(_) => "action error".to_string(),
+        BehaviorError::Failed(message)
+        | BehaviorError::Cancelled(message)
+        | BehaviorError::Replayed(message) => message.clone(),
+        BehaviorError::Thrown(thrown) => thrown.to_string(),
+        BehaviorError::AssertionFailed(failed) => failed.to_string(),
+        BehaviorError::ChildrenFailed { message, children } => {
+            describe_children(message, children, &error_message)
+        }
+    }
+}
+
+/// Reports running trees through `tracing`: a `node` span for every node entered, nested like
+/// the nodes, with an event for the error and the result of the node in it. `Log` node messages
+/// are events at their own level, the other tree events are debug events.
+#[cfg(feature = "tracing")]
+#[derive(Debug, Default)]
+pub struct TracingObserver {
+    // the spans of the nodes entered and not exited yet
+    spans: HashMap<NodePath, tracing::Span>,
+}
+
+#[cfg(feature = "tracing")]
+impl TracingObserver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the span of the node at `path`, or of the closest node above it that has one
+    fn span(&self, path: &[usize]) -> tracing::Span {
+        (0..=path.len())
+            .rev()
+            .find_map(|len| self.spans.get(&path[..len]))
+            .cloned()
+            .unwrap_or_else(tracing::Span::current)
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl<A: Debug> BehaviorObserver<A> for TracingObserver {
+    fn on_event(&mut self, path: &[usize], event: &TreeEvent) {
+        let _span = self.span(path).entered();
+        match event {
+            TreeEvent::LogEmitted { level, message } => match level {
+                LogLevel::Trace => tracing::trace!("{}", message),
+                LogLevel::Debug => tracing::debug!("{}", message),
+                LogLevel::Info => tracing::info!("{}", message),
+                LogLevel::Warn => tracing::warn!("{}", message),
+                LogLevel::Error => tracing::error!("{}", message),
+            },
+            event => tracing::debug!(?event),
+        }
+    }
+
+    fn on_node_enter(&mut self, path: &[usize], node: &Behavior<A>) {
+        let parent = self.span(path);
+        let span = match node {
+            Behavior::Action(action) => {
+                tracing::debug_span!(parent: &parent, "node", ?path, ?action)
+            }
+            _ => tracing::debug_span!(parent: &parent, "node", ?path),
+        };
+        self.spans.insert(path.to_vec(), span);
+    }
+
+    fn on_error(&mut self, path: &[usize], _: &Behavior<A>, err: &BehaviorError<A::ActionError>)
+    where
+        A: Actionable,
+    {
+        let _span = self.span(path).entered();
+        tracing::debug!(error = %error_message(err), "node failed");
+    }
+
+    fn on_node_exit(
+        &mut self,
+        path: &[usize],
+        _: &Behavior<A>,
+        result: &Result<Response, BehaviorError<A::ActionError>>,
+    ) where
+        A: Actionable,
+    {
+        let _span = self.span(path).entered();
+        self.spans.remove(path);
+        match result {
+            Ok(response) => tracing::debug!(?response, "node exited"),
+            Err(err) => tracing::debug!(error = %error_message(err), "node exited"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::observer::{RecordingObserver, TraceEvent};
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{Actionable, Behavior, Response};
+
+    // succeeds or fails as told, noting that it ran
+    #[derive(Clone, Debug)]
+    struct Step(bool);
+
+    impl Actionable for Step {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = usize;
+
+        async fn run(&self, _: &(), steps: &mut usize) -> Result<Response, String> {
+            *steps += 1;
+            Ok(if self.0 {
+                Response::Success
+            } else {
+                Response::Failure
+            })
+        }
+    }
+
+    fn enter(path: &[usize]) -> TraceEvent {
+        TraceEvent::Enter {
+            path: path.to_vec(),
+        }
+    }
+
+    fn exit(path: &[usize], result: Result<Response, String>) -> TraceEvent {
+        TraceEvent::Exit {
+            path: path.to_vec(),
+            result,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_nodes_are_entered_and_exited_in_run_order() {
+        let bt = Select(vec![
+            Sequence(vec![Action(Step(false)), Action(Step(true))]),
+            Action(Step(true)),
+        ]);
+        let recorder = RecordingObserver::default();
+        let mut steps = 0;
+        let response = bt.run_with_observer(&(), &mut steps, recorder.clone());
+        assert_eq!(response.await.unwrap(), Response::Success);
+        assert_eq!(steps, 2);
+
+        // the second step of the sequence is never entered
+        assert_eq!(
+            recorder.events(),
+            [
+                enter(&[]),
+                enter(&[0]),
+                enter(&[0, 0]),
+                exit(&[0, 0], Ok(Response::Failure)),
+                exit(&[0], Ok(Response::Failure)),
+                enter(&[1]),
+                exit(&[1], Ok(Response::Success)),
+                exit(&[], Ok(Response::Success)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_recorded_on_every_node_they_leave() {
+        let bt: Behavior<Step> = Sequence(vec![Throw {
+            code: "E42".to_string(),
+            message: None,
+        }]);
+        let recorder = RecordingObserver::default();
+        let err = bt
+            .run_with_observer(&(), &mut 0, recorder.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            recorder.events(),
+            [
+                enter(&[]),
+                enter(&[0]),
+                TraceEvent::Error {
+                    path: vec![0],
+                    message: err.to_string(),
+                },
+                exit(&[0], Err(err.to_string())),
+                exit(&[], Err(err.to_string())),
+            ]
+        );
+
+        // a select fails with the error of its child, reported only where it started
+        let unresolved = Subtree {
+            name: "dock".to_string(),
+            remapping: Default::default(),
+        };
+        let bt: Behavior<Step> = Select(vec![unresolved, Action(Step(false))]);
+        let recorder = RecordingObserver::default();
+        let err = bt
+            .run_with_observer(&(), &mut 0, recorder.clone())
+            .await
+            .unwrap_err();
+        let errors: Vec<_> = recorder
+            .events()
+            .into_iter()
+            .filter(|event| matches!(event, TraceEvent::Error { .. }))
+            .collect();
+        assert_eq!(
+            errors,
+            [TraceEvent::Error {
+                path: vec![0],
+                message: "subtree `dock` was never resolved by a SubtreeRegistry".to_string(),
+            },]
+        );
+        assert_eq!(err.origin(), Some(&[0][..]));
+    }
+
+    // collects what a `tracing` subscriber writes
+    #[cfg(feature = "tracing")]
+    #[derive(Clone, Default)]
+    struct Output(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    #[cfg(feature = "tracing")]
+    impl std::io::Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tokio::test]
+    async fn test_tracing_observer_reports_nodes_and_logs() {
+        use crate::behavior_tree::observer::{LogLevel, TracingObserver};
+
+        let output = Output::default();
+        let writer = output.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_max_level(tracing::Level::DEBUG)
+            .with_ansi(false)
+            .without_time()
+            .with_target(false)
+            .finish();
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        let bt = Select(vec![
+            Sequence(vec![Action(Step(false)), Action(Step(true))]),
+            Log {
+                level: LogLevel::Warn,
+                message: "falling back".to_string(),
+            },
+        ]);
+        let response = bt
+            .run_with_observer(&(), &mut 0, TracingObserver::new())
+            .await;
+        assert_eq!(response.unwrap(), Response::Success);
+
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "DEBUG node{path=[]}:node{path=[0]}:node{path=[0, 0] action=Step(false)}: node \
+                 exited response=Failure",
+                "DEBUG node{path=[]}:node{path=[0]}: node exited response=Failure",
+                " WARN node{path=[]}:node{path=[1]}: falling back",
+                "DEBUG node{path=[]}:node{path=[1]}: node exited response=Success",
+                "DEBUG node{path=[]}: node exited response=Success",
+            ]
+        );
+    }
+}
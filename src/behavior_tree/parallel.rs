@@ -0,0 +1,533 @@
+use crate::behavior_tree::blackboard::Blackboard;
+use crate::behavior_tree::instance::{NodeMemory, RunContext};
+use crate::behavior_tree::observer::{error_message, TreeEvent};
+use crate::behavior_tree::rng::TreeRng;
+use crate::behavior_tree::{
+    one_child_failed, Actionable, Behavior, BehaviorError, NodePath, Response,
+};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::future::poll_fn;
+use std::sync::mpsc;
+use std::task::Poll;
+
+type ChildResult<E> = Result<Response, BehaviorError<E>>;
+
+// why a child that returned `Failure` failed
+const RETURNED_FAILURE: &str = "returned Failure";
+
+/// Combines the state a child of a `Parallel` node ran on back into the state of the tree.
+pub trait Merge {
+    fn merge(&mut self, other: Self);
+}
+
+impl Merge for () {
+    fn merge(&mut self, _: ()) {}
+}
+
+/// When a `Parallel` node is decided.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ParallelPolicy {
+    // succeeds once every child succeeded, fails as soon as one fails
+    RequireAll,
+    // succeeds as soon as one child succeeds, fails once every child failed
+    RequireAny,
+    // succeeds once `successes` children succeeded, fails once `failures` children failed, or
+    // once every child finished without either
+    Threshold { successes: usize, failures: usize },
+}
+
+// the results of the children so far
+struct Tally {
+    // the path of the node
+    path: NodePath,
+    policy: ParallelPolicy,
+    running: bool,
+    // the children that finished without deciding the node, with why they failed if they did
+    finished: Vec<(usize, Option<String>)>,
+}
+
+impl Tally {
+    /// What the node results in if child `index` resulting in `result` decides it.
+    fn add<E>(&mut self, index: usize, result: ChildResult<E>) -> Option<ChildResult<E>> {
+        match (self.policy, result) {
+            (_, Ok(Response::Running)) => self.running = true,
+            (ParallelPolicy::RequireAll, Ok(Response::Success)) => {
+                self.finished.push((index, None))
+            }
+            (ParallelPolicy::RequireAll, Ok(Response::Failure)) => {
+                return Some(Ok(Response::Failure))
+            }
+            (ParallelPolicy::RequireAll, Err(e)) if !e.propagates() => {
+                return Some(Err(one_child_failed(&self.path, index, e)))
+            }
+            (ParallelPolicy::RequireAll, Err(e)) => return Some(Err(e)),
+            (ParallelPolicy::RequireAny, Ok(Response::Success)) => {
+                return Some(Ok(Response::Success))
+            }
+            (_, Err(e)) if e.is_hard() => return Some(Err(e)),
+            (ParallelPolicy::Threshold { successes, .. }, Ok(Response::Success)) => {
+                self.finished.push((index, None));
+                if self.successes() >= successes {
+                    return Some(Ok(Response::Success));
+                }
+            }
+            (policy, result) => {
+                let failure = match result {
+                    Err(err) => error_message(&err),
+                    Ok(_) => RETURNED_FAILURE.to_string(),
+                };
+                self.finished.push((index, Some(failure)));
+                match policy {
+                    ParallelPolicy::Threshold { failures, .. }
+                        if self.finished.len() - self.successes() >= failures =>
+                    {
+                        return Some(self.failed(format!(
+                            "parallel failure threshold of {} reached",
+                            failures
+                        )));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        None
+    }
+
+    /// What the node results in once every child ran without deciding it.
+    fn end<E>(self) -> ChildResult<E> {
+        if self.running {
+            return Ok(Response::Running);
+        }
+        match self.policy {
+            ParallelPolicy::RequireAll => Ok(Response::Success),
+            ParallelPolicy::RequireAny => self.failed("no parallel child succeeded".to_string()),
+            ParallelPolicy::Threshold { successes, .. } => self.failed(format!(
+                "parallel success threshold of {} not reached, {} succeeded",
+                successes,
+                self.successes(),
+            )),
+        }
+    }
+
+    // `Failure` if the children that failed all returned it, an error with `message` and why
+    // they failed otherwise
+    fn failed<E>(&self, message: String) -> ChildResult<E> {
+        let failures = self
+            .finished
+            .iter()
+            .filter_map(|(_, failure)| failure.as_ref());
+        if failures.clone().all(|failure| failure == RETURNED_FAILURE) {
+            return Ok(Response::Failure);
+        }
+        Err(BehaviorError::failed(format!(
+            "{} ({})",
+            message,
+            self.failures()
+        )))
+    }
+
+    fn successes(&self) -> usize {
+        let succeeded = self
+            .finished
+            .iter()
+            .filter(|(_, failure)| failure.is_none());
+        succeeded.count()
+    }
+
+    // why the children that failed did, in child order
+    fn failures(&self) -> String {
+        let mut failures: Vec<_> = self
+            .finished
+            .iter()
+            .filter_map(|(i, failure)| Some((*i, failure.as_ref()?)))
+            .collect();
+        failures.sort();
+        let failures: Vec<_> = failures
+            .iter()
+            .map(|(i, failure)| format!("child {}: {}", i, failure))
+            .collect();
+        failures.join("; ")
+    }
+}
+
+// a child of the node running on a context and state of its own
+struct Branch<A: Actionable> {
+    index: usize,
+    ctx: RunContext<A>,
+    state: A::ActionState,
+    done: bool,
+}
+
+/// Runs a `Parallel` node. The children that finished without deciding the node while others
+/// were still running are kept in the memory of the node and don't run again until it is decided.
+pub(crate) async fn run<A: Actionable>(
+    children: &[Behavior<A>],
+    policy: ParallelPolicy,
+    ctx: &mut RunContext<A>,
+    args: &A::ActionArgs,
+    state: &mut A::ActionState,
+) -> ChildResult<A::ActionError> {
+    let Some((clone, merge)) = ctx.parallel else {
+        return Err(BehaviorError::failed(
+            "parallel nodes need an instance built with_parallel",
+        ));
+    };
+    let finished = match ctx.memory.remove(&ctx.path) {
+        Some(NodeMemory::Finished(finished)) => finished,
+        _ => vec![],
+    };
+    let mut tally = Tally {
+        path: ctx.path.clone(),
+        policy,
+        running: false,
+        finished: finished.clone(),
+    };
+    let pending = (0..children.len()).filter(|i| !finished.iter().any(|(done, _)| done == i));
+
+    let mut decided = None;
+    if ctx.is_instrumented() {
+        // whatever watches the actions sees them one by one, in order
+        for i in pending {
+            let result = children[i].run_child(i, ctx, args, state).await;
+            decided = tally.add(i, result);
+            if decided.is_some() {
+                break;
+            }
+        }
+    } else {
+        let (sender, events) = mpsc::channel::<(NodePath, TreeEvent)>();
+        let mut branches: Vec<Branch<A>> = pending
+            .map(|index| Branch {
+                index,
+                ctx: RunContext {
+                    path: ctx.path.clone(),
+                    rng: TreeRng::seeded(ctx.rng.next_u64()),
+                    memory: ctx.memory.clone(),
+                    blackboard: ctx.blackboard.clone(),
+                    field_access: ctx.field_access,
+                    accessors: ctx.accessors.clone(),
+                    observers: vec![Box::new(sender.clone())],
+                    state_debug: ctx.state_debug,
+                    assert_mode: ctx.assert_mode,
+                    clock: ctx.clock.clone(),
+                    sleeper: ctx.sleeper.clone(),
+                    cancellation: ctx.cancellation.clone(),
+                    parallel: ctx.parallel,
+                    visited: ctx.visited.as_ref().map(|_| vec![]),
+                    toggles: ctx.toggles.clone(),
+                    heartbeats: ctx.heartbeats.clone(),
+                    typed: ctx.typed.clone(),
+                    namespaces: ctx.namespaces.clone(),
+                    ..RunContext::default()
+                },
+                state: clone(state),
+                done: false,
+            })
+            .collect();
+        {
+            let mut running: Vec<_> = branches
+                .iter_mut()
+                .map(|branch| {
+                    let child = &children[branch.index];
+                    let run =
+                        child.run_child(branch.index, &mut branch.ctx, args, &mut branch.state);
+                    Some((run, branch.index, &mut branch.done))
+                })
+                .collect();
+            poll_fn(|cx| {
+                for slot in running.iter_mut() {
+                    let Some((run, index, done)) = slot else {
+                        continue;
+                    };
+                    if let Poll::Ready(result) = run.as_mut().poll(cx) {
+                        **done = true;
+                        decided = tally.add(*index, result);
+                        *slot = None;
+                        if decided.is_some() {
+                            return Poll::Ready(());
+                        }
+                    }
+                }
+                if running.iter().all(Option::is_none) {
+                    Poll::Ready(())
+                } else {
+                    Poll::Pending
+                }
+            })
+            .await;
+            // children still running are stopped here
+        }
+
+        let before = ctx.blackboard.clone();
+
+        for branch in branches {
+            let mut prefix = ctx.path.clone();
+            prefix.push(branch.index);
+            ctx.memory.retain(|path, _| !path.starts_with(&prefix));
+            // the changes of a stopped child are lost
+            if !branch.done {
+                continue;
+            }
+            merge(state, branch.state);
+            let memory = branch.ctx.memory.into_iter();
+            ctx.memory
+                .extend(memory.filter(|(path, _)| path.starts_with(&prefix)));
+            merge_blackboard(&mut ctx.blackboard, &before, &branch.ctx.blackboard);
+            if let (Some(visited), Some(branch_visited)) = (&mut ctx.visited, branch.ctx.visited) {
+                visited.extend(branch_visited);
+            }
+        }
+        drop(sender);
+        for (path, event) in events.try_iter() {
+            for observer in &mut ctx.observers {
+                observer.on_event(&path, &event);
+            }
+        }
+    }
+
+    if let Some(result) = decided {
+        return result;
+    }
+    let finished = tally.finished.clone();
+    let result = tally.end();
+    if matches!(result, Ok(Response::Running)) {
+        ctx.memory
+            .insert(ctx.path.clone(), NodeMemory::Finished(finished));
+    }
+    result
+}
+
+// writes the keys a child changed on its copy of the blackboard, `before` it ran, to `blackboard`
+fn merge_blackboard(blackboard: &mut Blackboard, before: &Blackboard, copy: &Blackboard) {
+    let keys: BTreeSet<&str> = before.keys().chain(copy.keys()).collect();
+    for key in keys {
+        if before.get(key) == copy.get(key) {
+            continue;
+        }
+        match copy.get(key) {
+            Some(value) => blackboard.set(key, value.clone()),
+            None => {
+                blackboard.remove(key);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use crate::behavior_tree::parallel::{Merge, ParallelPolicy};
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{Actionable, Behavior, Response, TreeInstance};
+    use std::collections::BTreeSet;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tokio::time::{sleep, Instant};
+
+    // errands of a ship, taking the given milliseconds
+    #[derive(Clone, Debug)]
+    enum Errand {
+        Do(&'static str, u64),
+        Fail(u64),
+        // running until a second run
+        Charge,
+    }
+
+    // what the ship got done
+    #[derive(Clone, Debug, Default, PartialEq)]
+    struct Done(BTreeSet<&'static str>);
+
+    impl Merge for Done {
+        fn merge(&mut self, other: Done) {
+            self.0.extend(other.0);
+        }
+    }
+
+    impl Actionable for Errand {
+        type ActionError = String;
+        // counts the errands started
+        type ActionArgs = AtomicUsize;
+        type ActionState = Done;
+
+        async fn run(&self, started: &AtomicUsize, done: &mut Done) -> Result<Response, String> {
+            started.fetch_add(1, Ordering::Relaxed);
+            match self {
+                Errand::Do(name, millis) => {
+                    sleep(Duration::from_millis(*millis)).await;
+                    done.0.insert(name);
+                    Ok(Response::Success)
+                }
+                Errand::Fail(millis) => {
+                    sleep(Duration::from_millis(*millis)).await;
+                    Ok(Response::Failure)
+                }
+                Errand::Charge if done.0.insert("charging") => Ok(Response::Running),
+                Errand::Charge => Ok(Response::Success),
+            }
+        }
+    }
+
+    fn unresolved() -> Behavior<Errand> {
+        Subtree {
+            name: "dock".to_string(),
+            remapping: Default::default(),
+        }
+    }
+
+    fn parallel(policy: ParallelPolicy, errands: &[Errand]) -> Behavior<Errand> {
+        Parallel {
+            children: errands.iter().cloned().map(Action).collect(),
+            policy,
+        }
+    }
+
+    async fn run(bt: Behavior<Errand>) -> (Result<Response, String>, Done, Duration) {
+        let mut instance = TreeInstance::new(bt).with_parallel();
+        let (started, mut done) = (Instant::now(), Done::default());
+        let result = instance.run(&AtomicUsize::new(0), &mut done).await;
+        (result.map_err(|e| e.to_string()), done, started.elapsed())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_children_run_concurrently() {
+        let errands = [Errand::Do("refuel", 100), Errand::Do("scan", 100)];
+        let (result, done, elapsed) = run(parallel(ParallelPolicy::RequireAll, &errands)).await;
+        assert_eq!(result, Ok(Response::Success));
+        assert_eq!(elapsed, Duration::from_millis(100));
+        assert_eq!(done.0, BTreeSet::from(["refuel", "scan"]));
+
+        // the first success stops the other children, losing what they did
+        let errands = [Errand::Do("refuel", 100), Errand::Do("scan", 40)];
+        let (result, done, elapsed) = run(parallel(ParallelPolicy::RequireAny, &errands)).await;
+        assert_eq!(result, Ok(Response::Success));
+        assert_eq!(elapsed, Duration::from_millis(40));
+        assert_eq!(done.0, BTreeSet::from(["scan"]));
+
+        let without = TreeInstance::new(parallel(ParallelPolicy::RequireAll, &errands))
+            .run(&AtomicUsize::new(0), &mut Done::default())
+            .await;
+        assert_eq!(
+            without.unwrap_err().to_string(),
+            "parallel nodes need an instance built with_parallel"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failures_under_each_policy() {
+        // a failure decides a node requiring all children right away
+        let errands = [Errand::Do("refuel", 100), Errand::Fail(30)];
+        let (result, done, elapsed) = run(parallel(ParallelPolicy::RequireAll, &errands)).await;
+        assert_eq!(result, Ok(Response::Failure));
+        assert_eq!(elapsed, Duration::from_millis(30));
+        assert!(done.0.is_empty());
+
+        let bt = Parallel {
+            children: vec![Action(Errand::Do("refuel", 100)), unresolved()],
+            policy: ParallelPolicy::RequireAll,
+        };
+        let (result, _, _) = run(bt).await;
+        assert_eq!(
+            result,
+            Err(
+                "one behavior failed ([1]: subtree `dock` was never resolved by a SubtreeRegistry)"
+                    .to_string()
+            )
+        );
+
+        // a node requiring any child fails once all did
+        let bt = Parallel {
+            children: vec![Action(Errand::Fail(30)), AlwaysFail],
+            policy: ParallelPolicy::RequireAny,
+        };
+        let (result, _, elapsed) = run(bt).await;
+        assert_eq!(result, Ok(Response::Failure));
+        assert_eq!(elapsed, Duration::from_millis(30));
+
+        // with why each failed if one failed with an error
+        let bt = Parallel {
+            children: vec![Action(Errand::Fail(30)), unresolved()],
+            policy: ParallelPolicy::RequireAny,
+        };
+        let (result, _, _) = run(bt).await;
+        assert_eq!(
+            result,
+            Err("no parallel child succeeded (child 0: returned Failure; \
+                 child 1: subtree `dock` was never resolved by a SubtreeRegistry)"
+                .to_string())
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_thresholds_decide_on_the_counts() {
+        let errands = [
+            Errand::Do("refuel", 50),
+            Errand::Fail(20),
+            Errand::Do("scan", 100),
+            Errand::Do("mine", 300),
+        ];
+        let threshold = |successes, failures| ParallelPolicy::Threshold {
+            successes,
+            failures,
+        };
+        let (result, done, elapsed) = run(parallel(threshold(2, 2), &errands)).await;
+        assert_eq!(result, Ok(Response::Success));
+        assert_eq!(elapsed, Duration::from_millis(100));
+        assert_eq!(done.0, BTreeSet::from(["refuel", "scan"]));
+
+        let (result, _, elapsed) = run(parallel(threshold(2, 1), &errands)).await;
+        assert_eq!(result, Ok(Response::Failure));
+        assert_eq!(elapsed, Duration::from_millis(20));
+
+        let (result, _, elapsed) = run(parallel(threshold(4, 2), &errands)).await;
+        assert_eq!(result, Ok(Response::Failure));
+        assert_eq!(elapsed, Duration::from_millis(300));
+
+        let children = errands[..3].iter().cloned().map(Action);
+        let bt = Parallel {
+            children: children.chain([unresolved()]).collect(),
+            policy: threshold(3, 2),
+        };
+        let (result, _, _) = run(bt).await;
+        assert_eq!(
+            result,
+            Err(
+                "parallel failure threshold of 2 reached (child 1: returned Failure; \
+                 child 3: subtree `dock` was never resolved by a SubtreeRegistry)"
+                    .to_string()
+            )
+        );
+
+        assert_eq!(
+            parallel(threshold(5, 1), &errands).config_error().unwrap(),
+            "parallel success threshold of 5 is above its 4 children"
+        );
+        assert_eq!(
+            parallel(threshold(1, 0), &errands).config_error().unwrap(),
+            "parallel thresholds must be at least 1"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_running_children_keep_the_node_running() {
+        let bt = parallel(
+            ParallelPolicy::RequireAll,
+            &[Errand::Do("refuel", 100), Errand::Charge],
+        );
+        let mut instance = TreeInstance::new(bt).with_parallel();
+        let (started, mut done) = (AtomicUsize::new(0), Done::default());
+        assert_eq!(
+            instance.run(&started, &mut done).await.unwrap(),
+            Response::Running
+        );
+        assert_eq!(done.0, BTreeSet::from(["charging", "refuel"]));
+
+        // the refuel that succeeded doesn't run again
+        assert_eq!(
+            instance.run(&started, &mut done).await.unwrap(),
+            Response::Success
+        );
+        assert_eq!(started.load(Ordering::Relaxed), 3);
+    }
+}
@@ -0,0 +1,332 @@
+use crate::behavior_tree::observer::{BehaviorObserver, TreeEvent};
+use crate::behavior_tree::NodePath;
+use std::collections::VecDeque;
+use std::mem;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+/// Delivers the events of tree instances to observers on a thread of its own, so slow
+/// observers don't slow down ticks.
+///
+/// The events wait in a queue of `capacity` events. When it is full, a droppable event (see
+/// [`TreeEvent::is_droppable`]) is dropped: the new one if it is droppable itself, else the
+/// oldest droppable one waiting. Other events are never dropped and may grow the queue beyond
+/// its capacity.
+pub struct EventPipeline<A> {
+    capacity: usize,
+    observers: Vec<Box<dyn BehaviorObserver<A>>>,
+}
+
+/// Counts of an [`EventPipeline`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PipelineMetrics {
+    /// Events waiting to be delivered.
+    pub depth: usize,
+    pub dropped: u64,
+    pub delivered: u64,
+}
+
+struct Queue {
+    capacity: usize,
+    events: VecDeque<(NodePath, TreeEvent)>,
+    metrics: PipelineMetrics,
+    // the forwarding thread is delivering events it took from the queue
+    delivering: bool,
+    closed: bool,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap()
+    }
+
+    fn wait<'a>(&self, queue: MutexGuard<'a, Queue>) -> MutexGuard<'a, Queue> {
+        self.changed.wait(queue).unwrap()
+    }
+}
+
+impl<A: 'static> EventPipeline<A> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            observers: vec![],
+        }
+    }
+
+    pub fn with_observer(mut self, observer: impl BehaviorObserver<A> + 'static) -> Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    /// Starts the forwarding thread. Register the returned observer with the instances whose
+    /// events the pipeline delivers.
+    pub fn start(self) -> (PipelineObserver, PipelineHandle) {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                capacity: self.capacity,
+                events: VecDeque::new(),
+                metrics: PipelineMetrics::default(),
+                delivering: false,
+                closed: false,
+            }),
+            changed: Condvar::new(),
+        });
+        let mut observers = self.observers;
+        let forwarding = shared.clone();
+        let thread = thread::spawn(move || forward(&forwarding, &mut observers));
+        (
+            PipelineObserver(shared.clone()),
+            PipelineHandle {
+                shared,
+                thread: Some(thread),
+            },
+        )
+    }
+}
+
+fn forward<A>(shared: &Shared, observers: &mut [Box<dyn BehaviorObserver<A>>]) {
+    loop {
+        let batch = {
+            let mut queue = shared.lock();
+            while queue.events.is_empty() && !queue.closed {
+                queue = shared.wait(queue);
+            }
+            if queue.events.is_empty() {
+                return;
+            }
+            queue.delivering = true;
+            queue.metrics.depth = 0;
+            mem::take(&mut queue.events)
+        };
+        for (path, event) in &batch {
+            for observer in observers.iter_mut() {
+                observer.on_event(path, event);
+            }
+        }
+        let mut queue = shared.lock();
+        queue.delivering = false;
+        queue.metrics.delivered += batch.len() as u64;
+        shared.changed.notify_all();
+    }
+}
+
+/// Queues the events of the instances it is registered with for an [`EventPipeline`].
+#[derive(Clone)]
+pub struct PipelineObserver(Arc<Shared>);
+
+impl<A> BehaviorObserver<A> for PipelineObserver {
+    fn on_event(&mut self, path: &[usize], event: &TreeEvent) {
+        let mut queue = self.0.lock();
+        if queue.events.len() >= queue.capacity {
+            if event.is_droppable() {
+                queue.metrics.dropped += 1;
+                return;
+            }
+            let oldest = queue
+                .events
+                .iter()
+                .position(|(_, event)| event.is_droppable());
+            if let Some(oldest) = oldest {
+                queue.events.remove(oldest);
+                queue.metrics.dropped += 1;
+            }
+        }
+        queue.events.push_back((path.to_vec(), event.clone()));
+        queue.metrics.depth = queue.events.len();
+        self.0.changed.notify_all();
+    }
+}
+
+/// Controls a running [`EventPipeline`]. Dropping it delivers the waiting events and stops the
+/// forwarding thread.
+pub struct PipelineHandle {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PipelineHandle {
+    pub fn metrics(&self) -> PipelineMetrics {
+        self.shared.lock().metrics
+    }
+
+    /// Blocks the calling thread until the observers got every event queued so far.
+    pub fn flush(&self) {
+        let mut queue = self.shared.lock();
+        while !queue.events.is_empty() || queue.delivering {
+            queue = self.shared.wait(queue);
+        }
+    }
+}
+
+impl Drop for PipelineHandle {
+    fn drop(&mut self) {
+        self.shared.lock().closed = true;
+        self.shared.changed.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::observer::{BehaviorObserver, LogLevel, TreeEvent};
+    use crate::behavior_tree::pipeline::{EventPipeline, PipelineMetrics};
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{Actionable, Response, TreeInstance};
+    use std::sync::mpsc;
+    use std::sync::{Arc, Condvar, Mutex};
+
+    #[derive(Clone, Debug)]
+    struct Increase;
+
+    impl Actionable for Increase {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = u32;
+
+        async fn run(&self, _: &(), count: &mut u32) -> Result<Response, String> {
+            *count += 1;
+            Ok(Response::Success)
+        }
+    }
+
+    #[derive(Default)]
+    struct Gate {
+        open: Mutex<bool>,
+        opened: Condvar,
+    }
+
+    impl Gate {
+        fn wait(&self) {
+            let mut open = self.open.lock().unwrap();
+            while !*open {
+                open = self.opened.wait(open).unwrap();
+            }
+        }
+
+        fn open(&self) {
+            *self.open.lock().unwrap() = true;
+            self.opened.notify_all();
+        }
+    }
+
+    // an observer stuck on each event until the test opens the gate
+    struct Stuck {
+        gate: Arc<Gate>,
+        started: mpsc::Sender<()>,
+        seen: Arc<Mutex<Vec<TreeEvent>>>,
+    }
+
+    impl<A> BehaviorObserver<A> for Stuck {
+        fn on_event(&mut self, _: &[usize], event: &TreeEvent) {
+            self.seen.lock().unwrap().push(event.clone());
+            let _ = self.started.send(());
+            self.gate.wait();
+        }
+    }
+
+    fn stuck() -> (Stuck, Arc<Gate>, mpsc::Receiver<()>) {
+        let gate = Arc::new(Gate::default());
+        let (started, on_started) = mpsc::channel();
+        let observer = Stuck {
+            gate: gate.clone(),
+            started,
+            seen: Arc::default(),
+        };
+        (observer, gate, on_started)
+    }
+
+    fn log(level: LogLevel, message: &str) -> TreeEvent {
+        TreeEvent::LogEmitted {
+            level,
+            message: message.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stuck_observer_doesnt_hold_up_ticks() {
+        let (observer, gate, _) = stuck();
+        let seen = observer.seen.clone();
+        let (pipeline, handle) = EventPipeline::<Increase>::new(100)
+            .with_observer(observer)
+            .start();
+        let bt = Sequence(vec![
+            Log {
+                level: LogLevel::Info,
+                message: "tick".to_string(),
+            },
+            Action(Increase),
+        ]);
+        let mut instance = TreeInstance::new(bt).with_observer(pipeline);
+        let mut count = 0;
+
+        for _ in 0..10 {
+            instance.run(&(), &mut count).await.unwrap();
+        }
+        assert_eq!(count, 10);
+        gate.open();
+
+        handle.flush();
+        assert_eq!(*seen.lock().unwrap(), vec![log(LogLevel::Info, "tick"); 10]);
+        assert_eq!(
+            handle.metrics(),
+            PipelineMetrics {
+                depth: 0,
+                dropped: 0,
+                delivered: 10,
+            }
+        );
+    }
+
+    #[test]
+    fn test_full_queue_drops_low_priority_events() {
+        let (observer, gate, on_started) = stuck();
+        let seen = observer.seen.clone();
+        let (mut pipeline, handle) = EventPipeline::<Increase>::new(3)
+            .with_observer(observer)
+            .start();
+        let mut emit = |event: TreeEvent| {
+            BehaviorObserver::<Increase>::on_event(&mut pipeline, &[], &event);
+        };
+        let thrown = |code: &str| TreeEvent::Thrown {
+            code: code.to_string(),
+            message: None,
+        };
+
+        // the observer takes the first event and gets stuck on it, the others queue up
+        emit(thrown("first"));
+        on_started.recv().unwrap();
+        for i in 1..=5 {
+            emit(log(LogLevel::Debug, &i.to_string()));
+        }
+        emit(log(LogLevel::Error, "kept"));
+        emit(thrown("last"));
+        assert_eq!(
+            handle.metrics(),
+            PipelineMetrics {
+                depth: 3,
+                dropped: 4,
+                delivered: 0,
+            }
+        );
+
+        gate.open();
+        handle.flush();
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                thrown("first"),
+                log(LogLevel::Debug, "3"),
+                log(LogLevel::Error, "kept"),
+                thrown("last"),
+            ]
+        );
+        assert_eq!(handle.metrics().delivered, 4);
+    }
+}
@@ -0,0 +1,264 @@
+use crate::behavior_tree::instance::{NodeMemory, RunContext};
+use crate::behavior_tree::rng::TreeRng;
+use crate::behavior_tree::{one_child_failed, Actionable, Behavior, BehaviorError, Response};
+
+/// Why weighted children can't make up a `WeightedSelect`.
+pub(crate) fn config_error<A>(children: &[(f32, Behavior<A>)]) -> Option<String> {
+    if children.is_empty() {
+        return Some("select has no children".to_string());
+    }
+    let invalid = children.iter().enumerate().find_map(|(i, (weight, _))| {
+        if weight.is_nan() {
+            Some(format!("weighted select child {} has a NaN weight", i))
+        } else if *weight < 0.0 {
+            Some(format!(
+                "weighted select child {} has negative weight {}",
+                i, weight
+            ))
+        } else if weight.is_infinite() {
+            Some(format!(
+                "weighted select child {} has an infinite weight",
+                i
+            ))
+        } else {
+            None
+        }
+    });
+    if invalid.is_some() {
+        return invalid;
+    }
+    if children.iter().all(|(weight, _)| *weight == 0.0) {
+        return Some("weighted select children all have weight 0".to_string());
+    }
+    None
+}
+
+/// The order a `WeightedSelect` tries its children in: each next child drawn in proportion to
+/// its weight among the children left, the children of weight 0 last, in their order.
+pub(crate) fn weighted_order(weights: &[f32], rng: &mut TreeRng) -> Vec<usize> {
+    let mut weighted: Vec<(usize, f64)> = weights
+        .iter()
+        .enumerate()
+        .map(|(i, weight)| (i, if *weight > 0.0 { *weight as f64 } else { 0.0 }))
+        .collect();
+    let mut order = Vec::with_capacity(weights.len());
+    while weighted.iter().any(|(_, weight)| *weight > 0.0) {
+        let total: f64 = weighted.iter().map(|(_, weight)| weight).sum();
+        let mut pick = rng.gen_f64() * total;
+        let position = weighted
+            .iter()
+            .position(|(_, weight)| {
+                if *weight > 0.0 && pick < *weight {
+                    return true;
+                }
+                pick -= weight;
+                false
+            })
+            // rounding can leave the pick just past the last weight
+            .unwrap_or_else(|| weighted.iter().rposition(|(_, w)| *w > 0.0).unwrap());
+        order.push(weighted.remove(position).0);
+    }
+    order.extend(weighted.into_iter().map(|(i, _)| i));
+    order
+}
+
+/// Runs the children of a `RandomSelect` or `WeightedSelect` like a `Select`, in `order`. The
+/// child that returned `Running` last time is kept in the memory of the node and runs first.
+pub(crate) async fn select<A: Actionable>(
+    children: &[&Behavior<A>],
+    mut order: Vec<usize>,
+    ctx: &mut RunContext<A>,
+    args: &A::ActionArgs,
+    state: &mut A::ActionState,
+) -> Result<Response, BehaviorError<A::ActionError>> {
+    if let Some(NodeMemory::Cursor(running)) = ctx.memory.remove(&ctx.path) {
+        if running < children.len() {
+            order.retain(|i| *i != running);
+            order.insert(0, running);
+        }
+    }
+    for i in order {
+        match children[i].run_child(i, ctx, args, state).await {
+            Ok(Response::Failure) => {}
+            Ok(Response::Running) => {
+                ctx.set_cursor(i);
+                return Ok(Response::Running);
+            }
+            Ok(r) => return Ok(r),
+            Err(e) if e.propagates() => return Err(e),
+            Err(e) => return Err(one_child_failed(&ctx.path, i, e)),
+        }
+    }
+    Ok(Response::Failure)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{Actionable, Behavior, Response, TreeInstance};
+    use std::collections::HashMap;
+
+    // succeeds if the route is open
+    #[derive(Clone, Debug)]
+    struct Route(u32);
+
+    impl Actionable for Route {
+        type ActionError = String;
+        type ActionArgs = Vec<u32>;
+        type ActionState = Vec<u32>;
+
+        async fn run(&self, open: &Vec<u32>, taken: &mut Vec<u32>) -> Result<Response, String> {
+            if !open.contains(&self.0) {
+                return Ok(Response::Failure);
+            }
+            taken.push(self.0);
+            Ok(Response::Success)
+        }
+    }
+
+    fn routes(routes: &[u32]) -> Vec<Behavior<Route>> {
+        routes.iter().map(|r| Action(Route(*r))).collect()
+    }
+
+    fn weighted(weights: &[f32]) -> Behavior<Route> {
+        let routes = routes(&[1, 2, 3]);
+        WeightedSelect(weights.iter().copied().zip(routes).collect())
+    }
+
+    async fn taken(bt: Behavior<Route>, seed: u64, open: &[u32], runs: usize) -> Vec<u32> {
+        let mut taken = vec![];
+        let mut instance = TreeInstance::with_seed(bt, seed);
+        for _ in 0..runs {
+            instance.run(&open.to_vec(), &mut taken).await.unwrap();
+        }
+        taken
+    }
+
+    fn count(taken: &[u32], route: u32) -> usize {
+        taken.iter().filter(|r| **r == route).count()
+    }
+
+    #[tokio::test]
+    async fn test_random_select_shuffles_with_the_seed() {
+        let bt = RandomSelect(routes(&[1, 2, 3]));
+        let all = taken(bt.clone(), 11, &[1, 2, 3], 300).await;
+        assert_eq!(all, taken(bt.clone(), 11, &[1, 2, 3], 300).await);
+        for route in 1..=3 {
+            let count = count(&all, route);
+            assert!(
+                (60..140).contains(&count),
+                "route {} taken {} times",
+                route,
+                count
+            );
+        }
+
+        // falls back to the other children until one succeeds
+        assert_eq!(taken(bt.clone(), 5, &[2], 20).await, vec![2; 20]);
+        let result = TreeInstance::new(bt).run(&vec![], &mut vec![]).await;
+        assert_eq!(result.unwrap(), Response::Failure);
+    }
+
+    #[tokio::test]
+    async fn test_weighted_select_follows_weights() {
+        let taken_first = taken(weighted(&[1.0, 3.0, 0.0]), 7, &[1, 2, 3], 400).await;
+        let firsts = count(&taken_first, 1);
+        assert!(
+            (60..140).contains(&firsts),
+            "route 1 taken {} times",
+            firsts
+        );
+        assert_eq!(count(&taken_first, 3), 0);
+
+        // falls back to the other children, and to children of weight 0 last
+        assert_eq!(taken(weighted(&[5.0, 0.5, 0.0]), 7, &[3], 1).await, [3]);
+        let result = TreeInstance::new(weighted(&[5.0, 0.5, 0.0]))
+            .run(&vec![], &mut vec![])
+            .await;
+        assert_eq!(result.unwrap(), Response::Failure);
+    }
+
+    #[test]
+    fn test_weights_are_validated() {
+        let error = |weights: &[f32]| weighted(weights).config_error();
+        assert_eq!(error(&[1.0, 0.0, 2.5]), None);
+        assert_eq!(
+            error(&[1.0, -0.5, 1.0]).unwrap(),
+            "weighted select child 1 has negative weight -0.5"
+        );
+        assert_eq!(
+            error(&[f32::NAN, 1.0, 1.0]).unwrap(),
+            "weighted select child 0 has a NaN weight"
+        );
+        assert_eq!(
+            error(&[1.0, 1.0, f32::INFINITY]).unwrap(),
+            "weighted select child 2 has an infinite weight"
+        );
+        assert_eq!(
+            error(&[0.0, 0.0, 0.0]).unwrap(),
+            "weighted select children all have weight 0"
+        );
+        assert_eq!(
+            WeightedSelect::<Route>(vec![]).config_error().unwrap(),
+            "select has no children"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_weights_fail_the_run() {
+        let result = TreeInstance::new(weighted(&[1.0, f32::NAN, 1.0]))
+            .run(&vec![1, 2, 3], &mut vec![])
+            .await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "weighted select child 1 has a NaN weight"
+        );
+    }
+
+    // answers `Running` the first time it runs, and succeeds the second time
+    #[derive(Clone, Debug)]
+    struct Haul(&'static str);
+
+    impl Actionable for Haul {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = HashMap<&'static str, u32>;
+
+        async fn run(
+            &self,
+            _: &(),
+            runs: &mut HashMap<&'static str, u32>,
+        ) -> Result<Response, String> {
+            let runs = runs.entry(self.0).or_default();
+            *runs += 1;
+            match runs {
+                1 => Ok(Response::Running),
+                _ => Ok(Response::Success),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_running_children_are_resumed() {
+        let hauls = || {
+            vec![
+                Action(Haul("ore")),
+                Action(Haul("fuel")),
+                Action(Haul("food")),
+            ]
+        };
+        let weighted = WeightedSelect(vec![1.0, 1.0, 1.0].into_iter().zip(hauls()).collect());
+        for bt in [RandomSelect(hauls()), weighted] {
+            for seed in 0..10 {
+                let mut instance = TreeInstance::with_seed(bt.clone(), seed);
+                let mut runs = HashMap::new();
+                let first = instance.run(&(), &mut runs).await.unwrap();
+                assert_eq!(first, Response::Running);
+                let second = instance.run(&(), &mut runs).await.unwrap();
+                assert_eq!(second, Response::Success);
+                // only the child that was running ran again
+                assert_eq!(runs.len(), 1, "seed {}: {:?}", seed, runs);
+            }
+        }
+    }
+}
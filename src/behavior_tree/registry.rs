@@ -0,0 +1,308 @@
+use crate::behavior_tree::boxed::DynActionable;
+use crate::behavior_tree::{ActionContext, Actionable, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+pub type SharedAction<Args, State, E> = Arc<dyn DynActionable<Args, State, E>>;
+
+type Factory<Args, State, E> =
+    Box<dyn Fn(&Value) -> Result<SharedAction<Args, State, E>, String> + Send + Sync>;
+
+enum Registered<Args, State, E> {
+    Action(SharedAction<Args, State, E>),
+    Factory(Factory<Args, State, E>),
+}
+
+/// The actions a host offers to dynamic trees, by name.
+pub struct ActionRegistry<Args, State, E> {
+    actions: BTreeMap<String, Registered<Args, State, E>>,
+}
+
+impl<Args, State, E> Default for ActionRegistry<Args, State, E> {
+    fn default() -> Self {
+        Self {
+            actions: BTreeMap::new(),
+        }
+    }
+}
+
+impl<Args, State, E> ActionRegistry<Args, State, E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers an action that ignores the payload of the leaves naming it.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        action: impl DynActionable<Args, State, E> + 'static,
+    ) {
+        self.actions
+            .insert(name.into(), Registered::Action(Arc::new(action)));
+    }
+
+    /// Registers a factory building the action from the payload of the leaf naming it. The
+    /// action is built each time the leaf runs.
+    pub fn register_factory(
+        &mut self,
+        name: impl Into<String>,
+        factory: impl Fn(&Value) -> Result<SharedAction<Args, State, E>, String> + Send + Sync + 'static,
+    ) {
+        self.actions
+            .insert(name.into(), Registered::Factory(Box::new(factory)));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.actions.contains_key(name)
+    }
+
+    /// The registered names in alphabetical order.
+    pub fn names(&self) -> Vec<String> {
+        self.actions.keys().cloned().collect()
+    }
+
+    /// The action `name` stands for, built from `payload` if it was registered with a factory.
+    pub fn resolve(
+        &self,
+        name: &str,
+        payload: &Value,
+    ) -> Result<SharedAction<Args, State, E>, DynamicError<E>> {
+        match self.actions.get(name) {
+            Some(Registered::Action(action)) => Ok(action.clone()),
+            Some(Registered::Factory(factory)) => {
+                factory(payload).map_err(|message| DynamicError::InvalidPayload {
+                    name: name.to_string(),
+                    message,
+                })
+            }
+            None => Err(DynamicError::UnknownAction {
+                name: name.to_string(),
+                available: self.names(),
+            }),
+        }
+    }
+}
+
+/// The args of a tree of [`DynamicAction`]s: the registry the leaves are looked up in, and the
+/// args passed on to the registered actions.
+pub struct DynamicArgs<Args, State, E> {
+    pub registry: Arc<ActionRegistry<Args, State, E>>,
+    pub args: Args,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DynamicError<E> {
+    #[error("unknown action `{name}`, available: {}", quoted(available))]
+    UnknownAction {
+        name: String,
+        available: Vec<String>,
+    },
+    #[error("invalid payload for action `{name}`: {message}")]
+    InvalidPayload { name: String, message: String },
+    #[error("{0}")]
+    Action(E),
+}
+
+fn quoted(names: &[String]) -> String {
+    if names.is_empty() {
+        return "none".to_string();
+    }
+    names
+        .iter()
+        .map(|name| format!("`{}`", name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// keeps `DynamicAction` `Send` and `Sync` whatever the types are
+type Marker<Args, State, E> = fn() -> (Args, State, E);
+
+/// A leaf naming an action of the [`ActionRegistry`] in its args. Trees of them can be loaded
+/// from any file and run by a host that registered the actions the file uses:
+///
+/// ```json
+/// {"Action": {"name": "jump", "payload": {"to": "X1-B"}}}
+/// ```
+#[derive(Serialize, Deserialize)]
+pub struct DynamicAction<Args, State, E> {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub payload: Value,
+    #[serde(skip)]
+    marker: PhantomData<Marker<Args, State, E>>,
+}
+
+impl<Args, State, E> DynamicAction<Args, State, E> {
+    pub fn new(name: impl Into<String>, payload: Value) -> Self {
+        Self {
+            name: name.into(),
+            payload,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<Args, State, E> Clone for DynamicAction<Args, State, E> {
+    fn clone(&self) -> Self {
+        Self::new(self.name.clone(), self.payload.clone())
+    }
+}
+
+impl<Args, State, E> fmt::Debug for DynamicAction<Args, State, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DynamicAction")
+            .field("name", &self.name)
+            .field("payload", &self.payload)
+            .finish()
+    }
+}
+
+impl<Args, State, E> Actionable for DynamicAction<Args, State, E>
+where
+    Args: Send + Sync + 'static,
+    State: Send + Sync + 'static,
+    E: Send + Sync + 'static,
+{
+    type ActionError = DynamicError<E>;
+    type ActionArgs = DynamicArgs<Args, State, E>;
+    type ActionState = State;
+
+    async fn run(
+        &self,
+        args: &Self::ActionArgs,
+        state: &mut Self::ActionState,
+    ) -> Result<Response, Self::ActionError> {
+        self.run_with(&ActionContext::default(), args, state).await
+    }
+
+    async fn run_with(
+        &self,
+        cx: &ActionContext,
+        args: &Self::ActionArgs,
+        state: &mut Self::ActionState,
+    ) -> Result<Response, Self::ActionError> {
+        let action = args.registry.resolve(&self.name, &self.payload)?;
+        action
+            .run_boxed(cx, &args.args, state)
+            .await
+            .map_err(DynamicError::Action)
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::registry::{
+        ActionRegistry, DynamicAction, DynamicArgs, DynamicError,
+    };
+    use crate::behavior_tree::{Actionable, Behavior, BehaviorError, Response};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Ship {
+        at: String,
+        sold: Vec<(String, u32)>,
+    }
+
+    #[derive(Clone, Debug)]
+    struct Jump {
+        to: String,
+    }
+
+    #[derive(Clone, Debug)]
+    struct Sell;
+
+    impl Actionable for Jump {
+        type ActionError = String;
+        type ActionArgs = u32;
+        type ActionState = Ship;
+
+        async fn run(&self, _: &u32, ship: &mut Ship) -> Result<Response, String> {
+            ship.at = self.to.clone();
+            Ok(Response::Success)
+        }
+    }
+
+    impl Actionable for Sell {
+        type ActionError = String;
+        type ActionArgs = u32;
+        type ActionState = Ship;
+
+        async fn run(&self, price: &u32, ship: &mut Ship) -> Result<Response, String> {
+            ship.sold.push((ship.at.clone(), *price));
+            Ok(Response::Success)
+        }
+    }
+
+    type Dynamic = DynamicAction<u32, Ship, String>;
+
+    fn registry() -> Arc<ActionRegistry<u32, Ship, String>> {
+        let mut registry = ActionRegistry::new();
+        registry.register("sell", Sell);
+        registry.register_factory("jump", |payload| {
+            let to = payload["to"].as_str().ok_or("`to` is missing")?;
+            Ok(Arc::new(Jump { to: to.to_string() }))
+        });
+        Arc::new(registry)
+    }
+
+    #[tokio::test]
+    async fn test_run_tree_of_registered_actions() {
+        let bt: Behavior<Dynamic> = serde_json::from_value(json!({"Sequence": [
+            {"Action": {"name": "jump", "payload": {"to": "X1-A"}}},
+            {"Action": {"name": "sell"}},
+            {"Action": {"name": "jump", "payload": {"to": "X1-B"}}},
+            {"Action": {"name": "sell"}},
+        ]}))
+        .unwrap();
+        let args = DynamicArgs {
+            registry: registry(),
+            args: 12,
+        };
+        let mut ship = Ship::default();
+
+        bt.run(&args, &mut ship).await.unwrap();
+        assert_eq!(
+            ship.sold,
+            vec![("X1-A".to_string(), 12), ("X1-B".to_string(), 12)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unknown_action_and_invalid_payload() {
+        let args = DynamicArgs {
+            registry: registry(),
+            args: 12,
+        };
+        let mut ship = Ship::default();
+
+        let err = Dynamic::new("buy", json!(null))
+            .run(&args, &mut ship)
+            .await
+            .unwrap_err();
+        assert!(matches!(&err, DynamicError::UnknownAction { name, .. } if name == "buy"));
+        assert_eq!(
+            err.to_string(),
+            "unknown action `buy`, available: `jump`, `sell`"
+        );
+
+        let bt = Behavior::Action(Dynamic::new("jump", json!({"destination": "X1-A"})));
+        let err = bt.run(&args, &mut ship).await.unwrap_err();
+        assert!(matches!(
+            err,
+            BehaviorError::Action(DynamicError::InvalidPayload { .. })
+        ));
+        assert_eq!(
+            err.to_string(),
+            "invalid payload for action `jump`: `to` is missing"
+        );
+    }
+}
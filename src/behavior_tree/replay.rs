@@ -0,0 +1,480 @@
+use crate::behavior_tree::audit::{self, StateChange};
+use crate::behavior_tree::instance::RunContext;
+use crate::behavior_tree::rng::TreeRng;
+use crate::behavior_tree::{Actionable, Behavior, BehaviorError, NodePath, Response};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Everything [`replay_full`] needs to run a tree again as it ran once.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionTrace {
+    /// The seed of the rng of the recorded run.
+    pub seed: u64,
+    /// The action leaves in the order they ran.
+    pub steps: Vec<TraceStep>,
+    /// The state as the recorded run left it.
+    pub final_state: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceStep {
+    pub path: NodePath,
+    /// The action of the leaf, serialized.
+    pub action: Value,
+    pub outcome: Outcome,
+    /// What the action changed in the state.
+    pub changes: Vec<StateChange>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Outcome {
+    Success,
+    Running,
+    // the action returned `Response::Failure`
+    Failure,
+    // the action returned an error
+    Failed,
+}
+
+/// The first point where a replay stopped following its trace.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Divergence {
+    /// Step `step` ran another leaf than the trace has there, or another action at the same
+    /// path. `expected` is `None` if the trace has no more steps.
+    UnexpectedNode {
+        step: usize,
+        expected: Option<(NodePath, Value)>,
+        actual: (NodePath, Value),
+    },
+    /// A change recorded for step `step` didn't apply to the replayed state.
+    DiffMismatch {
+        step: usize,
+        pointer: String,
+        message: String,
+    },
+    /// The replay finished without running the actions of the trace from `step` on.
+    MissingSteps { step: usize },
+    /// All steps replayed, yet the state differs from the recorded final state.
+    FinalState { changes: Vec<StateChange> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayResult<S> {
+    pub state: S,
+    /// `None` if the replay diverged, or the run failed.
+    pub response: Option<Response>,
+    pub divergence: Option<Divergence>,
+}
+
+// what the action leaves of a run do besides running their action
+pub(crate) enum Trace<A: Actionable> {
+    Record {
+        serialize: Serializers<A>,
+        steps: Vec<TraceStep>,
+    },
+    Replay(Replayer<A>),
+}
+
+pub(crate) struct Serializers<A: Actionable> {
+    action: fn(&A) -> Value,
+    state: fn(&A::ActionState) -> Value,
+}
+
+impl<A> Serializers<A>
+where
+    A: Actionable + Serialize,
+    A::ActionState: Serialize,
+{
+    fn new() -> Self {
+        Self {
+            action: |action| serde_json::to_value(action).unwrap_or(Value::Null),
+            state: |state| serde_json::to_value(state).unwrap_or(Value::Null),
+        }
+    }
+}
+
+pub(crate) struct Replayer<A: Actionable> {
+    serialize: Serializers<A>,
+    deserialize: fn(Value) -> Result<A::ActionState, String>,
+    steps: Vec<TraceStep>,
+    next: usize,
+    divergence: Option<Divergence>,
+}
+
+impl<A: Actionable> Replayer<A> {
+    /// Stands in for `action` at `path`: applies the changes recorded for it and returns the
+    /// recorded outcome.
+    pub(crate) fn step(
+        &mut self,
+        path: &[usize],
+        action: &A,
+        state: &mut A::ActionState,
+    ) -> Result<Response, BehaviorError<A::ActionError>> {
+        let step = self.next;
+        self.next += 1;
+        match self.apply(
+            step,
+            (path.to_vec(), (self.serialize.action)(action)),
+            state,
+        ) {
+            Ok(outcome) => match outcome {
+                Outcome::Success => Ok(Response::Success),
+                Outcome::Running => Ok(Response::Running),
+                Outcome::Failure => Ok(Response::Failure),
+                Outcome::Failed => Err(BehaviorError::Replayed(
+                    "the recorded action failed".to_string(),
+                )),
+            },
+            Err(divergence) => {
+                self.divergence = Some(divergence);
+                Err(BehaviorError::Cancelled(
+                    "the replay diverged from its trace".to_string(),
+                ))
+            }
+        }
+    }
+
+    fn apply(
+        &self,
+        step: usize,
+        actual: (NodePath, Value),
+        state: &mut A::ActionState,
+    ) -> Result<Outcome, Divergence> {
+        let expected = self
+            .steps
+            .get(step)
+            .map(|recorded| (recorded.path.clone(), recorded.action.clone()));
+        if expected.as_ref() != Some(&actual) {
+            return Err(Divergence::UnexpectedNode {
+                step,
+                expected,
+                actual,
+            });
+        }
+        let recorded = &self.steps[step];
+        let mismatch = |pointer: &str, message| Divergence::DiffMismatch {
+            step,
+            pointer: pointer.to_string(),
+            message,
+        };
+        let mut value = (self.serialize.state)(state);
+        for change in &recorded.changes {
+            apply(&mut value, change).map_err(|message| mismatch(&change.pointer, message))?;
+        }
+        *state = (self.deserialize)(value).map_err(|message| mismatch("", message))?;
+        Ok(recorded.outcome)
+    }
+}
+
+impl<A: Actionable> Trace<A> {
+    /// The state before an action runs, if the run is recorded.
+    pub(crate) fn before(&self, state: &A::ActionState) -> Option<Value> {
+        match self {
+            Trace::Record { serialize, .. } => Some((serialize.state)(state)),
+            Trace::Replay(_) => None,
+        }
+    }
+
+    pub(crate) fn record(
+        &mut self,
+        path: &[usize],
+        action: &A,
+        outcome: Outcome,
+        before: &Value,
+        state: &A::ActionState,
+    ) {
+        if let Trace::Record { serialize, steps } = self {
+            steps.push(TraceStep {
+                path: path.to_vec(),
+                action: (serialize.action)(action),
+                outcome,
+                changes: audit::changes(before, &(serialize.state)(state)),
+            });
+        }
+    }
+}
+
+/// Runs `behavior` once like [`Behavior::run`] with an rng seeded with `seed`, and records what
+/// its actions did.
+pub async fn record_full<A>(
+    behavior: &Behavior<A>,
+    seed: u64,
+    args: &A::ActionArgs,
+    state: &mut A::ActionState,
+) -> (
+    Result<Response, BehaviorError<A::ActionError>>,
+    ExecutionTrace,
+)
+where
+    A: Actionable + Serialize,
+    A::ActionState: Serialize,
+{
+    let mut ctx = RunContext {
+        rng: TreeRng::seeded(seed),
+        trace: Some(Trace::Record {
+            serialize: Serializers::new(),
+            steps: vec![],
+        }),
+        ..RunContext::default()
+    };
+    let result = behavior.run_in(&mut ctx, args, state).await;
+    let steps = match ctx.trace {
+        Some(Trace::Record { steps, .. }) => steps,
+        _ => vec![],
+    };
+    let trace = ExecutionTrace {
+        seed,
+        steps,
+        final_state: serde_json::to_value(state).unwrap_or(Value::Null),
+    };
+    (result, trace)
+}
+
+/// Runs `behavior` again from `initial_state` the way `trace` recorded it: each action leaf
+/// applies its recorded changes to the state and returns its recorded outcome instead of running.
+/// Reports where the replay first did something else than the trace.
+///
+/// `args` are only passed on to the nodes, the actions don't run.
+pub async fn replay_full<A>(
+    behavior: &Behavior<A>,
+    initial_state: A::ActionState,
+    trace: &ExecutionTrace,
+    args: &A::ActionArgs,
+) -> ReplayResult<A::ActionState>
+where
+    A: Actionable + Serialize,
+    A::ActionState: Serialize + DeserializeOwned,
+{
+    let mut ctx = RunContext {
+        rng: TreeRng::seeded(trace.seed),
+        trace: Some(Trace::Replay(Replayer {
+            serialize: Serializers::new(),
+            deserialize: |value| serde_json::from_value(value).map_err(|err| err.to_string()),
+            steps: trace.steps.clone(),
+            next: 0,
+            divergence: None,
+        })),
+        ..RunContext::default()
+    };
+    let mut state = initial_state;
+    let result = behavior.run_in(&mut ctx, args, &mut state).await;
+    let Some(Trace::Replay(replayer)) = ctx.trace else {
+        unreachable!("the replay is set above")
+    };
+
+    let divergence = replayer.divergence.or_else(|| {
+        if replayer.next < replayer.steps.len() {
+            return Some(Divergence::MissingSteps {
+                step: replayer.next,
+            });
+        }
+        let final_state = serde_json::to_value(&state).unwrap_or(Value::Null);
+        let changes = audit::changes(&trace.final_state, &final_state);
+        (!changes.is_empty()).then_some(Divergence::FinalState { changes })
+    });
+    ReplayResult {
+        state,
+        response: result.ok().filter(|_| divergence.is_none()),
+        divergence,
+    }
+}
+
+// applies `change` to `value` if the value at its pointer is still the old one
+fn apply(value: &mut Value, change: &StateChange) -> Result<(), String> {
+    let found = value.pointer(&change.pointer);
+    if found != change.old.as_ref() {
+        return Err(format!(
+            "expected {}, found {}",
+            show(change.old.as_ref()),
+            show(found)
+        ));
+    }
+    let Some((parent, last)) = change.pointer.rsplit_once('/') else {
+        // the pointer is "", the state itself changed
+        *value = change.new.clone().unwrap_or(Value::Null);
+        return Ok(());
+    };
+    let key = last.replace("~1", "/").replace("~0", "~");
+    match (value.pointer_mut(parent), change.new.clone()) {
+        (Some(Value::Object(map)), Some(new)) => {
+            map.insert(key, new);
+        }
+        (Some(Value::Object(map)), None) => {
+            map.remove(&key);
+        }
+        (Some(Value::Array(items)), new) => {
+            let index: usize = key
+                .parse()
+                .map_err(|_| format!("`{}` isn't an index", key))?;
+            match new {
+                Some(new) if index == items.len() => items.push(new),
+                Some(new) => items[index] = new,
+                None => {
+                    items.remove(index);
+                }
+            }
+        }
+        _ => return Err(format!("`{}` isn't an object or array", parent)),
+    }
+    Ok(())
+}
+
+fn show(value: Option<&Value>) -> String {
+    value.map_or_else(|| "nothing".to_string(), Value::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::audit::StateChange;
+    use crate::behavior_tree::replay::{record_full, replay_full, Divergence, Outcome};
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{Actionable, Behavior, Response};
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+    struct Ship {
+        fuel: u32,
+        at: String,
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    enum ShipAction {
+        Refuel,
+        Jump(String),
+        // fails when the tank is not full
+        CheckFull,
+    }
+
+    impl Actionable for ShipAction {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = Ship;
+
+        async fn run(&self, _: &(), ship: &mut Ship) -> Result<Response, String> {
+            match self {
+                ShipAction::Refuel => ship.fuel = 100,
+                ShipAction::Jump(to) => {
+                    ship.fuel -= 40;
+                    ship.at = to.clone();
+                }
+                ShipAction::CheckFull if ship.fuel < 100 => return Ok(Response::Failure),
+                ShipAction::CheckFull => {}
+            }
+            Ok(Response::Success)
+        }
+    }
+
+    fn jump(to: &str) -> Behavior<ShipAction> {
+        Action(ShipAction::Jump(to.to_string()))
+    }
+
+    fn tree() -> Behavior<ShipAction> {
+        Sequence(vec![
+            Select(vec![
+                Action(ShipAction::CheckFull),
+                Action(ShipAction::Refuel),
+            ]),
+            jump("X1-A"),
+            jump("X1-B"),
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_replay_follows_the_recorded_run() {
+        let (result, trace) = record_full(&tree(), 1, &(), &mut Ship::default()).await;
+        assert_eq!(result.unwrap(), Response::Success);
+        let outcomes: Vec<_> = trace.steps.iter().map(|step| step.outcome).collect();
+        assert_eq!(
+            outcomes,
+            [
+                Outcome::Failure,
+                Outcome::Success,
+                Outcome::Success,
+                Outcome::Success
+            ]
+        );
+        assert_eq!(trace.final_state, json!({"fuel": 20, "at": "X1-B"}));
+
+        let replayed = replay_full(&tree(), Ship::default(), &trace, &()).await;
+        assert_eq!(replayed.divergence, None);
+        assert_eq!(replayed.response, Some(Response::Success));
+        assert_eq!(
+            replayed.state,
+            Ship {
+                fuel: 20,
+                at: "X1-B".to_string()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replay_reports_the_first_divergence() {
+        let (_, trace) = record_full(&tree(), 1, &(), &mut Ship::default()).await;
+
+        // the tree got another jump before the first one
+        let mut changed = tree();
+        if let Sequence(children) = &mut changed {
+            children.insert(1, jump("X1-C"));
+        }
+        let replayed = replay_full(&changed, Ship::default(), &trace, &()).await;
+        assert_eq!(
+            replayed.divergence,
+            Some(Divergence::UnexpectedNode {
+                step: 2,
+                expected: Some((vec![1], json!({"Jump": "X1-A"}))),
+                actual: (vec![1], json!({"Jump": "X1-C"})),
+            })
+        );
+        assert_eq!(replayed.response, None);
+    }
+
+    #[tokio::test]
+    async fn test_replay_reports_state_that_doesnt_match_the_diffs() {
+        let (_, trace) = record_full(&tree(), 1, &(), &mut Ship::default()).await;
+
+        let replayed = replay_full(
+            &tree(),
+            Ship {
+                fuel: 50,
+                ..Ship::default()
+            },
+            &trace,
+            &(),
+        )
+        .await;
+        assert_eq!(
+            replayed.divergence,
+            Some(Divergence::DiffMismatch {
+                step: 1,
+                pointer: "/fuel".to_string(),
+                message: "expected 0, found 50".to_string(),
+            })
+        );
+
+        let replayed = replay_full(&Sequence(vec![tree()]), Ship::default(), &trace, &()).await;
+        assert!(matches!(
+            replayed.divergence,
+            Some(Divergence::UnexpectedNode { step: 0, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_replay_reports_a_different_final_state() {
+        let (_, mut trace) = record_full(&tree(), 1, &(), &mut Ship::default()).await;
+        trace.final_state["fuel"] = json!(25);
+
+        let replayed = replay_full(&tree(), Ship::default(), &trace, &()).await;
+        assert_eq!(
+            replayed.divergence,
+            Some(Divergence::FinalState {
+                changes: vec![StateChange {
+                    pointer: "/fuel".to_string(),
+                    old: Some(json!(25)),
+                    new: Some(json!(20)),
+                }]
+            })
+        );
+    }
+}
@@ -0,0 +1,639 @@
+use crate::behavior_tree::audit::StateChange;
+use crate::behavior_tree::observer::{BehaviorObserver, TraceEvent, TreeEvent};
+use crate::behavior_tree::replay::{ExecutionTrace, Outcome, TraceStep};
+use crate::behavior_tree::{Behavior, BehaviorError, NodePath, Response};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::sync::{Arc, Mutex};
+
+/// The most rows a section of a report lists; the rest are counted.
+pub const MAX_ROWS: usize = 1000;
+
+/// What happened at the nodes of a tree, collected from traces, observed events and errors for
+/// [`to_html`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TreeStats {
+    pub nodes: BTreeMap<NodePath, NodeStats>,
+    /// The events in the order they were sent, with the path of the node that sent them.
+    pub events: Vec<(NodePath, TreeEvent)>,
+    /// Error messages with the path of the node they happened at, `[]` if that isn't known.
+    pub errors: Vec<(NodePath, String)>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeStats {
+    pub successes: u64,
+    pub running: u64,
+    pub failures: u64,
+    pub last: Option<Outcome>,
+}
+
+impl NodeStats {
+    pub fn runs(&self) -> u64 {
+        self.successes + self.running + self.failures
+    }
+
+    fn record(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Success => self.successes += 1,
+            Outcome::Running => self.running += 1,
+            Outcome::Failure | Outcome::Failed => self.failures += 1,
+        }
+        self.last = Some(outcome);
+    }
+}
+
+impl TreeStats {
+    pub fn from_trace(trace: &ExecutionTrace) -> Self {
+        let mut stats = Self::default();
+        stats.record_trace(trace);
+        stats
+    }
+
+    /// Counts the outcomes of the steps of `trace`. Failed steps are recorded as errors too.
+    pub fn record_trace(&mut self, trace: &ExecutionTrace) {
+        for step in &trace.steps {
+            self.record_outcome(&step.path, step.outcome);
+            if step.outcome == Outcome::Failed {
+                self.record_error(
+                    step.path.clone(),
+                    format!("{} failed", compact(&step.action)),
+                );
+            }
+        }
+    }
+
+    /// Counts the outcomes of the nodes a
+    /// [`RecordingObserver`](crate::behavior_tree::observer::RecordingObserver) saw exit, errors
+    /// as `Failed`.
+    pub fn record_exits(&mut self, events: &[TraceEvent]) {
+        for event in events {
+            if let TraceEvent::Exit { path, result } = event {
+                let outcome = match result {
+                    Ok(Response::Success) => Outcome::Success,
+                    Ok(Response::Running) => Outcome::Running,
+                    Ok(Response::Failure) => Outcome::Failure,
+                    Err(_) => Outcome::Failed,
+                };
+                self.record_outcome(path, outcome);
+            }
+        }
+    }
+
+    pub fn record_outcome(&mut self, path: &[usize], outcome: Outcome) {
+        self.nodes.entry(path.to_vec()).or_default().record(outcome);
+    }
+
+    pub fn record_event(&mut self, path: &[usize], event: &TreeEvent) {
+        self.events.push((path.to_vec(), event.clone()));
+    }
+
+    pub fn record_error(&mut self, path: NodePath, message: impl Into<String>) {
+        self.errors.push((path, message.into()));
+    }
+
+    /// Records the error of a failed run, at the path it started at if it knows it.
+    pub fn record_result<E: fmt::Display>(&mut self, result: &Result<Response, BehaviorError<E>>) {
+        if let Err(err) = result {
+            let path = err.origin().map_or_else(Vec::new, <[usize]>::to_vec);
+            self.record_error(path, err.to_string());
+        }
+    }
+}
+
+// lets shared stats collect the events of an instance they were registered with
+impl<A> BehaviorObserver<A> for Arc<Mutex<TreeStats>> {
+    fn on_event(&mut self, path: &[usize], event: &TreeEvent) {
+        self.lock().unwrap().record_event(path, event);
+    }
+}
+
+/// Renders `behavior` with how it ran as a standalone HTML page, with its styles and script
+/// inline: the tree with each node colored by its last outcome, the errors, per-node counts,
+/// the event timeline and the steps of `trace` with what they changed in the state. Sections
+/// list at most [`MAX_ROWS`] rows and say how many they left out.
+pub fn to_html<A: Serialize>(
+    behavior: &Behavior<A>,
+    trace: &ExecutionTrace,
+    stats: &TreeStats,
+) -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>Behavior tree report</title>\n<style>\n");
+    html.push_str(STYLE);
+    html.push_str("</style>\n</head>\n<body>\n<h1>Behavior tree report</h1>\n");
+    let _ = writeln!(
+        html,
+        "<p class=\"summary\">seed {} &middot; {} steps &middot; {} events &middot; {} errors</p>",
+        trace.seed,
+        trace.steps.len(),
+        stats.events.len(),
+        stats.errors.len()
+    );
+
+    html.push_str("<section id=\"tree\">\n<h2>Tree</h2>\n");
+    html.push_str("<button type=\"button\" data-toggle=\"open\">Expand all</button>\n");
+    html.push_str("<button type=\"button\" data-toggle=\"close\">Collapse all</button>\n");
+    let mut path = vec![];
+    tree_html(&mut html, behavior, &mut path, stats);
+    html.push_str("</section>\n");
+
+    section(
+        &mut html,
+        "errors",
+        "Errors",
+        &["Node", "Error"],
+        &stats.errors,
+        |html, (path, message)| {
+            let _ = write!(
+                html,
+                "<td>{}</td><td>{}</td>",
+                path_link(path),
+                escape(message)
+            );
+        },
+    );
+    let nodes: Vec<_> = stats.nodes.iter().collect();
+    section(
+        &mut html,
+        "stats",
+        "Nodes",
+        &["Node", "Runs", "Successes", "Running", "Failures", "Last"],
+        &nodes,
+        |html, (path, node)| {
+            let _ = write!(
+                html,
+                "<td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>",
+                path_link(path),
+                node.runs(),
+                node.successes,
+                node.running,
+                node.failures,
+                node.last.map_or("-", outcome_name)
+            );
+        },
+    );
+    section(
+        &mut html,
+        "timeline",
+        "Events",
+        &["#", "Node", "Event"],
+        &stats.events.iter().enumerate().collect::<Vec<_>>(),
+        |html, (i, (path, event))| {
+            let _ = write!(html, "<td>{}</td><td>{}</td><td>", i, path_link(path));
+            event_html(html, event);
+            html.push_str("</td>");
+        },
+    );
+    section(
+        &mut html,
+        "steps",
+        "Steps",
+        &["#", "Node", "Action", "Outcome", "State changes"],
+        &trace.steps.iter().enumerate().collect::<Vec<_>>(),
+        |html, (i, step)| step_html(html, *i, step),
+    );
+
+    html.push_str("<script>\n");
+    html.push_str(SCRIPT);
+    html.push_str("</script>\n</body>\n</html>\n");
+    html
+}
+
+fn tree_html<A: Serialize>(
+    html: &mut String,
+    node: &Behavior<A>,
+    path: &mut NodePath,
+    stats: &TreeStats,
+) {
+    let status = stats
+        .nodes
+        .get(path.as_slice())
+        .and_then(|node| node.last)
+        .map_or("none", outcome_name);
+    let (kind, detail) = label(node);
+    let mut summary = format!("<span class=\"kind\">{}</span>", kind);
+    if let Some(detail) = detail {
+        let _ = write!(
+            summary,
+            " <span class=\"detail\">{}</span>",
+            escape(&detail)
+        );
+    }
+    if let Some(node) = stats.nodes.get(path.as_slice()) {
+        let _ = write!(summary, " <span class=\"runs\">{} runs</span>", node.runs());
+    }
+    let _ = write!(summary, " <span class=\"path\">{:?}</span>", path);
+
+    let id = node_id(path);
+    let children = node.children();
+    if children.is_empty() {
+        let _ = writeln!(
+            html,
+            "<div class=\"node leaf status-{}\" id=\"{}\">{}</div>",
+            status, id, summary
+        );
+        return;
+    }
+    let _ = writeln!(
+        html,
+        "<details open class=\"node status-{}\" id=\"{}\"><summary>{}</summary>",
+        status, id, summary
+    );
+    for (i, child) in children.into_iter().enumerate() {
+        path.push(i);
+        tree_html(html, child, path, stats);
+        path.pop();
+    }
+    html.push_str("</details>\n");
+}
+
+// the kind of a node and what sets it apart from other nodes of its kind
+pub(crate) fn label<A: Serialize>(node: &Behavior<A>) -> (&'static str, Option<String>) {
+    let serialized =
+        |value: &A| serde_json::to_value(value).map_or_else(|_| "?".to_string(), |v| compact(&v));
+    match node {
+        Behavior::Action(action) => ("Action", Some(serialized(action))),
+        Behavior::Invert(_) => ("Invert", None),
+        Behavior::Select(_) => ("Select", None),
+        Behavior::Sequence(_) => ("Sequence", None),
+        Behavior::ReactiveSequence(_) => ("ReactiveSequence", None),
+        Behavior::ReactiveSelect(_) => ("ReactiveSelect", None),
+        Behavior::While { .. } => ("While", None),
+        Behavior::LazySequence(_) => ("LazySequence", None),
+        Behavior::LazySelect(_) => ("LazySelect", None),
+        Behavior::AdaptiveSelect { strategy, .. } => {
+            ("AdaptiveSelect", Some(format!("{:?}", strategy)))
+        }
+        Behavior::RandomSelect(_) => ("RandomSelect", None),
+        Behavior::WeightedSelect(children) => {
+            let weights: Vec<_> = children.iter().map(|(w, _)| w.to_string()).collect();
+            ("WeightedSelect", Some(weights.join(", ")))
+        }
+        Behavior::Parallel { policy, .. } => ("Parallel", Some(format!("{:?}", policy))),
+        Behavior::Experiment { key, variants, .. } => {
+            let names: Vec<_> = variants.iter().map(|v| v.name.as_str()).collect();
+            ("Experiment", Some(format!("{}: {}", key, names.join(", "))))
+        }
+        Behavior::CheckKey { key, value } => ("CheckKey", Some(format!("{} = {}", key, value))),
+        Behavior::SetKey { key, value } => ("SetKey", Some(format!("{} = {}", key, value))),
+        Behavior::Pipeline(stages) => {
+            let names: Vec<_> = stages.iter().map(|stage| stage.name.as_str()).collect();
+            ("Pipeline", Some(names.join(" -> ")))
+        }
+        Behavior::OnChanged { key, .. } => ("OnChanged", Some(key.clone())),
+        Behavior::Expr { source } => ("Expr", Some(source.to_string())),
+        Behavior::Compare { left, op, right } => {
+            ("Compare", Some(format!("{} {} {}", left, op, right)))
+        }
+        Behavior::Log { level, message } => ("Log", Some(format!("{:?}: {}", level, message))),
+        Behavior::Throw { code, .. } => ("Throw", Some(code.clone())),
+        Behavior::TryCatch { .. } => ("TryCatch", None),
+        Behavior::SleepUntil { until } => ("SleepUntil", Some(until.to_string())),
+        Behavior::Schedule { windows } => {
+            let windows: Vec<_> = windows.iter().map(|w| w.spec()).collect();
+            ("Schedule", Some(windows.join(", ")))
+        }
+        Behavior::GracefulTimeout { soft, hard, .. } => {
+            ("GracefulTimeout", Some(format!("{:?}/{:?}", soft, hard)))
+        }
+        Behavior::StallGuard {
+            stall_after,
+            cancel,
+            ..
+        } => (
+            "StallGuard",
+            Some(format!(
+                "{:?}{}",
+                stall_after,
+                if *cancel { ", cancel" } else { "" }
+            )),
+        ),
+        Behavior::Retry {
+            max_attempts,
+            backoff,
+            ..
+        } => (
+            "Retry",
+            Some(match backoff {
+                Some(backoff) => format!("{} attempts, {:?} apart", max_attempts, backoff),
+                None => format!("{} attempts", max_attempts),
+            }),
+        ),
+        Behavior::Repeat { times, .. } => ("Repeat", Some(format!("{} times", times))),
+        Behavior::Timeout { duration, .. } => ("Timeout", Some(format!("{:?}", duration))),
+        Behavior::Cooldown { duration, .. } => ("Cooldown", Some(format!("{:?}", duration))),
+        Behavior::Jitter { min, max } => ("Jitter", Some(format!("{:?}..{:?}", min, max))),
+        Behavior::Breakpoint { label } => ("Breakpoint", Some(label.clone())),
+        Behavior::Named { name, .. } => ("Named", Some(name.clone())),
+        Behavior::Namespaced { namespace, .. } => ("Namespaced", Some(namespace.clone())),
+        Behavior::AlwaysFail => ("AlwaysFail", None),
+        Behavior::Spawn { handle_key, .. } => ("Spawn", Some(handle_key.clone())),
+        Behavior::Join { handle_key, .. } => ("Join", Some(handle_key.clone())),
+        Behavior::Subtree { name, .. } => ("Subtree", Some(name.clone())),
+        Behavior::Decorated { decorator, .. } => ("Decorated", Some(decorator.name())),
+        Behavior::Composite { node, .. } => ("Composite", Some(node.name())),
+        Behavior::Opaque { kind, .. } => ("Opaque", Some(kind.clone())),
+        Behavior::Assert { message, .. } => ("Assert", Some(message.clone())),
+    }
+}
+
+// a table of `rows`, of which at most `MAX_ROWS` are listed
+fn section<T>(
+    html: &mut String,
+    id: &str,
+    title: &str,
+    columns: &[&str],
+    rows: &[T],
+    mut row: impl FnMut(&mut String, &T),
+) {
+    let _ = writeln!(
+        html,
+        "<section id=\"{}\">\n<h2>{} ({})</h2>",
+        id,
+        title,
+        rows.len()
+    );
+    if rows.is_empty() {
+        html.push_str("<p class=\"empty\">none</p>\n</section>\n");
+        return;
+    }
+    html.push_str("<table>\n<tr>");
+    for column in columns {
+        let _ = write!(html, "<th>{}</th>", column);
+    }
+    html.push_str("</tr>\n");
+    for item in rows.iter().take(MAX_ROWS) {
+        html.push_str("<tr>");
+        row(html, item);
+        html.push_str("</tr>\n");
+    }
+    if rows.len() > MAX_ROWS {
+        let _ = writeln!(
+            html,
+            "<tr class=\"more\"><td colspan=\"{}\">&hellip; {} more not shown</td></tr>",
+            columns.len(),
+            rows.len() - MAX_ROWS
+        );
+    }
+    html.push_str("</table>\n</section>\n");
+}
+
+fn event_html(html: &mut String, event: &TreeEvent) {
+    if let TreeEvent::StateChanged { changes, truncated } = event {
+        html.push_str("<span class=\"kind\">StateChanged</span>");
+        changes_html(html, changes);
+        if *truncated {
+            html.push_str("<span class=\"detail\">(more changes not listed)</span>");
+        }
+        return;
+    }
+    match serde_json::to_value(event) {
+        Ok(Value::Object(map)) => {
+            for (kind, content) in map {
+                let _ = write!(
+                    html,
+                    "<span class=\"kind\">{}</span> <span class=\"detail\">{}</span>",
+                    escape(&kind),
+                    escape(&compact(&content))
+                );
+            }
+        }
+        _ => html.push_str(&escape(&format!("{:?}", event))),
+    }
+}
+
+fn step_html(html: &mut String, i: usize, step: &TraceStep) {
+    let outcome = outcome_name(step.outcome);
+    let _ = write!(
+        html,
+        "<td>{}</td><td>{}</td><td>{}</td><td class=\"status-{}\">{}</td><td>",
+        i,
+        path_link(&step.path),
+        escape(&compact(&step.action)),
+        outcome,
+        outcome
+    );
+    changes_html(html, &step.changes);
+    html.push_str("</td>");
+}
+
+fn changes_html(html: &mut String, changes: &[StateChange]) {
+    if changes.is_empty() {
+        return;
+    }
+    html.push_str("<ul class=\"diff\">");
+    for change in changes.iter().take(MAX_ROWS) {
+        let value = |value: &Option<Value>| value.as_ref().map_or("-".to_string(), compact);
+        let _ = write!(
+            html,
+            "<li><code>{}</code> <del>{}</del> &rarr; <ins>{}</ins></li>",
+            escape(&change.pointer),
+            escape(&value(&change.old)),
+            escape(&value(&change.new))
+        );
+    }
+    if changes.len() > MAX_ROWS {
+        let _ = write!(
+            html,
+            "<li class=\"more\">&hellip; {} more not shown</li>",
+            changes.len() - MAX_ROWS
+        );
+    }
+    html.push_str("</ul>");
+}
+
+fn path_link(path: &[usize]) -> String {
+    format!("<a href=\"#{}\">{:?}</a>", node_id(path), path)
+}
+
+fn node_id(path: &[usize]) -> String {
+    let mut id = "node".to_string();
+    for i in path {
+        let _ = write!(id, "-{}", i);
+    }
+    id
+}
+
+pub(crate) fn outcome_name(outcome: Outcome) -> &'static str {
+    match outcome {
+        Outcome::Success => "success",
+        Outcome::Running => "running",
+        Outcome::Failure => "failure",
+        Outcome::Failed => "failed",
+    }
+}
+
+pub(crate) fn compact(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em; color: #222; }
+.summary, .path, .empty { color: #777; }
+.node { margin-left: 1.2em; padding: 1px 4px; border-left: 3px solid #ccc; }
+.leaf { margin-left: 2.2em; }
+.kind { font-weight: bold; }
+.detail { font-family: monospace; }
+.runs { color: #555; font-size: 90%; }
+.status-success { border-left-color: #2a9d3f; }
+.status-running { border-left-color: #e0a100; }
+.status-failure { border-left-color: #e76f51; }
+.status-failed { border-left-color: #d62828; background: #fdecea; }
+td.status-success { color: #2a9d3f; }
+td.status-running { color: #b07d00; }
+td.status-failure { color: #e76f51; }
+td.status-failed { color: #d62828; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { border: 1px solid #ddd; padding: 2px 6px; text-align: left; vertical-align: top; }
+tr.more td, li.more { color: #777; font-style: italic; }
+ul.diff { margin: 0; padding-left: 1em; font-family: monospace; }
+del { color: #d62828; }
+ins { color: #2a9d3f; text-decoration: none; }
+:target { outline: 2px solid #4a7bd6; }
+";
+
+const SCRIPT: &str = "\
+document.querySelectorAll('button[data-toggle]').forEach(function (button) {
+  button.addEventListener('click', function () {
+    var open = button.dataset.toggle === 'open';
+    document.querySelectorAll('#tree details').forEach(function (d) { d.open = open; });
+  });
+});
+function reveal() {
+  var node = location.hash && document.getElementById(location.hash.slice(1));
+  for (; node; node = node.parentElement) {
+    if (node.tagName === 'DETAILS') node.open = true;
+  }
+}
+window.addEventListener('hashchange', reveal);
+reveal();
+";
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::observer::LogLevel;
+    use crate::behavior_tree::replay::{record_full, ExecutionTrace, Outcome, TraceStep};
+    use crate::behavior_tree::report::{to_html, TreeStats, MAX_ROWS};
+    use crate::behavior_tree::toggle::WhenDisabled;
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{Actionable, Behavior, Response, TreeInstance};
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    enum Ship {
+        Dock,
+        Refuel { units: u32 },
+    }
+
+    impl Actionable for Ship {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = u32;
+
+        async fn run(&self, _: &(), fuel: &mut u32) -> Result<Response, String> {
+            match self {
+                Ship::Dock => Ok(Response::Success),
+                Ship::Refuel { units } if *fuel + units > 100 => Ok(Response::Failure),
+                Ship::Refuel { units } => {
+                    *fuel += units;
+                    Ok(Response::Success)
+                }
+            }
+        }
+    }
+
+    fn refuel() -> Behavior<Ship> {
+        Named {
+            name: "refuel trip".to_string(),
+            when_disabled: WhenDisabled::Fail,
+            child: Box::new(Sequence(vec![
+                Log {
+                    level: LogLevel::Info,
+                    message: "docking".to_string(),
+                },
+                Action(Ship::Dock),
+                Action(Ship::Refuel { units: 30 }),
+                Assert {
+                    condition: Box::new(Action(Ship::Refuel { units: 50 })),
+                    message: "<tank> would overflow".to_string(),
+                },
+            ])),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_report_shows_the_run() {
+        let stats = Arc::new(Mutex::new(TreeStats::default()));
+        let mut instance = TreeInstance::new(refuel()).with_observer(stats.clone());
+        let result = instance.run(&(), &mut 40).await;
+        let (_, trace) = record_full(&refuel(), 7, &(), &mut 40).await;
+        let mut stats = stats.lock().unwrap().clone();
+        stats.record_result(&result);
+        stats.record_trace(&trace);
+
+        let html = to_html(&refuel(), &trace, &stats);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(
+            !html.contains("http"),
+            "the report links to nothing outside"
+        );
+        assert!(html.contains("<style>") && html.contains("<script>"));
+        for marker in [
+            "<p class=\"summary\">seed 7 &middot; 3 steps &middot; 1 events &middot; 1 errors</p>",
+            "<details open class=\"node status-none\" id=\"node\"><summary><span class=\"kind\">Named</span> <span class=\"detail\">refuel trip</span>",
+            "<div class=\"node leaf status-success\" id=\"node-0-1\"><span class=\"kind\">Action</span> <span class=\"detail\">Dock</span> <span class=\"runs\">1 runs</span>",
+            "<div class=\"node leaf status-failure\" id=\"node-0-3-0\">",
+            "<td><a href=\"#node-0-3\">[0, 3]</a></td><td>assertion failed at [0, 3]: &lt;tank&gt; would overflow</td>",
+            "<span class=\"kind\">LogEmitted</span>",
+            "<li><code></code> <del>40</del> &rarr; <ins>70</ins></li>",
+            "<h2>Steps (3)</h2>",
+        ] {
+            assert!(html.contains(marker), "missing {}\n{}", marker, html);
+        }
+    }
+
+    #[test]
+    fn test_long_traces_are_truncated() {
+        let step = TraceStep {
+            path: vec![0, 1],
+            action: json!("Dock"),
+            outcome: Outcome::Success,
+            changes: vec![],
+        };
+        let trace = ExecutionTrace {
+            seed: 0,
+            steps: vec![step; MAX_ROWS + 25],
+            final_state: json!(0),
+        };
+        let html = to_html(&refuel(), &trace, &TreeStats::from_trace(&trace));
+        assert!(html.contains("&hellip; 25 more not shown"));
+        assert!(html.contains(&format!(
+            "<span class=\"runs\">{} runs</span>",
+            MAX_ROWS + 25
+        )));
+        assert_eq!(html.matches("<td>Dock</td>").count(), MAX_ROWS);
+    }
+}
@@ -0,0 +1,89 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// Small, seedable pseudo random number generator (SplitMix64) used by every node that needs
+/// randomness, so a tree run can be reproduced from its seed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TreeRng {
+    state: u64,
+}
+
+impl TreeRng {
+    pub fn seeded(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn from_entropy() -> Self {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(0x5eed);
+        Self::seeded(hasher.finish())
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniformly distributed float in `[0, 1)`.
+    pub fn gen_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniformly distributed integer in `[low, high]`. `low` must not be greater than `high`.
+    pub fn gen_inclusive(&mut self, low: u64, high: u64) -> u64 {
+        match (high - low).checked_add(1) {
+            Some(len) => low + self.next_u64() % len,
+            None => self.next_u64(),
+        }
+    }
+
+    /// Uniformly distributed index in `[0, len)`. `len` must not be zero.
+    pub fn gen_index(&mut self, len: usize) -> usize {
+        (self.gen_f64() * len as f64) as usize
+    }
+
+    /// Puts `items` in a uniformly random order.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.gen_index(i + 1));
+        }
+    }
+}
+
+impl Default for TreeRng {
+    fn default() -> Self {
+        Self::from_entropy()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::rng::TreeRng;
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let mut a = TreeRng::seeded(42);
+        let mut b = TreeRng::seeded(42);
+
+        let xs: Vec<u64> = (0..10).map(|_| a.next_u64()).collect();
+        let ys: Vec<u64> = (0..10).map(|_| b.next_u64()).collect();
+        assert_eq!(xs, ys);
+    }
+
+    #[test]
+    fn test_ranges() {
+        let mut rng = TreeRng::seeded(7);
+
+        for _ in 0..1000 {
+            let f = rng.gen_f64();
+            assert!((0.0..1.0).contains(&f));
+            assert!(rng.gen_index(3) < 3);
+        }
+    }
+}
@@ -0,0 +1,372 @@
+use crate::behavior_tree::cancel::CancellationToken;
+use crate::behavior_tree::debugger::DebugController;
+use crate::behavior_tree::observer::{BehaviorObserver, TreeEvent};
+use crate::behavior_tree::{Actionable, BehaviorError, NodePath, Response, TreeInstance};
+use futures_core::Stream;
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::future::poll_fn;
+use std::mem;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+/// What a failing `Assert` does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AssertMode {
+    // abort the whole tree run with an `AssertionFailed` error
+    #[default]
+    Fatal,
+    // emit a `Warn` level `LogEmitted` event and let the assert succeed
+    Warn,
+}
+
+/// Drives a tree instance tick by tick with run-wide options applied.
+pub struct Runner<A: Actionable> {
+    instance: TreeInstance<A>,
+    assert_mode: AssertMode,
+    debugger: Option<DebugController>,
+    cancellation: CancellationToken,
+    ticks: u64,
+    results_buffer: usize,
+    results: Option<Results<A::ActionError>>,
+    // the events of the current tick, once results were asked for
+    events: Option<EventCollector>,
+}
+
+struct Results<E> {
+    subscribers: Vec<Weak<Mutex<Subscriber>>>,
+    describe: fn(&BehaviorError<E>) -> String,
+}
+
+impl<E> Results<E> {
+    fn send(&mut self, result: TickResult, buffer: usize) {
+        self.subscribers.retain(|subscriber| {
+            let Some(subscriber) = subscriber.upgrade() else {
+                return false;
+            };
+            let mut subscriber = subscriber.lock().unwrap();
+            if subscriber.results.len() >= buffer {
+                subscriber.results.pop_front();
+                subscriber.missed += 1;
+            }
+            subscriber.results.push_back(result.clone());
+            subscriber.wake();
+            true
+        });
+    }
+}
+
+// the results of this run end with the sender
+impl<E> Drop for Results<E> {
+    fn drop(&mut self) {
+        for subscriber in self.subscribers.iter().filter_map(Weak::upgrade) {
+            let mut subscriber = subscriber.lock().unwrap();
+            subscriber.closed = true;
+            subscriber.wake();
+        }
+    }
+}
+
+// the results one `TickResults` didn't take yet
+#[derive(Default)]
+struct Subscriber {
+    results: VecDeque<TickResult>,
+    missed: u64,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl Subscriber {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Summary of one tick of a [`Runner`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TickResult {
+    /// Counts the ticks of the runner from 1.
+    pub tick: u64,
+    /// The error is the `Display` output of the tick's error.
+    pub response: Result<Response, String>,
+    pub duration: Duration,
+    /// The nodes the tick ran in the order they started.
+    pub visited: Vec<NodePath>,
+    /// The events the tick emitted, with the path of the node that emitted them.
+    pub events: Vec<(NodePath, TreeEvent)>,
+}
+
+/// The tick results of a runner as a [`Stream`], from the next tick until the tree finishes:
+/// the tick that succeeds or fails is the last item.
+///
+/// The runner never waits for a consumer. It keeps the results that weren't taken yet in a
+/// buffer of `Runner::with_results_buffer` items, and drops the oldest ones when it is full;
+/// [`TickResults::missed`] counts them.
+pub struct TickResults {
+    // `None` once the last result was taken
+    subscriber: Option<Arc<Mutex<Subscriber>>>,
+    missed: u64,
+}
+
+impl TickResults {
+    /// The next result, `None` once the last one was taken.
+    pub async fn next(&mut self) -> Option<TickResult> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+
+    /// How many results were dropped because they weren't taken in time.
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+}
+
+impl Stream for TickResults {
+    type Item = TickResult;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<TickResult>> {
+        let Some(shared) = self.subscriber.clone() else {
+            return Poll::Ready(None);
+        };
+        let mut subscriber = shared.lock().unwrap();
+        self.missed += mem::take(&mut subscriber.missed);
+        if let Some(result) = subscriber.results.pop_front() {
+            return Poll::Ready(Some(result));
+        }
+        if subscriber.closed {
+            drop(subscriber);
+            self.subscriber = None;
+            return Poll::Ready(None);
+        }
+        subscriber.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[derive(Clone, Default)]
+struct EventCollector(Arc<Mutex<Vec<(NodePath, TreeEvent)>>>);
+
+impl<A> BehaviorObserver<A> for EventCollector {
+    fn on_event(&mut self, path: &[usize], event: &TreeEvent) {
+        self.0.lock().unwrap().push((path.to_vec(), event.clone()));
+    }
+}
+
+impl<A> Runner<A>
+where
+    A: Actionable,
+{
+    pub fn new(instance: TreeInstance<A>) -> Self {
+        Self {
+            instance,
+            assert_mode: AssertMode::default(),
+            debugger: None,
+            cancellation: CancellationToken::new(),
+            ticks: 0,
+            results_buffer: 16,
+            results: None,
+            events: None,
+        }
+    }
+
+    pub fn with_assert_mode(mut self, assert_mode: AssertMode) -> Self {
+        self.assert_mode = assert_mode;
+        self
+    }
+
+    /// Enables debugging: breakpoints pause the tree until `controller` resumes it.
+    pub fn with_debugger(mut self, controller: DebugController) -> Self {
+        self.debugger = Some(controller);
+        self
+    }
+
+    /// Lets `token` interrupt the ticks of this runner while they wait, e.g. in `SleepUntil`,
+    /// and stop them before they start another node.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
+
+    /// How many tick results are kept for slow consumers of [`Runner::results`], 16 by default.
+    pub fn with_results_buffer(mut self, size: usize) -> Self {
+        self.results_buffer = size.max(1);
+        self
+    }
+
+    /// Subscribes to the results of the ticks from the next one until the tree finishes.
+    pub fn results(&mut self) -> TickResults
+    where
+        A::ActionError: Display,
+    {
+        if self.events.is_none() {
+            let events = EventCollector::default();
+            self.instance
+                .context
+                .observers
+                .push(Box::new(events.clone()));
+            self.events = Some(events);
+        }
+        self.instance.context.visited.get_or_insert_with(Vec::new);
+        let results = self.results.get_or_insert_with(|| Results {
+            subscribers: vec![],
+            describe: |err| err.to_string(),
+        });
+        let subscriber = Arc::default();
+        results.subscribers.push(Arc::downgrade(&subscriber));
+        TickResults {
+            subscriber: Some(subscriber),
+            missed: 0,
+        }
+    }
+
+    pub fn instance(&self) -> &TreeInstance<A> {
+        &self.instance
+    }
+
+    pub fn instance_mut(&mut self) -> &mut TreeInstance<A> {
+        &mut self.instance
+    }
+
+    /// Makes the next tick start the tree over instead of resuming its running nodes, as
+    /// [`TreeInstance::reset`] does.
+    pub fn reset(&mut self) {
+        self.instance.reset();
+    }
+
+    pub async fn tick(
+        &mut self,
+        args: &A::ActionArgs,
+        state: &mut A::ActionState,
+    ) -> Result<Response, BehaviorError<A::ActionError>> {
+        self.instance.context.assert_mode = self.assert_mode;
+        self.instance.context.debugger = self.debugger.clone();
+        self.instance.context.cancellation = self.cancellation.clone();
+        self.ticks += 1;
+        let start = self.instance.context.sleeper.now();
+        let (result, visited) = self.instance.run_visiting(args, state).await;
+        let events = match &self.events {
+            Some(events) => mem::take(&mut *events.0.lock().unwrap()),
+            None => vec![],
+        };
+
+        if let Some(results) = &mut self.results {
+            // nobody listening is fine, the results are only offered
+            let tick = TickResult {
+                tick: self.ticks,
+                response: result.as_ref().copied().map_err(results.describe),
+                duration: self.instance.context.sleeper.now() - start,
+                visited: visited.unwrap_or_default(),
+                events,
+            };
+            results.send(tick, self.results_buffer);
+            if !matches!(result, Ok(Response::Running)) {
+                // dropping the sender ends the results of this run
+                self.results = None;
+            }
+        }
+        result
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use crate::behavior_tree::observer::{LogLevel, TreeEvent};
+    use crate::behavior_tree::runner::{Runner, TickResult};
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{Actionable, Response, TreeInstance};
+    use futures_util::StreamExt;
+    use std::time::Duration;
+
+    // counts the state down by one per tick, running until it reaches 0
+    #[derive(Clone, Debug)]
+    struct Countdown;
+
+    impl Actionable for Countdown {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = i32;
+
+        async fn run(&self, _: &(), left: &mut i32) -> Result<Response, String> {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            *left -= 1;
+            match *left {
+                0 => Ok(Response::Success),
+                left if left > 0 => Ok(Response::Running),
+                _ => Err("counted past 0".to_string()),
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_tick_results_while_ticking() {
+        // the inverted log fails, so the select goes on to pass on the countdown's response; the
+        // ticks after the first resume at the running countdown
+        let bt = Select(vec![
+            Invert(Box::new(Log {
+                level: LogLevel::Info,
+                message: "counting down".to_string(),
+            })),
+            Action(Countdown),
+        ]);
+        let mut runner = Runner::new(TreeInstance::new(bt));
+        let results = runner.results();
+        let mut left = 3;
+
+        let ticking = async {
+            while runner.tick(&(), &mut left).await.unwrap() == Response::Running {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        };
+        let consuming = results.collect::<Vec<_>>();
+        let ((), items) = tokio::join!(ticking, consuming);
+
+        let expected: Vec<_> = [Response::Running, Response::Running, Response::Success]
+            .into_iter()
+            .zip(1..)
+            .map(|(response, tick)| TickResult {
+                tick,
+                response: Ok(response),
+                duration: Duration::from_millis(10),
+                visited: match tick {
+                    1 => vec![vec![], vec![0], vec![0, 0], vec![1]],
+                    _ => vec![vec![], vec![1]],
+                },
+                events: match tick {
+                    1 => vec![(
+                        vec![0, 0],
+                        TreeEvent::LogEmitted {
+                            level: LogLevel::Info,
+                            message: "counting down".to_string(),
+                        },
+                    )],
+                    _ => vec![],
+                },
+            })
+            .collect();
+        assert_eq!(items, expected);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_tick_results_drop_the_oldest_and_end_with_the_error() {
+        let mut runner = Runner::new(TreeInstance::new(Action(Countdown))).with_results_buffer(1);
+        let mut results = runner.results();
+        let mut left = 3;
+        for _ in 0..3 {
+            runner.tick(&(), &mut left).await.unwrap();
+        }
+
+        let last = results.next().await.unwrap();
+        assert_eq!((last.tick, last.response), (3, Ok(Response::Success)));
+        assert_eq!(results.missed(), 2);
+        assert!(results.next().await.is_none());
+
+        let mut results = runner.results();
+        assert!(runner.tick(&(), &mut left).await.is_err());
+        let failed = results.next().await.unwrap();
+        assert_eq!(failed.response, Err("counted past 0".to_string()));
+        assert!(results.next().await.is_none());
+    }
+}
@@ -0,0 +1,615 @@
+use crate::behavior_tree::audit::{self, StateChange};
+use crate::behavior_tree::blackboard::BlackboardValue;
+use crate::behavior_tree::json_state::{JsonState, PathError};
+use crate::behavior_tree::loader::{action_name, LoadError, LoadedTree, UntypedTree};
+use crate::behavior_tree::{Actionable, Behavior, Blackboard, NodePath, Response, TreeInstance};
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+
+/// How a scripted action or a whole scenario run ends. A run that ends with a node failing is
+/// a `Failure`; `Error` is an action error or a fatal one, which aborts the run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ScenarioResponse {
+    Success,
+    Running,
+    Failure,
+    Error,
+}
+
+impl fmt::Display for ScenarioResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// One run of a scripted action: what it responds, the message of an error, and the writes it does
+/// to the state (by JSON pointer) and to the blackboard. The blackboard writes land after the
+/// tick, as a host would apply them.
+///
+/// Written as just the response, `"Success"`, or as an object:
+/// `{"response": "Error", "message": "no fuel", "state": {"/ship/fuel": 0}}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptedOutcome {
+    pub response: ScenarioResponse,
+    pub message: Option<String>,
+    pub state: BTreeMap<String, Value>,
+    pub blackboard: Blackboard,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OutcomeObject {
+    response: ScenarioResponse,
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(default)]
+    state: BTreeMap<String, Value>,
+    #[serde(default)]
+    blackboard: Blackboard,
+}
+
+impl<'de> Deserialize<'de> for ScriptedOutcome {
+    // by hand, so a bad outcome gets a better message than an untagged enum's "data did not
+    // match any variant"
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let outcome = match Value::deserialize(deserializer)? {
+            Value::String(response) => OutcomeObject {
+                response: ScenarioResponse::deserialize(Value::String(response))
+                    .map_err(de::Error::custom)?,
+                message: None,
+                state: BTreeMap::new(),
+                blackboard: Blackboard::new(),
+            },
+            object @ Value::Object(_) => {
+                OutcomeObject::deserialize(object).map_err(de::Error::custom)?
+            }
+            other => {
+                return Err(de::Error::custom(format!(
+                    "expected an outcome like \"Success\" or {{\"response\": \"Failure\", \"message\": \"...\"}}, got {}",
+                    other
+                )))
+            }
+        };
+        Ok(ScriptedOutcome {
+            response: outcome.response,
+            message: outcome.message,
+            state: outcome.state,
+            blackboard: outcome.blackboard,
+        })
+    }
+}
+
+/// Paths of nodes that have to or must not run in any tick of a scenario.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VisitedPaths {
+    #[serde(default)]
+    pub required: Vec<NodePath>,
+    #[serde(default)]
+    pub forbidden: Vec<NodePath>,
+}
+
+/// What a scenario checks after its run.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expectations {
+    /// The response of the last tick.
+    #[serde(default)]
+    pub response: Option<ScenarioResponse>,
+    /// Text the error of a failed run has to contain.
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Keys that have to hold these values at the end; other keys aren't checked.
+    #[serde(default)]
+    pub blackboard: Blackboard,
+    /// Values the state has to hold at the end, by JSON pointer.
+    #[serde(default)]
+    pub state: BTreeMap<String, Value>,
+    #[serde(default)]
+    pub visited: VisitedPaths,
+}
+
+/// A test of a tree file: the blackboard and state it starts from, what its actions do and
+/// what has to hold when it's done. The schema is in `schemas/scenario.schema.json`.
+///
+/// The actions are scripted by name, the name the loader gives them: `"Refuel"` and
+/// `{"Refuel": {...}}` are both `Refuel`. Each run of an action takes the next of its outcomes,
+/// the last one repeats. The tree ticks until it no longer responds `Running`, at most
+/// `max_ticks` times.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Added to the blackboard the tree file declares, replacing values of the same key.
+    #[serde(default)]
+    pub blackboard: Blackboard,
+    #[serde(default = "empty_object")]
+    pub state: Value,
+    #[serde(default)]
+    pub actions: BTreeMap<String, Vec<ScriptedOutcome>>,
+    #[serde(default = "default_max_ticks")]
+    pub max_ticks: usize,
+    pub expect: Expectations,
+}
+
+fn empty_object() -> Value {
+    Value::Object(Default::default())
+}
+
+fn default_max_ticks() -> usize {
+    100
+}
+
+impl Scenario {
+    pub fn from_json(json: &str) -> Result<Self, ScenarioError> {
+        let scenario: Scenario = serde_json::from_str(json).map_err(ScenarioError::Json)?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    /// Checks what the schema can't: every scripted action has an outcome, and the tree gets at
+    /// least one tick.
+    pub fn validate(&self) -> Result<(), ScenarioError> {
+        for (name, outcomes) in &self.actions {
+            if outcomes.is_empty() {
+                return Err(ScenarioError::NoOutcomes(name.clone()));
+            }
+        }
+        if self.max_ticks == 0 {
+            return Err(ScenarioError::NoTicks);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScenarioError {
+    #[error(transparent)]
+    Tree(#[from] LoadError),
+    #[error("invalid scenario: {0}")]
+    Json(serde_json::Error),
+    #[error("action `{0}` is scripted without outcomes")]
+    NoOutcomes(String),
+    #[error("`max_ticks` has to be at least 1")]
+    NoTicks,
+    #[error("action `{0}` is scripted but the tree has no such action")]
+    UnknownAction(String),
+    #[error("invalid state pointer in the scenario: {0}")]
+    Path(#[from] PathError),
+}
+
+/// An expectation of a scenario that didn't hold.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum ScenarioFailure {
+    #[error("expected the run to end with {expected}, it ended with {actual}")]
+    Response {
+        expected: ScenarioResponse,
+        actual: ScenarioResponse,
+    },
+    #[error("expected an error containing {expected:?}, got {}", describe_error(.actual))]
+    Error {
+        expected: String,
+        actual: Option<String>,
+    },
+    #[error("blackboard `{key}`: expected {expected}, got {}", describe_value(.actual))]
+    Blackboard {
+        key: String,
+        expected: BlackboardValue,
+        actual: Option<BlackboardValue>,
+    },
+    #[error("state `{pointer}`: {}", describe_changes(.changes))]
+    State {
+        pointer: String,
+        // from the expected value to the actual one
+        changes: Vec<StateChange>,
+    },
+    #[error("node {0:?} was required to run but never did")]
+    NotVisited(NodePath),
+    #[error("node {0:?} was forbidden but ran")]
+    ForbiddenVisited(NodePath),
+}
+
+fn describe_error(error: &Option<String>) -> String {
+    match error {
+        Some(error) => format!("{:?}", error),
+        None => "no error".to_string(),
+    }
+}
+
+fn describe_value(value: &Option<BlackboardValue>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => "nothing".to_string(),
+    }
+}
+
+fn describe_changes(changes: &[StateChange]) -> String {
+    let show = |value: &Option<Value>| match value {
+        Some(value) => value.to_string(),
+        None => "nothing".to_string(),
+    };
+    changes
+        .iter()
+        .map(|change| {
+            let at = if change.pointer.is_empty() {
+                String::new()
+            } else {
+                format!("at `{}` ", change.pointer)
+            };
+            format!(
+                "{}expected {}, got {}",
+                at,
+                show(&change.old),
+                show(&change.new)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// How a scenario went.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScenarioReport {
+    pub ticks: usize,
+    pub response: Result<Response, String>,
+    pub blackboard: Blackboard,
+    pub state: Value,
+    pub failures: Vec<ScenarioFailure>,
+}
+
+impl ScenarioReport {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl fmt::Display for ScenarioReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ended = match &self.response {
+            Ok(response) => format!("{:?}", response),
+            Err(error) => format!("Failure: {}", error),
+        };
+        let verdict = if self.passed() { "PASS" } else { "FAIL" };
+        let ticks = if self.ticks == 1 { "tick" } else { "ticks" };
+        write!(f, "{} after {} {} ({})", verdict, self.ticks, ticks, ended)?;
+        for failure in &self.failures {
+            write!(f, "\n  - {}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+/// An action of a tree file that does what a scenario scripted for its name.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(transparent)]
+pub struct ScriptedAction(pub Value);
+
+/// The scripts of a scenario run, shared by all its actions.
+pub struct Scripts(Mutex<ScriptState>);
+
+struct ScriptState {
+    outcomes: BTreeMap<String, VecDeque<ScriptedOutcome>>,
+    // blackboard writes of the actions of this tick
+    blackboard: Blackboard,
+}
+
+impl Scripts {
+    pub fn new(actions: BTreeMap<String, Vec<ScriptedOutcome>>) -> Self {
+        Self(Mutex::new(ScriptState {
+            outcomes: actions
+                .into_iter()
+                .map(|(name, outcomes)| (name, outcomes.into()))
+                .collect(),
+            blackboard: Blackboard::new(),
+        }))
+    }
+
+    fn take_blackboard(&self) -> Blackboard {
+        std::mem::take(&mut self.0.lock().unwrap().blackboard)
+    }
+}
+
+impl Actionable for ScriptedAction {
+    type ActionError = String;
+    type ActionArgs = Scripts;
+    type ActionState = JsonState;
+
+    async fn run(&self, scripts: &Scripts, state: &mut JsonState) -> Result<Response, String> {
+        let name = self.name();
+        let outcome = {
+            let mut scripts = scripts.0.lock().unwrap();
+            let outcomes = scripts
+                .outcomes
+                .get_mut(&name)
+                .ok_or_else(|| format!("action `{}` has no scripted outcome", name))?;
+            let outcome = if outcomes.len() > 1 {
+                outcomes.pop_front()
+            } else {
+                outcomes.front().cloned()
+            };
+            let outcome =
+                outcome.ok_or_else(|| format!("action `{}` is scripted without outcomes", name))?;
+            scripts.blackboard.extend(outcome.blackboard.clone());
+            outcome
+        };
+        for (pointer, value) in outcome.state {
+            state.set(&pointer, value).map_err(|err| err.to_string())?;
+        }
+        match outcome.response {
+            ScenarioResponse::Success => Ok(Response::Success),
+            ScenarioResponse::Running => Ok(Response::Running),
+            ScenarioResponse::Failure => Ok(Response::Failure),
+            ScenarioResponse::Error => Err(outcome
+                .message
+                .unwrap_or_else(|| format!("`{}` failed as scripted", name))),
+        }
+    }
+
+    fn name(&self) -> String {
+        action_name(&self.0)
+    }
+}
+
+/// Loads the tree file `tree_json` and runs the scenario `scenario_json` against it. Errors are
+/// for documents that don't load; expectations that don't hold are failures of the report.
+pub async fn run_scenario(
+    tree_json: &str,
+    scenario_json: &str,
+) -> Result<ScenarioReport, ScenarioError> {
+    let untyped = UntypedTree::from_json(tree_json)?;
+    let scenario = Scenario::from_json(scenario_json)?;
+    run_loaded(untyped, scenario).await
+}
+
+/// Like [`run_scenario`] for documents that are already parsed.
+pub async fn run_loaded(
+    untyped: UntypedTree,
+    scenario: Scenario,
+) -> Result<ScenarioReport, ScenarioError> {
+    scenario.validate()?;
+    let mut names = BTreeSet::new();
+    let behavior = untyped.behavior.map_actions(&mut |_, payload| {
+        names.insert(action_name(&payload));
+        Behavior::Action(ScriptedAction(payload))
+    });
+    if let Some(unknown) = scenario.actions.keys().find(|name| !names.contains(*name)) {
+        return Err(ScenarioError::UnknownAction(unknown.clone()));
+    }
+    for pointer in scenario.expect.state.keys() {
+        JsonState::default().get(pointer)?;
+    }
+    let loaded = LoadedTree {
+        blackboard: untyped.blackboard,
+        runtime_keys: untyped.runtime_keys,
+        required_capabilities: untyped.required_capabilities,
+        defs: untyped.defs,
+        behavior,
+    };
+
+    let mut instance = TreeInstance::from_loaded(loaded, scenario.blackboard)
+        .with_json_state()
+        .with_history(1);
+    let scripts = Scripts::new(scenario.actions);
+    let mut state = JsonState::new(scenario.state);
+    let mut visited = BTreeSet::new();
+    let mut ticks = 0;
+    let (response, actual) = loop {
+        ticks += 1;
+        let (result, tick_visited) = instance.run_visiting(&scripts, &mut state).await;
+        instance.blackboard_mut().extend(scripts.take_blackboard());
+        visited.extend(tick_visited.into_iter().flatten());
+        let actual = match &result {
+            Ok(Response::Success) => ScenarioResponse::Success,
+            Ok(Response::Running) if ticks < scenario.max_ticks => continue,
+            Ok(Response::Running) => ScenarioResponse::Running,
+            Ok(Response::Failure) => ScenarioResponse::Failure,
+            Err(err) if err.is_hard() => ScenarioResponse::Error,
+            Err(_) => ScenarioResponse::Failure,
+        };
+        break (result.map_err(|err| err.to_string()), actual);
+    };
+
+    let failures = check(
+        &scenario.expect,
+        &response,
+        actual,
+        instance.blackboard(),
+        &state,
+        &visited,
+    );
+    Ok(ScenarioReport {
+        ticks,
+        response,
+        blackboard: instance.blackboard().clone(),
+        state: state.into_value(),
+        failures,
+    })
+}
+
+fn check(
+    expect: &Expectations,
+    response: &Result<Response, String>,
+    actual: ScenarioResponse,
+    blackboard: &Blackboard,
+    state: &JsonState,
+    visited: &BTreeSet<NodePath>,
+) -> Vec<ScenarioFailure> {
+    let mut failures = vec![];
+    if let Some(expected) = expect.response.filter(|expected| *expected != actual) {
+        failures.push(ScenarioFailure::Response { expected, actual });
+    }
+    if let Some(expected) = &expect.error {
+        let error = response.as_ref().err();
+        if !error.is_some_and(|error| error.contains(expected.as_str())) {
+            failures.push(ScenarioFailure::Error {
+                expected: expected.clone(),
+                actual: error.cloned(),
+            });
+        }
+    }
+    for key in expect.blackboard.keys() {
+        let expected = expect.blackboard.get(key).expect("a key of the blackboard");
+        let actual = blackboard.get(key);
+        if actual != Some(expected) {
+            failures.push(ScenarioFailure::Blackboard {
+                key: key.to_string(),
+                expected: expected.clone(),
+                actual: actual.cloned(),
+            });
+        }
+    }
+    for (pointer, expected) in &expect.state {
+        // the pointers were checked before the run
+        let actual = state.get(pointer).ok().flatten();
+        if actual != Some(expected) {
+            let changes = match actual {
+                Some(actual) => audit::changes(expected, actual),
+                None => vec![StateChange {
+                    pointer: String::new(),
+                    old: Some(expected.clone()),
+                    new: None,
+                }],
+            };
+            failures.push(ScenarioFailure::State {
+                pointer: pointer.clone(),
+                changes,
+            });
+        }
+    }
+    for path in &expect.visited.required {
+        if !visited.contains(path) {
+            failures.push(ScenarioFailure::NotVisited(path.clone()));
+        }
+    }
+    for path in &expect.visited.forbidden {
+        if visited.contains(path) {
+            failures.push(ScenarioFailure::ForbiddenVisited(path.clone()));
+        }
+    }
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::audit::StateChange;
+    use crate::behavior_tree::loader::UntypedTree;
+    use crate::behavior_tree::scenario::{
+        run_loaded, run_scenario, Scenario, ScenarioError, ScenarioFailure,
+    };
+    use crate::behavior_tree::Response;
+    use serde_json::json;
+
+    const PATROL: &str = include_str!("../../fixtures/scenarios/patrol.json");
+
+    #[tokio::test]
+    async fn test_passing_scenario() {
+        let scenario = include_str!("../../fixtures/scenarios/refuels_when_low.json");
+        let report = run_scenario(PATROL, scenario).await.unwrap();
+        assert!(report.passed(), "{}", report);
+        assert_eq!((report.ticks, report.response), (1, Ok(Response::Success)));
+        assert_eq!(report.state["ship"]["fuel"], json!(100));
+    }
+
+    #[tokio::test]
+    async fn test_scenario_fails_on_a_blackboard_assertion() {
+        let scenario = include_str!("../../fixtures/scenarios/wrong_destination.json");
+        let report = run_scenario(PATROL, scenario).await.unwrap();
+        assert_eq!(
+            report.failures,
+            vec![
+                ScenarioFailure::Blackboard {
+                    key: "destination".to_string(),
+                    expected: "X1-C".into(),
+                    actual: Some("X1-B".into()),
+                },
+                ScenarioFailure::State {
+                    pointer: "/ship".to_string(),
+                    changes: vec![StateChange {
+                        pointer: "/fuel".to_string(),
+                        old: Some(json!(40)),
+                        new: Some(json!(35)),
+                    }],
+                },
+            ]
+        );
+        assert_eq!(
+            report.to_string(),
+            "FAIL after 1 tick (Success)\
+            \n  - blackboard `destination`: expected \"X1-C\", got \"X1-B\"\
+            \n  - state `/ship`: at `/fuel` expected 40, got 35"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scenario_fails_on_a_forbidden_path() {
+        let scenario = include_str!("../../fixtures/scenarios/never_refuels.json");
+        let report = run_scenario(PATROL, scenario).await.unwrap();
+        assert_eq!(
+            report.failures,
+            vec![ScenarioFailure::ForbiddenVisited(vec![0, 1])]
+        );
+        assert_eq!(report.response, Ok(Response::Failure));
+    }
+
+    #[tokio::test]
+    async fn test_scripted_errors_abort_the_run() {
+        let scenario = json!({
+            "blackboard": {"fuel_low": true},
+            "actions": {
+                "Refuel": [{"response": "Error", "message": "market is closed"}],
+                "Jump": ["Success"]
+            },
+            "expect": {"response": "Error", "visited": {"forbidden": [[1]]}}
+        });
+        let report = run_scenario(PATROL, &scenario.to_string()).await.unwrap();
+        assert!(report.passed(), "{}", report);
+        assert_eq!(report.response, Err("market is closed".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_scenario_errors_point_at_the_mistake() {
+        let error = |scenario: serde_json::Value| async move {
+            run_scenario(PATROL, &scenario.to_string())
+                .await
+                .unwrap_err()
+                .to_string()
+        };
+        let expect = json!({"response": "Success"});
+        assert_eq!(
+            error(json!({"actions": {"Refuel": ["Sucess"]}, "expect": expect})).await,
+            "invalid scenario: unknown variant `Sucess`, expected one of `Success`, `Running`, `Failure`, `Error` at line 1 column 31"
+        );
+        assert_eq!(
+            error(json!({"expect": {"respone": "Success"}})).await,
+            "invalid scenario: unknown field `respone`, expected one of `response`, `error`, `blackboard`, `state`, `visited` at line 1 column 20"
+        );
+        assert_eq!(
+            error(json!({"actions": {"Teleport": ["Success"]}, "expect": expect})).await,
+            "action `Teleport` is scripted but the tree has no such action"
+        );
+        assert!(matches!(
+            run_scenario(PATROL, r#"{"expect": {"state": {"ship": 1}}}"#).await,
+            Err(ScenarioError::Path(_))
+        ));
+
+        // scenarios built in code are checked as well
+        let mut scenario = Scenario::from_json(&json!({"expect": expect}).to_string()).unwrap();
+        scenario.actions.insert("Refuel".to_string(), vec![]);
+        let untyped = UntypedTree::from_json(PATROL).unwrap();
+        assert!(matches!(
+            run_loaded(untyped.clone(), scenario.clone()).await,
+            Err(ScenarioError::NoOutcomes(name)) if name == "Refuel"
+        ));
+        scenario.actions.clear();
+        scenario.max_ticks = 0;
+        assert!(matches!(
+            run_loaded(untyped, scenario).await,
+            Err(ScenarioError::NoTicks)
+        ));
+    }
+}
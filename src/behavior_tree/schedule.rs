@@ -0,0 +1,509 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DAY_NAMES: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+const MINUTES_PER_DAY: i64 = 24 * 60;
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("invalid time window `{spec}`: {message}")]
+pub struct WindowError {
+    pub spec: String,
+    pub message: String,
+}
+
+/// A recurring span of time a `Schedule` node lets its tree through in, written as text and
+/// parsed when the tree is built or loaded. Two forms are supported:
+///
+/// - days and a time range with an optional UTC offset, e.g. `Mon-Fri 22:00-06:00 +02:00` or
+///   `Sat,Sun 00:00-24:00`. Without days the window is open every day, without an offset the
+///   times are UTC. A range ending at or before its start crosses midnight into the next day,
+///   and the days say on which days the window opens.
+/// - a cron-like `minute hour day-of-month month day-of-week` line in UTC, e.g.
+///   `* 0-5 * * 1-5`. The window is every minute the line matches. Fields take `*`, numbers,
+///   ranges `a-b`, steps `*/n` or `a-b/n` and lists `a,b`; days of week go from 0 (Sunday) to
+///   7 (Sunday again) or use the names above. As in cron, a minute matches either day field
+///   when both are restricted.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "String", into = "String"))]
+pub struct TimeWindow {
+    spec: String,
+    kind: WindowKind,
+}
+
+#[derive(Debug, Clone)]
+enum WindowKind {
+    Weekly {
+        // bit 0 is Monday
+        days: u8,
+        // minutes since midnight, `end` up to 24:00
+        start: i64,
+        end: i64,
+        offset: i64,
+    },
+    Cron(Cron),
+}
+
+#[derive(Debug, Clone)]
+struct Cron {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    // bit 0 is Sunday
+    days_of_week: u64,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl TimeWindow {
+    pub fn parse(spec: &str) -> Result<Self, WindowError> {
+        let error = |message: String| WindowError {
+            spec: spec.to_string(),
+            message,
+        };
+        let fields: Vec<&str> = spec.split_whitespace().collect();
+        let kind = if fields.iter().any(|field| field.contains(':')) {
+            parse_weekly(&fields).map_err(error)?
+        } else {
+            WindowKind::Cron(Cron::parse(&fields).map_err(error)?)
+        };
+        Ok(Self {
+            spec: spec.to_string(),
+            kind,
+        })
+    }
+
+    pub fn spec(&self) -> &str {
+        &self.spec
+    }
+
+    /// Whether `time` falls inside the window. Seconds are ignored.
+    pub fn contains(&self, time: SystemTime) -> bool {
+        let utc_minutes = match time.duration_since(UNIX_EPOCH) {
+            Ok(since) => (since.as_secs() / 60) as i64,
+            Err(before) => -(before.duration().as_secs().div_ceil(60) as i64),
+        };
+        match &self.kind {
+            WindowKind::Weekly {
+                days,
+                start,
+                end,
+                offset,
+            } => {
+                let local = utc_minutes + offset;
+                let (day, minute) = (
+                    local.div_euclid(MINUTES_PER_DAY),
+                    local.rem_euclid(MINUTES_PER_DAY),
+                );
+                let opens_on = |day: i64| days & (1 << weekday(day)) != 0;
+                if start < end {
+                    opens_on(day) && (*start..*end).contains(&minute)
+                } else {
+                    (opens_on(day) && minute >= *start) || (opens_on(day - 1) && minute < *end)
+                }
+            }
+            WindowKind::Cron(cron) => cron.matches(utc_minutes),
+        }
+    }
+}
+
+impl PartialEq for TimeWindow {
+    fn eq(&self, other: &Self) -> bool {
+        self.spec == other.spec
+    }
+}
+
+impl fmt::Display for TimeWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.spec)
+    }
+}
+
+impl TryFrom<String> for TimeWindow {
+    type Error = WindowError;
+
+    fn try_from(spec: String) -> Result<Self, Self::Error> {
+        TimeWindow::parse(&spec)
+    }
+}
+
+impl From<TimeWindow> for String {
+    fn from(window: TimeWindow) -> Self {
+        window.spec
+    }
+}
+
+// `[days] HH:MM-HH:MM [offset]`
+fn parse_weekly(fields: &[&str]) -> Result<WindowKind, String> {
+    let range = fields
+        .iter()
+        .position(|field| field.contains('-') && field.contains(':') && !is_offset(field))
+        .ok_or("expected a time range like `22:00-06:00`")?;
+    let days = match &fields[..range] {
+        [] => 0x7f,
+        [days] => parse_days(days)?,
+        _ => return Err("expected the days as one list like `Mon-Fri,Sun`".to_string()),
+    };
+    let (start, end) = fields[range]
+        .split_once('-')
+        .expect("the range contains `-`");
+    let (start, end) = (parse_time(start)?, parse_time(end)?);
+    if start == MINUTES_PER_DAY {
+        return Err("the window can't start at 24:00".to_string());
+    }
+    let offset = match &fields[range + 1..] {
+        [] => 0,
+        [offset] => parse_offset(offset)?,
+        [_, extra, ..] => return Err(format!("unexpected `{}`", extra)),
+    };
+    Ok(WindowKind::Weekly {
+        days,
+        start,
+        end,
+        offset,
+    })
+}
+
+fn is_offset(field: &str) -> bool {
+    field.starts_with(['+', '-']) || field == "UTC" || field == "Z"
+}
+
+fn parse_days(days: &str) -> Result<u8, String> {
+    let mut set = 0;
+    for item in days.split(',') {
+        let (first, last) = item.split_once('-').unwrap_or((item, item));
+        let (first, last) = (day_index(first)?, day_index(last)?);
+        if first > last {
+            return Err(format!("days `{}` are out of order", item));
+        }
+        for day in first..=last {
+            set |= 1 << day;
+        }
+    }
+    Ok(set)
+}
+
+fn day_index(name: &str) -> Result<usize, String> {
+    DAY_NAMES
+        .iter()
+        .position(|day| day.eq_ignore_ascii_case(name))
+        .ok_or_else(|| {
+            format!(
+                "unknown day `{}`, expected one of {}",
+                name,
+                DAY_NAMES.join(", ")
+            )
+        })
+}
+
+// minutes since midnight of `HH:MM`, up to 24:00
+fn parse_time(time: &str) -> Result<i64, String> {
+    hours_minutes(time)
+        .filter(|minutes| *minutes <= MINUTES_PER_DAY)
+        .ok_or_else(|| format!("invalid time `{}`, expected HH:MM", time))
+}
+
+// minutes east of UTC of `UTC`, `Z` or `+HH:MM`/`-HH:MM`
+fn parse_offset(offset: &str) -> Result<i64, String> {
+    if offset == "UTC" || offset == "Z" {
+        return Ok(0);
+    }
+    let sign = match offset.chars().next() {
+        Some('+') => 1,
+        Some('-') => -1,
+        _ => 0,
+    };
+    offset
+        .get(1..)
+        .and_then(hours_minutes)
+        .filter(|minutes| sign != 0 && *minutes <= 14 * 60)
+        .map(|minutes| sign * minutes)
+        .ok_or_else(|| format!("invalid UTC offset `{}`, expected +HH:MM or -HH:MM", offset))
+}
+
+fn hours_minutes(s: &str) -> Option<i64> {
+    let (hours, minutes) = s.split_once(':')?;
+    if minutes.len() != 2 {
+        return None;
+    }
+    let (hours, minutes) = (number(hours)?, number(minutes)?);
+    (minutes <= 59).then_some(hours * 60 + minutes)
+}
+
+fn number(s: &str) -> Option<i64> {
+    if s.is_empty() || s.len() > 2 || !s.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+impl Cron {
+    fn parse(fields: &[&str]) -> Result<Self, String> {
+        let [minute, hour, day_of_month, month, day_of_week] = fields else {
+            return Err(format!(
+                "expected a time range or the 5 fields of a cron line, got {} field(s)",
+                fields.len()
+            ));
+        };
+        let mut days_of_week = cron_field(day_of_week, "day of week", 0, 7)?;
+        // 7 is Sunday too
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week |= 1;
+        }
+        Ok(Self {
+            minutes: cron_field(minute, "minute", 0, 59)?,
+            hours: cron_field(hour, "hour", 0, 23)?,
+            days_of_month: cron_field(day_of_month, "day of month", 1, 31)?,
+            months: cron_field(month, "month", 1, 12)?,
+            days_of_week,
+            any_day_of_month: day_of_month.starts_with('*'),
+            any_day_of_week: day_of_week.starts_with('*'),
+        })
+    }
+
+    fn matches(&self, utc_minutes: i64) -> bool {
+        let (day, minute) = (
+            utc_minutes.div_euclid(MINUTES_PER_DAY),
+            utc_minutes.rem_euclid(MINUTES_PER_DAY),
+        );
+        let (_, month, day_of_month) = civil_from_days(day);
+        let has = |set: u64, value: i64| set & (1 << value) != 0;
+        let day_of_month = has(self.days_of_month, day_of_month);
+        let day_of_week = has(self.days_of_week, (weekday(day) + 1) % 7);
+        let day_matches = match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        };
+        has(self.minutes, minute % 60)
+            && has(self.hours, minute / 60)
+            && has(self.months, month)
+            && day_matches
+    }
+}
+
+// the set of values of one cron field, bit `n` standing for `n`
+fn cron_field(field: &str, name: &str, min: u32, max: u32) -> Result<u64, String> {
+    let invalid = |item: &str| format!("invalid {} `{}`", name, item);
+    let value = |v: &str| -> Result<u32, String> {
+        let parsed = match v.parse() {
+            Ok(parsed) => Some(parsed),
+            // day names in the day of week field, Sunday being 0
+            Err(_) if max == 7 => day_index(v).ok().map(|day| (day as u32 + 1) % 7),
+            Err(_) => None,
+        };
+        parsed
+            .filter(|parsed| (min..=max).contains(parsed))
+            .ok_or_else(|| format!("{} `{}` is not in {}-{}", name, v, min, max))
+    };
+    let mut set = 0;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return Err(invalid(item)),
+            },
+            None => (item, 1),
+        };
+        let (first, last) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((first, last)) => (value(first)?, value(last)?),
+            None => (value(range)?, value(range)?),
+        };
+        if first > last {
+            return Err(invalid(item));
+        }
+        for v in (first..=last).step_by(step as usize) {
+            set |= 1 << v;
+        }
+    }
+    Ok(set)
+}
+
+// 0 for Monday, of the day `day` days after 1970-01-01, a Thursday
+fn weekday(day: i64) -> i64 {
+    (day + 3).rem_euclid(7)
+}
+
+// the (year, month, day) `days` days after 1970-01-01, in the proleptic gregorian calendar
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::blackboard::BlackboardValue;
+    use crate::behavior_tree::clock::parse_timestamp;
+    #[cfg(feature = "tokio")]
+    use crate::behavior_tree::clock::TokioClock;
+    use crate::behavior_tree::schedule::TimeWindow;
+    use crate::behavior_tree::Behavior::*;
+    #[cfg(feature = "tokio")]
+    use crate::behavior_tree::TreeInstance;
+    use crate::behavior_tree::{Actionable, Behavior, Response};
+    use std::time::SystemTime;
+
+    #[derive(Clone, Debug)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    struct Scan;
+
+    impl Actionable for Scan {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = u32;
+
+        async fn run(&self, _: &(), scans: &mut u32) -> Result<Response, String> {
+            *scans += 1;
+            Ok(Response::Success)
+        }
+    }
+
+    fn at(timestamp: &str) -> SystemTime {
+        parse_timestamp(&BlackboardValue::from(timestamp)).unwrap()
+    }
+
+    fn windows(specs: &[&str]) -> Vec<TimeWindow> {
+        specs
+            .iter()
+            .map(|spec| TimeWindow::parse(spec).unwrap())
+            .collect()
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn test_schedule_runs_only_inside_its_windows() {
+        let bt = Sequence(vec![
+            Schedule {
+                windows: windows(&["Fri 22:00-02:00 +02:00", "Sat,Sun 10:00-12:00"]),
+            },
+            Action(Scan),
+        ]);
+        // 2024-03-01 is a Friday
+        let cases = [
+            ("2024-03-01T19:59:00Z", false),
+            ("2024-03-01T20:00:00Z", true),
+            ("2024-03-01T23:59:59Z", true),
+            ("2024-03-02T00:30:00+02:00", true),
+            ("2024-03-01T23:59:00-01:00", false),
+            ("2024-03-02T10:00:00Z", true),
+            ("2024-03-02T12:00:00Z", false),
+            ("2024-03-03T11:15:00Z", true),
+            ("2024-03-04T11:15:00Z", false),
+        ];
+        for (time, inside) in cases {
+            let mut instance =
+                TreeInstance::new(bt.clone()).with_clock(TokioClock::starting_at(at(time)));
+            let mut scans = 0;
+            let result = instance.run(&(), &mut scans).await.unwrap();
+            let expected = if inside {
+                Response::Success
+            } else {
+                Response::Failure
+            };
+            assert_eq!(result, expected, "{}", time);
+            assert_eq!(scans, u32::from(inside), "{}", time);
+        }
+
+        let schedule = bt.children()[0].clone();
+        let monday = TokioClock::starting_at(at("2024-03-04T11:15:00Z"));
+        let mut instance = TreeInstance::new(schedule).with_clock(monday);
+        let result = instance.run(&(), &mut 0).await.unwrap();
+        assert_eq!(result, Response::Failure);
+    }
+
+    #[test]
+    fn test_cron_windows() {
+        // off-peak: the first six hours of weekdays, and the last day of the year
+        let off_peak = windows(&["* 0-5 * * Mon-Fri", "*/15 * 31 12 *"]);
+        let inside = |time: &str| off_peak.iter().any(|window| window.contains(at(time)));
+        assert!(inside("2024-03-04T05:59:00Z"));
+        assert!(!inside("2024-03-04T06:00:00Z"));
+        assert!(!inside("2024-03-03T01:00:00Z"));
+        assert!(inside("2023-12-31T17:45:00Z"));
+        assert!(!inside("2023-12-31T17:46:00Z"));
+
+        // both day fields restricted: either one matches
+        let window = TimeWindow::parse("0 12 1 * 0").unwrap();
+        assert!(window.contains(at("2024-03-01T12:00:00Z")));
+        assert!(window.contains(at("2024-03-03T12:00:00Z")));
+        assert!(!window.contains(at("2024-03-02T12:00:00Z")));
+    }
+
+    #[test]
+    fn test_invalid_windows() {
+        for (spec, message) in [
+            (
+                "Mon-Fri",
+                "expected a time range or the 5 fields of a cron line, got 1 field(s)",
+            ),
+            ("Fri-Mon 10:00-12:00", "days `Fri-Mon` are out of order"),
+            (
+                "Mo 10:00-12:00",
+                "unknown day `Mo`, expected one of Mon, Tue, Wed, Thu, Fri, Sat, Sun",
+            ),
+            ("10:00-25:00", "invalid time `25:00`, expected HH:MM"),
+            (
+                "10:00-12:00 +2",
+                "invalid UTC offset `+2`, expected +HH:MM or -HH:MM",
+            ),
+            ("* 24 * * *", "hour `24` is not in 0-23"),
+            ("*/0 * * * *", "invalid minute `*/0`"),
+        ] {
+            let err = TimeWindow::parse(spec).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("invalid time window `{}`: {}", spec, message)
+            );
+        }
+
+        let bt: Behavior<Scan> = Schedule { windows: vec![] };
+        assert_eq!(
+            bt.config_error().as_deref(),
+            Some("schedule has no windows")
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_windows_are_loaded_from_text() {
+        use crate::behavior_tree::loader::LoadedTree;
+
+        let json =
+            r#"{"tree": {"Schedule": {"windows": ["Sat,Sun 22:00-06:00 -05:00", "* 0-5 * * *"]}}}"#;
+        let tree = LoadedTree::<Scan>::from_json(json).unwrap();
+        assert_eq!(
+            serde_json::to_string(&tree.behavior).unwrap(),
+            r#"{"Schedule":{"windows":["Sat,Sun 22:00-06:00 -05:00","* 0-5 * * *"]}}"#
+        );
+
+        let err = LoadedTree::<Scan>::from_json(r#"{"tree": {"Schedule": {"windows": []}}}"#)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid node at []: schedule has no windows"
+        );
+        let json = r#"{"tree": {"Schedule": {"windows": ["24:00-06:00"]}}}"#;
+        let err = LoadedTree::<Scan>::from_json(json).unwrap_err();
+        assert!(
+            err.to_string().contains("the window can't start at 24:00"),
+            "{}",
+            err
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use crate::behavior_tree::arbiter::{ResourceArbiter, ResourceStats};
+use crate::behavior_tree::runner::Runner;
+use crate::behavior_tree::toggle::{NodeRef, Toggles};
+use crate::behavior_tree::{Actionable, BehaviorError, Response};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Identifies an entry of a [`Scheduler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EntryId(u64);
+
+struct Entry<A: Actionable> {
+    id: EntryId,
+    runner: Runner<A>,
+    args: A::ActionArgs,
+    state: A::ActionState,
+}
+
+/// Ticks many runners, each with its own args and state. The same tree can be added several
+/// times with different args, e.g. one entry per agent.
+pub struct Scheduler<A: Actionable> {
+    entries: Vec<Entry<A>>,
+    next_id: u64,
+    arbiter: Option<Arc<ResourceArbiter>>,
+    toggles: Toggles,
+}
+
+impl<A> Scheduler<A>
+where
+    A: Actionable,
+{
+    pub fn new() -> Self {
+        Self {
+            entries: vec![],
+            next_id: 0,
+            arbiter: None,
+            toggles: Toggles::default(),
+        }
+    }
+
+    /// Sets the arbiter whose resources the trees of the entries share, for its stats.
+    pub fn with_arbiter(mut self, arbiter: Arc<ResourceArbiter>) -> Self {
+        self.arbiter = Some(arbiter);
+        self
+    }
+
+    /// Use and contention of the resources of the arbiter, empty without arbiter.
+    pub fn resource_stats(&self) -> BTreeMap<String, ResourceStats> {
+        self.arbiter
+            .as_ref()
+            .map(|arbiter| arbiter.stats())
+            .unwrap_or_default()
+    }
+
+    /// Switches a node on or off in the instances of every entry, including entries added
+    /// later, see [`TreeInstance::set_enabled`](crate::behavior_tree::TreeInstance::set_enabled).
+    pub fn set_enabled(&mut self, node: impl Into<NodeRef>, enabled: bool) {
+        let node = node.into();
+        for entry in &mut self.entries {
+            entry
+                .runner
+                .instance_mut()
+                .set_enabled(node.clone(), enabled);
+        }
+        self.toggles.set_enabled(node, enabled);
+    }
+
+    /// The nodes switched off with [`set_enabled`](Self::set_enabled), paths first.
+    pub fn disabled(&self) -> Vec<NodeRef> {
+        self.toggles.disabled().cloned().collect()
+    }
+
+    pub fn add(
+        &mut self,
+        mut runner: Runner<A>,
+        args: A::ActionArgs,
+        state: A::ActionState,
+    ) -> EntryId {
+        for node in self.toggles.disabled() {
+            runner.instance_mut().set_enabled(node.clone(), false);
+        }
+        let id = EntryId(self.next_id);
+        self.next_id += 1;
+        self.entries.push(Entry {
+            id,
+            runner,
+            args,
+            state,
+        });
+        id
+    }
+
+    /// Takes an entry out of the scheduler, handing back its runner, args and state.
+    pub fn remove(&mut self, id: EntryId) -> Option<(Runner<A>, A::ActionArgs, A::ActionState)> {
+        let index = self.entries.iter().position(|entry| entry.id == id)?;
+        let entry = self.entries.remove(index);
+        Some((entry.runner, entry.args, entry.state))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = EntryId> + '_ {
+        self.entries.iter().map(|entry| entry.id)
+    }
+
+    pub fn state(&self, id: EntryId) -> Option<&A::ActionState> {
+        self.entry(id).map(|entry| &entry.state)
+    }
+
+    pub fn state_mut(&mut self, id: EntryId) -> Option<&mut A::ActionState> {
+        self.entry_mut(id).map(|entry| &mut entry.state)
+    }
+
+    pub fn runner(&self, id: EntryId) -> Option<&Runner<A>> {
+        self.entry(id).map(|entry| &entry.runner)
+    }
+
+    pub fn runner_mut(&mut self, id: EntryId) -> Option<&mut Runner<A>> {
+        self.entry_mut(id).map(|entry| &mut entry.runner)
+    }
+
+    /// Ticks one entry with its own args and state.
+    pub async fn tick(
+        &mut self,
+        id: EntryId,
+    ) -> Option<Result<Response, BehaviorError<A::ActionError>>> {
+        let entry = self.entry_mut(id)?;
+        Some(entry.runner.tick(&entry.args, &mut entry.state).await)
+    }
+
+    /// Ticks every entry once, in the order they were added.
+    pub async fn tick_all(
+        &mut self,
+    ) -> Vec<(EntryId, Result<Response, BehaviorError<A::ActionError>>)> {
+        let mut results = Vec::with_capacity(self.entries.len());
+        for entry in &mut self.entries {
+            let result = entry.runner.tick(&entry.args, &mut entry.state).await;
+            results.push((entry.id, result));
+        }
+        results
+    }
+
+    fn entry(&self, id: EntryId) -> Option<&Entry<A>> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    fn entry_mut(&mut self, id: EntryId) -> Option<&mut Entry<A>> {
+        self.entries.iter_mut().find(|entry| entry.id == id)
+    }
+}
+
+impl<A: Actionable> Default for Scheduler<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::runner::Runner;
+    use crate::behavior_tree::scheduler::Scheduler;
+    use crate::behavior_tree::{Actionable, Behavior, Response, TreeInstance};
+    use std::sync::Mutex;
+
+    // stands in for an HTTP client pool, which can't be cloned
+    struct Client {
+        requests: Mutex<Vec<String>>,
+    }
+
+    struct AgentArgs {
+        endpoint: String,
+        client: Client,
+    }
+
+    #[derive(Clone, Debug)]
+    enum AgentAction {
+        Scan,
+    }
+
+    impl Actionable for AgentAction {
+        type ActionError = anyhow::Error;
+        type ActionArgs = AgentArgs;
+        type ActionState = Vec<String>;
+
+        async fn run(
+            &self,
+            args: &Self::ActionArgs,
+            state: &mut Self::ActionState,
+        ) -> Result<Response, Self::ActionError> {
+            match self {
+                AgentAction::Scan => {
+                    let url = format!("{}/scan", args.endpoint);
+                    args.client.requests.lock().unwrap().push(url.clone());
+                    state.push(url);
+                    Ok(Response::Success)
+                }
+            }
+        }
+    }
+
+    fn args(endpoint: &str) -> AgentArgs {
+        AgentArgs {
+            endpoint: endpoint.to_string(),
+            client: Client {
+                requests: Mutex::new(vec![]),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_entries_run_with_their_own_args() {
+        let bt = Behavior::Action(AgentAction::Scan);
+        let mut scheduler = Scheduler::new();
+        let alpha = scheduler.add(
+            Runner::new(TreeInstance::new(bt.clone())),
+            args("https://alpha"),
+            vec![],
+        );
+        let beta = scheduler.add(
+            Runner::new(TreeInstance::new(bt)),
+            args("https://beta"),
+            vec![],
+        );
+
+        let results = scheduler.tick_all().await;
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+        scheduler.tick(beta).await.unwrap().unwrap();
+
+        assert_eq!(
+            scheduler.state(alpha),
+            Some(&vec!["https://alpha/scan".to_string()])
+        );
+        assert_eq!(
+            scheduler.state(beta),
+            Some(&vec!["https://beta/scan".to_string(); 2])
+        );
+
+        let (_, beta_args, _) = scheduler.remove(beta).unwrap();
+        assert_eq!(beta_args.client.requests.lock().unwrap().len(), 2);
+        assert_eq!(scheduler.ids().collect::<Vec<_>>(), vec![alpha]);
+    }
+}
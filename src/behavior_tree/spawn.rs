@@ -0,0 +1,517 @@
+use crate::behavior_tree::blackboard::BlackboardValue;
+use crate::behavior_tree::clock::Sleeper;
+use crate::behavior_tree::heartbeat::Heartbeats;
+use crate::behavior_tree::instance::RunContext;
+use crate::behavior_tree::observer::TreeEvent;
+use crate::behavior_tree::typed::TypedBlackboard;
+use crate::behavior_tree::{Actionable, Behavior, BehaviorError, NodePath, Response};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+type Events = mpsc::Sender<(NodePath, TreeEvent)>;
+
+// what the child of a `Spawn` node resulted in, with the error as text
+type Outcome = Result<Response, String>;
+
+// ids are unique across instances, so a `Join` can tell handles of other instances apart
+static NEXT_ID: AtomicI64 = AtomicI64::new(1);
+
+// starts a task running the child of the `Spawn` node at the path on clones of the args and state
+type StartFn<A> = fn(
+    &Behavior<A>,
+    Detached,
+    &<A as Actionable>::ActionArgs,
+    &<A as Actionable>::ActionState,
+) -> JoinHandle<Outcome>;
+
+/// Runs the children of `Spawn` nodes on tokio tasks of their own, and keeps track of the tasks
+/// so the instance can cancel them.
+pub(crate) struct Spawner<A: Actionable> {
+    start: StartFn<A>,
+    // kept until joined, so a `Join` can still get the outcome of a finished task
+    tasks: Vec<Task>,
+    ended: HashMap<i64, Ended>,
+    sender: Events,
+    events: mpsc::Receiver<(NodePath, TreeEvent)>,
+}
+
+struct Task {
+    id: i64,
+    path: NodePath,
+    handle: JoinHandle<Outcome>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Ended {
+    Joined,
+    Cancelled,
+}
+
+// what a detached subtree needs besides its child, args and state
+pub(crate) struct Detached {
+    id: i64,
+    path: NodePath,
+    sender: Events,
+    typed: TypedBlackboard,
+    sleeper: Arc<dyn Sleeper>,
+}
+
+impl<A: Actionable> Spawner<A> {
+    pub(crate) fn new() -> Self
+    where
+        A: 'static,
+        A::ActionArgs: Clone + 'static,
+        A::ActionState: Clone + 'static,
+        A::ActionError: Display,
+    {
+        let (sender, events) = mpsc::channel();
+        Self {
+            start: start::<A>,
+            tasks: vec![],
+            ended: HashMap::new(),
+            sender,
+            events,
+        }
+    }
+
+    /// Starts `child` in the background and returns the id of its task.
+    pub(crate) fn spawn(
+        &mut self,
+        child: &Behavior<A>,
+        path: &[usize],
+        typed: TypedBlackboard,
+        sleeper: Arc<dyn Sleeper>,
+        args: &A::ActionArgs,
+        state: &A::ActionState,
+    ) -> i64 {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let detached = Detached {
+            id,
+            path: path.to_vec(),
+            sender: self.sender.clone(),
+            typed,
+            sleeper,
+        };
+        let handle = (self.start)(child, detached, args, state);
+        self.tasks.push(Task {
+            id,
+            path: path.to_vec(),
+            handle,
+        });
+        id
+    }
+
+    /// The ids of the tasks that haven't finished yet.
+    pub(crate) fn running(&self) -> Vec<i64> {
+        self.tasks
+            .iter()
+            .filter(|task| !task.handle.is_finished())
+            .map(|task| task.id)
+            .collect()
+    }
+
+    // removes the task `id` to await it
+    fn take(&mut self, id: i64) -> Result<Task, String> {
+        if let Some(i) = self.tasks.iter().position(|task| task.id == id) {
+            return Ok(self.tasks.remove(i));
+        }
+        Err(match self.ended.get(&id) {
+            Some(Ended::Joined) => format!("spawn {} was already joined", id),
+            Some(Ended::Cancelled) => format!("spawn {} was cancelled", id),
+            None => format!("spawn {} was not started by this instance", id),
+        })
+    }
+
+    /// The events the detached subtrees sent since the last call.
+    pub(crate) fn take_events(&self) -> Vec<(NodePath, TreeEvent)> {
+        self.events.try_iter().collect()
+    }
+
+    /// Cancels the tasks that are still running and waits for them to stop.
+    pub(crate) async fn shutdown(&mut self) {
+        for task in self.tasks.drain(..) {
+            self.ended.insert(task.id, Ended::Cancelled);
+            task.handle.abort();
+            if let Err(err) = task.handle.await {
+                let error = if err.is_cancelled() {
+                    "cancelled by shutdown".to_string()
+                } else {
+                    "the spawned task panicked".to_string()
+                };
+                let finished = TreeEvent::SpawnFinished {
+                    id: task.id,
+                    error: Some(error),
+                };
+                let _ = self.sender.send((task.path, finished));
+            }
+        }
+    }
+}
+
+// tasks of a dropped instance would keep running with nobody to collect their events
+impl<A: Actionable> Drop for Spawner<A> {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.handle.abort();
+        }
+    }
+}
+
+fn start<A>(
+    child: &Behavior<A>,
+    detached: Detached,
+    args: &A::ActionArgs,
+    state: &A::ActionState,
+) -> JoinHandle<Outcome>
+where
+    A: Actionable + 'static,
+    A::ActionArgs: Clone + 'static,
+    A::ActionState: Clone + 'static,
+    A::ActionError: Display,
+{
+    let child = child.clone();
+    let args = args.clone();
+    let mut state = state.clone();
+    tokio::spawn(async move {
+        let mut ctx = RunContext::<A> {
+            path: detached.path,
+            observers: vec![Box::new(detached.sender)],
+            typed: detached.typed,
+            heartbeats: Heartbeats::timed_by(detached.sleeper.clone()),
+            sleeper: detached.sleeper,
+            ..RunContext::default()
+        };
+        let result = child.run_child(0, &mut ctx, &args, &mut state);
+        let outcome = result.await.map_err(|err| err.to_string());
+        ctx.emit(TreeEvent::SpawnFinished {
+            id: detached.id,
+            error: outcome.as_ref().err().cloned(),
+        });
+        outcome
+    })
+}
+
+/// Runs a `Join` node: waits for the task whose id is stored under `handle_key` and results in
+/// what its subtree resulted in. The task is cancelled if it doesn't finish within `timeout`.
+pub(crate) async fn join<A: Actionable>(
+    ctx: &mut RunContext<A>,
+    handle_key: &str,
+    timeout: Option<Duration>,
+) -> Result<Response, BehaviorError<A::ActionError>> {
+    let id = match ctx.blackboard.get(handle_key) {
+        Some(BlackboardValue::Int(id)) => *id,
+        Some(other) => {
+            return Err(BehaviorError::failed(format!(
+                "blackboard key `{}` is {}, not a spawn handle",
+                handle_key, other
+            )))
+        }
+        None => {
+            return Err(BehaviorError::failed(format!(
+                "blackboard key `{}` is not set",
+                handle_key
+            )))
+        }
+    };
+    let Some(spawner) = &mut ctx.spawner else {
+        return Err(BehaviorError::failed(
+            "spawning needs an instance built with_spawning",
+        ));
+    };
+    let mut task = spawner.take(id).map_err(BehaviorError::failed)?;
+
+    let sleeper = ctx.sleeper.clone();
+    let expired = async {
+        match timeout {
+            Some(timeout) => sleeper.sleep(timeout).await,
+            None => std::future::pending().await,
+        }
+    };
+    let joined = tokio::select! {
+        joined = &mut task.handle => joined,
+        _ = expired => {
+            task.handle.abort();
+            spawner.ended.insert(id, Ended::Cancelled);
+            return Ok(Response::Failure);
+        }
+        _ = ctx.cancellation.cancelled() => {
+            // the task keeps running and can be joined by a later run
+            spawner.tasks.push(task);
+            return Err(BehaviorError::Cancelled(format!(
+                "cancelled while joining spawn {}",
+                id
+            )));
+        }
+    };
+    spawner.ended.insert(id, Ended::Joined);
+    match joined {
+        Ok(Ok(response)) => Ok(response),
+        Ok(Err(err)) => Err(BehaviorError::failed(format!(
+            "spawn {} failed: {}",
+            id, err
+        ))),
+        Err(err) if err.is_cancelled() => {
+            Err(BehaviorError::failed(format!("spawn {} was cancelled", id)))
+        }
+        Err(_) => Err(BehaviorError::failed(format!("spawn {} panicked", id))),
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use crate::behavior_tree::blackboard::BlackboardValue;
+    use crate::behavior_tree::observer::{BehaviorObserver, TreeEvent};
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{Actionable, Behavior, NodePath, Response, TreeInstance};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[derive(Clone, Debug)]
+    enum Job {
+        Wait(u64),
+        Mark(&'static str),
+        Fail,
+    }
+
+    // clones of the state share the marks, so detached subtrees can report back
+    type Marks = Arc<Mutex<Vec<&'static str>>>;
+
+    impl Actionable for Job {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = Marks;
+
+        async fn run(&self, _: &(), marks: &mut Marks) -> Result<Response, String> {
+            match self {
+                Job::Wait(millis) => tokio::time::sleep(Duration::from_millis(*millis)).await,
+                Job::Mark(mark) => marks.lock().unwrap().push(mark),
+                Job::Fail => return Err("survey failed".to_string()),
+            }
+            Ok(Response::Success)
+        }
+    }
+
+    fn spawn(child: Behavior<Job>) -> Behavior<Job> {
+        Spawn {
+            child: Box::new(Sequence(vec![Action(Job::Wait(1000)), child])),
+            handle_key: "survey".to_string(),
+        }
+    }
+
+    fn spawning(child: Behavior<Job>) -> Behavior<Job> {
+        Sequence(vec![spawn(child), Action(Job::Mark("moved on"))])
+    }
+
+    fn join(timeout: Option<u64>) -> Behavior<Job> {
+        Join {
+            handle_key: "survey".to_string(),
+            timeout: timeout.map(Duration::from_millis),
+        }
+    }
+
+    fn handle(instance: &TreeInstance<Job>) -> i64 {
+        match instance.blackboard().get("survey") {
+            Some(BlackboardValue::Int(id)) => *id,
+            other => panic!("no spawn handle: {:?}", other),
+        }
+    }
+
+    // runs `node` alone in the context of `instance`, where its spawns are
+    async fn run_alone(
+        instance: &mut TreeInstance<Job>,
+        node: Behavior<Job>,
+        marks: &Marks,
+    ) -> Result<Response, String> {
+        let mut marks = marks.clone();
+        let result = node.run_in(&mut instance.context, &(), &mut marks).await;
+        result.map_err(|err| err.to_string())
+    }
+
+    type Events = Arc<Mutex<Vec<(NodePath, TreeEvent)>>>;
+
+    #[derive(Clone, Default)]
+    struct Recorder(Events);
+
+    impl<A> BehaviorObserver<A> for Recorder {
+        fn on_event(&mut self, path: &[usize], event: &TreeEvent) {
+            self.0.lock().unwrap().push((path.to_vec(), event.clone()));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_spawned_children_run_in_the_background() {
+        let recorder = Recorder::default();
+        let mut instance = TreeInstance::new(spawning(Action(Job::Mark("surveyed"))))
+            .with_spawning()
+            .with_observer(recorder.clone());
+        let marks = Marks::default();
+        let start = Instant::now();
+        instance.run(&(), &mut marks.clone()).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert_eq!(*marks.lock().unwrap(), ["moved on"]);
+        let id = handle(&instance);
+        assert_eq!(instance.running_spawns(), [id]);
+
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert_eq!(*marks.lock().unwrap(), ["moved on", "surveyed"]);
+        assert!(instance.running_spawns().is_empty());
+        instance.shutdown().await;
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [(vec![0], TreeEvent::SpawnFinished { id, error: None })]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_spawn_errors_reach_the_observers() {
+        let recorder = Recorder::default();
+        let mut instance = TreeInstance::new(spawning(Action(Job::Fail)))
+            .with_spawning()
+            .with_observer(recorder.clone());
+        let mut marks = Marks::default();
+        instance.run(&(), &mut marks).await.unwrap();
+        let id = handle(&instance);
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        // events of detached subtrees are delivered when the instance runs again
+        instance.run(&(), &mut marks).await.unwrap();
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [(
+                vec![0],
+                TreeEvent::SpawnFinished {
+                    id,
+                    error: Some("survey failed".to_string())
+                }
+            )]
+        );
+        assert_ne!(handle(&instance), id);
+
+        let without_spawning = TreeInstance::new(spawning(Action(Job::Fail)))
+            .run(&(), &mut marks)
+            .await;
+        assert!(without_spawning.is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shutdown_cancels_unfinished_spawns() {
+        let recorder = Recorder::default();
+        let mut instance = TreeInstance::new(spawning(Action(Job::Mark("surveyed"))))
+            .with_spawning()
+            .with_observer(recorder.clone());
+        let marks = Marks::default();
+        instance.run(&(), &mut marks.clone()).await.unwrap();
+        let first = handle(&instance);
+        instance.run(&(), &mut marks.clone()).await.unwrap();
+        let second = handle(&instance);
+        assert_eq!(instance.running_spawns(), [first, second]);
+
+        instance.shutdown().await;
+        assert!(instance.running_spawns().is_empty());
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert_eq!(*marks.lock().unwrap(), ["moved on", "moved on"]);
+        let cancelled = |id| {
+            (
+                vec![0],
+                TreeEvent::SpawnFinished {
+                    id,
+                    error: Some("cancelled by shutdown".to_string()),
+                },
+            )
+        };
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [cancelled(first), cancelled(second)]
+        );
+
+        // dropping the instance cancels its spawns too
+        instance.run(&(), &mut marks.clone()).await.unwrap();
+        drop(instance);
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert_eq!(marks.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_join_waits_for_the_spawned_child() {
+        let bt = Sequence(vec![
+            spawn(Action(Job::Mark("surveyed"))),
+            Action(Job::Wait(400)),
+            Action(Job::Mark("traded")),
+            join(None),
+            Action(Job::Mark("joined")),
+        ]);
+        let mut instance = TreeInstance::new(bt).with_spawning();
+        let marks = Marks::default();
+        let start = Instant::now();
+        instance.run(&(), &mut marks.clone()).await.unwrap();
+        // the trade overlapped the survey
+        assert_eq!(start.elapsed(), Duration::from_millis(1000));
+        assert_eq!(*marks.lock().unwrap(), ["traded", "surveyed", "joined"]);
+
+        // the outcome of the child is the outcome of the join
+        let bt = Sequence(vec![spawn(Action(Job::Fail)), join(None)]);
+        let mut instance = TreeInstance::new(bt).with_spawning();
+        let err = instance.run(&(), &mut marks.clone()).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "one behavior failed ([1]: spawn 4 failed: survey failed)"
+        );
+        let id = handle(&instance);
+        assert_eq!(
+            run_alone(&mut instance, join(None), &marks).await,
+            Err(format!("spawn {} was already joined", id))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_join_timeouts_cancel_the_spawn() {
+        let mut instance = TreeInstance::new(spawn(Action(Job::Mark("surveyed")))).with_spawning();
+        let marks = Marks::default();
+        instance.run(&(), &mut marks.clone()).await.unwrap();
+        let id = handle(&instance);
+
+        let start = Instant::now();
+        assert_eq!(
+            run_alone(&mut instance, join(Some(300)), &marks).await,
+            Ok(Response::Failure)
+        );
+        assert_eq!(start.elapsed(), Duration::from_millis(300));
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(marks.lock().unwrap().is_empty());
+
+        assert_eq!(
+            run_alone(&mut instance, join(None), &marks).await,
+            Err(format!("spawn {} was cancelled", id))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_join_needs_a_handle_of_its_instance() {
+        let marks = Marks::default();
+        let mut instance = TreeInstance::new(join(None)).with_spawning();
+        let err = instance.run(&(), &mut marks.clone()).await.unwrap_err();
+        assert_eq!(err.to_string(), "blackboard key `survey` is not set");
+
+        instance.blackboard_mut().set("survey", "X1-A1");
+        let err = instance.run(&(), &mut marks.clone()).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "blackboard key `survey` is \"X1-A1\", not a spawn handle"
+        );
+
+        let mut other = TreeInstance::new(spawn(Action(Job::Mark("surveyed")))).with_spawning();
+        other.run(&(), &mut marks.clone()).await.unwrap();
+        let id = handle(&other);
+        instance.blackboard_mut().set("survey", id);
+        let err = instance.run(&(), &mut marks.clone()).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("spawn {} was not started by this instance", id)
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use crate::behavior_tree::blackboard::rename_template_keys;
+use crate::behavior_tree::compare::ValueRef;
+use crate::behavior_tree::expr::ExprError;
+use crate::behavior_tree::Behavior;
+use std::collections::BTreeMap;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SubtreeError {
+    #[error("unknown subtree `{}` (used via {})", .chain.last().map_or("", String::as_str), .chain.join(" -> "))]
+    Unknown { chain: Vec<String> },
+    #[error("subtree definitions are recursive: {}", .chain.join(" -> "))]
+    Recursive { chain: Vec<String> },
+    #[error("remapping the keys of subtree `{name}`: {source}")]
+    InvalidRemapping { name: String, source: ExprError },
+}
+
+/// Named trees that `Subtree` nodes stand in for, so a tree like "refuel if low" is written
+/// once and used by every tree that needs it, each time with its own blackboard keys.
+///
+/// A subtree's `remapping` maps the keys the registered tree uses to the keys of the tree
+/// using it: with `{"fuel": "probe_fuel"}` every node of the subtree reading or writing `fuel`
+/// uses `probe_fuel` instead. Keys without an entry are kept. The keys of `CheckKey`,
+/// `SetKey`, `OnChanged`, `Compare`, `SleepUntil`, `Experiment`, `Spawn`, `Join` and pipeline
+/// stages are remapped, as are the `bb.` keys of expressions and the `${key}` placeholders of
+/// `Log` and `Throw` messages. The params of `Decorated` and `Composite` nodes are their own
+/// business and kept as they are.
+pub struct SubtreeRegistry<A> {
+    trees: BTreeMap<String, Behavior<A>>,
+}
+
+impl<A> Default for SubtreeRegistry<A> {
+    fn default() -> Self {
+        Self {
+            trees: BTreeMap::new(),
+        }
+    }
+}
+
+impl<A: Clone> SubtreeRegistry<A> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `tree` under `name`, replacing the tree registered under it before. The tree
+    /// may itself use subtrees; they are resolved along with it.
+    pub fn register(&mut self, name: impl Into<String>, tree: Behavior<A>) {
+        self.trees.insert(name.into(), tree);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Behavior<A>> {
+        self.trees.get(name)
+    }
+
+    /// `tree` with every `Subtree` node replaced by the tree registered under its name, with
+    /// its keys remapped. Fails on names without a tree and on subtrees using themselves.
+    pub fn resolve(&self, mut tree: Behavior<A>) -> Result<Behavior<A>, SubtreeError> {
+        self.expand(&mut tree, &mut vec![])?;
+        Ok(tree)
+    }
+
+    fn expand(&self, node: &mut Behavior<A>, chain: &mut Vec<String>) -> Result<(), SubtreeError> {
+        let Behavior::Subtree { name, remapping } = node else {
+            for child in node.children_mut() {
+                self.expand(child, chain)?;
+            }
+            return Ok(());
+        };
+        let recursive = chain.contains(name);
+        chain.push(name.clone());
+        if recursive {
+            return Err(SubtreeError::Recursive {
+                chain: chain.clone(),
+            });
+        }
+        let Some(definition) = self.trees.get(name.as_str()) else {
+            return Err(SubtreeError::Unknown {
+                chain: chain.clone(),
+            });
+        };
+        let mut expanded = definition.clone();
+        // the subtrees inside remap to the keys of this one, which is then remapped as a whole
+        self.expand(&mut expanded, chain)?;
+        chain.pop();
+        if !remapping.is_empty() {
+            rename_keys(&mut expanded, remapping).map_err(|source| {
+                SubtreeError::InvalidRemapping {
+                    name: name.clone(),
+                    source,
+                }
+            })?;
+        }
+        *node = expanded;
+        Ok(())
+    }
+}
+
+// renames the blackboard keys the nodes of `node` use as `remapping` says
+fn rename_keys<A>(
+    node: &mut Behavior<A>,
+    remapping: &BTreeMap<String, String>,
+) -> Result<(), ExprError> {
+    let rename = |key: &str| remapping.get(key).cloned();
+    let rename_in = |key: &mut String| {
+        if let Some(renamed) = rename(key) {
+            *key = renamed;
+        }
+    };
+    let rename_ref = |value: &mut ValueRef| {
+        if let ValueRef::Key(key) = value {
+            rename_in(key);
+        }
+    };
+    match node {
+        Behavior::CheckKey { key, .. } | Behavior::OnChanged { key, .. } => rename_in(key),
+        Behavior::SetKey { key, value } => {
+            rename_in(key);
+            rename_ref(value);
+        }
+        Behavior::Compare { left, right, .. } => {
+            rename_ref(left);
+            rename_ref(right);
+        }
+        Behavior::SleepUntil { until } => rename_ref(until),
+        Behavior::Experiment { key, .. } => rename_ref(key),
+        Behavior::Spawn { handle_key, .. } | Behavior::Join { handle_key, .. } => {
+            rename_in(handle_key)
+        }
+        Behavior::Pipeline(stages) => {
+            for stage in stages {
+                stage.inputs.iter_mut().for_each(rename_in);
+                stage.outputs.iter_mut().for_each(rename_in);
+            }
+        }
+        Behavior::Expr { source } => *source = source.rename_keys(&rename)?,
+        Behavior::Log { message, .. } => *message = rename_template_keys(message, &rename),
+        Behavior::Throw {
+            message: Some(message),
+            ..
+        } => *message = rename_template_keys(message, &rename),
+        _ => {}
+    }
+    for child in node.children_mut() {
+        rename_keys(child, remapping)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::compare::{CompareOp, ValueRef};
+    use crate::behavior_tree::expr::Expression;
+    use crate::behavior_tree::observer::LogLevel;
+    use crate::behavior_tree::subtree::{SubtreeError, SubtreeRegistry};
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{Actionable, Behavior, Response, TreeInstance};
+    use std::collections::BTreeMap;
+
+    #[derive(Clone, Debug)]
+    enum Probe {
+        Refuel,
+    }
+
+    impl Actionable for Probe {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = Vec<String>;
+
+        async fn run(&self, _: &(), log: &mut Vec<String>) -> Result<Response, String> {
+            log.push(format!("{:?}", self));
+            Ok(Response::Success)
+        }
+    }
+
+    fn subtree(name: &str, remapping: &[(&str, &str)]) -> Behavior<Probe> {
+        Subtree {
+            name: name.to_string(),
+            remapping: remapping
+                .iter()
+                .map(|(from, to)| (from.to_string(), to.to_string()))
+                .collect(),
+        }
+    }
+
+    // refuels and notes it if `fuel` is low
+    fn refuel_if_low() -> Behavior<Probe> {
+        Sequence(vec![
+            CheckKey {
+                key: "fuel".to_string(),
+                value: "low".into(),
+            },
+            Action(Probe::Refuel),
+            SetKey {
+                key: "fuel".to_string(),
+                value: ValueRef::Literal("full".into()),
+            },
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_subtrees_run_on_their_remapped_keys() {
+        let mut registry = SubtreeRegistry::new();
+        registry.register("refuel_if_low", refuel_if_low());
+        let bt = registry
+            .resolve(Sequence(vec![
+                subtree("refuel_if_low", &[("fuel", "probe_fuel")]),
+                subtree("refuel_if_low", &[("fuel", "drone_fuel")]),
+                Invert(Box::new(subtree("refuel_if_low", &[]))),
+            ]))
+            .unwrap();
+
+        let mut instance = TreeInstance::new(bt);
+        instance.blackboard_mut().set("probe_fuel", "full");
+        instance.blackboard_mut().set("drone_fuel", "low");
+        instance.blackboard_mut().set("fuel", "high");
+        let mut log = vec![];
+        // the probe's check fails, which fails the whole sequence
+        let result = instance.run(&(), &mut log).await.unwrap();
+        assert_eq!(result, Response::Failure);
+        assert!(log.is_empty());
+
+        instance.blackboard_mut().set("probe_fuel", "low");
+        let result = instance.run(&(), &mut log).await.unwrap();
+        assert_eq!(result, Response::Success);
+        assert_eq!(log, ["Refuel", "Refuel"]);
+        let blackboard = instance.blackboard();
+        assert_eq!(blackboard.get("probe_fuel"), Some(&"full".into()));
+        assert_eq!(blackboard.get("drone_fuel"), Some(&"full".into()));
+        assert_eq!(blackboard.get("fuel"), Some(&"high".into()));
+    }
+
+    #[test]
+    fn test_nested_subtrees_are_remapped_from_the_inside_out() {
+        let mut registry = SubtreeRegistry::new();
+        registry.register("refuel_if_low", refuel_if_low());
+        registry.register(
+            "probe",
+            Sequence(vec![
+                subtree("refuel_if_low", &[("fuel", "tank")]),
+                Expr {
+                    source: Expression::parse("bb.tank == \"full\" && bb.fuel.max > 2").unwrap(),
+                },
+                Log {
+                    level: LogLevel::Info,
+                    message: "tank ${tank}, ${fuel}".to_string(),
+                },
+                Compare {
+                    left: ValueRef::Key("tank".to_string()),
+                    op: CompareOp::Eq,
+                    right: ValueRef::Key("fuel".to_string()),
+                },
+            ]),
+        );
+        let bt = registry
+            .resolve(subtree("probe", &[("tank", "probe_fuel")]))
+            .unwrap();
+
+        let Sequence(children) = bt else {
+            panic!("{:?}", bt);
+        };
+        let Sequence(refuel) = &children[0] else {
+            panic!("{:?}", children[0]);
+        };
+        assert!(matches!(&refuel[0], CheckKey { key, .. } if key == "probe_fuel"));
+        assert!(matches!(&refuel[2], SetKey { key, .. } if key == "probe_fuel"));
+        let Expr { source } = &children[1] else {
+            panic!("{:?}", children[1]);
+        };
+        assert_eq!(
+            source.source(),
+            "bb.probe_fuel == \"full\" && bb.fuel.max > 2"
+        );
+        assert!(
+            matches!(&children[2], Log { message, .. } if message == "tank ${probe_fuel}, ${fuel}")
+        );
+        assert!(matches!(
+            &children[3],
+            Compare { left: ValueRef::Key(left), right: ValueRef::Key(right), .. }
+                if left == "probe_fuel" && right == "fuel"
+        ));
+    }
+
+    #[test]
+    fn test_unknown_and_recursive_subtrees_are_errors() {
+        let mut registry = SubtreeRegistry::new();
+        registry.register("patrol", Sequence(vec![subtree("dock", &[])]));
+        registry.register("dock", Select(vec![subtree("undock", &[])]));
+        let err = registry.resolve(subtree("patrol", &[])).unwrap_err();
+        assert!(matches!(&err, SubtreeError::Unknown { .. }));
+        assert_eq!(
+            err.to_string(),
+            "unknown subtree `undock` (used via patrol -> dock -> undock)"
+        );
+
+        registry.register("undock", Invert(Box::new(subtree("patrol", &[]))));
+        let err = registry.resolve(subtree("dock", &[])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "subtree definitions are recursive: dock -> undock -> patrol -> dock"
+        );
+
+        let bad = BTreeMap::from([("tank".to_string(), "not a key".to_string())]);
+        registry.register(
+            "check",
+            Expr {
+                source: Expression::parse("bb.tank > 1").unwrap(),
+            },
+        );
+        let err = registry
+            .resolve(Subtree {
+                name: "check".to_string(),
+                remapping: bad,
+            })
+            .unwrap_err();
+        assert!(
+            matches!(err, SubtreeError::InvalidRemapping { .. }),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unresolved_subtrees_fail() {
+        let err = subtree("refuel_if_low", &[])
+            .run(&(), &mut vec![])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("never resolved"), "{}", err);
+    }
+}
@@ -0,0 +1,261 @@
+//! The few synchronization primitives the evaluator needs, built on wakers alone so they work on
+//! any executor.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+#[derive(Default)]
+struct Waiters {
+    // bumped by every `notify_waiters`
+    generation: u64,
+    next_id: u64,
+    wakers: BTreeMap<u64, Waker>,
+}
+
+/// Wakes every task waiting on it at once.
+#[derive(Default)]
+pub(crate) struct Notify {
+    waiters: Mutex<Waiters>,
+}
+
+impl Notify {
+    /// Completes at the next `notify_waiters` after this call, even if it is first polled after
+    /// that, so a waiter can check its condition between the two without missing a wakeup.
+    pub(crate) fn notified(&self) -> Notified<'_> {
+        let generation = self.waiters.lock().unwrap().generation;
+        Notified {
+            notify: self,
+            generation,
+            id: None,
+        }
+    }
+
+    pub(crate) fn notify_waiters(&self) {
+        let wakers = {
+            let mut waiters = self.waiters.lock().unwrap();
+            waiters.generation += 1;
+            std::mem::take(&mut waiters.wakers)
+        };
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+    }
+}
+
+pub(crate) struct Notified<'a> {
+    notify: &'a Notify,
+    generation: u64,
+    // the key of the waker it left, once it was polled
+    id: Option<u64>,
+}
+
+impl Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut waiters = self.notify.waiters.lock().unwrap();
+        if waiters.generation != self.generation {
+            return Poll::Ready(());
+        }
+        let id = match self.id {
+            Some(id) => id,
+            None => {
+                waiters.next_id += 1;
+                waiters.next_id
+            }
+        };
+        waiters.wakers.insert(id, cx.waker().clone());
+        drop(waiters);
+        self.id = Some(id);
+        Poll::Pending
+    }
+}
+
+// a tree waiting on a token that is never cancelled mustn't leave a waker behind each time
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.notify.waiters.lock().unwrap().wakers.remove(&id);
+        }
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    available: usize,
+    next_id: u64,
+    // the acquires waiting, first come first served
+    waiting: VecDeque<(u64, Waker)>,
+}
+
+/// Hands out a fixed number of permits, to the waiting acquires in the order they came.
+pub(crate) struct Semaphore {
+    queue: Mutex<Queue>,
+}
+
+/// One permit of a [`Semaphore`], given back when dropped.
+pub(crate) struct Permit(Arc<Semaphore>);
+
+impl Semaphore {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            queue: Mutex::new(Queue {
+                available: capacity,
+                ..Queue::default()
+            }),
+        }
+    }
+
+    pub(crate) fn available_permits(&self) -> usize {
+        self.queue.lock().unwrap().available
+    }
+
+    /// A permit if one is free and nobody is waiting for one.
+    pub(crate) fn try_acquire(self: &Arc<Self>) -> Option<Permit> {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.available == 0 || !queue.waiting.is_empty() {
+            return None;
+        }
+        queue.available -= 1;
+        Some(Permit(self.clone()))
+    }
+
+    /// Waits for a permit behind the acquires that came first.
+    pub(crate) fn acquire(self: &Arc<Self>) -> Acquire {
+        Acquire {
+            semaphore: self.clone(),
+            id: None,
+        }
+    }
+
+    // wakes the acquire first in line if there is a permit for it
+    fn wake_next(queue: &Queue) {
+        if queue.available > 0 {
+            if let Some((_, waker)) = queue.waiting.front() {
+                waker.wake_by_ref();
+            }
+        }
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut queue = self.0.queue.lock().unwrap();
+        queue.available += 1;
+        Semaphore::wake_next(&queue);
+    }
+}
+
+pub(crate) struct Acquire {
+    semaphore: Arc<Semaphore>,
+    // its place in the queue, once it has one
+    id: Option<u64>,
+}
+
+impl Future for Acquire {
+    type Output = Permit;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Permit> {
+        let semaphore = self.semaphore.clone();
+        let mut queue = semaphore.queue.lock().unwrap();
+        let first = match self.id {
+            Some(id) => queue.waiting.front().is_some_and(|(first, _)| *first == id),
+            None => queue.waiting.is_empty(),
+        };
+        if first && queue.available > 0 {
+            queue.available -= 1;
+            if self.id.take().is_some() {
+                queue.waiting.pop_front();
+            }
+            // more permits may have been given back while this one waited
+            Semaphore::wake_next(&queue);
+            return Poll::Ready(Permit(semaphore.clone()));
+        }
+        match self.id {
+            Some(id) => {
+                if let Some(waiting) = queue.waiting.iter_mut().find(|(i, _)| *i == id) {
+                    waiting.1 = cx.waker().clone();
+                }
+            }
+            None => {
+                queue.next_id += 1;
+                let id = queue.next_id;
+                queue.waiting.push_back((id, cx.waker().clone()));
+                self.id = Some(id);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+// an acquire given up on, e.g. by a timeout, leaves the queue and lets the next one in
+impl Drop for Acquire {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            let mut queue = self.semaphore.queue.lock().unwrap();
+            queue.waiting.retain(|(i, _)| *i != id);
+            Semaphore::wake_next(&queue);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::sync::{Notify, Semaphore};
+    use futures_util::poll;
+    use std::pin::pin;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_notified_sees_notifications_after_it_was_made() {
+        let notify = Notify::default();
+        let early = notify.notified();
+        notify.notify_waiters();
+        // made before the notification, polled after it
+        early.await;
+
+        let mut waiting = pin!(notify.notified());
+        assert!(poll!(&mut waiting).is_pending());
+        {
+            let mut dropped = pin!(notify.notified());
+            assert!(poll!(&mut dropped).is_pending());
+        }
+        assert_eq!(notify.waiters.lock().unwrap().wakers.len(), 1);
+        notify.notify_waiters();
+        assert!(poll!(&mut waiting).is_ready());
+    }
+
+    #[tokio::test]
+    async fn test_permits_go_to_the_acquires_in_order() {
+        let semaphore = Arc::new(Semaphore::new(1));
+        let held = semaphore.try_acquire().unwrap();
+        assert!(semaphore.try_acquire().is_none());
+
+        let mut first = pin!(semaphore.acquire());
+        let mut second = pin!(semaphore.acquire());
+        assert!(poll!(&mut second).is_pending());
+        assert!(poll!(&mut first).is_pending());
+        drop(held);
+        // `second` came first
+        assert!(poll!(&mut first).is_pending());
+        let permit = poll!(&mut second);
+        assert!(permit.is_ready());
+        drop(permit);
+        assert!(poll!(&mut first).is_ready());
+
+        // an acquire given up on lets the next one in
+        let semaphore = Arc::new(Semaphore::new(1));
+        let held = semaphore.try_acquire().unwrap();
+        let mut given_up = Box::pin(semaphore.acquire());
+        let mut next = pin!(semaphore.acquire());
+        assert!(poll!(&mut given_up).is_pending());
+        assert!(poll!(&mut next).is_pending());
+        drop(given_up);
+        drop(held);
+        assert!(poll!(&mut next).is_ready());
+        assert_eq!(semaphore.available_permits(), 1);
+    }
+}
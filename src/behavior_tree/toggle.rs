@@ -0,0 +1,263 @@
+use crate::behavior_tree::{Behavior, NodePath};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// A node to switch on or off at runtime: the node at a path, or every `Named` node with a
+/// name.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum NodeRef {
+    Path(NodePath),
+    Name(String),
+}
+
+impl From<&str> for NodeRef {
+    fn from(name: &str) -> Self {
+        NodeRef::Name(name.to_string())
+    }
+}
+
+impl From<String> for NodeRef {
+    fn from(name: String) -> Self {
+        NodeRef::Name(name)
+    }
+}
+
+impl From<NodePath> for NodeRef {
+    fn from(path: NodePath) -> Self {
+        NodeRef::Path(path)
+    }
+}
+
+impl From<&[usize]> for NodeRef {
+    fn from(path: &[usize]) -> Self {
+        NodeRef::Path(path.to_vec())
+    }
+}
+
+impl<const N: usize> From<[usize; N]> for NodeRef {
+    fn from(path: [usize; N]) -> Self {
+        NodeRef::Path(path.to_vec())
+    }
+}
+
+impl fmt::Display for NodeRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeRef::Path(path) => write!(f, "{:?}", path),
+            NodeRef::Name(name) => write!(f, "`{}`", name),
+        }
+    }
+}
+
+/// What a disabled node results in instead of running. Set on `Named` nodes; nodes disabled by
+/// path that aren't `Named` fail.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum WhenDisabled {
+    // for optional steps
+    Succeed,
+    // for gated branches
+    #[default]
+    Fail,
+}
+
+impl WhenDisabled {
+    pub fn is_default(&self) -> bool {
+        *self == WhenDisabled::default()
+    }
+}
+
+/// The nodes disabled in an instance.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Toggles {
+    disabled: BTreeSet<NodeRef>,
+}
+
+impl Toggles {
+    pub(crate) fn set_enabled(&mut self, node: NodeRef, enabled: bool) {
+        if enabled {
+            self.disabled.remove(&node);
+        } else {
+            self.disabled.insert(node);
+        }
+    }
+
+    pub(crate) fn disabled(&self) -> impl Iterator<Item = &NodeRef> {
+        self.disabled.iter()
+    }
+
+    /// What `node` at `path` results in if it is disabled, `None` if it is enabled.
+    pub(crate) fn check<A>(&self, path: &[usize], node: &Behavior<A>) -> Option<WhenDisabled> {
+        if self.disabled.is_empty() {
+            return None;
+        }
+        let by_name = match node {
+            Behavior::Named {
+                name,
+                when_disabled,
+                ..
+            } => Some((name, *when_disabled)),
+            _ => None,
+        };
+        let disabled = self.disabled.iter().any(|disabled| match disabled {
+            NodeRef::Path(disabled) => disabled == path,
+            NodeRef::Name(disabled) => by_name.is_some_and(|(name, _)| name == disabled),
+        });
+        disabled.then(|| by_name.map_or(WhenDisabled::default(), |(_, outcome)| outcome))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::observer::{BehaviorObserver, TreeEvent};
+    use crate::behavior_tree::runner::Runner;
+    use crate::behavior_tree::scheduler::Scheduler;
+    use crate::behavior_tree::toggle::{NodeRef, WhenDisabled};
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{Actionable, Behavior, NodePath, Response, TreeInstance};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Step {
+        Scan,
+        Trade,
+        Refuel,
+    }
+
+    impl Actionable for Step {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = Vec<Step>;
+
+        async fn run(&self, _: &(), done: &mut Vec<Step>) -> Result<Response, String> {
+            done.push(self.clone());
+            Ok(Response::Success)
+        }
+    }
+
+    fn named(name: &str, when_disabled: WhenDisabled, child: Behavior<Step>) -> Behavior<Step> {
+        Named {
+            name: name.to_string(),
+            when_disabled,
+            child: Box::new(child),
+        }
+    }
+
+    fn trip() -> Behavior<Step> {
+        Sequence(vec![
+            named("scan", WhenDisabled::Succeed, Action(Step::Scan)),
+            named("trade", WhenDisabled::Fail, Action(Step::Trade)),
+            Action(Step::Refuel),
+        ])
+    }
+
+    type Events = Arc<Mutex<Vec<(NodePath, TreeEvent)>>>;
+
+    #[derive(Clone, Default)]
+    struct Recorder(Events);
+
+    impl<A> BehaviorObserver<A> for Recorder {
+        fn on_event(&mut self, path: &[usize], event: &TreeEvent) {
+            self.0.lock().unwrap().push((path.to_vec(), event.clone()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_disabled_nodes_are_skipped() {
+        let recorder = Recorder::default();
+        let mut instance = TreeInstance::new(trip()).with_observer(recorder.clone());
+
+        instance.set_enabled("scan", false);
+        let mut done = vec![];
+        instance.run(&(), &mut done).await.unwrap();
+        assert_eq!(done, [Step::Trade, Step::Refuel]);
+
+        instance.set_enabled([1], false);
+        let mut done = vec![];
+        assert_eq!(
+            instance.run(&(), &mut done).await.unwrap(),
+            Response::Failure
+        );
+        assert_eq!(done, []);
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![
+                (
+                    vec![0],
+                    TreeEvent::SkippedDisabled {
+                        outcome: WhenDisabled::Succeed
+                    }
+                ),
+                (
+                    vec![0],
+                    TreeEvent::SkippedDisabled {
+                        outcome: WhenDisabled::Succeed
+                    }
+                ),
+                (
+                    vec![1],
+                    TreeEvent::SkippedDisabled {
+                        outcome: WhenDisabled::Fail
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            instance.disabled(),
+            [NodeRef::Path(vec![1]), NodeRef::Name("scan".to_string())]
+        );
+
+        instance.set_enabled("scan", true);
+        instance.set_enabled([1], true);
+        let mut done = vec![];
+        instance.run(&(), &mut done).await.unwrap();
+        assert_eq!(done, [Step::Scan, Step::Trade, Step::Refuel]);
+        assert!(instance.disabled().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_toggles_every_entry() {
+        let mut scheduler = Scheduler::new();
+        let first = scheduler.add(Runner::new(TreeInstance::new(trip())), (), vec![]);
+        scheduler.set_enabled("trade", false);
+        let second = scheduler.add(Runner::new(TreeInstance::new(trip())), (), vec![]);
+        for (_, result) in scheduler.tick_all().await {
+            assert_eq!(result.unwrap(), Response::Failure);
+        }
+        assert_eq!(scheduler.state(first), Some(&vec![Step::Scan]));
+        assert_eq!(scheduler.state(second), Some(&vec![Step::Scan]));
+        assert_eq!(scheduler.disabled(), [NodeRef::from("trade")]);
+
+        scheduler.set_enabled("trade", true);
+        for (_, result) in scheduler.tick_all().await {
+            assert!(result.is_ok());
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn test_toggles_survive_snapshots() {
+        let mut instance = TreeInstance::new(trip());
+        instance.set_enabled("trade", false);
+        let json = serde_json::to_string(&instance.snapshot()).unwrap();
+
+        let mut restored = TreeInstance::new(trip());
+        restored.restore(serde_json::from_str(&json).unwrap());
+        assert_eq!(restored.disabled(), [NodeRef::from("trade")]);
+        let mut done = vec![];
+        assert_eq!(
+            restored.run(&(), &mut done).await.unwrap(),
+            Response::Failure
+        );
+        assert_eq!(done, [Step::Scan]);
+
+        // snapshots taken before toggles existed restore with every node enabled
+        let old: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let old = serde_json::json!({"rng": old["rng"], "memory": []});
+        restored.restore(serde_json::from_value(old).unwrap());
+        assert!(restored.disabled().is_empty());
+    }
+}
@@ -0,0 +1,247 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+/// The name of an entry of a [`TypedBlackboard`] holding a `T`.
+///
+/// Keys are usually declared once, next to the actions using them:
+/// `const FUEL: TypedKey<u32> = TypedKey::new("fuel");`
+pub struct TypedKey<T> {
+    name: &'static str,
+    value: PhantomData<fn() -> T>,
+}
+
+impl<T> TypedKey<T> {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            value: PhantomData,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<T> Clone for TypedKey<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for TypedKey<T> {}
+
+impl<T> fmt::Debug for TypedKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TypedKey({:?})", self.name)
+    }
+}
+
+type Entries = HashMap<String, Box<dyn Any + Send + Sync>>;
+
+/// Values of any type shared by the actions of a tree instance, next to the state they run on.
+/// Subtrees that only need a few values can read and write them here instead of depending on
+/// the whole state type of a project.
+///
+/// Actions reach the blackboard of their run through
+/// [`ActionContext::typed_blackboard`](crate::behavior_tree::ActionContext::typed_blackboard). Under a
+/// `Namespaced` node the keys are prefixed with its namespace, so the same subtree can be used
+/// twice without the two sharing entries. Clones share the same entries; the children of a
+/// `Parallel` node and spawned subtrees write to the blackboard of the tree right away, unlike
+/// the [`Blackboard`](crate::behavior_tree::blackboard::Blackboard) of the instance.
+#[derive(Clone, Default)]
+pub struct TypedBlackboard {
+    entries: Arc<Mutex<Entries>>,
+    // the namespaces above, each followed by a `/`
+    prefix: String,
+}
+
+impl TypedBlackboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A clone of the value under `key`, `None` if there is none.
+    pub fn get<T: Clone + 'static>(&self, key: TypedKey<T>) -> Option<T> {
+        self.with(key, |value| value.cloned())
+    }
+
+    /// Calls `f` with the value under `key`, to read it without cloning it. `f` can't use the
+    /// blackboard itself.
+    pub fn with<T: 'static, R>(&self, key: TypedKey<T>, f: impl FnOnce(Option<&T>) -> R) -> R {
+        let entries = self.entries.lock().unwrap();
+        let value = entries.get(&self.entry(key));
+        f(value.and_then(|value| value.downcast_ref()))
+    }
+
+    /// Calls `f` with the value under `key` and returns what it returned, inserting
+    /// `T::default()` first if there is no value of type `T`. `f` can't use the blackboard
+    /// itself.
+    pub fn update<T, R>(&self, key: TypedKey<T>, f: impl FnOnce(&mut T) -> R) -> R
+    where
+        T: Default + Send + Sync + 'static,
+    {
+        let mut entries = self.entries.lock().unwrap();
+        let value = entries
+            .entry(self.entry(key))
+            .or_insert_with(|| Box::<T>::default());
+        if !value.is::<T>() {
+            *value = Box::<T>::default();
+        }
+        f(value.downcast_mut().expect("the entry was just replaced"))
+    }
+
+    /// Stores `value` under `key`, replacing whatever was there, of any type.
+    pub fn set<T: Send + Sync + 'static>(&self, key: TypedKey<T>, value: T) {
+        let entry = self.entry(key);
+        self.entries.lock().unwrap().insert(entry, Box::new(value));
+    }
+
+    /// Removes the value under `key` and returns it, `None` if there was none of type `T`.
+    pub fn remove<T: 'static>(&self, key: TypedKey<T>) -> Option<T> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = self.entry(key);
+        if !entries.get(&entry)?.is::<T>() {
+            return None;
+        }
+        let value = entries.remove(&entry)?;
+        value.downcast().ok().map(|value| *value)
+    }
+
+    /// Whether there is a value of any type under `key`.
+    pub fn contains<T>(&self, key: TypedKey<T>) -> bool {
+        self.entries.lock().unwrap().contains_key(&self.entry(key))
+    }
+
+    /// The same entries, with keys under `namespace` inside the namespace of this one.
+    pub fn namespaced(&self, namespace: &str) -> Self {
+        Self {
+            entries: self.entries.clone(),
+            prefix: format!("{}{}/", self.prefix, namespace),
+        }
+    }
+
+    /// The keys of all entries, with their namespaces, in no particular order.
+    pub fn keys(&self) -> Vec<String> {
+        self.entries.lock().unwrap().keys().cloned().collect()
+    }
+
+    fn entry<T>(&self, key: TypedKey<T>) -> String {
+        format!("{}{}", self.prefix, key.name)
+    }
+}
+
+impl fmt::Debug for TypedBlackboard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut keys = self.keys();
+        keys.sort();
+        f.debug_struct("TypedBlackboard")
+            .field("prefix", &self.prefix)
+            .field("keys", &keys)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::behavior_tree::typed::{TypedBlackboard, TypedKey};
+    use crate::behavior_tree::Behavior::*;
+    use crate::behavior_tree::{ActionContext, Actionable, Behavior, Response, TreeInstance};
+
+    const CARGO: TypedKey<u32> = TypedKey::new("cargo");
+
+    // works on the cargo on the blackboard, the state only counts the actions run
+    #[derive(Clone, Debug)]
+    enum Hold {
+        Load(u32),
+        IsFull,
+    }
+
+    impl Actionable for Hold {
+        type ActionError = String;
+        type ActionArgs = ();
+        type ActionState = usize;
+
+        async fn run(&self, args: &(), runs: &mut usize) -> Result<Response, String> {
+            self.run_with(&ActionContext::default(), args, runs).await
+        }
+
+        async fn run_with(
+            &self,
+            cx: &ActionContext,
+            _: &(),
+            runs: &mut usize,
+        ) -> Result<Response, String> {
+            *runs += 1;
+            let typed = cx.typed_blackboard();
+            match self {
+                Hold::Load(units) => typed.update(CARGO, |cargo| *cargo += units),
+                Hold::IsFull if typed.get(CARGO) >= Some(10) => return Ok(Response::Success),
+                Hold::IsFull => return Ok(Response::Failure),
+            }
+            Ok(Response::Success)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_actions_share_the_typed_blackboard() {
+        let bt = Sequence(vec![Action(Hold::Load(4)), Action(Hold::IsFull)]);
+        let mut instance = TreeInstance::new(bt);
+        let typed = instance.typed_blackboard();
+        let mut runs = 0;
+        let result = instance.run(&(), &mut runs).await.unwrap();
+        assert_eq!(result, Response::Failure);
+        assert_eq!(typed.get(CARGO), Some(4));
+
+        typed.set(CARGO, 8);
+        let result = instance.run(&(), &mut runs).await.unwrap();
+        assert_eq!(result, Response::Success);
+        assert_eq!(typed.get(CARGO), Some(12));
+        assert_eq!(runs, 4);
+
+        // run on its own, an action has a blackboard of its own
+        Hold::Load(1).run(&(), &mut runs).await.unwrap();
+        assert_eq!(typed.get(CARGO), Some(12));
+    }
+
+    #[tokio::test]
+    async fn test_namespaces_keep_subtrees_apart() {
+        let namespaced = |namespace: &str, child| Namespaced {
+            namespace: namespace.to_string(),
+            child: Box::new(child),
+        };
+        let bt: Behavior<Hold> = Sequence(vec![
+            namespaced("escort", Action(Hold::Load(3))),
+            namespaced("miner", namespaced("drone", Action(Hold::Load(5)))),
+            Action(Hold::Load(1)),
+        ]);
+        let mut instance = TreeInstance::new(bt);
+        instance.run(&(), &mut 0).await.unwrap();
+
+        let typed = instance.typed_blackboard();
+        let mut keys = typed.keys();
+        keys.sort();
+        assert_eq!(keys, ["cargo", "escort/cargo", "miner/drone/cargo"]);
+        assert_eq!(typed.get(CARGO), Some(1));
+        assert_eq!(typed.namespaced("escort").get(CARGO), Some(3));
+        let drone = typed.namespaced("miner").namespaced("drone");
+        assert_eq!(drone.get(CARGO), Some(5));
+    }
+
+    #[test]
+    fn test_values_are_only_read_as_their_type() {
+        let typed = TypedBlackboard::new();
+        typed.set(CARGO, 7);
+        let as_text: TypedKey<String> = TypedKey::new("cargo");
+        assert_eq!(typed.get(as_text), None);
+        assert_eq!(typed.remove(as_text), None);
+        assert!(typed.contains(as_text));
+
+        assert_eq!(typed.with(CARGO, |cargo| cargo.copied()), Some(7));
+        assert_eq!(typed.remove(CARGO), Some(7));
+        assert!(!typed.contains(CARGO));
+    }
+}
@@ -0,0 +1,63 @@
+//! Runs scenarios against a tree file and reports which of them pass.
+//!
+//! `bt-test <tree.json> <scenario.json>...` exits with 0 if every scenario passed, 1 if one
+//! failed and 2 if a file couldn't be read or loaded.
+
+use async_behavior_tree::behavior_tree::scenario::run_scenario;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: bt-test <tree.json> <scenario.json>...";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some((tree_path, scenario_paths)) = args.split_first() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+    if scenario_paths.is_empty() || tree_path == "--help" {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    }
+    let tree = match std::fs::read_to_string(tree_path) {
+        Ok(tree) => tree,
+        Err(err) => {
+            eprintln!("{}: {}", tree_path, err);
+            return ExitCode::from(2);
+        }
+    };
+
+    let mut failed = 0;
+    for scenario_path in scenario_paths {
+        let report = match std::fs::read_to_string(scenario_path) {
+            Ok(scenario) => run_scenario(&tree, &scenario).await,
+            Err(err) => {
+                eprintln!("{}: {}", scenario_path, err);
+                return ExitCode::from(2);
+            }
+        };
+        match report {
+            Ok(report) => {
+                println!("{}: {}", scenario_path, report);
+                if !report.passed() {
+                    failed += 1;
+                }
+            }
+            Err(err) => {
+                eprintln!("{}: {}", scenario_path, err);
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    println!(
+        "{} of {} scenarios passed",
+        scenario_paths.len() - failed,
+        scenario_paths.len()
+    );
+    if failed > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
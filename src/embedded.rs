@@ -0,0 +1,263 @@
+//! The part of the library that builds without `std`: [`Response`] and a small tree of the
+//! control-flow nodes of `behavior_tree::Behavior`, with its evaluator, on `core` and `alloc`
+//! only. Without the default `std` feature it is all the crate builds, for trees running on
+//! microcontrollers.
+//!
+//! Neither actions nor the futures they return need to be `Send`, so actions can hold
+//! peripherals and trees run on single-threaded executors like embassy's. There are no
+//! time-based nodes; actions wait on the timer of their target themselves. A tree keeps nothing
+//! between runs: a node that returned `Running` starts over on the next run, like
+//! `Behavior::run` outside of a `TreeInstance`.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Response {
+    Success,
+    // not done yet; in a `TreeInstance` the composites on the way resume at the running child
+    // on the next run instead of starting over
+    Running,
+    // the action didn't work out, the enclosing nodes may try something else; an `Err` is a
+    // hard error the fallback nodes pass on
+    Failure,
+}
+
+/// Something an embedded tree can run as a leaf. Unlike `behavior_tree::Actionable` it asks for
+/// neither `Clone` nor `Send`, of the action or of the future of `run`.
+pub trait Actionable {
+    type ActionError;
+    type ActionArgs;
+    type ActionState;
+
+    fn run(
+        &self,
+        args: &Self::ActionArgs,
+        state: &mut Self::ActionState,
+    ) -> impl Future<Output = Result<Response, Self::ActionError>>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Behavior<A> {
+    Action(A),
+    Invert(Box<Behavior<A>>),
+    Select(Vec<Behavior<A>>),
+    Sequence(Vec<Behavior<A>>),
+    // Runs the action while the condition succeeds, and succeeds once the condition fails. Fails
+    // as soon as the action fails.
+    While {
+        condition: Box<Behavior<A>>,
+        action: Box<Behavior<A>>,
+    },
+    // Runs `child` until it succeeded `times` times, stopping at the first run that doesn't
+    // succeed and resulting in what that run resulted in. Succeeds right away for `times` 0.
+    Repeat {
+        child: Box<Behavior<A>>,
+        times: usize,
+    },
+    // Always fails.
+    AlwaysFail,
+}
+
+/// The error of the action that stopped a run, with the path of its leaf: the child indices
+/// taken from the root, `While` using `0` for its condition and `1` for its action.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActionFailed<E> {
+    pub path: Vec<usize>,
+    pub error: E,
+}
+
+impl<E: fmt::Display> fmt::Display for ActionFailed<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "action at {:?} failed: {}", self.path, self.error)
+    }
+}
+
+impl<E: core::error::Error + 'static> core::error::Error for ActionFailed<E> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+type LocalBoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+type RunResult<E> = Result<Response, ActionFailed<E>>;
+
+impl<A: Actionable> Behavior<A> {
+    /// Runs the tree once. An action error stops the run right away; no node handles it.
+    pub async fn run(
+        &self,
+        args: &A::ActionArgs,
+        state: &mut A::ActionState,
+    ) -> RunResult<A::ActionError> {
+        self.run_at(&mut Vec::new(), args, state).await
+    }
+
+    // boxed, as nodes run their children through it
+    fn run_at<'a>(
+        &'a self,
+        path: &'a mut Vec<usize>,
+        args: &'a A::ActionArgs,
+        state: &'a mut A::ActionState,
+    ) -> LocalBoxFuture<'a, RunResult<A::ActionError>> {
+        Box::pin(async move {
+            match self {
+                Behavior::Action(action) => {
+                    action.run(args, state).await.map_err(|error| ActionFailed {
+                        path: path.clone(),
+                        error,
+                    })
+                }
+                Behavior::Invert(child) => match child.run_child(0, path, args, state).await? {
+                    Response::Success => Ok(Response::Failure),
+                    Response::Running => Ok(Response::Running),
+                    Response::Failure => Ok(Response::Success),
+                },
+                Behavior::Select(children) => {
+                    for (i, child) in children.iter().enumerate() {
+                        match child.run_child(i, path, args, state).await? {
+                            Response::Failure => continue,
+                            r => return Ok(r),
+                        }
+                    }
+                    Ok(Response::Failure)
+                }
+                Behavior::Sequence(children) => {
+                    for (i, child) in children.iter().enumerate() {
+                        match child.run_child(i, path, args, state).await? {
+                            Response::Success => continue,
+                            r => return Ok(r),
+                        }
+                    }
+                    Ok(Response::Success)
+                }
+                Behavior::While { condition, action } => loop {
+                    match condition.run_child(0, path, args, state).await? {
+                        Response::Failure => return Ok(Response::Success),
+                        Response::Running => return Ok(Response::Running),
+                        Response::Success => {}
+                    }
+                    match action.run_child(1, path, args, state).await? {
+                        Response::Success => continue,
+                        r => return Ok(r),
+                    }
+                },
+                Behavior::Repeat { child, times } => {
+                    for _ in 0..*times {
+                        match child.run_child(0, path, args, state).await? {
+                            Response::Success => continue,
+                            r => return Ok(r),
+                        }
+                    }
+                    Ok(Response::Success)
+                }
+                Behavior::AlwaysFail => Ok(Response::Failure),
+            }
+        })
+    }
+
+    async fn run_child(
+        &self,
+        index: usize,
+        path: &mut Vec<usize>,
+        args: &A::ActionArgs,
+        state: &mut A::ActionState,
+    ) -> RunResult<A::ActionError> {
+        path.push(index);
+        let result = self.run_at(path, args, state).await;
+        path.pop();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::embedded::Behavior::*;
+    use crate::embedded::{ActionFailed, Actionable, Behavior, Response};
+    use alloc::boxed::Box;
+    use alloc::rc::Rc;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use core::cell::Cell;
+
+    // a pin of a board, shared with the test through an `Rc` so the action isn't `Send`
+    #[derive(Debug)]
+    enum Pin {
+        IsHigh(Rc<Cell<bool>>),
+        Toggle(Rc<Cell<bool>>),
+        Short,
+    }
+
+    impl Actionable for Pin {
+        type ActionError = &'static str;
+        type ActionArgs = ();
+        type ActionState = Vec<&'static str>;
+
+        async fn run(&self, _: &(), log: &mut Vec<&'static str>) -> Result<Response, &'static str> {
+            match self {
+                Pin::IsHigh(pin) if pin.get() => Ok(Response::Success),
+                Pin::IsHigh(_) => Ok(Response::Failure),
+                Pin::Toggle(pin) => {
+                    pin.set(!pin.get());
+                    log.push(if pin.get() { "high" } else { "low" });
+                    Ok(Response::Success)
+                }
+                Pin::Short => Err("short circuit"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_control_flow_nodes_run_without_std() {
+        let pin = Rc::new(Cell::new(false));
+        let is_high = || Action(Pin::IsHigh(pin.clone()));
+        let toggle = || Action(Pin::Toggle(pin.clone()));
+        let bt: Behavior<Pin> = Sequence(vec![
+            Select(vec![is_high(), toggle()]),
+            Repeat {
+                child: Box::new(toggle()),
+                times: 3,
+            },
+            While {
+                condition: Box::new(Invert(Box::new(is_high()))),
+                action: Box::new(toggle()),
+            },
+            Invert(Box::new(AlwaysFail)),
+        ]);
+        let mut log = vec![];
+        assert_eq!(bt.run(&(), &mut log).await, Ok(Response::Success));
+        assert_eq!(log, ["high", "low", "high", "low", "high"]);
+
+        // the pin is high now, so the select doesn't toggle it
+        log.clear();
+        assert_eq!(bt.run(&(), &mut log).await, Ok(Response::Success));
+        assert_eq!(log, ["low", "high", "low", "high"]);
+    }
+
+    #[tokio::test]
+    async fn test_action_errors_stop_the_run_with_their_path() {
+        let bt: Behavior<Pin> = Select(vec![
+            AlwaysFail,
+            Sequence(vec![Invert(Box::new(AlwaysFail)), Action(Pin::Short)]),
+        ]);
+        let failed = bt.run(&(), &mut vec![]).await.unwrap_err();
+        assert_eq!(
+            failed,
+            ActionFailed {
+                path: vec![1, 1],
+                error: "short circuit",
+            }
+        );
+        assert_eq!(
+            alloc::format!("{}", failed),
+            "action at [1, 1] failed: short circuit"
+        );
+    }
+}
@@ -0,0 +1,23 @@
+//! Async behavior trees: the tree library, used by `examples/greetings.rs` and the `bt-test`
+//! scenario runner.
+//!
+//! Trees run on any executor. Nodes that wait, like `Timeout` or `SleepUntil`, do so through the
+//! [`Sleeper`](behavior_tree::clock::Sleeper) of their instance, which with the default `tokio`
+//! feature is tokio's timer. `Spawn` nodes start their children on tokio tasks, so they need
+//! that feature and a tokio runtime; without the feature tokio isn't a dependency at all.
+//!
+//! The default `std` feature builds the [`behavior_tree`] module. Without it the crate is
+//! `no_std` and only builds [`embedded`], the control-flow nodes on `alloc` alone;
+//! `cargo check-embedded` builds that for a `thumbv7em-none-eabihf` microcontroller.
+//!
+//! `benches/evaluator.rs` measures the overhead of running trees that are deep, wide or loop a
+//! lot; run it with `cargo bench` and compare with an earlier run saved by
+//! `cargo bench -- --save-baseline`.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod behavior_tree;
+pub mod embedded;